@@ -0,0 +1,189 @@
+#include <sv2.h>
+
+#include <iostream>
+
+#include <unistd.h>
+
+#include <stdio.h>
+
+#include <sys/socket.h>
+
+#include <stdlib.h>
+
+#include <netinet/in.h>
+
+#include <string.h>
+
+using namespace std;
+#define PORT 8081
+
+// Authority key pair of the pool, the Rust client knows the public key
+static const uint8_t AUTHORITY_PUBLIC_K[32] = {
+  0x24, 0xee, 0x3c, 0x38, 0x04, 0xa1, 0xaa, 0xa4, 0xc0, 0x3b, 0x80, 0xea, 0x19, 0xf7, 0xa5, 0x86,
+  0x3c, 0x91, 0x6e, 0x89, 0x94, 0xb7, 0xdb, 0x94, 0xa3, 0xba, 0xd7, 0xee, 0x09, 0x2b, 0x6c, 0xe7,
+};
+static const uint8_t AUTHORITY_PRIVATE_K[32] = {
+  0x65, 0x99, 0x5e, 0xb1, 0x96, 0x31, 0xf4, 0x78, 0xa4, 0x6f, 0xfa, 0x5c, 0xf1, 0xe5, 0x45, 0x09,
+  0x1e, 0xfe, 0x95, 0x0e, 0xae, 0xac, 0x74, 0x82, 0xff, 0xdc, 0x06, 0xeb, 0x6a, 0x89, 0xf6, 0x97,
+};
+
+static const uint32_t CHANNEL_ID = 1;
+
+void read_exact(int socket, uint8_t *buffer, uintptr_t len) {
+  uintptr_t byte_read = 0;
+  while (byte_read < len) {
+    byte_read += read(socket, buffer + byte_read, len - byte_read);
+  }
+}
+
+void send_encrypted(int socket, CSv2Message *message, NoiseEncoderWrapper *encoder, NoiseStateWrapper *state) {
+  CResult<CVec, Sv2Error> encoded = encode_encrypted(message, encoder, state);
+  switch (encoded.tag) {
+
+  case CResult < CVec, Sv2Error > ::Tag::Ok:
+    write(socket, encoded.ok._0.data, encoded.ok._0.len);
+    flush_noise_encoder(encoder);
+    break;
+  case CResult < CVec, Sv2Error > ::Tag::Err:
+    cout << "Some error occurred while encoding \n";
+    break;
+  };
+}
+
+void on_open_channel(int socket, COpenStandardMiningChannel request, NoiseEncoderWrapper *encoder, NoiseStateWrapper *state) {
+  cout << "OPEN STANDARD MINING CHANNEL\n";
+  cout << "  Request id: " << request.request_id << "\n";
+  cout << "  Nominal hash rate: " << request.nominal_hash_rate << "\n";
+
+  const uint8_t extranonce_prefix[4] = {0, 0, 0, 1};
+
+  COpenStandardMiningChannelSuccess success;
+  success.request_id = request.request_id;
+  success.channel_id = CHANNEL_ID;
+  success.target = cvec_from_buffer(request.max_target.data, request.max_target.len);
+  success.extranonce_prefix = cvec_from_buffer(extranonce_prefix, 4);
+  success.group_channel_id = 0;
+
+  CSv2Message response;
+  response.tag = CSv2Message::Tag::OpenStandardMiningChannelSuccess;
+  response.open_standard_mining_channel_success._0 = success;
+
+  send_encrypted(socket, &response, encoder, state);
+  drop_sv2_message(response);
+}
+
+void on_share(int socket, SubmitSharesStandard share, NoiseEncoderWrapper *encoder, NoiseStateWrapper *state) {
+  cout << "SUBMIT SHARES STANDARD\n";
+  cout << "  Sequence number: " << share.sequence_number << "\n";
+
+  SubmitSharesSuccess success;
+  success.channel_id = share.channel_id;
+  success.last_sequence_number = share.sequence_number;
+  success.new_submits_accepted_count = 1;
+  success.new_shares_sum = 1;
+
+  CSv2Message response;
+  response.tag = CSv2Message::Tag::SubmitSharesSuccess;
+  response.submit_shares_success._0 = success;
+
+  send_encrypted(socket, &response, encoder, state);
+  drop_sv2_message(response);
+}
+
+void on_success(int socket, CSv2Message message, NoiseEncoderWrapper *encoder, NoiseStateWrapper *state) {
+  switch (message.tag) {
+  case CSv2Message::Tag::OpenStandardMiningChannel:
+    on_open_channel(socket, message.open_standard_mining_channel._0, encoder, state);
+    break;
+  case CSv2Message::Tag::SubmitSharesStandard:
+    on_share(socket, message.submit_shares_standard._0, encoder, state);
+    break;
+  default:
+    cout << "Unexpected message \n";
+    break;
+  }
+  drop_sv2_message(message);
+}
+
+void on_error(Sv2Error error) {
+  switch (error.tag) {
+  case Sv2Error::Tag::MissingBytes:
+    break;
+  default:
+    cout << "An unkwon error occured \n";
+    break;
+  }
+}
+
+int main() {
+  int server_fd, new_socket;
+  struct sockaddr_in address;
+  int opt = 1;
+  int addrlen = sizeof(address);
+
+  if ((server_fd = socket(AF_INET, SOCK_STREAM, 0)) == 0) {
+    perror("socket failed");
+    exit(EXIT_FAILURE);
+  }
+  if (setsockopt(server_fd, SOL_SOCKET, SO_REUSEADDR | SO_REUSEPORT, &
+      opt, sizeof(opt))) {
+    perror("setsockopt");
+    exit(EXIT_FAILURE);
+  }
+  address.sin_family = AF_INET;
+  address.sin_addr.s_addr = INADDR_ANY;
+  address.sin_port = htons(PORT);
+
+  if (bind(server_fd, (struct sockaddr * ) & address,
+      sizeof(address)) < 0) {
+    perror("bind failed");
+    exit(EXIT_FAILURE);
+  }
+  if (listen(server_fd, 3) < 0) {
+    perror("listen");
+    exit(EXIT_FAILURE);
+  }
+  if ((new_socket = accept(server_fd, (struct sockaddr * ) & address,
+      (socklen_t * ) & addrlen)) < 0) {
+    perror("accept");
+    exit(EXIT_FAILURE);
+  }
+
+  // Noise handshake, the pool is the responder
+  NoiseStateWrapper * state = new_noise_responder(AUTHORITY_PUBLIC_K, AUTHORITY_PRIVATE_K, 3600);
+  if (state == nullptr) {
+    cout << "Invalid authority key pair \n";
+    exit(EXIT_FAILURE);
+  }
+  uint8_t first_message[RESPONDER_EXPECTED_HANDSHAKE_MESSAGE_SIZE];
+  read_exact(new_socket, first_message, RESPONDER_EXPECTED_HANDSHAKE_MESSAGE_SIZE);
+  CResult<CVec, Sv2Error> second_message = noise_step_1(state, first_message, RESPONDER_EXPECTED_HANDSHAKE_MESSAGE_SIZE);
+  if (second_message.tag == CResult < CVec, Sv2Error > ::Tag::Err) {
+    cout << "Noise handshake failed \n";
+    exit(EXIT_FAILURE);
+  }
+  write(new_socket, second_message.ok._0.data, second_message.ok._0.len);
+  cout << "NOISE HANDSHAKE COMPLETED\n";
+
+  NoiseDecoderWrapper * decoder = new_noise_decoder();
+  NoiseEncoderWrapper * encoder = new_noise_encoder();
+
+  while (true) {
+    CVec buffer = get_noise_writable(decoder);
+    read_exact(new_socket, buffer.data, buffer.len);
+
+    CResult < CSv2Message, Sv2Error > frame = next_encrypted_frame(decoder, state);
+
+    switch (frame.tag) {
+
+    case CResult < CSv2Message, Sv2Error > ::Tag::Ok:
+      on_success(new_socket, frame.ok._0, encoder, state);
+      break;
+    case CResult < CSv2Message, Sv2Error > ::Tag::Err:
+      on_error(frame.err._0);
+      break;
+    };
+  }
+
+  return 0;
+}
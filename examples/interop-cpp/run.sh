@@ -2,10 +2,12 @@
 
 touch libsv2_ffi.a
 touch a.out
+touch mining-pool.out
 
 # CLEAN
 rm -f libsv2_ffi.a
 rm -f a.out
+rm -f mining-pool.out
 rm -f sv2.h
 
 cargo build \
@@ -17,9 +19,12 @@ cargo build \
 ../../scripts/build_header.sh ../../protocols && mv ../../scripts/sv2.h .
 
 g++ -I ./ ./template-provider/template-provider.cpp  libsv2_ffi.a  -lpthread -ldl
+g++ -I ./ ./mining-pool/mining-pool.cpp  libsv2_ffi.a  -lpthread -ldl -o mining-pool.out
 
 ./a.out &
 provider_pid=$!
+./mining-pool.out &
+pool_pid=$!
 sleep 1 # wait for provider to start listening
 cargo run &
 run_pid=$!
@@ -28,10 +33,11 @@ run_pid=$!
 if [ -n "$1" ]; then
     sleep "$1"
 
-  if ps -p $provider_pid > /dev/null && ps -p $run_pid > /dev/null
+  if ps -p $provider_pid > /dev/null && ps -p $pool_pid > /dev/null && ps -p $run_pid > /dev/null
   then
       echo "Success!"
       kill $provider_pid
+      kill $pool_pid
       kill $run_pid
   else
       echo "Failure!!!"
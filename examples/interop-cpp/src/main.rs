@@ -1,3 +1,6 @@
+#[cfg(not(feature = "with_serde"))]
+mod mining;
+
 fn main() -> Result<(), std::io::Error> {
     use main_::main;
     main()
@@ -85,6 +88,12 @@ mod main_ {
     }
 
     pub fn main() -> Result<(), std::io::Error> {
+        std::thread::spawn(|| {
+            // The mining client never returns, if it does the interop test must fail
+            let _ = std::panic::catch_unwind(super::mining::main);
+            std::process::exit(1);
+        });
+
         let mut encoder = Encoder::<SetupConnection>::new();
 
         let setup_connection = SetupConnection {
@@ -0,0 +1,200 @@
+//! Downstream mining client that talks to the C++ mining pool over a Noise encrypted connection.
+use codec_sv2::{
+    Frame, HandshakeRole, Initiator, NoiseEncoder, StandardNoiseDecoder, StandardSv2Frame, State,
+};
+use const_sv2::{
+    CHANNEL_BIT_OPEN_STANDARD_MINING_CHANNEL, CHANNEL_BIT_SUBMIT_SHARES_STANDARD,
+    INITIATOR_EXPECTED_HANDSHAKE_MESSAGE_SIZE, MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL,
+    MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL_SUCCESS, MESSAGE_TYPE_SUBMIT_SHARES_STANDARD,
+    MESSAGE_TYPE_SUBMIT_SHARES_SUCCESS,
+};
+use mining_sv2::{
+    OpenStandardMiningChannel, OpenStandardMiningChannelSuccess, SubmitSharesStandard,
+    SubmitSharesSuccess,
+};
+use std::{
+    convert::{TryFrom, TryInto},
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+use binary_sv2::{
+    decodable::{DecodableField, FieldMarker},
+    encodable::EncodableField,
+    from_bytes, Deserialize, Error,
+};
+
+/// Same authority key used by the C++ mining pool.
+const AUTHORITY_PUBLIC_K: [u8; 32] = [
+    0x24, 0xee, 0x3c, 0x38, 0x04, 0xa1, 0xaa, 0xa4, 0xc0, 0x3b, 0x80, 0xea, 0x19, 0xf7, 0xa5, 0x86,
+    0x3c, 0x91, 0x6e, 0x89, 0x94, 0xb7, 0xdb, 0x94, 0xa3, 0xba, 0xd7, 0xee, 0x09, 0x2b, 0x6c, 0xe7,
+];
+
+#[derive(Clone, Debug)]
+pub enum Mining<'a> {
+    OpenStandardMiningChannel(OpenStandardMiningChannel<'a>),
+    OpenStandardMiningChannelSuccess(OpenStandardMiningChannelSuccess<'a>),
+    SubmitSharesStandard(SubmitSharesStandard),
+    SubmitSharesSuccess(SubmitSharesSuccess),
+}
+
+impl binary_sv2::GetSize for Mining<'_> {
+    fn get_size(&self) -> usize {
+        match self {
+            Mining::OpenStandardMiningChannel(a) => a.get_size(),
+            Mining::OpenStandardMiningChannelSuccess(a) => a.get_size(),
+            Mining::SubmitSharesStandard(a) => a.get_size(),
+            Mining::SubmitSharesSuccess(a) => a.get_size(),
+        }
+    }
+}
+
+impl<'decoder> Deserialize<'decoder> for Mining<'decoder> {
+    fn get_structure(_v: &[u8]) -> std::result::Result<Vec<FieldMarker>, binary_sv2::Error> {
+        unimplemented!()
+    }
+    fn from_decoded_fields(
+        _v: Vec<DecodableField<'decoder>>,
+    ) -> std::result::Result<Self, binary_sv2::Error> {
+        unimplemented!()
+    }
+}
+
+impl<'a> TryFrom<(u8, &'a mut [u8])> for Mining<'a> {
+    type Error = Error;
+
+    fn try_from(v: (u8, &'a mut [u8])) -> Result<Self, Self::Error> {
+        let msg_type = v.0;
+        match msg_type {
+            MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL_SUCCESS => {
+                let message: OpenStandardMiningChannelSuccess<'a> = from_bytes(v.1)?;
+                Ok(Mining::OpenStandardMiningChannelSuccess(message))
+            }
+            MESSAGE_TYPE_SUBMIT_SHARES_SUCCESS => {
+                let message: SubmitSharesSuccess = from_bytes(v.1)?;
+                Ok(Mining::SubmitSharesSuccess(message))
+            }
+            _ => panic!(),
+        }
+    }
+}
+
+impl<'decoder> From<Mining<'decoder>> for EncodableField<'decoder> {
+    fn from(m: Mining<'decoder>) -> Self {
+        match m {
+            Mining::OpenStandardMiningChannel(a) => a.into(),
+            Mining::OpenStandardMiningChannelSuccess(a) => a.into(),
+            Mining::SubmitSharesStandard(a) => a.into(),
+            Mining::SubmitSharesSuccess(a) => a.into(),
+        }
+    }
+}
+
+fn send(
+    stream: &mut TcpStream,
+    encoder: &mut NoiseEncoder<Mining<'static>>,
+    state: &mut State,
+    message: Mining<'static>,
+    message_type: u8,
+    channel_bit: bool,
+) -> Result<(), std::io::Error> {
+    let frame = StandardSv2Frame::from_message(message, message_type, 0, channel_bit).unwrap();
+    let encoded = encoder.encode(frame.into(), state).unwrap();
+    stream.write_all(encoded.as_ref())
+}
+
+// Returns the message type and a copy of the payload of the next decrypted frame
+fn next_payload(
+    stream: &mut TcpStream,
+    decoder: &mut StandardNoiseDecoder<Mining<'static>>,
+    state: &mut State,
+) -> Result<(u8, Vec<u8>), std::io::Error> {
+    loop {
+        let buffer = decoder.writable();
+        stream.read_exact(buffer)?;
+        if let Ok(Frame::Sv2(mut f)) = decoder.next_frame(state) {
+            let msg_type = f.get_header().unwrap().msg_type();
+            return Ok((msg_type, f.payload().to_vec()));
+        }
+    }
+}
+
+pub fn main() -> Result<(), std::io::Error> {
+    #[allow(deprecated)]
+    std::thread::sleep_ms(2000);
+
+    let mut stream = TcpStream::connect("0.0.0.0:8081")?;
+
+    let initiator = Initiator::from_raw_k(AUTHORITY_PUBLIC_K).unwrap();
+    let mut state = State::initialized(HandshakeRole::Initiator(initiator));
+    let first_message = state.step_0().unwrap();
+    stream.write_all(&first_message.get_payload_when_handshaking())?;
+    let mut second_message = [0_u8; INITIATOR_EXPECTED_HANDSHAKE_MESSAGE_SIZE];
+    stream.read_exact(&mut second_message)?;
+    let mut state = state.step_2(second_message).unwrap();
+    println!("RUST NOISE HANDSHAKE COMPLETED");
+
+    let mut encoder = NoiseEncoder::<Mining<'static>>::new();
+    let mut decoder = StandardNoiseDecoder::<Mining<'static>>::new();
+
+    let open_channel = OpenStandardMiningChannel {
+        request_id: 1.into(),
+        user_identity: "interop-cpp".to_string().into_bytes().try_into().unwrap(),
+        nominal_hash_rate: 1_000_000.0,
+        max_target: vec![0xff; 32].try_into().unwrap(),
+    };
+    send(
+        &mut stream,
+        &mut encoder,
+        &mut state,
+        Mining::OpenStandardMiningChannel(open_channel),
+        MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL,
+        CHANNEL_BIT_OPEN_STANDARD_MINING_CHANNEL,
+    )?;
+
+    let (msg_type, mut payload) = next_payload(&mut stream, &mut decoder, &mut state)?;
+    let message: Mining = (msg_type, payload.as_mut_slice()).try_into().unwrap();
+    let channel_id = match message {
+        Mining::OpenStandardMiningChannelSuccess(m) => {
+            println!("RUST CHANNEL OPENED");
+            println!("  channel id: {}", m.channel_id);
+            m.channel_id
+        }
+        _ => panic!(),
+    };
+
+    let mut sequence_number = 0;
+    loop {
+        #[allow(deprecated)]
+        std::thread::sleep_ms(500);
+
+        let share = SubmitSharesStandard {
+            channel_id,
+            sequence_number,
+            job_id: 0,
+            nonce: sequence_number,
+            ntime: 0x29ab5f49,
+            version: 0x20000000,
+        };
+        send(
+            &mut stream,
+            &mut encoder,
+            &mut state,
+            Mining::SubmitSharesStandard(share),
+            MESSAGE_TYPE_SUBMIT_SHARES_STANDARD,
+            CHANNEL_BIT_SUBMIT_SHARES_STANDARD,
+        )?;
+
+        let (msg_type, mut payload) = next_payload(&mut stream, &mut decoder, &mut state)?;
+        let message: Mining = (msg_type, payload.as_mut_slice()).try_into().unwrap();
+        match message {
+            Mining::SubmitSharesSuccess(m) => {
+                assert_eq!(m.last_sequence_number, sequence_number);
+                println!("RUST SHARE ACCEPTED");
+                println!("  sequence number: {}", m.last_sequence_number);
+            }
+            _ => panic!(),
+        }
+        sequence_number += 1;
+    }
+}
@@ -249,7 +249,7 @@ impl<'a> IsServer<'a> for Server<'a> {
     }
 
     /// Indicates to the server that the client supports the mining.set_extranonce method.
-    fn handle_extranonce_subscribe(&self) {}
+    fn handle_extranonce_subscribe(&mut self) {}
 
     fn is_authorized(&self, _name: &str) -> bool {
         true
@@ -52,7 +52,6 @@ use utils::{Extranonce, HexU32Be};
 
 /// json_rpc Response are not handled cause stratum v1 does not have any request from a server to a
 /// client
-/// TODO: Should update to accommodate miner requesting a difficulty change
 ///
 /// A stratum v1 server represent a single connection with a client
 ///
@@ -88,7 +87,18 @@ pub trait IsServer<'a> {
         Self: std::marker::Sized,
     {
         match request {
-            methods::Client2Server::SuggestDifficulty() => Ok(None),
+            methods::Client2Server::SuggestDifficulty(suggest_difficulty) => {
+                self.handle_suggest_difficulty(&suggest_difficulty);
+                Ok(None)
+            }
+            methods::Client2Server::SuggestTarget(suggest_target) => {
+                self.handle_suggest_target(&suggest_target);
+                Ok(None)
+            }
+            methods::Client2Server::GetTransactions(get_transactions) => {
+                let transactions = self.handle_get_transactions(&get_transactions);
+                Ok(Some(get_transactions.respond(transactions)))
+            }
             methods::Client2Server::Authorize(authorize) => {
                 let authorized = self.handle_authorize(&authorize);
                 if authorized {
@@ -119,13 +129,25 @@ pub trait IsServer<'a> {
                     None => self.version_rolling_mask().is_none(),
                 };
 
-                let is_valid_submission = self.is_authorized(&submit.user_name)
-                    && self.extranonce2_size() == submit.extra_nonce2.len()
-                    && has_valid_version_bits;
+                // the share is not handled but the client, that may still authorize the worker,
+                // is not disconnected
+                if !self.is_authorized(&submit.user_name) {
+                    return Ok(Some(submit.respond_with_error(
+                        client_to_server::SubmitError::UnauthorizedWorker,
+                    )));
+                }
+
+                let is_valid_submission =
+                    self.extranonce2_size() == submit.extra_nonce2.len() && has_valid_version_bits;
 
                 if is_valid_submission {
-                    let accepted = self.handle_submit(&submit);
-                    Ok(Some(submit.respond(accepted)))
+                    match self.check_submit(&submit) {
+                        Ok(()) => {
+                            let accepted = self.handle_submit(&submit);
+                            Ok(Some(submit.respond(accepted)))
+                        }
+                        Err(e) => Ok(Some(submit.respond_with_error(e))),
+                    }
                 } else {
                     Err(Error::InvalidSubmission)
                 }
@@ -184,8 +206,32 @@ pub trait IsServer<'a> {
     ///
     fn handle_submit(&self, request: &client_to_server::Submit<'a>) -> bool;
 
+    /// Called before [`IsServer::handle_submit`] for every well formed submission, when it returns
+    /// an error the share is not handled and the client gets an error response with that code.
+    /// By default every share is handed to `handle_submit`.
+    fn check_submit(
+        &self,
+        _request: &client_to_server::Submit<'a>,
+    ) -> Result<(), client_to_server::SubmitError> {
+        Ok(())
+    }
+
     /// Indicates to the server that the client supports the mining.set_extranonce method.
-    fn handle_extranonce_subscribe(&self);
+    fn handle_extranonce_subscribe(&mut self);
+
+    /// The client asks for the difficulty of its shares, usually before the first job. No
+    /// response is sent, by default the suggestion is ignored.
+    fn handle_suggest_difficulty(&mut self, _request: &client_to_server::SuggestDifficulty) {}
+
+    /// Like [`IsServer::handle_suggest_difficulty`] but with the target, by default the
+    /// suggestion is ignored.
+    fn handle_suggest_target(&mut self, _request: &client_to_server::SuggestTarget) {}
+
+    /// Returns the hex encoded transactions of the job asked by the client, by default none are
+    /// disclosed.
+    fn handle_get_transactions(&self, _request: &client_to_server::GetTransactions) -> Vec<String> {
+        vec![]
+    }
 
     fn is_authorized(&self, name: &str) -> bool;
 
@@ -311,6 +357,10 @@ pub trait IsClient<'a> {
                 self.handle_set_version_mask(&mut set_version_mask)?;
                 Ok(None)
             }
+            methods::Server2Client::Reconnect(reconnect) => {
+                self.handle_reconnect(&reconnect)?;
+                Ok(None)
+            }
         }
     }
 
@@ -392,6 +442,12 @@ pub trait IsClient<'a> {
         m: &mut server_to_client::SetVersionMask,
     ) -> Result<(), Error<'a>>;
 
+    /// Called when the server asks to move to another endpoint, clients that do not support
+    /// reconnecting can ignore it
+    fn handle_reconnect(&mut self, _m: &server_to_client::Reconnect) -> Result<(), Error<'a>> {
+        Ok(())
+    }
+
     fn handle_subscribe(
         &mut self,
         subscribe: &server_to_client::Subscribe<'a>,
@@ -7,9 +7,9 @@ use std::convert::{TryFrom, TryInto};
 
 use crate::{
     error::Error,
-    json_rpc::{Message, Response, StandardRequest},
+    json_rpc::{JsonRpcError, Message, Response, StandardRequest},
     methods::ParsingMethodError,
-    utils::{Extranonce, HexU32Be},
+    utils::{Extranonce, HexBytes, HexU32Be},
 };
 
 #[cfg(test)]
@@ -100,7 +100,55 @@ fn from_to_json_rpc(auth: Authorize) -> bool {
 #[derive(Debug, Clone, Copy)]
 pub struct ExtranonceSubscribe();
 
-// mining.get_transactions
+/// _mining.get_transactions("job id")_
+///
+/// Asks for the transactions of the block template of a job, so that the client can check what
+/// it is mining on. The result is the list of the hex encoded transactions, without the coinbase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetTransactions {
+    pub id: u64,
+    pub job_id: String,
+}
+
+impl GetTransactions {
+    pub fn respond(self, transactions: Vec<String>) -> Response {
+        // infallible
+        let result = serde_json::to_value(transactions).unwrap();
+        Response {
+            id: self.id,
+            result,
+            error: None,
+        }
+    }
+}
+
+impl From<GetTransactions> for Message {
+    fn from(get_transactions: GetTransactions) -> Self {
+        Message::StandardRequest(StandardRequest {
+            id: get_transactions.id,
+            method: "mining.get_transactions".into(),
+            params: (&[get_transactions.job_id][..]).into(),
+        })
+    }
+}
+
+impl TryFrom<StandardRequest> for GetTransactions {
+    type Error = ParsingMethodError;
+
+    fn try_from(msg: StandardRequest) -> Result<Self, Self::Error> {
+        match msg.params.as_array() {
+            Some(params) => {
+                let job_id = match &params[..] {
+                    [JString(a)] => a.into(),
+                    _ => return Err(ParsingMethodError::wrong_args_from_value(msg.params)),
+                };
+                let id = msg.id;
+                Ok(Self { id, job_id })
+            }
+            None => Err(ParsingMethodError::not_array_from_value(msg.params)),
+        }
+    }
+}
 
 /// _mining.submit("username", "job id", "ExtraNonce2", "nTime", "nOnce")_
 ///
@@ -137,6 +185,54 @@ impl<'a> Submit<'a> {
             error: None,
         }
     }
+
+    /// Rejects the share with one of the error codes that miners and pools conventionally use
+    pub fn respond_with_error(self, error: SubmitError) -> Response {
+        Response {
+            id: self.id,
+            result: Null,
+            error: Some(JsonRpcError {
+                code: error.code(),
+                message: error.message().to_string(),
+                data: None,
+            }),
+        }
+    }
+}
+
+/// Reasons for rejecting a [`Submit`], the codes are the ones used by most stratum v1 servers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmitError {
+    Other,
+    JobNotFound,
+    DuplicateShare,
+    LowDifficulty,
+    UnauthorizedWorker,
+    NotSubscribed,
+}
+
+impl SubmitError {
+    pub fn code(&self) -> i32 {
+        match self {
+            SubmitError::Other => 20,
+            SubmitError::JobNotFound => 21,
+            SubmitError::DuplicateShare => 22,
+            SubmitError::LowDifficulty => 23,
+            SubmitError::UnauthorizedWorker => 24,
+            SubmitError::NotSubscribed => 25,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            SubmitError::Other => "Other/Unknown",
+            SubmitError::JobNotFound => "Job not found",
+            SubmitError::DuplicateShare => "Duplicate share",
+            SubmitError::LowDifficulty => "Low difficulty share",
+            SubmitError::UnauthorizedWorker => "Unauthorized worker",
+            SubmitError::NotSubscribed => "Not subscribed",
+        }
+    }
 }
 
 impl<'a> From<Submit<'a>> for Message {
@@ -257,6 +353,25 @@ fn submit_from_to_json_rpc(submit: Submit<'static>) -> bool {
     submit == TryInto::<Submit>::try_into(request).unwrap()
 }
 
+#[test]
+fn test_submit_respond_with_error() {
+    let submit = Submit {
+        user_name: "user".to_string(),
+        job_id: "1".to_string(),
+        extra_nonce2: Extranonce::try_from(vec![0_u8; 4]).unwrap(),
+        time: HexU32Be(0),
+        nonce: HexU32Be(0),
+        version_bits: None,
+        id: 7,
+    };
+    let response = submit.respond_with_error(SubmitError::LowDifficulty);
+    let error = response.error.clone().unwrap();
+    assert_eq!(response.id, 7);
+    assert_eq!(error.code, 23);
+    assert_eq!(error.message, "Low difficulty share");
+    assert!(matches!(Message::from(response), Message::ErrorResponse(_)));
+}
+
 /// _mining.subscribe("user agent/version", "extranonce1")_
 ///
 /// extranonce1 specifies a [mining.notify][a] extranonce1 the client wishes to
@@ -656,9 +771,142 @@ impl From<InfoParams> for serde_json::Map<String, Value> {
     }
 }
 
-// mining.suggest_difficulty
+/// _mining.suggest_difficulty(difficulty)_
+///
+/// Asks the server for the difficulty of the shares that the client is going to submit, many
+/// miners send it right after mining.subscribe. The server may ignore it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuggestDifficulty {
+    pub id: u64,
+    pub value: f64,
+}
+
+impl From<SuggestDifficulty> for Message {
+    fn from(suggest: SuggestDifficulty) -> Self {
+        Message::StandardRequest(StandardRequest {
+            id: suggest.id,
+            method: "mining.suggest_difficulty".into(),
+            params: vec![Value::from(suggest.value)].into(),
+        })
+    }
+}
+
+impl TryFrom<StandardRequest> for SuggestDifficulty {
+    type Error = ParsingMethodError;
+
+    fn try_from(msg: StandardRequest) -> Result<Self, Self::Error> {
+        match msg.params.as_array() {
+            Some(params) => {
+                let value = match &params[..] {
+                    [JNumber(a)] => a.as_f64().ok_or_else(|| {
+                        ParsingMethodError::not_float_from_value(params[0].clone())
+                    })?,
+                    _ => return Err(ParsingMethodError::wrong_args_from_value(msg.params)),
+                };
+                let id = msg.id;
+                Ok(Self { id, value })
+            }
+            None => Err(ParsingMethodError::not_array_from_value(msg.params)),
+        }
+    }
+}
+
+/// _mining.suggest_target("full target")_
+///
+/// Like [`SuggestDifficulty`] but with the hex encoded target, big endian as in mining.notify
+/// headers. The server may ignore it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuggestTarget {
+    pub id: u64,
+    pub target: HexBytes,
+}
 
-// mining.suggest_target
+impl From<SuggestTarget> for Message {
+    fn from(suggest: SuggestTarget) -> Self {
+        Message::StandardRequest(StandardRequest {
+            id: suggest.id,
+            method: "mining.suggest_target".into(),
+            params: vec![Value::from(suggest.target)].into(),
+        })
+    }
+}
+
+impl TryFrom<StandardRequest> for SuggestTarget {
+    type Error = ParsingMethodError;
+
+    fn try_from(msg: StandardRequest) -> Result<Self, Self::Error> {
+        match msg.params.as_array() {
+            Some(params) => {
+                let target: HexBytes = match &params[..] {
+                    [JString(a)] => a.as_str().try_into()?,
+                    _ => return Err(ParsingMethodError::wrong_args_from_value(msg.params)),
+                };
+                if target.len() > 32 {
+                    return Err(ParsingMethodError::wrong_args_from_value(msg.params));
+                }
+                let id = msg.id;
+                Ok(Self { id, target })
+            }
+            None => Err(ParsingMethodError::not_array_from_value(msg.params)),
+        }
+    }
+}
+
+#[test]
+fn test_suggest_difficulty() {
+    let client_message = r#"{"id":3,
+            "method": "mining.suggest_difficulty",
+            "params":[1024]
+        }"#;
+    let client_message: StandardRequest = serde_json::from_str(client_message).unwrap();
+    let suggest = SuggestDifficulty::try_from(client_message).unwrap();
+    assert_eq!(suggest.id, 3);
+    assert_eq!(suggest.value, 1024.0);
+    let request = match Message::from(suggest.clone()) {
+        Message::StandardRequest(s) => s,
+        _ => panic!(),
+    };
+    assert_eq!(suggest, SuggestDifficulty::try_from(request).unwrap());
+
+    let client_message = r#"{"id":3,
+            "method": "mining.suggest_difficulty",
+            "params":["1024"]
+        }"#;
+    let client_message: StandardRequest = serde_json::from_str(client_message).unwrap();
+    assert!(SuggestDifficulty::try_from(client_message).is_err());
+}
+
+#[test]
+fn test_suggest_target() {
+    let client_message = r#"{"id":4,
+            "method": "mining.suggest_target",
+            "params":["00000000ffff0000000000000000000000000000000000000000000000000000"]
+        }"#;
+    let client_message: StandardRequest = serde_json::from_str(client_message).unwrap();
+    let suggest = SuggestTarget::try_from(client_message).unwrap();
+    let target: Vec<u8> = suggest.target.clone().into();
+    assert_eq!(target.len(), 32);
+    assert_eq!(target[..6], [0, 0, 0, 0, 255, 255]);
+    let request = match Message::from(suggest.clone()) {
+        Message::StandardRequest(s) => s,
+        _ => panic!(),
+    };
+    assert_eq!(suggest, SuggestTarget::try_from(request).unwrap());
+}
+
+#[test]
+fn test_get_transactions() {
+    let client_message = r#"{"id":5,
+            "method": "mining.get_transactions",
+            "params":["1a"]
+        }"#;
+    let client_message: StandardRequest = serde_json::from_str(client_message).unwrap();
+    let get_transactions = GetTransactions::try_from(client_message).unwrap();
+    assert_eq!(get_transactions.job_id, "1a");
+    let response = get_transactions.respond(vec!["0100".to_string()]);
+    assert_eq!(response.id, 5);
+    assert_eq!(response.result, serde_json::json!(["0100"]));
+}
 
 // mining.minimum_difficulty (extension)
 #[test]
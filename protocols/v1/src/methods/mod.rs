@@ -125,7 +125,9 @@ pub enum Method<'a> {
 
 #[derive(Debug, Clone)]
 pub enum Client2Server<'a> {
-    SuggestDifficulty(),
+    SuggestDifficulty(client_to_server::SuggestDifficulty),
+    SuggestTarget(client_to_server::SuggestTarget),
+    GetTransactions(client_to_server::GetTransactions),
     Subscribe(client_to_server::Subscribe<'a>),
     Authorize(client_to_server::Authorize),
     ExtranonceSubscribe(client_to_server::ExtranonceSubscribe),
@@ -159,6 +161,7 @@ pub enum Server2Client<'a> {
     SetDifficulty(server_to_client::SetDifficulty),
     SetExtranonce(server_to_client::SetExtranonce<'a>),
     SetVersionMask(server_to_client::SetVersionMask),
+    Reconnect(server_to_client::Reconnect),
 }
 
 impl<'a> From<Server2Client<'a>> for Method<'a> {
@@ -218,7 +221,29 @@ impl<'a> TryFrom<Message> for Method<'a> {
         match &msg {
             Message::StandardRequest(request) => match &request.method[..] {
                 "mining.suggest_difficulty" => {
-                    Ok(Method::Client2Server(Client2Server::SuggestDifficulty()))
+                    let method = request
+                        .clone()
+                        .try_into()
+                        .map_err(|e: ParsingMethodError| e.as_method_error(msg))?;
+                    Ok(Method::Client2Server(Client2Server::SuggestDifficulty(
+                        method,
+                    )))
+                }
+                "mining.suggest_target" => {
+                    let method = request
+                        .clone()
+                        .try_into()
+                        .map_err(|e: ParsingMethodError| e.as_method_error(msg))?;
+                    Ok(Method::Client2Server(Client2Server::SuggestTarget(method)))
+                }
+                "mining.get_transactions" => {
+                    let method = request
+                        .clone()
+                        .try_into()
+                        .map_err(|e: ParsingMethodError| e.as_method_error(msg))?;
+                    Ok(Method::Client2Server(Client2Server::GetTransactions(
+                        method,
+                    )))
                 }
                 "mining.subscribe" => {
                     let method = request
@@ -282,6 +307,13 @@ impl<'a> TryFrom<Message> for Method<'a> {
                         .map_err(|e: ParsingMethodError| e.as_method_error(msg))?;
                     Ok(Method::Server2Client(Server2Client::SetExtranonce(method)))
                 }
+                "client.reconnect" => {
+                    let method = notification
+                        .clone()
+                        .try_into()
+                        .map_err(|e: ParsingMethodError| e.as_method_error(msg))?;
+                    Ok(Method::Server2Client(Server2Client::Reconnect(method)))
+                }
                 _ => Err(MethodError::MethodNotFound(notification.clone().method)),
             },
             Message::OkResponse(response) => response
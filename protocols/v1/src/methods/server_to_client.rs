@@ -13,7 +13,106 @@ use crate::{
 
 // client.get_version()
 
-// client.reconnect
+/// client.reconnect(hostname, port, waittime)
+///
+/// The server asks the client to close the connection and to connect to `hostname`:`port` after
+/// `waittime` seconds. Every parameter is optional, a missing hostname or port means the current
+/// one. `port` and `wait_time` are only sent when the parameters before them are set.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reconnect {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub wait_time: Option<u32>,
+}
+
+impl From<Reconnect> for Message {
+    fn from(r: Reconnect) -> Self {
+        let params: Vec<Value> = match (r.host, r.port, r.wait_time) {
+            (None, _, _) => vec![],
+            (Some(host), None, _) => vec![host.into()],
+            (Some(host), Some(port), None) => vec![host.into(), port.into()],
+            (Some(host), Some(port), Some(wait_time)) => {
+                vec![host.into(), port.into(), wait_time.into()]
+            }
+        };
+        Message::Notification(Notification {
+            method: "client.reconnect".to_string(),
+            params: params.into(),
+        })
+    }
+}
+
+impl TryFrom<Notification> for Reconnect {
+    type Error = ParsingMethodError;
+
+    fn try_from(msg: Notification) -> Result<Self, Self::Error> {
+        let params = msg
+            .params
+            .as_array()
+            .ok_or_else(|| ParsingMethodError::not_array_from_value(msg.params.clone()))?;
+        if params.len() > 3 {
+            return Err(ParsingMethodError::wrong_args_from_value(msg.params));
+        }
+        let host = match params.first() {
+            Some(JString(host)) => Some(host.clone()),
+            Some(a) => return Err(ParsingMethodError::not_string_from_value(a.clone())),
+            None => None,
+        };
+        // some servers send the port as a string
+        let port = match params.get(1) {
+            Some(JNumber(b)) => Some(
+                b.as_u64()
+                    .and_then(|b| u16::try_from(b).ok())
+                    .ok_or_else(|| ParsingMethodError::not_unsigned_from_value(b.clone()))?,
+            ),
+            Some(JString(b)) => Some(
+                b.parse()
+                    .map_err(|_| ParsingMethodError::wrong_args_from_value(msg.params.clone()))?,
+            ),
+            Some(_) => return Err(ParsingMethodError::wrong_args_from_value(msg.params)),
+            None => None,
+        };
+        let wait_time = match params.get(2) {
+            Some(JNumber(c)) => Some(
+                c.as_u64()
+                    .and_then(|c| u32::try_from(c).ok())
+                    .ok_or_else(|| ParsingMethodError::not_unsigned_from_value(c.clone()))?,
+            ),
+            Some(_) => return Err(ParsingMethodError::wrong_args_from_value(msg.params)),
+            None => None,
+        };
+        Ok(Reconnect {
+            host,
+            port,
+            wait_time,
+        })
+    }
+}
+
+#[test]
+fn reconnect_round_trip() {
+    let reconnect = Reconnect {
+        host: Some("pool.example.com".to_string()),
+        port: Some(3333),
+        wait_time: Some(5),
+    };
+    let message: Message = reconnect.clone().into();
+    let notification = match message {
+        Message::Notification(n) => n,
+        _ => panic!("client.reconnect must be a notification"),
+    };
+    assert_eq!(notification.method, "client.reconnect");
+    assert_eq!(Reconnect::try_from(notification).unwrap(), reconnect);
+
+    let notification: Notification = serde_json::from_str(
+        r#"{"method":"client.reconnect","params":["pool.example.com","3333"]}"#,
+    )
+    .unwrap();
+    let reconnect = Reconnect::try_from(notification).unwrap();
+    assert_eq!(reconnect.port, Some(3333));
+    assert_eq!(reconnect.wait_time, None);
+}
 
 // client.show_message
 
@@ -42,6 +42,11 @@
 /// extensions.
 pub const EXTENSION_TYPE_NO_EXTENSION: u16 = 0;
 
+/// Identifier of the Worker-Specific Hashrate Tracking extension. It is the type of the TLV
+/// fields appended to `SubmitSharesExtended` to carry the identity of the worker that found the
+/// share.
+pub const EXTENSION_TYPE_WORKER_HASHRATE_TRACKING: u16 = 0x0002;
+
 /// Size of the SV2 frame header in bytes.
 pub const SV2_FRAME_HEADER_SIZE: usize = 6;
 
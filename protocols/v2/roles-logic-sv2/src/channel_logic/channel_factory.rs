@@ -1,4 +1,7 @@
-use super::extended_to_standard_job;
+use super::{
+    extended_to_standard_job,
+    vardiff::{ChannelVardiff, VardiffConfig},
+};
 use crate::{
     common_properties::StandardChannel,
     job_creator::{self, JobsCreators},
@@ -10,7 +13,7 @@ use crate::{
 use mining_sv2::{
     ExtendedExtranonce, NewExtendedMiningJob, NewMiningJob, OpenExtendedMiningChannelSuccess,
     OpenMiningChannelError, OpenStandardMiningChannelSuccess, SetCustomMiningJob,
-    SetCustomMiningJobSuccess, SetNewPrevHash, SubmitSharesError, SubmitSharesExtended,
+    SetCustomMiningJobSuccess, SetNewPrevHash, SetTarget, SubmitSharesError, SubmitSharesExtended,
     SubmitSharesStandard, Target,
 };
 
@@ -955,12 +958,41 @@ impl ChannelFactory {
             },
         }
     }
-    /// updates the downstream target for the given channel_id
+    /// updates the downstream target for the given channel_id, extended or standard
     fn update_target_for_channel(&mut self, channel_id: u32, new_target: Target) -> Option<bool> {
-        let channel = self.extended_channels.get_mut(&channel_id)?;
-        channel.target = new_target.into();
+        if let Some(channel) = self.extended_channels.get_mut(&channel_id) {
+            channel.target = new_target.into();
+            return Some(true);
+        }
+        if let Some(channel) = self
+            .standard_channels_for_hom_downstreams
+            .get_mut(&channel_id)
+        {
+            channel.target = new_target;
+            return Some(true);
+        }
+        let group_id = self.channel_to_group_id.get(&channel_id)?;
+        let complete_id = GroupId::into_complete_id(*group_id, channel_id);
+        let channel = self
+            .standard_channels_for_non_hom_downstreams
+            .get_mut(&complete_id)?;
+        channel.target = new_target;
         Some(true)
     }
+    /// current downstream target of the given channel_id, extended or standard
+    fn channel_target(&self, channel_id: u32) -> Option<Target> {
+        if let Some(channel) = self.extended_channels.get(&channel_id) {
+            return Some(channel.target.clone().into());
+        }
+        if let Some(channel) = self.standard_channels_for_hom_downstreams.get(&channel_id) {
+            return Some(channel.target.clone());
+        }
+        let group_id = self.channel_to_group_id.get(&channel_id)?;
+        let complete_id = GroupId::into_complete_id(*group_id, channel_id);
+        self.standard_channels_for_non_hom_downstreams
+            .get(&complete_id)
+            .map(|channel| channel.target.clone())
+    }
 }
 
 /// Used by a pool to in order to manage all downstream channel. It add job creation capabilities
@@ -973,6 +1005,9 @@ pub struct PoolChannelFactory {
     pool_signature: String,
     // extedned_channel_id -> SetCustomMiningJob
     negotiated_jobs: HashMap<u32, SetCustomMiningJob<'static>, BuildNoHashHasher<u32>>,
+    vardiff: Option<VardiffConfig>,
+    // channel_id -> vardiff state, only filled when vardiff is enabled
+    channels_vardiff: HashMap<u32, ChannelVardiff, BuildNoHashHasher<u32>>,
 }
 
 impl PoolChannelFactory {
@@ -1012,8 +1047,16 @@ impl PoolChannelFactory {
             pool_coinbase_outputs,
             pool_signature,
             negotiated_jobs: HashMap::with_hasher(BuildNoHashHasher::default()),
+            vardiff: None,
+            channels_vardiff: HashMap::with_hasher(BuildNoHashHasher::default()),
         }
     }
+    /// Enables server side vardiff, see [`super::vardiff`]. The targets of the channels opened
+    /// from now on are computed for `config.shares_per_minute`.
+    pub fn set_vardiff(&mut self, config: VardiffConfig) {
+        self.inner.share_per_min = config.shares_per_minute;
+        self.vardiff = Some(config);
+    }
     /// Calls [`ChannelFactory::add_standard_channel`]
     pub fn add_standard_channel(
         &mut self,
@@ -1021,9 +1064,25 @@ impl PoolChannelFactory {
         downstream_hash_rate: f32,
         is_header_only: bool,
         id: u32,
-    ) -> Result<Vec<Mining>, Error> {
-        self.inner
-            .add_standard_channel(request_id, downstream_hash_rate, is_header_only, id)
+    ) -> Result<Vec<Mining<'static>>, Error> {
+        let downstream_hash_rate = self.sanitize_hashrate(downstream_hash_rate);
+        let result: Vec<Mining<'static>> = self
+            .inner
+            .add_standard_channel(request_id, downstream_hash_rate, is_header_only, id)?
+            .into_iter()
+            .map(|message| message.into_static())
+            .collect();
+        let channel_ids: Vec<u32> = result
+            .iter()
+            .filter_map(|message| match message {
+                Mining::OpenStandardMiningChannelSuccess(success) => Some(success.channel_id),
+                _ => None,
+            })
+            .collect();
+        for channel_id in channel_ids {
+            self.start_vardiff(channel_id, downstream_hash_rate);
+        }
+        Ok(result)
     }
     /// Calls [`ChannelFactory::new_extended_channel`]
     pub fn new_extended_channel(
@@ -1032,8 +1091,92 @@ impl PoolChannelFactory {
         hash_rate: f32,
         min_extranonce_size: u16,
     ) -> Result<Vec<Mining<'static>>, Error> {
+        let hash_rate = self.sanitize_hashrate(hash_rate);
+        let result = self
+            .inner
+            .new_extended_channel(request_id, hash_rate, min_extranonce_size)?;
+        for message in &result {
+            if let Mining::OpenExtendedMiningChannelSuccess(success) = message {
+                self.start_vardiff(success.channel_id, hash_rate);
+            }
+        }
+        Ok(result)
+    }
+    fn sanitize_hashrate(&self, hash_rate: f32) -> f32 {
+        match &self.vardiff {
+            Some(config) => config.sanitize_hashrate(hash_rate),
+            None => hash_rate,
+        }
+    }
+    fn start_vardiff(&mut self, channel_id: u32, hash_rate: f32) {
+        if self.vardiff.is_some() {
+            self.channels_vardiff
+                .insert(channel_id, ChannelVardiff::new(hash_rate, now()));
+        }
+    }
+    /// Counts the accepted shares of each channel for vardiff
+    fn on_share_checked(&mut self, channel_id: u32, result: &Result<OnNewShare, Error>) {
+        if let Ok(OnNewShare::ShareMeetDownstreamTarget)
+        | Ok(OnNewShare::ShareMeetBitcoinTarget(_)) = result
+        {
+            if let Some(vardiff) = self.channels_vardiff.get_mut(&channel_id) {
+                vardiff.on_share_accepted();
+            }
+        }
+    }
+    /// Called when the downstream sends `UpdateChannel`: the target of the channel is set from
+    /// the declared hashrate, that also becomes the new starting point for vardiff. Returns the
+    /// new target of the channel.
+    pub fn update_channel_hashrate(
+        &mut self,
+        channel_id: u32,
+        hash_rate: f32,
+    ) -> Result<binary_sv2::U256<'static>, Error> {
+        let hash_rate = self.sanitize_hashrate(hash_rate);
+        let target =
+            crate::utils::hash_rate_to_target(hash_rate.into(), self.inner.share_per_min.into())?;
         self.inner
-            .new_extended_channel(request_id, hash_rate, min_extranonce_size)
+            .update_target_for_channel(channel_id, target.clone().into());
+        if let Some(vardiff) = self.channels_vardiff.get_mut(&channel_id) {
+            *vardiff = ChannelVardiff::new(hash_rate, now());
+        }
+        Ok(target)
+    }
+    /// Measures the hashrate of `channel_id` from its accepted shares and returns the
+    /// `SetTarget` to send downstream if the channel target has changed. Returns `None` when
+    /// vardiff is not enabled or when the target is still good.
+    pub fn update_vardiff(&mut self, channel_id: u32) -> Result<Option<SetTarget<'static>>, Error> {
+        let config = match &self.vardiff {
+            Some(config) => *config,
+            None => return Ok(None),
+        };
+        let new_hash_rate = match self.channels_vardiff.get_mut(&channel_id) {
+            Some(vardiff) => match vardiff.try_update(&config, now()) {
+                Some(new_hash_rate) => new_hash_rate,
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        let maximum_target = crate::utils::hash_rate_to_target(
+            new_hash_rate.into(),
+            config.shares_per_minute.into(),
+        )?;
+        debug!(
+            "Vardiff: channel {} hashrate {} h/s",
+            channel_id, new_hash_rate
+        );
+        self.inner
+            .update_target_for_channel(channel_id, maximum_target.clone().into());
+        Ok(Some(SetTarget {
+            channel_id,
+            maximum_target,
+        }))
+    }
+    /// Hashrate of the channel measured by vardiff, `None` if vardiff is not enabled
+    pub fn channel_hashrate(&self, channel_id: u32) -> Option<f32> {
+        self.channels_vardiff
+            .get(&channel_id)
+            .map(|vardiff| vardiff.hashrate())
     }
     /// Called when we want to replicate a channel already opened by another actor.
     /// is used only in the jd client from the template provider module to mock a pool.
@@ -1069,7 +1212,8 @@ impl PoolChannelFactory {
         self.inner.on_new_prev_hash(new_prev_hash)?;
         Ok(job_id)
     }
-    /// Called only when a new template is received by a Template Provider
+    /// Called only when a new template is received by a Template Provider. The template's
+    /// `coinbase_tx_value_remaining` is distributed across the pool outputs.
     pub fn on_new_template(
         &mut self,
         m: &mut NewTemplate<'static>,
@@ -1089,6 +1233,13 @@ impl PoolChannelFactory {
         &mut self,
         m: SubmitSharesStandard,
     ) -> Result<OnNewShare, Error> {
+        let channel_id = m.channel_id;
+        let result = self.check_shares_standard(m);
+        self.on_share_checked(channel_id, &result);
+        result
+    }
+
+    fn check_shares_standard(&mut self, m: SubmitSharesStandard) -> Result<OnNewShare, Error> {
         match self.inner.channel_to_group_id.get(&m.channel_id) {
             Some(g_id) => {
                 let referenced_job = self
@@ -1147,6 +1298,13 @@ impl PoolChannelFactory {
         &mut self,
         m: SubmitSharesExtended,
     ) -> Result<OnNewShare, Error> {
+        let channel_id = m.channel_id;
+        let result = self.check_shares_extended(m);
+        self.on_share_checked(channel_id, &result);
+        result
+    }
+
+    fn check_shares_extended(&mut self, m: SubmitSharesExtended) -> Result<OnNewShare, Error> {
         let target = self.job_creator.last_target();
         // When downstream set a custom mining job we add the job to the negotiated job
         // hashmap, with the extended channel id as a key. Whenever the pool receive a share must
@@ -1263,10 +1421,35 @@ impl PoolChannelFactory {
         self.inner.extended_channels.keys().copied().collect()
     }
 
+    /// Replaces the outputs that the pool adds to every coinbase. The `value` of each output is
+    /// used as its weight when `coinbase_tx_value_remaining` is split across them on the next
+    /// template, see [`job_creator::split_coinbase_value`].
     pub fn update_pool_outputs(&mut self, outs: Vec<TxOut>) {
         self.pool_coinbase_outputs = outs;
     }
 
+    /// Returns the id of the template of the job the shares of `channel_id` are checked
+    /// against, `None` when the channel mines a custom job
+    pub fn share_template_id(&self, channel_id: u32) -> Option<u64> {
+        if self.negotiated_jobs.contains_key(&channel_id) {
+            return None;
+        }
+        let (job, _) = self.inner.last_valid_job.as_ref()?;
+        self.job_creator.get_template_id_from_job(job.job_id)
+    }
+
+    /// Replaces the signature that the pool adds to the coinbase script, it is used from the
+    /// next template
+    pub fn update_pool_signature(&mut self, pool_signature: String) {
+        self.pool_signature = pool_signature;
+    }
+
+    /// calls [`ChannelFactory::channel_target`]
+    /// Get the current target of a downstream channel.
+    pub fn channel_target(&self, channel_id: u32) -> Option<Target> {
+        self.inner.channel_target(channel_id)
+    }
+
     /// calls [`ChannelFactory::update_target_for_channel`]
     /// Set a partucular downstream channel target.
     pub fn update_target_for_channel(
@@ -1728,6 +1911,12 @@ impl ProxyExtendedChannelFactory {
         self.inner.extranonces.get_range0_len()
     }
 
+    /// Replaces the extranonce space of the factory, e.g. when the upstream sends a new
+    /// extranonce prefix. Only the channels opened after the call use the new space.
+    pub fn update_extranonces(&mut self, extranonces: ExtendedExtranonce) {
+        self.inner.extranonces = extranonces;
+    }
+
     /// calls [`ChannelFactory::update_target_for_channel`]
     pub fn update_target_for_channel(
         &mut self,
@@ -1736,6 +1925,11 @@ impl ProxyExtendedChannelFactory {
     ) -> Option<bool> {
         self.inner.update_target_for_channel(channel_id, new_target)
     }
+
+    /// calls [`ChannelFactory::channel_target`]
+    pub fn channel_target(&self, channel_id: u32) -> Option<Target> {
+        self.inner.channel_target(channel_id)
+    }
 }
 
 /// Used by proxies for tracking upstream targets.
@@ -1756,6 +1950,14 @@ impl ExtendedChannelKind {
         }
     }
 }
+/// Unix timestamp in seconds
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1,5 +1,6 @@
 pub mod channel_factory;
 pub mod proxy_group_channel;
+pub mod vardiff;
 
 use mining_sv2::{NewExtendedMiningJob, NewMiningJob};
 use std::convert::TryInto;
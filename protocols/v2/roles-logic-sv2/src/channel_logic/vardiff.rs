@@ -0,0 +1,132 @@
+//! Server side variable difficulty for the channels of a
+//! [`PoolChannelFactory`](super::channel_factory::PoolChannelFactory).
+//!
+//! The nominal hashrate sent by the downstream when a channel is opened or updated is only the
+//! starting point. The hashrate of every channel is measured from the rate of its accepted
+//! shares and, at most once every `retarget_interval_secs`, the channel target is moved so that
+//! the channel submits about `shares_per_minute` shares.
+
+/// Retarget only when the measured hashrate differs more than this fraction from the current one
+const MIN_HASHRATE_CHANGE: f32 = 0.3;
+/// Max factor the hashrate of a channel can grow in a single retarget
+const MAX_HASHRATE_INCREASE: f32 = 16.0;
+/// Factor the hashrate of a channel is divided by when no share has been submitted since the
+/// last retarget
+const NO_SHARES_HASHRATE_DECREASE: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VardiffConfig {
+    /// Number of shares per minute each channel should submit
+    pub shares_per_minute: f32,
+    /// Min number of seconds between two retargets of the same channel
+    pub retarget_interval_secs: u64,
+    /// Lower bound for the hashrate of a channel in h/s, it is also used in place of declared
+    /// hashrates that are not valid (zero, negative or not finite)
+    pub min_hashrate: f32,
+}
+
+impl VardiffConfig {
+    /// Returns `hashrate` if it is a valid hashrate, `min_hashrate` otherwise
+    pub fn sanitize_hashrate(&self, hashrate: f32) -> f32 {
+        if hashrate.is_finite() && hashrate > self.min_hashrate {
+            hashrate
+        } else {
+            self.min_hashrate
+        }
+    }
+}
+
+/// Vardiff state of a single channel
+#[derive(Debug, Clone)]
+pub struct ChannelVardiff {
+    hashrate: f32,
+    shares_since_last_update: u32,
+    // unix timestamp in seconds
+    last_update: u64,
+}
+
+impl ChannelVardiff {
+    pub fn new(hashrate: f32, now: u64) -> Self {
+        Self {
+            hashrate,
+            shares_since_last_update: 0,
+            last_update: now,
+        }
+    }
+
+    /// Hashrate of the channel in h/s, the target of the channel is derived from it
+    pub fn hashrate(&self) -> f32 {
+        self.hashrate
+    }
+
+    pub fn on_share_accepted(&mut self) {
+        self.shares_since_last_update += 1;
+    }
+
+    /// Measures the channel hashrate from the shares accepted since the last update. Returns
+    /// the new hashrate when the channel target has to be changed.
+    pub fn try_update(&mut self, config: &VardiffConfig, now: u64) -> Option<f32> {
+        let elapsed = now.saturating_sub(self.last_update);
+        if elapsed == 0 || elapsed < config.retarget_interval_secs {
+            return None;
+        }
+        let new_hashrate = if self.shares_since_last_update == 0 {
+            self.hashrate / NO_SHARES_HASHRATE_DECREASE
+        } else {
+            let realized_shares_per_minute =
+                self.shares_since_last_update as f32 * 60.0 / elapsed as f32;
+            self.hashrate
+                * (realized_shares_per_minute / config.shares_per_minute).min(MAX_HASHRATE_INCREASE)
+        };
+        let new_hashrate = config.sanitize_hashrate(new_hashrate);
+        self.shares_since_last_update = 0;
+        self.last_update = now;
+        if (new_hashrate - self.hashrate).abs() < self.hashrate * MIN_HASHRATE_CHANGE {
+            return None;
+        }
+        self.hashrate = new_hashrate;
+        Some(new_hashrate)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: VardiffConfig = VardiffConfig {
+        shares_per_minute: 6.0,
+        retarget_interval_secs: 60,
+        min_hashrate: 1000.0,
+    };
+
+    #[test]
+    fn test_vardiff_follows_share_rate() {
+        let mut channel = ChannelVardiff::new(1_000_000.0, 0);
+        // the miner is 2x faster than declared
+        for _ in 0..12 {
+            channel.on_share_accepted();
+        }
+        assert_eq!(channel.try_update(&CONFIG, 30), None);
+        assert_eq!(channel.try_update(&CONFIG, 60), Some(2_000_000.0));
+        // the share rate is on target
+        for _ in 0..6 {
+            channel.on_share_accepted();
+        }
+        assert_eq!(channel.try_update(&CONFIG, 120), None);
+        assert_eq!(channel.hashrate(), 2_000_000.0);
+    }
+
+    #[test]
+    fn test_vardiff_corrects_wrong_declared_hashrate() {
+        // declared far too high, no share is found
+        let mut channel = ChannelVardiff::new(1e15, 0);
+        assert_eq!(channel.try_update(&CONFIG, 60), Some(2.5e14));
+        // declared far too low, the target is flooded with shares
+        let mut channel = ChannelVardiff::new(CONFIG.sanitize_hashrate(0.0), 0);
+        for _ in 0..100_000 {
+            channel.on_share_accepted();
+        }
+        assert_eq!(channel.try_update(&CONFIG, 60), Some(16_000.0));
+        assert_eq!(CONFIG.sanitize_hashrate(f32::NAN), CONFIG.min_hashrate);
+    }
+}
@@ -82,6 +82,9 @@ pub trait IsMiningUpstream<Down: IsMiningDownstream, Sel: DownstreamMiningSelect
     /// should return total hash rate local to the node
     fn total_hash_rate(&self) -> u64;
     fn add_hash_rate(&mut self, to_add: u64);
+    /// Called when the hash rate of a downstream stops being sent to the node, nodes that do not
+    /// track their hash rate can ignore it
+    fn remove_hash_rate(&mut self, _to_remove: u64) {}
     fn get_opened_channels(&mut self) -> &mut Vec<UpstreamChannel>;
     fn update_channels(&mut self, c: UpstreamChannel);
     fn is_header_only(&self) -> bool {
@@ -98,6 +101,11 @@ pub trait IsMiningDownstream: IsDownstream {
     fn is_header_only(&self) -> bool {
         self.get_downstream_mining_data().header_only
     }
+    /// Id of the upstream that the downstream has been paired with, if the downstream keeps track
+    /// of it. When it is known the routing logic opens the downstream channels with this upstream.
+    fn paired_upstream_id(&self) -> Option<u32> {
+        None
+    }
 }
 
 /// Implemented for the NullDownstreamMiningSelector
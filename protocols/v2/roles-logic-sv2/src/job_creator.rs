@@ -65,6 +65,9 @@ impl JobsCreators {
     }
 
     /// used to create new jobs when a new template arrives
+    ///
+    /// `coinbase_tx_value_remaining` is split across `pool_coinbase_outputs` as described in
+    /// [`split_coinbase_value`]
     pub fn on_new_template(
         &mut self,
         template: &mut NewTemplate,
@@ -72,6 +75,10 @@ impl JobsCreators {
         mut pool_coinbase_outputs: Vec<TxOut>,
         pool_signature: String,
     ) -> Result<NewExtendedMiningJob<'static>, Error> {
+        split_coinbase_value(
+            &mut pool_coinbase_outputs,
+            template.coinbase_tx_value_remaining,
+        )?;
         let server_tx_outputs = template.coinbase_tx_outputs.to_vec();
         let mut outputs = tx_outputs_to_costum_scripts(&server_tx_outputs);
         pool_coinbase_outputs.append(&mut outputs);
@@ -86,7 +93,7 @@ impl JobsCreators {
         self.templte_to_job_id.insert(template_id, next_job_id);
         new_extended_job(
             template,
-            &mut pool_coinbase_outputs,
+            &pool_coinbase_outputs,
             pool_signature,
             next_job_id,
             version_rolling_allowed,
@@ -135,6 +142,29 @@ impl JobsCreators {
     }
 }
 
+/// Splits `value_remaining` across `outputs`. The `value` already set on each output is used as
+/// its weight, so an output with value 2 receives twice as much as an output with value 1. If
+/// every weight is 0 the first output receives the whole amount. What is lost rounding down the
+/// other outputs goes to the first output.
+pub fn split_coinbase_value(outputs: &mut [TxOut], value_remaining: u64) -> Result<(), Error> {
+    if outputs.is_empty() {
+        return Err(Error::EmptyCoinbaseOutputs);
+    }
+    let total_weight: u128 = outputs.iter().map(|out| out.value as u128).sum();
+    if total_weight == 0 {
+        outputs[0].value = value_remaining;
+        return Ok(());
+    }
+    let mut distributed = 0;
+    for out in outputs.iter_mut() {
+        // Can not overflow: out.value <= total_weight
+        out.value = (value_remaining as u128 * out.value as u128 / total_weight) as u64;
+        distributed += out.value;
+    }
+    outputs[0].value += value_remaining - distributed;
+    Ok(())
+}
+
 pub fn extended_job_from_custom_job(
     referenced_job: &mining_sv2::SetCustomMiningJob,
     pool_signature: String,
@@ -142,6 +172,10 @@ pub fn extended_job_from_custom_job(
 ) -> Result<NewExtendedMiningJob<'static>, Error> {
     let mut outputs =
         tx_outputs_to_costum_scripts(referenced_job.coinbase_tx_outputs.clone().as_ref());
+    outputs
+        .first_mut()
+        .ok_or(Error::EmptyCoinbaseOutputs)?
+        .value = referenced_job.coinbase_tx_value_remaining;
     let mut template = NewTemplate {
         template_id: 0,
         future_template: false,
@@ -157,7 +191,7 @@ pub fn extended_job_from_custom_job(
     };
     new_extended_job(
         &mut template,
-        &mut outputs,
+        &outputs,
         pool_signature,
         0,
         true,
@@ -170,23 +204,19 @@ pub fn extended_job_from_custom_job(
 ///
 /// Pool related arguments:
 ///
-/// * `coinbase_outputs`: coinbase output transactions specified by the pool.
+/// * `coinbase_outputs`: coinbase output transactions specified by the pool, with their values
+///   already set.
 /// * `job_id`: incremented job identifier specified by the pool.
 /// * `version_rolling_allowed`: boolean specified by the channel.
 /// * `extranonce_len`: extranonce length specified by the channel.
 fn new_extended_job(
     new_template: &mut NewTemplate,
-    coinbase_outputs: &mut [TxOut],
+    coinbase_outputs: &[TxOut],
     pool_signature: String,
     job_id: u32,
     version_rolling_allowed: bool,
     extranonce_len: u8,
 ) -> Result<NewExtendedMiningJob<'static>, Error> {
-    coinbase_outputs[0].value = match new_template.coinbase_tx_value_remaining.checked_mul(1) {
-        //check that value_remaining is updated by TP
-        Some(result) => result,
-        None => return Err(Error::ValueRemainingNotUpdated),
-    };
     let tx_version = new_template
         .coinbase_tx_version
         .try_into()
@@ -464,24 +494,14 @@ impl StrippedCoinbaseTx {
     }
 }
 
-// Test
-#[cfg(test)]
-
-pub mod tests {
+/// Random templates, used by the property tests and by the mock Template Provider of the
+/// integration tests
+#[cfg(any(test, feature = "prop_test"))]
+pub mod template_gen {
     use super::*;
-    use crate::utils::merkle_root_from_path;
-    #[cfg(feature = "prop_test")]
-    use binary_sv2::u256_from_int;
     use quickcheck::{Arbitrary, Gen};
     use std::{cmp, vec};
 
-    #[cfg(feature = "prop_test")]
-    use std::borrow::BorrowMut;
-
-    use stratum_common::bitcoin::{
-        consensus::Encodable, secp256k1::Secp256k1, Network, PrivateKey, PublicKey,
-    };
-
     pub fn template_from_gen(g: &mut Gen) -> NewTemplate<'static> {
         let mut coinbase_prefix_gen = Gen::new(255);
         let mut coinbase_prefix: vec::Vec<u8> = vec::Vec::new();
@@ -522,6 +542,24 @@ pub mod tests {
             merkle_path,
         }
     }
+}
+
+// Test
+#[cfg(test)]
+
+pub mod tests {
+    use super::{template_gen::template_from_gen, *};
+    use crate::utils::merkle_root_from_path;
+    #[cfg(feature = "prop_test")]
+    use binary_sv2::u256_from_int;
+    use quickcheck::Gen;
+
+    #[cfg(feature = "prop_test")]
+    use std::borrow::BorrowMut;
+
+    use stratum_common::bitcoin::{
+        consensus::Encodable, secp256k1::Secp256k1, Network, PrivateKey, PublicKey,
+    };
 
     const PRIVATE_KEY_BTC: [u8; 32] = [34; 32];
     const NETWORK: Network = Network::Testnet;
@@ -682,6 +720,32 @@ pub mod tests {
         assert!(outs[1] == tx2);
     }
 
+    #[test]
+    fn split_coinbase_value_by_weight() {
+        let out = |value| TxOut {
+            value,
+            script_pubkey: stratum_common::bitcoin::Script::new_p2pk(&new_pub_key()),
+        };
+
+        let mut outputs = vec![out(0), out(0)];
+        split_coinbase_value(&mut outputs, 1000).unwrap();
+        assert_eq!(outputs[0].value, 1000);
+        assert_eq!(outputs[1].value, 0);
+
+        let mut outputs = vec![out(1), out(1), out(1)];
+        split_coinbase_value(&mut outputs, 1000).unwrap();
+        assert_eq!(outputs[0].value, 334);
+        assert_eq!(outputs[1].value, 333);
+        assert_eq!(outputs[2].value, 333);
+
+        let mut outputs = vec![out(98), out(2)];
+        split_coinbase_value(&mut outputs, 625_000_000).unwrap();
+        assert_eq!(outputs[0].value, 612_500_000);
+        assert_eq!(outputs[1].value, 12_500_000);
+
+        assert!(split_coinbase_value(&mut [], 1000).is_err());
+    }
+
     // test that witness stripped tx id matches that of the txid of the coinbase
     #[test]
     fn stripped_tx_id() {
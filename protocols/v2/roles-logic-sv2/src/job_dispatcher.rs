@@ -250,10 +250,7 @@ mod tests {
     use super::*;
     use crate::{
         errors::Error,
-        job_creator::{
-            tests::{new_pub_key, template_from_gen},
-            JobsCreators,
-        },
+        job_creator::{template_gen::template_from_gen, tests::new_pub_key, JobsCreators},
     };
     use binary_sv2::{u256_from_int, U256};
     use mining_sv2::Extranonce;
@@ -236,14 +236,18 @@ impl<
         request: &mut OpenStandardMiningChannel,
         downstream_mining_data: &CommonDownstreamData,
     ) -> Result<Arc<Mutex<Up>>, Error> {
-        let upstreams = self
-            .downstream_to_upstream_map
-            .get(downstream_mining_data)
-            .ok_or(Error::NoCompatibleUpstream(*downstream_mining_data))?;
-        // If we are here a list of possible upstreams has been already selected
-        // TODO the upstream selection logic should be specified by the caller
-        let upstream =
-            Self::select_upstreams(&mut upstreams.to_vec()).ok_or(Error::NoUpstreamsConnected)?;
+        let upstream = match self.paired_upstream(&downstream)? {
+            Some(upstream) => upstream,
+            None => {
+                let upstreams = self
+                    .downstream_to_upstream_map
+                    .get(downstream_mining_data)
+                    .ok_or(Error::NoCompatibleUpstream(*downstream_mining_data))?;
+                // If we are here a list of possible upstreams has been already selected
+                self.select_upstreams(&mut upstreams.to_vec())
+                    .ok_or(Error::NoUpstreamsConnected)?
+            }
+        };
         let old_id = request.get_request_id_as_u32();
         let new_req_id = upstream
             .safe_lock(|u| u.get_mapper().unwrap().on_open_channel(old_id))
@@ -263,30 +267,6 @@ pub struct MiningProxyRoutingLogic<
     pub upstream_selector: GeneralMiningSelector<Sel, Down, Up>,
     pub downstream_id_generator: Id,
     pub downstream_to_upstream_map: HashMap<CommonDownstreamData, Vec<Arc<Mutex<Up>>>>,
-    //pub upstream_startegy: MiningUpstreamSelectionStrategy<Up,Down,Sel>,
-}
-
-fn minor_total_hr_upstream<Down, Up, Sel>(ups: &mut [Arc<Mutex<Up>>]) -> Arc<Mutex<Up>>
-where
-    Down: IsMiningDownstream + D,
-    Up: IsMiningUpstream<Down, Sel> + D,
-    Sel: DownstreamMiningSelector<Down> + D,
-{
-    ups.iter_mut()
-        .reduce(|acc, item| {
-            // Is fine to unwrap a safe_lock result
-            if acc.safe_lock(|x| x.total_hash_rate()).unwrap()
-                < item.safe_lock(|x| x.total_hash_rate()).unwrap()
-            {
-                acc
-            } else {
-                item
-            }
-        })
-        // Internal private function we only call thi function with non void vectors so is safe to
-        // unwrap here
-        .unwrap()
-        .clone()
 }
 
 fn filter_header_only<Down, Up, Sel>(ups: &mut [Arc<Mutex<Up>>]) -> Vec<Arc<Mutex<Up>>>
@@ -306,45 +286,41 @@ where
         .collect()
 }
 
-/// If only one upstream is avaiable return it.
-/// Try to return an upstream that is not header only.
-/// Return the upstream that has less hash rate from downstreams.
-fn select_upstream<Down, Up, Sel>(ups: &mut [Arc<Mutex<Up>>]) -> Option<Arc<Mutex<Up>>>
-where
-    Down: IsMiningDownstream + D,
-    Up: IsMiningUpstream<Down, Sel> + D,
-    Sel: DownstreamMiningSelector<Down> + D,
-{
-    if ups.is_empty() {
-        None
-    } else if ups.len() == 1 {
-        Some(ups[0].clone())
-    } else if !filter_header_only(ups).is_empty() {
-        Some(minor_total_hr_upstream(&mut filter_header_only(ups)))
-    } else {
-        Some(minor_total_hr_upstream(ups))
-    }
-}
-
 impl<
         Down: IsMiningDownstream + D,
         Up: IsMiningUpstream<Down, Sel> + D,
         Sel: DownstreamMiningSelector<Down> + D,
     > MiningProxyRoutingLogic<Down, Up, Sel>
 {
-    /// TODO this should stay in a enum UpstreamSelectionLogic that get passed from the caller to
-    /// the several methods
-    fn select_upstreams(ups: &mut [Arc<Mutex<Up>>]) -> Option<Arc<Mutex<Up>>> {
-        select_upstream(ups)
+    /// Try to return an upstream that is not header only, the upstream is chosen with the
+    /// selection strategy of the upstream selector.
+    fn select_upstreams(&self, ups: &mut [Arc<Mutex<Up>>]) -> Option<Arc<Mutex<Up>>> {
+        let not_header_only = filter_header_only(ups);
+        if not_header_only.is_empty() {
+            self.upstream_selector.select_upstream(ups)
+        } else {
+            self.upstream_selector.select_upstream(&not_header_only)
+        }
+    }
+
+    /// The upstream that the downstream has been paired with, when the downstream keeps track of
+    /// it
+    fn paired_upstream(
+        &self,
+        downstream: &Arc<Mutex<Down>>,
+    ) -> Result<Option<Arc<Mutex<Up>>>, Error> {
+        let upstream_id = downstream
+            .safe_lock(|d| d.paired_upstream_id())
+            .map_err(|e| Error::PoisonLock(e.to_string()))?;
+        Ok(upstream_id.and_then(|id| self.upstream_selector.get_upstream(id)))
     }
 
     /// On setup connection the proxy finds all the upstreams that support the downstream connection,
     /// creates a downstream message parser that points to all the possible upstreams, and then responds
     /// with suppported flags.
     ///
-    /// The upstream with min total_hash_rate is selected (TODO a method to let the caller which
-    /// upstream select from the possible ones should be added
-    /// on_setup_connection_mining_header_only_2 that return a Vec of possibe upstreams)
+    /// The upstream is selected with the strategy of the upstream selector, see
+    /// [`crate::selectors::UpstreamSelectionStrategy`]
     ///
     /// This function returns a downstream id that the new created downstream must return via the
     /// trait function get_id and the flags of the paired upstream
@@ -353,9 +329,9 @@ impl<
         pair_settings: &PairSettings,
     ) -> Result<(CommonDownstreamData, SetupConnectionSuccess), Error> {
         let mut upstreams = self.upstream_selector.on_setup_connection(pair_settings)?;
-        // TODO the upstream selection logic should be specified by the caller
-        let upstream =
-            Self::select_upstreams(&mut upstreams.0).ok_or(Error::NoUpstreamsConnected)?;
+        let upstream = self
+            .select_upstreams(&mut upstreams.0)
+            .ok_or(Error::NoUpstreamsConnected)?;
         let downstream_data = CommonDownstreamData {
             header_only: true,
             work_selection: false,
@@ -396,11 +372,14 @@ impl<
             .safe_lock(|d| d.get_downstream_mining_data())
             .map_err(|e| crate::Error::PoisonLock(e.to_string()))?;
         // header only downstream must map to only one upstream
-        let upstream = self
-            .downstream_to_upstream_map
-            .get(&downstream_mining_data)
-            .ok_or(crate::Error::NoCompatibleUpstream(downstream_mining_data))?[0]
-            .clone();
+        let upstream = match self.paired_upstream(&downstream)? {
+            Some(upstream) => upstream,
+            None => self
+                .downstream_to_upstream_map
+                .get(&downstream_mining_data)
+                .ok_or(crate::Error::NoCompatibleUpstream(downstream_mining_data))?[0]
+                .clone(),
+        };
         #[cfg(feature = "with_serde")]
         upstream
             .safe_lock(|u| {
@@ -86,7 +86,7 @@ impl<Down: IsMiningDownstream> DownstreamMiningSelector<Down>
 
     fn remove_downstream(&mut self, d: &Arc<Mutex<Down>>) {
         for dws in self.channel_id_to_downstreams.values_mut() {
-            dws.retain(|d| !Arc::ptr_eq(d, d));
+            dws.retain(|down| !Arc::ptr_eq(down, d));
         }
 
         self._remove_downstream(d);
@@ -209,6 +209,74 @@ pub trait UpstreamMiningSelctor<
     fn get_upstream(&self, upstream_id: u32) -> Option<Arc<Mutex<Up>>>;
 }
 
+/// How an upstream is chosen between the ones that can accept a downstream. Every strategy is
+/// based on the hashrate that the downstreams already send to each upstream, see
+/// [`IsMiningUpstream::total_hash_rate`].
+#[derive(Debug, Clone)]
+pub enum UpstreamSelectionStrategy {
+    /// The upstream with the smallest hashrate
+    LeastLoaded,
+    /// The first upstream in the list of ids, the next ones are used only when the previous are
+    /// not available. Upstreams that are not in the list come last.
+    Priority(Vec<u32>),
+    /// The hashrate is split between the upstreams proportionally to their weight (upstream id ->
+    /// weight). Upstreams without a positive weight are used only when no weighted upstream is
+    /// available.
+    Weighted(HashMap<u32, f32, BuildNoHashHasher<u32>>),
+}
+
+impl Default for UpstreamSelectionStrategy {
+    fn default() -> Self {
+        Self::LeastLoaded
+    }
+}
+
+impl UpstreamSelectionStrategy {
+    /// Takes the id and the hashrate of each candidate upstream and returns the index of the
+    /// chosen one, `None` if there are no candidates
+    pub fn pick(&self, candidates: &[(u32, u64)]) -> Option<usize> {
+        let least_loaded = || {
+            candidates
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (_, hash_rate))| *hash_rate)
+                .map(|(index, _)| index)
+        };
+        match self {
+            Self::LeastLoaded => least_loaded(),
+            Self::Priority(ids) => candidates
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (id, _))| ids.iter().position(|x| x == id).unwrap_or(usize::MAX))
+                .map(|(index, _)| index),
+            Self::Weighted(weights) => {
+                let weighted = candidates
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, (id, hr))| match weights.get(id) {
+                        Some(weight) if *weight > 0.0 => Some((index, *hr as f64, *weight as f64)),
+                        _ => None,
+                    });
+                // The upstream that is further below its share of the hashrate, with equal load
+                // the one with the bigger weight
+                weighted
+                    .min_by(|(_, hr_a, weight_a), (_, hr_b, weight_b)| {
+                        (hr_a / weight_a)
+                            .partial_cmp(&(hr_b / weight_b))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                            .then(
+                                weight_b
+                                    .partial_cmp(weight_a)
+                                    .unwrap_or(std::cmp::Ordering::Equal),
+                            )
+                    })
+                    .map(|(index, _, _)| index)
+                    .or_else(least_loaded)
+            }
+        }
+    }
+}
+
 /// Upstream selector is used to chose between a set of known mining upstream nodes which one/ones
 /// can accept messages from a specific mining downstream node
 #[derive(Debug)]
@@ -217,8 +285,11 @@ pub struct GeneralMiningSelector<
     Down: IsMiningDownstream,
     Up: IsMiningUpstream<Down, Sel>,
 > {
+    /// Upstreams that are available
     pub upstreams: Vec<Arc<Mutex<Up>>>,
+    /// Every known upstream, also the ones that are not available
     pub id_to_upstream: HashMap<u32, Arc<Mutex<Up>>, BuildNoHashHasher<u32>>,
+    strategy: UpstreamSelectionStrategy,
     sel: std::marker::PhantomData<Sel>,
     down: std::marker::PhantomData<Down>,
 }
@@ -238,6 +309,7 @@ impl<
         Self {
             upstreams,
             id_to_upstream,
+            strategy: UpstreamSelectionStrategy::default(),
             sel: std::marker::PhantomData,
             down: std::marker::PhantomData,
         }
@@ -245,6 +317,35 @@ impl<
     pub fn update_upstreams(&mut self, upstreams: Vec<Arc<Mutex<Up>>>) {
         self.upstreams = upstreams;
     }
+
+    pub fn strategy(&self) -> &UpstreamSelectionStrategy {
+        &self.strategy
+    }
+
+    pub fn set_strategy(&mut self, strategy: UpstreamSelectionStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// Known upstreams that are not in the available ones
+    pub fn unavailable_upstreams(&self) -> Vec<Arc<Mutex<Up>>> {
+        self.id_to_upstream
+            .values()
+            .filter(|up| !self.upstreams.iter().any(|u| Arc::ptr_eq(u, up)))
+            .cloned()
+            .collect()
+    }
+
+    /// Chooses an upstream between `ups` according to the selection strategy
+    pub fn select_upstream(&self, ups: &[Arc<Mutex<Up>>]) -> Option<Arc<Mutex<Up>>> {
+        let candidates: Vec<(u32, u64)> = ups
+            .iter()
+            // Is ok to unwrap safe_lock result
+            .map(|up| up.safe_lock(|u| (u.get_id(), u.total_hash_rate())).unwrap())
+            .collect();
+        self.strategy
+            .pick(&candidates)
+            .map(|index| ups[index].clone())
+    }
 }
 impl<
         Sel: DownstreamMiningSelector<Down>,
@@ -291,3 +392,41 @@ impl<
         self.id_to_upstream.get(&upstream_id).cloned()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn least_loaded_picks_smallest_hash_rate() {
+        let strategy = UpstreamSelectionStrategy::LeastLoaded;
+        assert_eq!(strategy.pick(&[]), None);
+        assert_eq!(strategy.pick(&[(0, 100), (1, 10), (2, 50)]), Some(1));
+    }
+
+    #[test]
+    fn priority_picks_first_available() {
+        let strategy = UpstreamSelectionStrategy::Priority(vec![2, 0]);
+        assert_eq!(strategy.pick(&[(0, 0), (1, 0), (2, 1000)]), Some(2));
+        assert_eq!(strategy.pick(&[(0, 1000), (1, 0)]), Some(0));
+        assert_eq!(strategy.pick(&[(1, 0), (3, 0)]), Some(0));
+    }
+
+    #[test]
+    fn weighted_splits_hash_rate() {
+        let mut weights = HashMap::with_hasher(BuildNoHashHasher::default());
+        weights.insert(0, 75.0);
+        weights.insert(1, 25.0);
+        let strategy = UpstreamSelectionStrategy::Weighted(weights);
+        let mut candidates = [(0, 0), (1, 0), (2, 0)];
+        let mut picked = [0; 3];
+        for _ in 0..100 {
+            let index = strategy.pick(&candidates).unwrap();
+            candidates[index].1 += 10;
+            picked[index] += 1;
+        }
+        assert_eq!(picked, [75, 25, 0]);
+        // Upstreams without weight are used when the weighted ones are not available
+        assert_eq!(strategy.pick(&[(2, 100), (3, 10)]), Some(1));
+    }
+}
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, Str0255, B0255, B064K};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -37,6 +39,103 @@ pub struct AllocateMiningJobTokenSuccess<'decoder> {
     pub async_mining_allowed: bool,
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CAllocateMiningJobToken {
+    user_identifier: CVec,
+    request_id: u32,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_allocate_mining_job_token(s: CAllocateMiningJobToken) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CAllocateMiningJobToken {
+    fn drop(&mut self) {
+        free_vec(&mut self.user_identifier);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<AllocateMiningJobToken<'a>> for CAllocateMiningJobToken {
+    fn from(v: AllocateMiningJobToken<'a>) -> Self {
+        Self {
+            user_identifier: v.user_identifier.into(),
+            request_id: v.request_id,
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CAllocateMiningJobToken {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<AllocateMiningJobToken<'a>, Error> {
+        let user_identifier: Str0255 = self.user_identifier.as_mut_slice().try_into()?;
+        Ok(AllocateMiningJobToken {
+            user_identifier,
+            request_id: self.request_id,
+        })
+    }
+}
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CAllocateMiningJobTokenSuccess {
+    request_id: u32,
+    mining_job_token: CVec,
+    coinbase_output_max_additional_size: u32,
+    coinbase_output: CVec,
+    async_mining_allowed: bool,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_allocate_mining_job_token_success(s: CAllocateMiningJobTokenSuccess) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CAllocateMiningJobTokenSuccess {
+    fn drop(&mut self) {
+        free_vec(&mut self.mining_job_token);
+        free_vec(&mut self.coinbase_output);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<AllocateMiningJobTokenSuccess<'a>> for CAllocateMiningJobTokenSuccess {
+    fn from(v: AllocateMiningJobTokenSuccess<'a>) -> Self {
+        Self {
+            request_id: v.request_id,
+            mining_job_token: v.mining_job_token.into(),
+            coinbase_output_max_additional_size: v.coinbase_output_max_additional_size,
+            coinbase_output: v.coinbase_output.into(),
+            async_mining_allowed: v.async_mining_allowed,
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CAllocateMiningJobTokenSuccess {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<AllocateMiningJobTokenSuccess<'a>, Error> {
+        let mining_job_token: B0255 = self.mining_job_token.as_mut_slice().try_into()?;
+        let coinbase_output: B064K = self.coinbase_output.as_mut_slice().try_into()?;
+        Ok(AllocateMiningJobTokenSuccess {
+            request_id: self.request_id,
+            mining_job_token,
+            coinbase_output_max_additional_size: self.coinbase_output_max_additional_size,
+            coinbase_output,
+            async_mining_allowed: self.async_mining_allowed,
+        })
+    }
+}
+
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
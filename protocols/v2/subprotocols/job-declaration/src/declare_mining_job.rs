@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, free_vec_2, CVec, CVec2};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Seq064K, Serialize, ShortTxId, Str0255, B0255, B064K, U256};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -48,6 +50,178 @@ pub struct DeclareMiningJobError<'decoder> {
     pub error_details: B064K<'decoder>,
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CDeclareMiningJob {
+    request_id: u32,
+    mining_job_token: CVec,
+    version: u32,
+    coinbase_prefix: CVec,
+    coinbase_suffix: CVec,
+    tx_short_hash_nonce: u64,
+    tx_short_hash_list: CVec2,
+    tx_hash_list_hash: CVec,
+    excess_data: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_declare_mining_job(s: CDeclareMiningJob) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CDeclareMiningJob {
+    fn drop(&mut self) {
+        free_vec(&mut self.mining_job_token);
+        free_vec(&mut self.coinbase_prefix);
+        free_vec(&mut self.coinbase_suffix);
+        free_vec_2(&mut self.tx_short_hash_list);
+        free_vec(&mut self.tx_hash_list_hash);
+        free_vec(&mut self.excess_data);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<DeclareMiningJob<'a>> for CDeclareMiningJob {
+    fn from(v: DeclareMiningJob<'a>) -> Self {
+        Self {
+            request_id: v.request_id,
+            mining_job_token: v.mining_job_token.into(),
+            version: v.version,
+            coinbase_prefix: v.coinbase_prefix.into(),
+            coinbase_suffix: v.coinbase_suffix.into(),
+            tx_short_hash_nonce: v.tx_short_hash_nonce,
+            tx_short_hash_list: v.tx_short_hash_list.into(),
+            tx_hash_list_hash: v.tx_hash_list_hash.into(),
+            excess_data: v.excess_data.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CDeclareMiningJob {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<DeclareMiningJob<'a>, Error> {
+        let mining_job_token: B0255 = self.mining_job_token.as_mut_slice().try_into()?;
+        let coinbase_prefix: B064K = self.coinbase_prefix.as_mut_slice().try_into()?;
+        let coinbase_suffix: B064K = self.coinbase_suffix.as_mut_slice().try_into()?;
+
+        let tx_short_hash_list_ = self.tx_short_hash_list.as_mut_slice();
+        let mut tx_short_hash_list: Vec<ShortTxId> = Vec::new();
+        for cvec in tx_short_hash_list_ {
+            tx_short_hash_list.push(cvec.as_mut_slice().try_into()?);
+        }
+        let tx_short_hash_list = Seq064K::new(tx_short_hash_list)?;
+
+        let tx_hash_list_hash: U256 = self.tx_hash_list_hash.as_mut_slice().try_into()?;
+        let excess_data: B064K = self.excess_data.as_mut_slice().try_into()?;
+        Ok(DeclareMiningJob {
+            request_id: self.request_id,
+            mining_job_token,
+            version: self.version,
+            coinbase_prefix,
+            coinbase_suffix,
+            tx_short_hash_nonce: self.tx_short_hash_nonce,
+            tx_short_hash_list,
+            tx_hash_list_hash,
+            excess_data,
+        })
+    }
+}
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CDeclareMiningJobSuccess {
+    request_id: u32,
+    new_mining_job_token: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_declare_mining_job_success(s: CDeclareMiningJobSuccess) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CDeclareMiningJobSuccess {
+    fn drop(&mut self) {
+        free_vec(&mut self.new_mining_job_token);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<DeclareMiningJobSuccess<'a>> for CDeclareMiningJobSuccess {
+    fn from(v: DeclareMiningJobSuccess<'a>) -> Self {
+        Self {
+            request_id: v.request_id,
+            new_mining_job_token: v.new_mining_job_token.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CDeclareMiningJobSuccess {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<DeclareMiningJobSuccess<'a>, Error> {
+        let new_mining_job_token: B0255 = self.new_mining_job_token.as_mut_slice().try_into()?;
+        Ok(DeclareMiningJobSuccess {
+            request_id: self.request_id,
+            new_mining_job_token,
+        })
+    }
+}
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CDeclareMiningJobError {
+    request_id: u32,
+    error_code: CVec,
+    error_details: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_declare_mining_job_error(s: CDeclareMiningJobError) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CDeclareMiningJobError {
+    fn drop(&mut self) {
+        free_vec(&mut self.error_code);
+        free_vec(&mut self.error_details);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<DeclareMiningJobError<'a>> for CDeclareMiningJobError {
+    fn from(v: DeclareMiningJobError<'a>) -> Self {
+        Self {
+            request_id: v.request_id,
+            error_code: v.error_code.into(),
+            error_details: v.error_details.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CDeclareMiningJobError {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<DeclareMiningJobError<'a>, Error> {
+        let error_code: Str0255 = self.error_code.as_mut_slice().try_into()?;
+        let error_details: B064K = self.error_details.as_mut_slice().try_into()?;
+        Ok(DeclareMiningJobError {
+            request_id: self.request_id,
+            error_code,
+            error_details,
+        })
+    }
+}
+
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec_2, CVec2};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Seq064K, Serialize, U256};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -22,6 +24,54 @@ pub struct IdentifyTransactionsSuccess<'decoder> {
     pub tx_data_hashes: Seq064K<'decoder, U256<'decoder>>,
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CIdentifyTransactionsSuccess {
+    request_id: u32,
+    tx_data_hashes: CVec2,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_identify_transactions_success(s: CIdentifyTransactionsSuccess) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CIdentifyTransactionsSuccess {
+    fn drop(&mut self) {
+        free_vec_2(&mut self.tx_data_hashes);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<IdentifyTransactionsSuccess<'a>> for CIdentifyTransactionsSuccess {
+    fn from(v: IdentifyTransactionsSuccess<'a>) -> Self {
+        Self {
+            request_id: v.request_id,
+            tx_data_hashes: v.tx_data_hashes.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CIdentifyTransactionsSuccess {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<IdentifyTransactionsSuccess<'a>, Error> {
+        let tx_data_hashes_ = self.tx_data_hashes.as_mut_slice();
+        let mut tx_data_hashes: Vec<U256> = Vec::new();
+        for cvec in tx_data_hashes_ {
+            tx_data_hashes.push(cvec.as_mut_slice().try_into()?);
+        }
+        let tx_data_hashes = Seq064K::new(tx_data_hashes)?;
+        Ok(IdentifyTransactionsSuccess {
+            request_id: self.request_id,
+            tx_data_hashes,
+        })
+    }
+}
+
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
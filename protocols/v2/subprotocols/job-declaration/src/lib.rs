@@ -16,9 +16,24 @@ mod provide_missing_transactions;
 mod submit_solution;
 
 pub use allocate_mining_job_token::{AllocateMiningJobToken, AllocateMiningJobTokenSuccess};
+#[cfg(not(feature = "with_serde"))]
+pub use allocate_mining_job_token::{CAllocateMiningJobToken, CAllocateMiningJobTokenSuccess};
+#[cfg(not(feature = "with_serde"))]
+pub use declare_mining_job::{CDeclareMiningJob, CDeclareMiningJobError, CDeclareMiningJobSuccess};
 pub use declare_mining_job::{DeclareMiningJob, DeclareMiningJobError, DeclareMiningJobSuccess};
+#[cfg(not(feature = "with_serde"))]
+pub use identify_transactions::CIdentifyTransactionsSuccess;
 pub use identify_transactions::{IdentifyTransactions, IdentifyTransactionsSuccess};
+#[cfg(not(feature = "with_serde"))]
+pub use provide_missing_transactions::{
+    CProvideMissingTransactions, CProvideMissingTransactionsSuccess,
+};
 pub use provide_missing_transactions::{
     ProvideMissingTransactions, ProvideMissingTransactionsSuccess,
 };
+#[cfg(not(feature = "with_serde"))]
+pub use submit_solution::CSubmitSolutionJd;
 pub use submit_solution::SubmitSolutionJd;
+
+#[no_mangle]
+pub extern "C" fn _c_export_identify_transactions(_a: IdentifyTransactions) {}
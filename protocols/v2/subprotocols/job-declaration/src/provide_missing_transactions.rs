@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, free_vec_2, CVec, CVec2};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Seq064K, Serialize, B016M};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -30,6 +32,110 @@ pub struct ProvideMissingTransactionsSuccess<'decoder> {
     pub transaction_list: Seq064K<'decoder, B016M<'decoder>>,
 }
 
+/// C representation of [`ProvideMissingTransactions`], `unknown_tx_position_list` is serialized
+/// as consecutive little-endian `u16`.
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CProvideMissingTransactions {
+    request_id: u32,
+    unknown_tx_position_list: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_provide_missing_transactions(s: CProvideMissingTransactions) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CProvideMissingTransactions {
+    fn drop(&mut self) {
+        free_vec(&mut self.unknown_tx_position_list);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<ProvideMissingTransactions<'a>> for CProvideMissingTransactions {
+    fn from(v: ProvideMissingTransactions<'a>) -> Self {
+        let unknown_tx_position_list: Vec<u8> = v
+            .unknown_tx_position_list
+            .into_inner()
+            .into_iter()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        Self {
+            request_id: v.request_id,
+            unknown_tx_position_list: unknown_tx_position_list.as_slice().into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CProvideMissingTransactions {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<ProvideMissingTransactions<'a>, Error> {
+        let unknown_tx_position_list: Vec<u16> = self
+            .unknown_tx_position_list
+            .as_mut_slice()
+            .chunks_exact(2)
+            .map(|position| u16::from_le_bytes([position[0], position[1]]))
+            .collect();
+        Ok(ProvideMissingTransactions {
+            request_id: self.request_id,
+            unknown_tx_position_list: Seq064K::new(unknown_tx_position_list)?,
+        })
+    }
+}
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CProvideMissingTransactionsSuccess {
+    request_id: u32,
+    transaction_list: CVec2,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_provide_missing_transactions_success(s: CProvideMissingTransactionsSuccess) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CProvideMissingTransactionsSuccess {
+    fn drop(&mut self) {
+        free_vec_2(&mut self.transaction_list);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<ProvideMissingTransactionsSuccess<'a>> for CProvideMissingTransactionsSuccess {
+    fn from(v: ProvideMissingTransactionsSuccess<'a>) -> Self {
+        Self {
+            request_id: v.request_id,
+            transaction_list: v.transaction_list.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CProvideMissingTransactionsSuccess {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<ProvideMissingTransactionsSuccess<'a>, Error> {
+        let transaction_list_ = self.transaction_list.as_mut_slice();
+        let mut transaction_list: Vec<B016M> = Vec::new();
+        for cvec in transaction_list_ {
+            transaction_list.push(cvec.as_mut_slice().try_into()?);
+        }
+        let transaction_list = Seq064K::new(transaction_list)?;
+        Ok(ProvideMissingTransactionsSuccess {
+            request_id: self.request_id,
+            transaction_list,
+        })
+    }
+}
+
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, B032, U256};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -20,6 +22,63 @@ pub struct SubmitSolutionJd<'decoder> {
     pub version: u32,
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CSubmitSolutionJd {
+    extranonce: CVec,
+    prev_hash: CVec,
+    ntime: u32,
+    nonce: u32,
+    nbits: u32,
+    version: u32,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_submit_solution_jd(s: CSubmitSolutionJd) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CSubmitSolutionJd {
+    fn drop(&mut self) {
+        free_vec(&mut self.extranonce);
+        free_vec(&mut self.prev_hash);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<SubmitSolutionJd<'a>> for CSubmitSolutionJd {
+    fn from(v: SubmitSolutionJd<'a>) -> Self {
+        Self {
+            extranonce: v.extranonce.into(),
+            prev_hash: v.prev_hash.into(),
+            ntime: v.ntime,
+            nonce: v.nonce,
+            nbits: v.nbits,
+            version: v.version,
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CSubmitSolutionJd {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<SubmitSolutionJd<'a>, Error> {
+        let extranonce: B032 = self.extranonce.as_mut_slice().try_into()?;
+        let prev_hash: U256 = self.prev_hash.as_mut_slice().try_into()?;
+        Ok(SubmitSolutionJd {
+            extranonce,
+            prev_hash,
+            ntime: self.ntime,
+            nonce: self.nonce,
+            nbits: self.nbits,
+            version: self.version,
+        })
+    }
+}
+
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, Str0255};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -27,6 +29,49 @@ pub struct CloseChannel<'decoder> {
     pub reason_code: Str0255<'decoder>,
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CCloseChannel {
+    channel_id: u32,
+    reason_code: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_close_channel(s: CCloseChannel) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CCloseChannel {
+    fn drop(&mut self) {
+        free_vec(&mut self.reason_code);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<CloseChannel<'a>> for CCloseChannel {
+    fn from(v: CloseChannel<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            reason_code: v.reason_code.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CCloseChannel {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<CloseChannel<'a>, Error> {
+        let reason_code: Str0255 = self.reason_code.as_mut_slice().try_into()?;
+        Ok(CloseChannel {
+            channel_id: self.channel_id,
+            reason_code,
+        })
+    }
+}
+
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -126,25 +126,62 @@ mod set_target;
 mod submit_shares;
 mod update_channel;
 
+#[cfg(not(feature = "with_serde"))]
+pub use close_channel::CCloseChannel;
 pub use close_channel::CloseChannel;
 use core::ops::Range;
+#[cfg(not(feature = "with_serde"))]
+pub use new_mining_job::{CNewExtendedMiningJob, CNewMiningJob};
 pub use new_mining_job::{NewExtendedMiningJob, NewMiningJob};
+#[cfg(not(feature = "with_serde"))]
+pub use open_channel::{
+    COpenExtendedMiningChannel, COpenExtendedMiningChannelSuccess, COpenMiningChannelError,
+    COpenStandardMiningChannel, COpenStandardMiningChannelSuccess,
+};
 pub use open_channel::{
     OpenExtendedMiningChannel, OpenExtendedMiningChannelSuccess, OpenMiningChannelError,
     OpenStandardMiningChannel, OpenStandardMiningChannelSuccess,
 };
+#[cfg(not(feature = "with_serde"))]
+pub use reconnect::CReconnect;
 pub use reconnect::Reconnect;
+#[cfg(not(feature = "with_serde"))]
+pub use set_custom_mining_job::{CSetCustomMiningJob, CSetCustomMiningJobError};
 pub use set_custom_mining_job::{
     SetCustomMiningJob, SetCustomMiningJobError, SetCustomMiningJobSuccess,
 };
+#[cfg(not(feature = "with_serde"))]
+pub use set_extranonce_prefix::CSetExtranoncePrefix;
 pub use set_extranonce_prefix::SetExtranoncePrefix;
+#[cfg(not(feature = "with_serde"))]
+pub use set_group_channel::CSetGroupChannel;
 pub use set_group_channel::SetGroupChannel;
+#[cfg(not(feature = "with_serde"))]
+pub use set_new_prev_hash::CMiningSetNewPrevHash;
 pub use set_new_prev_hash::SetNewPrevHash;
+#[cfg(not(feature = "with_serde"))]
+pub use set_target::CSetTarget;
 pub use set_target::SetTarget;
 pub use submit_shares::{
-    SubmitSharesError, SubmitSharesExtended, SubmitSharesStandard, SubmitSharesSuccess,
+    user_identity_from_tlv, user_identity_tlv, SubmitSharesError, SubmitSharesExtended,
+    SubmitSharesStandard, SubmitSharesSuccess, MAX_TLV_USER_IDENTITY_LEN,
+    TLV_FIELD_TYPE_USER_IDENTITY,
 };
+#[cfg(not(feature = "with_serde"))]
+pub use submit_shares::{CSubmitSharesError, CSubmitSharesExtended};
+#[cfg(not(feature = "with_serde"))]
+pub use update_channel::{CUpdateChannel, CUpdateChannelError};
 pub use update_channel::{UpdateChannel, UpdateChannelError};
+
+#[no_mangle]
+pub extern "C" fn _c_export_submit_shares_standard(_a: SubmitSharesStandard) {}
+
+#[no_mangle]
+pub extern "C" fn _c_export_submit_shares_success(_a: SubmitSharesSuccess) {}
+
+#[no_mangle]
+pub extern "C" fn _c_export_set_custom_mining_job_success(_a: SetCustomMiningJobSuccess) {}
+
 const MAX_EXTRANONCE_LEN: usize = 32;
 
 /// Target is a 256-bit unsigned integer in little-endian
@@ -1122,4 +1159,27 @@ pub mod tests {
         let prefix_len = extended.get_prefix_len();
         assert!(prefix_len == 4);
     }
+
+    #[test]
+    fn test_user_identity_tlv() {
+        let share = SubmitSharesExtended {
+            channel_id: 1,
+            sequence_number: 2,
+            job_id: 3,
+            nonce: 4,
+            ntime: 5,
+            version: 6,
+            extranonce: vec![7; 8].try_into().unwrap(),
+        };
+        let mut payload = binary_sv2::to_bytes(share).unwrap();
+        assert_eq!(user_identity_from_tlv(&payload), None);
+
+        payload.extend_from_slice(&[0xff, 0xff, 1, 0, 0]);
+        payload.extend_from_slice(&user_identity_tlv("bc1qexample.rig1"));
+        assert_eq!(user_identity_from_tlv(&payload), Some("bc1qexample.rig1"));
+
+        let long = "a".repeat(MAX_TLV_USER_IDENTITY_LEN + 10);
+        let tlv = user_identity_tlv(&long);
+        assert_eq!(tlv.len(), 5 + MAX_TLV_USER_IDENTITY_LEN);
+    }
 }
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, free_vec_2, CVec, CVec2};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Seq0255, Serialize, Sv2Option, B032, B064K, U256};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -36,6 +38,67 @@ pub struct NewMiningJob<'decoder> {
     pub merkle_root: B032<'decoder>,
 }
 
+/// C representation of [`NewMiningJob`], `min_ntime` is meaningful only when `has_min_ntime` is
+/// true.
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CNewMiningJob {
+    channel_id: u32,
+    job_id: u32,
+    has_min_ntime: bool,
+    min_ntime: u32,
+    version: u32,
+    merkle_root: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_new_mining_job(s: CNewMiningJob) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CNewMiningJob {
+    fn drop(&mut self) {
+        free_vec(&mut self.merkle_root);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<NewMiningJob<'a>> for CNewMiningJob {
+    fn from(v: NewMiningJob<'a>) -> Self {
+        let min_ntime = v.min_ntime.into_inner();
+        Self {
+            channel_id: v.channel_id,
+            job_id: v.job_id,
+            has_min_ntime: min_ntime.is_some(),
+            min_ntime: min_ntime.unwrap_or(0),
+            version: v.version,
+            merkle_root: v.merkle_root.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CNewMiningJob {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<NewMiningJob<'a>, Error> {
+        let merkle_root: B032 = self.merkle_root.as_mut_slice().try_into()?;
+        let min_ntime = match self.has_min_ntime {
+            true => Some(self.min_ntime),
+            false => None,
+        };
+        Ok(NewMiningJob {
+            channel_id: self.channel_id,
+            job_id: self.job_id,
+            min_ntime: Sv2Option::new(min_ntime),
+            version: self.version,
+            merkle_root,
+        })
+    }
+}
+
 impl<'d> NewMiningJob<'d> {
     pub fn is_future(&self) -> bool {
         self.min_ntime.clone().into_inner().is_none()
@@ -104,6 +167,88 @@ pub struct NewExtendedMiningJob<'decoder> {
     pub coinbase_tx_suffix: B064K<'decoder>,
 }
 
+/// C representation of [`NewExtendedMiningJob`], `min_ntime` is meaningful only when
+/// `has_min_ntime` is true.
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CNewExtendedMiningJob {
+    channel_id: u32,
+    job_id: u32,
+    has_min_ntime: bool,
+    min_ntime: u32,
+    version: u32,
+    version_rolling_allowed: bool,
+    merkle_path: CVec2,
+    coinbase_tx_prefix: CVec,
+    coinbase_tx_suffix: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_new_extended_mining_job(s: CNewExtendedMiningJob) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CNewExtendedMiningJob {
+    fn drop(&mut self) {
+        free_vec_2(&mut self.merkle_path);
+        free_vec(&mut self.coinbase_tx_prefix);
+        free_vec(&mut self.coinbase_tx_suffix);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<NewExtendedMiningJob<'a>> for CNewExtendedMiningJob {
+    fn from(v: NewExtendedMiningJob<'a>) -> Self {
+        let min_ntime = v.min_ntime.into_inner();
+        Self {
+            channel_id: v.channel_id,
+            job_id: v.job_id,
+            has_min_ntime: min_ntime.is_some(),
+            min_ntime: min_ntime.unwrap_or(0),
+            version: v.version,
+            version_rolling_allowed: v.version_rolling_allowed,
+            merkle_path: v.merkle_path.into(),
+            coinbase_tx_prefix: v.coinbase_tx_prefix.into(),
+            coinbase_tx_suffix: v.coinbase_tx_suffix.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CNewExtendedMiningJob {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<NewExtendedMiningJob<'a>, Error> {
+        let min_ntime = match self.has_min_ntime {
+            true => Some(self.min_ntime),
+            false => None,
+        };
+
+        let merkle_path_ = self.merkle_path.as_mut_slice();
+        let mut merkle_path: Vec<U256> = Vec::new();
+        for cvec in merkle_path_ {
+            merkle_path.push(cvec.as_mut_slice().try_into()?);
+        }
+        let merkle_path = Seq0255::new(merkle_path)?;
+
+        let coinbase_tx_prefix: B064K = self.coinbase_tx_prefix.as_mut_slice().try_into()?;
+        let coinbase_tx_suffix: B064K = self.coinbase_tx_suffix.as_mut_slice().try_into()?;
+
+        Ok(NewExtendedMiningJob {
+            channel_id: self.channel_id,
+            job_id: self.job_id,
+            min_ntime: Sv2Option::new(min_ntime),
+            version: self.version,
+            version_rolling_allowed: self.version_rolling_allowed,
+            merkle_path,
+            coinbase_tx_prefix,
+            coinbase_tx_suffix,
+        })
+    }
+}
+
 impl<'d> NewExtendedMiningJob<'d> {
     pub fn is_future(&self) -> bool {
         self.min_ntime.clone().into_inner().is_none()
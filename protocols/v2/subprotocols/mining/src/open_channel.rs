@@ -2,7 +2,10 @@ use alloc::string::ToString;
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::{binary_codec_sv2, U32AsRef};
+use binary_sv2::{
+    binary_codec_sv2::{self, free_vec, CVec},
+    Error, U32AsRef,
+};
 use binary_sv2::{Deserialize, Serialize, Str0255, B032, U256};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -227,6 +230,262 @@ impl<'a> OpenMiningChannelError<'a> {
     }
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct COpenStandardMiningChannel {
+    request_id: u32,
+    user_identity: CVec,
+    nominal_hash_rate: f32,
+    max_target: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_open_standard_mining_channel(s: COpenStandardMiningChannel) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for COpenStandardMiningChannel {
+    fn drop(&mut self) {
+        free_vec(&mut self.user_identity);
+        free_vec(&mut self.max_target);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<OpenStandardMiningChannel<'a>> for COpenStandardMiningChannel {
+    fn from(v: OpenStandardMiningChannel<'a>) -> Self {
+        Self {
+            request_id: v.get_request_id_as_u32(),
+            user_identity: v.user_identity.into(),
+            nominal_hash_rate: v.nominal_hash_rate,
+            max_target: v.max_target.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> COpenStandardMiningChannel {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<OpenStandardMiningChannel<'a>, Error> {
+        let user_identity: Str0255 = self.user_identity.as_mut_slice().try_into()?;
+        let max_target: U256 = self.max_target.as_mut_slice().try_into()?;
+        Ok(OpenStandardMiningChannel {
+            request_id: self.request_id.into(),
+            user_identity,
+            nominal_hash_rate: self.nominal_hash_rate,
+            max_target,
+        })
+    }
+}
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct COpenStandardMiningChannelSuccess {
+    request_id: u32,
+    channel_id: u32,
+    target: CVec,
+    extranonce_prefix: CVec,
+    group_channel_id: u32,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_open_standard_mining_channel_success(s: COpenStandardMiningChannelSuccess) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for COpenStandardMiningChannelSuccess {
+    fn drop(&mut self) {
+        free_vec(&mut self.target);
+        free_vec(&mut self.extranonce_prefix);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<OpenStandardMiningChannelSuccess<'a>> for COpenStandardMiningChannelSuccess {
+    fn from(v: OpenStandardMiningChannelSuccess<'a>) -> Self {
+        Self {
+            request_id: v.get_request_id_as_u32(),
+            channel_id: v.channel_id,
+            target: v.target.into(),
+            extranonce_prefix: v.extranonce_prefix.into(),
+            group_channel_id: v.group_channel_id,
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> COpenStandardMiningChannelSuccess {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<OpenStandardMiningChannelSuccess<'a>, Error> {
+        let target: U256 = self.target.as_mut_slice().try_into()?;
+        let extranonce_prefix: B032 = self.extranonce_prefix.as_mut_slice().try_into()?;
+        Ok(OpenStandardMiningChannelSuccess {
+            request_id: self.request_id.into(),
+            channel_id: self.channel_id,
+            target,
+            extranonce_prefix,
+            group_channel_id: self.group_channel_id,
+        })
+    }
+}
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct COpenExtendedMiningChannel {
+    request_id: u32,
+    user_identity: CVec,
+    nominal_hash_rate: f32,
+    max_target: CVec,
+    min_extranonce_size: u16,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_open_extended_mining_channel(s: COpenExtendedMiningChannel) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for COpenExtendedMiningChannel {
+    fn drop(&mut self) {
+        free_vec(&mut self.user_identity);
+        free_vec(&mut self.max_target);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<OpenExtendedMiningChannel<'a>> for COpenExtendedMiningChannel {
+    fn from(v: OpenExtendedMiningChannel<'a>) -> Self {
+        Self {
+            request_id: v.request_id,
+            user_identity: v.user_identity.into(),
+            nominal_hash_rate: v.nominal_hash_rate,
+            max_target: v.max_target.into(),
+            min_extranonce_size: v.min_extranonce_size,
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> COpenExtendedMiningChannel {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<OpenExtendedMiningChannel<'a>, Error> {
+        let user_identity: Str0255 = self.user_identity.as_mut_slice().try_into()?;
+        let max_target: U256 = self.max_target.as_mut_slice().try_into()?;
+        Ok(OpenExtendedMiningChannel {
+            request_id: self.request_id,
+            user_identity,
+            nominal_hash_rate: self.nominal_hash_rate,
+            max_target,
+            min_extranonce_size: self.min_extranonce_size,
+        })
+    }
+}
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct COpenExtendedMiningChannelSuccess {
+    request_id: u32,
+    channel_id: u32,
+    target: CVec,
+    extranonce_size: u16,
+    extranonce_prefix: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_open_extended_mining_channel_success(s: COpenExtendedMiningChannelSuccess) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for COpenExtendedMiningChannelSuccess {
+    fn drop(&mut self) {
+        free_vec(&mut self.target);
+        free_vec(&mut self.extranonce_prefix);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<OpenExtendedMiningChannelSuccess<'a>> for COpenExtendedMiningChannelSuccess {
+    fn from(v: OpenExtendedMiningChannelSuccess<'a>) -> Self {
+        Self {
+            request_id: v.request_id,
+            channel_id: v.channel_id,
+            target: v.target.into(),
+            extranonce_size: v.extranonce_size,
+            extranonce_prefix: v.extranonce_prefix.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> COpenExtendedMiningChannelSuccess {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<OpenExtendedMiningChannelSuccess<'a>, Error> {
+        let target: U256 = self.target.as_mut_slice().try_into()?;
+        let extranonce_prefix: B032 = self.extranonce_prefix.as_mut_slice().try_into()?;
+        Ok(OpenExtendedMiningChannelSuccess {
+            request_id: self.request_id,
+            channel_id: self.channel_id,
+            target,
+            extranonce_size: self.extranonce_size,
+            extranonce_prefix,
+        })
+    }
+}
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct COpenMiningChannelError {
+    request_id: u32,
+    error_code: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_open_mining_channel_error(s: COpenMiningChannelError) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for COpenMiningChannelError {
+    fn drop(&mut self) {
+        free_vec(&mut self.error_code);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<OpenMiningChannelError<'a>> for COpenMiningChannelError {
+    fn from(v: OpenMiningChannelError<'a>) -> Self {
+        Self {
+            request_id: v.request_id,
+            error_code: v.error_code.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> COpenMiningChannelError {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<OpenMiningChannelError<'a>, Error> {
+        let error_code: Str0255 = self.error_code.as_mut_slice().try_into()?;
+        Ok(OpenMiningChannelError {
+            request_id: self.request_id,
+            error_code,
+        })
+    }
+}
+
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, Str0255};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -29,6 +31,49 @@ pub struct Reconnect<'decoder> {
     /// When 0, downstream node attempts to reconnect to its present port.
     pub new_port: u16,
 }
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CReconnect {
+    new_host: CVec,
+    new_port: u16,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_reconnect(s: CReconnect) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CReconnect {
+    fn drop(&mut self) {
+        free_vec(&mut self.new_host);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<Reconnect<'a>> for CReconnect {
+    fn from(v: Reconnect<'a>) -> Self {
+        Self {
+            new_host: v.new_host.into(),
+            new_port: v.new_port,
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CReconnect {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<Reconnect<'a>, Error> {
+        let new_host: Str0255 = self.new_host.as_mut_slice().try_into()?;
+        Ok(Reconnect {
+            new_host,
+            new_port: self.new_port,
+        })
+    }
+}
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
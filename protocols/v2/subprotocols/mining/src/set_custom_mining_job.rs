@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, free_vec_2, CVec, CVec2};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Seq0255, Serialize, Str0255, B0255, B064K, U256};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -58,12 +60,110 @@ pub struct SetCustomMiningJob<'decoder> {
     pub extranonce_size: u16,
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CSetCustomMiningJob {
+    channel_id: u32,
+    request_id: u32,
+    token: CVec,
+    version: u32,
+    prev_hash: CVec,
+    min_ntime: u32,
+    nbits: u32,
+    coinbase_tx_version: u32,
+    coinbase_prefix: CVec,
+    coinbase_tx_input_n_sequence: u32,
+    coinbase_tx_value_remaining: u64,
+    coinbase_tx_outputs: CVec,
+    coinbase_tx_locktime: u32,
+    merkle_path: CVec2,
+    extranonce_size: u16,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_set_custom_mining_job(s: CSetCustomMiningJob) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CSetCustomMiningJob {
+    fn drop(&mut self) {
+        free_vec(&mut self.token);
+        free_vec(&mut self.prev_hash);
+        free_vec(&mut self.coinbase_prefix);
+        free_vec(&mut self.coinbase_tx_outputs);
+        free_vec_2(&mut self.merkle_path);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<SetCustomMiningJob<'a>> for CSetCustomMiningJob {
+    fn from(v: SetCustomMiningJob<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            request_id: v.request_id,
+            token: v.token.into(),
+            version: v.version,
+            prev_hash: v.prev_hash.into(),
+            min_ntime: v.min_ntime,
+            nbits: v.nbits,
+            coinbase_tx_version: v.coinbase_tx_version,
+            coinbase_prefix: v.coinbase_prefix.into(),
+            coinbase_tx_input_n_sequence: v.coinbase_tx_input_n_sequence,
+            coinbase_tx_value_remaining: v.coinbase_tx_value_remaining,
+            coinbase_tx_outputs: v.coinbase_tx_outputs.into(),
+            coinbase_tx_locktime: v.coinbase_tx_locktime,
+            merkle_path: v.merkle_path.into(),
+            extranonce_size: v.extranonce_size,
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CSetCustomMiningJob {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<SetCustomMiningJob<'a>, Error> {
+        let token: B0255 = self.token.as_mut_slice().try_into()?;
+        let prev_hash: U256 = self.prev_hash.as_mut_slice().try_into()?;
+        let coinbase_prefix: B0255 = self.coinbase_prefix.as_mut_slice().try_into()?;
+        let coinbase_tx_outputs: B064K = self.coinbase_tx_outputs.as_mut_slice().try_into()?;
+
+        let merkle_path_ = self.merkle_path.as_mut_slice();
+        let mut merkle_path: Vec<U256> = Vec::new();
+        for cvec in merkle_path_ {
+            merkle_path.push(cvec.as_mut_slice().try_into()?);
+        }
+        let merkle_path = Seq0255::new(merkle_path)?;
+
+        Ok(SetCustomMiningJob {
+            channel_id: self.channel_id,
+            request_id: self.request_id,
+            token,
+            version: self.version,
+            prev_hash,
+            min_ntime: self.min_ntime,
+            nbits: self.nbits,
+            coinbase_tx_version: self.coinbase_tx_version,
+            coinbase_prefix,
+            coinbase_tx_input_n_sequence: self.coinbase_tx_input_n_sequence,
+            coinbase_tx_value_remaining: self.coinbase_tx_value_remaining,
+            coinbase_tx_outputs,
+            coinbase_tx_locktime: self.coinbase_tx_locktime,
+            merkle_path,
+            extranonce_size: self.extranonce_size,
+        })
+    }
+}
+
 /// # SetCustomMiningJob.Success (Server -> Client)
 ///
 /// Response from the server when it accepts the custom mining job. Client can start to mine on
 /// the job immediately (by using the job_id provided within this response).
 ///
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[repr(C)]
 pub struct SetCustomMiningJobSuccess {
     /// Extended channel identifier.
     pub channel_id: u32,
@@ -92,6 +192,52 @@ pub struct SetCustomMiningJobError<'decoder> {
     #[cfg_attr(feature = "with_serde", serde(borrow))]
     pub error_code: Str0255<'decoder>,
 }
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CSetCustomMiningJobError {
+    channel_id: u32,
+    request_id: u32,
+    error_code: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_set_custom_mining_job_error(s: CSetCustomMiningJobError) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CSetCustomMiningJobError {
+    fn drop(&mut self) {
+        free_vec(&mut self.error_code);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<SetCustomMiningJobError<'a>> for CSetCustomMiningJobError {
+    fn from(v: SetCustomMiningJobError<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            request_id: v.request_id,
+            error_code: v.error_code.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CSetCustomMiningJobError {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<SetCustomMiningJobError<'a>, Error> {
+        let error_code: Str0255 = self.error_code.as_mut_slice().try_into()?;
+        Ok(SetCustomMiningJobError {
+            channel_id: self.channel_id,
+            request_id: self.request_id,
+            error_code,
+        })
+    }
+}
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, B032};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -20,6 +22,49 @@ pub struct SetExtranoncePrefix<'decoder> {
     #[cfg_attr(feature = "with_serde", serde(borrow))]
     pub extranonce_prefix: B032<'decoder>,
 }
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CSetExtranoncePrefix {
+    channel_id: u32,
+    extranonce_prefix: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_set_extranonce_prefix(s: CSetExtranoncePrefix) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CSetExtranoncePrefix {
+    fn drop(&mut self) {
+        free_vec(&mut self.extranonce_prefix);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<SetExtranoncePrefix<'a>> for CSetExtranoncePrefix {
+    fn from(v: SetExtranoncePrefix<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            extranonce_prefix: v.extranonce_prefix.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CSetExtranoncePrefix {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<SetExtranoncePrefix<'a>, Error> {
+        let extranonce_prefix: B032 = self.extranonce_prefix.as_mut_slice().try_into()?;
+        Ok(SetExtranoncePrefix {
+            channel_id: self.channel_id,
+            extranonce_prefix,
+        })
+    }
+}
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Seq064K, Serialize};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -31,6 +33,62 @@ pub struct SetGroupChannel<'decoder> {
     #[cfg_attr(feature = "with_serde", serde(borrow))]
     pub channel_ids: Seq064K<'decoder, u32>,
 }
+
+/// C representation of [`SetGroupChannel`], `channel_ids` are serialized as consecutive
+/// little-endian `u32`.
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CSetGroupChannel {
+    group_channel_id: u32,
+    channel_ids: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_set_group_channel(s: CSetGroupChannel) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CSetGroupChannel {
+    fn drop(&mut self) {
+        free_vec(&mut self.channel_ids);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<SetGroupChannel<'a>> for CSetGroupChannel {
+    fn from(v: SetGroupChannel<'a>) -> Self {
+        let channel_ids: Vec<u8> = v
+            .channel_ids
+            .into_inner()
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        Self {
+            group_channel_id: v.group_channel_id,
+            channel_ids: channel_ids.as_slice().into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CSetGroupChannel {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<SetGroupChannel<'a>, Error> {
+        let channel_ids: Vec<u32> = self
+            .channel_ids
+            .as_mut_slice()
+            .chunks_exact(4)
+            .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+            .collect();
+        Ok(SetGroupChannel {
+            group_channel_id: self.group_channel_id,
+            channel_ids: Seq064K::new(channel_ids)?,
+        })
+    }
+}
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, U256};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -32,6 +34,60 @@ pub struct SetNewPrevHash<'decoder> {
     pub nbits: u32,
 }
 
+/// C representation of the mining [`SetNewPrevHash`]. It is prefixed with `Mining` so that it
+/// does not clash with the Template Distribution `CSetNewPrevHash` in the C headers.
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CMiningSetNewPrevHash {
+    channel_id: u32,
+    job_id: u32,
+    prev_hash: CVec,
+    min_ntime: u32,
+    nbits: u32,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_mining_set_new_prev_hash(s: CMiningSetNewPrevHash) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CMiningSetNewPrevHash {
+    fn drop(&mut self) {
+        free_vec(&mut self.prev_hash);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<SetNewPrevHash<'a>> for CMiningSetNewPrevHash {
+    fn from(v: SetNewPrevHash<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            job_id: v.job_id,
+            prev_hash: v.prev_hash.into(),
+            min_ntime: v.min_ntime,
+            nbits: v.nbits,
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CMiningSetNewPrevHash {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<SetNewPrevHash<'a>, Error> {
+        let prev_hash: U256 = self.prev_hash.as_mut_slice().try_into()?;
+        Ok(SetNewPrevHash {
+            channel_id: self.channel_id,
+            job_id: self.job_id,
+            prev_hash,
+            min_ntime: self.min_ntime,
+            nbits: self.nbits,
+        })
+    }
+}
+
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, U256};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -26,6 +28,49 @@ pub struct SetTarget<'decoder> {
     #[cfg_attr(feature = "with_serde", serde(borrow))]
     pub maximum_target: U256<'decoder>,
 }
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CSetTarget {
+    channel_id: u32,
+    maximum_target: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_set_target(s: CSetTarget) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CSetTarget {
+    fn drop(&mut self) {
+        free_vec(&mut self.maximum_target);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<SetTarget<'a>> for CSetTarget {
+    fn from(v: SetTarget<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            maximum_target: v.maximum_target.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CSetTarget {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<SetTarget<'a>, Error> {
+        let maximum_target: U256 = self.maximum_target.as_mut_slice().try_into()?;
+        Ok(SetTarget {
+            channel_id: self.channel_id,
+            maximum_target,
+        })
+    }
+}
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,8 @@
-#[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, Str0255, B032};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -10,6 +11,7 @@ use core::convert::TryInto;
 ///
 /// Client sends result of its hashing work to the server.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[repr(C)]
 pub struct SubmitSharesStandard {
     /// Channel identification.
     pub channel_id: u32,
@@ -59,6 +61,64 @@ pub struct SubmitSharesExtended<'decoder> {
     pub extranonce: B032<'decoder>,
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CSubmitSharesExtended {
+    channel_id: u32,
+    sequence_number: u32,
+    job_id: u32,
+    nonce: u32,
+    ntime: u32,
+    version: u32,
+    extranonce: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_submit_shares_extended(s: CSubmitSharesExtended) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CSubmitSharesExtended {
+    fn drop(&mut self) {
+        free_vec(&mut self.extranonce);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<SubmitSharesExtended<'a>> for CSubmitSharesExtended {
+    fn from(v: SubmitSharesExtended<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            sequence_number: v.sequence_number,
+            job_id: v.job_id,
+            nonce: v.nonce,
+            ntime: v.ntime,
+            version: v.version,
+            extranonce: v.extranonce.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CSubmitSharesExtended {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<SubmitSharesExtended<'a>, Error> {
+        let extranonce: B032 = self.extranonce.as_mut_slice().try_into()?;
+        Ok(SubmitSharesExtended {
+            channel_id: self.channel_id,
+            sequence_number: self.sequence_number,
+            job_id: self.job_id,
+            nonce: self.nonce,
+            ntime: self.ntime,
+            version: self.version,
+            extranonce,
+        })
+    }
+}
+
 /// # SubmitShares.Success (Server -> Client)
 ///
 /// Response to SubmitShares or SubmitSharesExtended, accepting results from the miner.
@@ -69,6 +129,7 @@ pub struct SubmitSharesExtended<'decoder> {
 /// actually increasing. It can simply use the last one received when sending a response. It is the
 /// client’s responsibility to keep the sequence numbers correct/useful.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[repr(C)]
 pub struct SubmitSharesSuccess {
     /// Channel identifier.
     pub channel_id: u32,
@@ -100,6 +161,52 @@ pub struct SubmitSharesError<'decoder> {
     pub error_code: Str0255<'decoder>,
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CSubmitSharesError {
+    channel_id: u32,
+    sequence_number: u32,
+    error_code: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_submit_shares_error(s: CSubmitSharesError) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CSubmitSharesError {
+    fn drop(&mut self) {
+        free_vec(&mut self.error_code);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<SubmitSharesError<'a>> for CSubmitSharesError {
+    fn from(v: SubmitSharesError<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            sequence_number: v.sequence_number,
+            error_code: v.error_code.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CSubmitSharesError {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<SubmitSharesError<'a>, Error> {
+        let error_code: Str0255 = self.error_code.as_mut_slice().try_into()?;
+        Ok(SubmitSharesError {
+            channel_id: self.channel_id,
+            sequence_number: self.sequence_number,
+            error_code,
+        })
+    }
+}
+
 impl<'a> SubmitSharesError<'a> {
     pub fn invalid_channel_error_code() -> &'static str {
         "invalid-channel-id"
@@ -114,6 +221,53 @@ impl<'a> SubmitSharesError<'a> {
         "invalid-job-id"
     }
 }
+
+/// Type of the TLV field that carries the `user_identity` of the worker that found a share
+pub const TLV_FIELD_TYPE_USER_IDENTITY: u8 = 0x01;
+/// Longest `user_identity` that can be carried in a TLV field
+pub const MAX_TLV_USER_IDENTITY_LEN: usize = 32;
+
+// channel_id, sequence_number, job_id, nonce, ntime and version
+const SUBMIT_SHARES_EXTENDED_FIXED_LEN: usize = 24;
+
+/// Encodes `user_identity` as the Worker-Specific Hashrate Tracking TLV that a proxy appends to a
+/// serialized `SubmitSharesExtended`, so that the upstream can credit the worker that found the
+/// share even if many workers are aggregated in the same extended channel. Identities longer than
+/// [`MAX_TLV_USER_IDENTITY_LEN`] bytes are truncated.
+///
+/// Upstreams that do not know the extension ignore the bytes that follow the message.
+pub fn user_identity_tlv(user_identity: &str) -> Vec<u8> {
+    let mut len = user_identity.len().min(MAX_TLV_USER_IDENTITY_LEN);
+    while !user_identity.is_char_boundary(len) {
+        len -= 1;
+    }
+    let mut tlv = Vec::with_capacity(5 + len);
+    tlv.extend_from_slice(&const_sv2::EXTENSION_TYPE_WORKER_HASHRATE_TRACKING.to_le_bytes());
+    tlv.push(TLV_FIELD_TYPE_USER_IDENTITY);
+    tlv.extend_from_slice(&(len as u16).to_le_bytes());
+    tlv.extend_from_slice(&user_identity.as_bytes()[..len]);
+    tlv
+}
+
+/// Returns the `user_identity` carried by the TLV fields that follow the serialized
+/// `SubmitSharesExtended` in `payload`, if any
+pub fn user_identity_from_tlv(payload: &[u8]) -> Option<&str> {
+    let extranonce_len = *payload.get(SUBMIT_SHARES_EXTENDED_FIXED_LEN)? as usize;
+    let mut tlvs = payload.get(SUBMIT_SHARES_EXTENDED_FIXED_LEN + 1 + extranonce_len..)?;
+    while tlvs.len() >= 5 {
+        let extension_type = u16::from_le_bytes([tlvs[0], tlvs[1]]);
+        let field_type = tlvs[2];
+        let len = u16::from_le_bytes([tlvs[3], tlvs[4]]) as usize;
+        let value = tlvs.get(5..5 + len)?;
+        if extension_type == const_sv2::EXTENSION_TYPE_WORKER_HASHRATE_TRACKING
+            && field_type == TLV_FIELD_TYPE_USER_IDENTITY
+        {
+            return core::str::from_utf8(value).ok();
+        }
+        tlvs = &tlvs[5 + len..];
+    }
+    None
+}
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -1,7 +1,9 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2;
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+#[cfg(not(feature = "with_serde"))]
+use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, Str0255, U256};
 #[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
@@ -32,6 +34,52 @@ pub struct UpdateChannel<'decoder> {
     pub maximum_target: U256<'decoder>,
 }
 
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CUpdateChannel {
+    channel_id: u32,
+    nominal_hash_rate: f32,
+    maximum_target: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_update_channel(s: CUpdateChannel) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CUpdateChannel {
+    fn drop(&mut self) {
+        free_vec(&mut self.maximum_target);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<UpdateChannel<'a>> for CUpdateChannel {
+    fn from(v: UpdateChannel<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            nominal_hash_rate: v.nominal_hash_rate,
+            maximum_target: v.maximum_target.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CUpdateChannel {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<UpdateChannel<'a>, Error> {
+        let maximum_target: U256 = self.maximum_target.as_mut_slice().try_into()?;
+        Ok(UpdateChannel {
+            channel_id: self.channel_id,
+            nominal_hash_rate: self.nominal_hash_rate,
+            maximum_target,
+        })
+    }
+}
+
 /// # Update.Error (Server -> Client)
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UpdateChannelError<'decoder> {
@@ -44,6 +92,49 @@ pub struct UpdateChannelError<'decoder> {
     #[cfg_attr(feature = "with_serde", serde(borrow))]
     pub error_code: Str0255<'decoder>,
 }
+
+#[repr(C)]
+#[cfg(not(feature = "with_serde"))]
+pub struct CUpdateChannelError {
+    channel_id: u32,
+    error_code: CVec,
+}
+
+#[no_mangle]
+#[cfg(not(feature = "with_serde"))]
+pub extern "C" fn free_update_channel_error(s: CUpdateChannelError) {
+    drop(s)
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl Drop for CUpdateChannelError {
+    fn drop(&mut self) {
+        free_vec(&mut self.error_code);
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> From<UpdateChannelError<'a>> for CUpdateChannelError {
+    fn from(v: UpdateChannelError<'a>) -> Self {
+        Self {
+            channel_id: v.channel_id,
+            error_code: v.error_code.into(),
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> CUpdateChannelError {
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_rust_rep_mut(&'a mut self) -> Result<UpdateChannelError<'a>, Error> {
+        let error_code: Str0255 = self.error_code.as_mut_slice().try_into()?;
+        Ok(UpdateChannelError {
+            channel_id: self.channel_id,
+            error_code,
+        })
+    }
+}
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -9,6 +9,27 @@ use common_messages_sv2::{
     CSetupConnection, CSetupConnectionError, ChannelEndpointChanged, SetupConnection,
     SetupConnectionError, SetupConnectionSuccess,
 };
+use job_declaration_sv2::{
+    AllocateMiningJobToken, AllocateMiningJobTokenSuccess, CAllocateMiningJobToken,
+    CAllocateMiningJobTokenSuccess, CDeclareMiningJob, CDeclareMiningJobError,
+    CDeclareMiningJobSuccess, CIdentifyTransactionsSuccess, CProvideMissingTransactions,
+    CProvideMissingTransactionsSuccess, CSubmitSolutionJd, DeclareMiningJob, DeclareMiningJobError,
+    DeclareMiningJobSuccess, IdentifyTransactions, IdentifyTransactionsSuccess,
+    ProvideMissingTransactions, ProvideMissingTransactionsSuccess, SubmitSolutionJd,
+};
+use mining_sv2::{
+    CCloseChannel, CMiningSetNewPrevHash, CNewExtendedMiningJob, CNewMiningJob,
+    COpenExtendedMiningChannel, COpenExtendedMiningChannelSuccess, COpenMiningChannelError,
+    COpenStandardMiningChannel, COpenStandardMiningChannelSuccess, CReconnect, CSetCustomMiningJob,
+    CSetCustomMiningJobError, CSetExtranoncePrefix, CSetGroupChannel, CSetTarget,
+    CSubmitSharesError, CSubmitSharesExtended, CUpdateChannel, CUpdateChannelError, CloseChannel,
+    NewExtendedMiningJob, NewMiningJob, OpenExtendedMiningChannel,
+    OpenExtendedMiningChannelSuccess, OpenMiningChannelError, OpenStandardMiningChannel,
+    OpenStandardMiningChannelSuccess, Reconnect, SetCustomMiningJob, SetCustomMiningJobError,
+    SetCustomMiningJobSuccess, SetExtranoncePrefix, SetGroupChannel,
+    SetNewPrevHash as MiningSetNewPrevHash, SetTarget, SubmitSharesError, SubmitSharesExtended,
+    SubmitSharesStandard, SubmitSharesSuccess, UpdateChannel, UpdateChannelError,
+};
 use template_distribution_sv2::{
     CNewTemplate, CRequestTransactionDataError, CRequestTransactionDataSuccess, CSetNewPrevHash,
     CSubmitSolution, CoinbaseOutputDataSize, NewTemplate, RequestTransactionData,
@@ -23,21 +44,53 @@ use binary_sv2::{
 };
 
 use const_sv2::{
-    CHANNEL_BIT_CHANNEL_ENDPOINT_CHANGED, CHANNEL_BIT_COINBASE_OUTPUT_DATA_SIZE,
-    CHANNEL_BIT_NEW_TEMPLATE, CHANNEL_BIT_REQUEST_TRANSACTION_DATA,
+    CHANNEL_BIT_ALLOCATE_MINING_JOB_TOKEN, CHANNEL_BIT_ALLOCATE_MINING_JOB_TOKEN_SUCCESS,
+    CHANNEL_BIT_CHANNEL_ENDPOINT_CHANGED, CHANNEL_BIT_CLOSE_CHANNEL,
+    CHANNEL_BIT_COINBASE_OUTPUT_DATA_SIZE, CHANNEL_BIT_DECLARE_MINING_JOB,
+    CHANNEL_BIT_DECLARE_MINING_JOB_ERROR, CHANNEL_BIT_DECLARE_MINING_JOB_SUCCESS,
+    CHANNEL_BIT_IDENTIFY_TRANSACTIONS, CHANNEL_BIT_IDENTIFY_TRANSACTIONS_SUCCESS,
+    CHANNEL_BIT_MINING_SET_NEW_PREV_HASH, CHANNEL_BIT_NEW_EXTENDED_MINING_JOB,
+    CHANNEL_BIT_NEW_MINING_JOB, CHANNEL_BIT_NEW_TEMPLATE, CHANNEL_BIT_OPEN_EXTENDED_MINING_CHANNEL,
+    CHANNEL_BIT_OPEN_EXTENDED_MINING_CHANNEL_SUCCES, CHANNEL_BIT_OPEN_MINING_CHANNEL_ERROR,
+    CHANNEL_BIT_OPEN_STANDARD_MINING_CHANNEL, CHANNEL_BIT_OPEN_STANDARD_MINING_CHANNEL_SUCCESS,
+    CHANNEL_BIT_PROVIDE_MISSING_TRANSACTIONS, CHANNEL_BIT_PROVIDE_MISSING_TRANSACTIONS_SUCCESS,
+    CHANNEL_BIT_RECONNECT, CHANNEL_BIT_REQUEST_TRANSACTION_DATA,
     CHANNEL_BIT_REQUEST_TRANSACTION_DATA_ERROR, CHANNEL_BIT_REQUEST_TRANSACTION_DATA_SUCCESS,
     CHANNEL_BIT_SETUP_CONNECTION, CHANNEL_BIT_SETUP_CONNECTION_ERROR,
-    CHANNEL_BIT_SETUP_CONNECTION_SUCCESS, CHANNEL_BIT_SET_NEW_PREV_HASH,
-    CHANNEL_BIT_SUBMIT_SOLUTION, EXTENSION_TYPE_NO_EXTENSION,
-    MESSAGE_TYPE_CHANNEL_ENDPOINT_CHANGED, MESSAGE_TYPE_COINBASE_OUTPUT_DATA_SIZE,
-    MESSAGE_TYPE_NEW_TEMPLATE, MESSAGE_TYPE_REQUEST_TRANSACTION_DATA,
-    MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_ERROR, MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_SUCCESS,
-    MESSAGE_TYPE_SETUP_CONNECTION, MESSAGE_TYPE_SETUP_CONNECTION_ERROR,
-    MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS, MESSAGE_TYPE_SET_NEW_PREV_HASH,
-    MESSAGE_TYPE_SUBMIT_SOLUTION,
+    CHANNEL_BIT_SETUP_CONNECTION_SUCCESS, CHANNEL_BIT_SET_CUSTOM_MINING_JOB,
+    CHANNEL_BIT_SET_CUSTOM_MINING_JOB_ERROR, CHANNEL_BIT_SET_CUSTOM_MINING_JOB_SUCCESS,
+    CHANNEL_BIT_SET_EXTRANONCE_PREFIX, CHANNEL_BIT_SET_GROUP_CHANNEL,
+    CHANNEL_BIT_SET_NEW_PREV_HASH, CHANNEL_BIT_SET_TARGET, CHANNEL_BIT_SUBMIT_SHARES_ERROR,
+    CHANNEL_BIT_SUBMIT_SHARES_EXTENDED, CHANNEL_BIT_SUBMIT_SHARES_STANDARD,
+    CHANNEL_BIT_SUBMIT_SHARES_SUCCESS, CHANNEL_BIT_SUBMIT_SOLUTION, CHANNEL_BIT_SUBMIT_SOLUTION_JD,
+    CHANNEL_BIT_UPDATE_CHANNEL, CHANNEL_BIT_UPDATE_CHANNEL_ERROR, EXTENSION_TYPE_NO_EXTENSION,
+    MESSAGE_TYPE_ALLOCATE_MINING_JOB_TOKEN, MESSAGE_TYPE_ALLOCATE_MINING_JOB_TOKEN_SUCCESS,
+    MESSAGE_TYPE_CHANNEL_ENDPOINT_CHANGED, MESSAGE_TYPE_CLOSE_CHANNEL,
+    MESSAGE_TYPE_COINBASE_OUTPUT_DATA_SIZE, MESSAGE_TYPE_DECLARE_MINING_JOB,
+    MESSAGE_TYPE_DECLARE_MINING_JOB_ERROR, MESSAGE_TYPE_DECLARE_MINING_JOB_SUCCESS,
+    MESSAGE_TYPE_IDENTIFY_TRANSACTIONS, MESSAGE_TYPE_IDENTIFY_TRANSACTIONS_SUCCESS,
+    MESSAGE_TYPE_MINING_SET_NEW_PREV_HASH, MESSAGE_TYPE_NEW_EXTENDED_MINING_JOB,
+    MESSAGE_TYPE_NEW_MINING_JOB, MESSAGE_TYPE_NEW_TEMPLATE,
+    MESSAGE_TYPE_OPEN_EXTENDED_MINING_CHANNEL, MESSAGE_TYPE_OPEN_EXTENDED_MINING_CHANNEL_SUCCES,
+    MESSAGE_TYPE_OPEN_MINING_CHANNEL_ERROR, MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL,
+    MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL_SUCCESS, MESSAGE_TYPE_PROVIDE_MISSING_TRANSACTIONS,
+    MESSAGE_TYPE_PROVIDE_MISSING_TRANSACTIONS_SUCCESS, MESSAGE_TYPE_RECONNECT,
+    MESSAGE_TYPE_REQUEST_TRANSACTION_DATA, MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_ERROR,
+    MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_SUCCESS, MESSAGE_TYPE_SETUP_CONNECTION,
+    MESSAGE_TYPE_SETUP_CONNECTION_ERROR, MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS,
+    MESSAGE_TYPE_SET_CUSTOM_MINING_JOB, MESSAGE_TYPE_SET_CUSTOM_MINING_JOB_ERROR,
+    MESSAGE_TYPE_SET_CUSTOM_MINING_JOB_SUCCESS, MESSAGE_TYPE_SET_EXTRANONCE_PREFIX,
+    MESSAGE_TYPE_SET_GROUP_CHANNEL, MESSAGE_TYPE_SET_NEW_PREV_HASH, MESSAGE_TYPE_SET_TARGET,
+    MESSAGE_TYPE_SUBMIT_SHARES_ERROR, MESSAGE_TYPE_SUBMIT_SHARES_EXTENDED,
+    MESSAGE_TYPE_SUBMIT_SHARES_STANDARD, MESSAGE_TYPE_SUBMIT_SHARES_SUCCESS,
+    MESSAGE_TYPE_SUBMIT_SOLUTION, MESSAGE_TYPE_SUBMIT_SOLUTION_JD, MESSAGE_TYPE_UPDATE_CHANNEL,
+    MESSAGE_TYPE_UPDATE_CHANNEL_ERROR,
 };
 use core::convert::{TryFrom, TryInto};
 
+mod noise;
+pub use noise::*;
+
 #[derive(Clone, Debug)]
 pub enum Sv2Message<'a> {
     CoinbaseOutputDataSize(CoinbaseOutputDataSize),
@@ -50,6 +103,38 @@ pub enum Sv2Message<'a> {
     ChannelEndpointChanged(ChannelEndpointChanged),
     SetupConnection(SetupConnection<'a>),
     SetupConnectionError(SetupConnectionError<'a>),
+    OpenStandardMiningChannel(OpenStandardMiningChannel<'a>),
+    OpenStandardMiningChannelSuccess(OpenStandardMiningChannelSuccess<'a>),
+    OpenExtendedMiningChannel(OpenExtendedMiningChannel<'a>),
+    OpenExtendedMiningChannelSuccess(OpenExtendedMiningChannelSuccess<'a>),
+    OpenMiningChannelError(OpenMiningChannelError<'a>),
+    UpdateChannel(UpdateChannel<'a>),
+    UpdateChannelError(UpdateChannelError<'a>),
+    CloseChannel(CloseChannel<'a>),
+    SetExtranoncePrefix(SetExtranoncePrefix<'a>),
+    SubmitSharesStandard(SubmitSharesStandard),
+    SubmitSharesExtended(SubmitSharesExtended<'a>),
+    SubmitSharesSuccess(SubmitSharesSuccess),
+    SubmitSharesError(SubmitSharesError<'a>),
+    NewMiningJob(NewMiningJob<'a>),
+    NewExtendedMiningJob(NewExtendedMiningJob<'a>),
+    MiningSetNewPrevHash(MiningSetNewPrevHash<'a>),
+    SetTarget(SetTarget<'a>),
+    SetCustomMiningJob(SetCustomMiningJob<'a>),
+    SetCustomMiningJobSuccess(SetCustomMiningJobSuccess),
+    SetCustomMiningJobError(SetCustomMiningJobError<'a>),
+    Reconnect(Reconnect<'a>),
+    SetGroupChannel(SetGroupChannel<'a>),
+    AllocateMiningJobToken(AllocateMiningJobToken<'a>),
+    AllocateMiningJobTokenSuccess(AllocateMiningJobTokenSuccess<'a>),
+    IdentifyTransactions(IdentifyTransactions),
+    IdentifyTransactionsSuccess(IdentifyTransactionsSuccess<'a>),
+    ProvideMissingTransactions(ProvideMissingTransactions<'a>),
+    ProvideMissingTransactionsSuccess(ProvideMissingTransactionsSuccess<'a>),
+    DeclareMiningJob(DeclareMiningJob<'a>),
+    DeclareMiningJobSuccess(DeclareMiningJobSuccess<'a>),
+    DeclareMiningJobError(DeclareMiningJobError<'a>),
+    SubmitSolutionJd(SubmitSolutionJd<'a>),
     SetupConnectionSuccess(SetupConnectionSuccess),
 }
 
@@ -71,6 +156,48 @@ impl<'a> Sv2Message<'a> {
             Sv2Message::SetupConnection(_) => MESSAGE_TYPE_SETUP_CONNECTION,
             Sv2Message::SetupConnectionError(_) => MESSAGE_TYPE_SETUP_CONNECTION_ERROR,
             Sv2Message::SetupConnectionSuccess(_) => MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS,
+            Sv2Message::OpenStandardMiningChannel(_) => MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL,
+            Sv2Message::OpenStandardMiningChannelSuccess(_) => {
+                MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL_SUCCESS
+            }
+            Sv2Message::OpenExtendedMiningChannel(_) => MESSAGE_TYPE_OPEN_EXTENDED_MINING_CHANNEL,
+            Sv2Message::OpenExtendedMiningChannelSuccess(_) => {
+                MESSAGE_TYPE_OPEN_EXTENDED_MINING_CHANNEL_SUCCES
+            }
+            Sv2Message::OpenMiningChannelError(_) => MESSAGE_TYPE_OPEN_MINING_CHANNEL_ERROR,
+            Sv2Message::UpdateChannel(_) => MESSAGE_TYPE_UPDATE_CHANNEL,
+            Sv2Message::UpdateChannelError(_) => MESSAGE_TYPE_UPDATE_CHANNEL_ERROR,
+            Sv2Message::CloseChannel(_) => MESSAGE_TYPE_CLOSE_CHANNEL,
+            Sv2Message::SetExtranoncePrefix(_) => MESSAGE_TYPE_SET_EXTRANONCE_PREFIX,
+            Sv2Message::SubmitSharesStandard(_) => MESSAGE_TYPE_SUBMIT_SHARES_STANDARD,
+            Sv2Message::SubmitSharesExtended(_) => MESSAGE_TYPE_SUBMIT_SHARES_EXTENDED,
+            Sv2Message::SubmitSharesSuccess(_) => MESSAGE_TYPE_SUBMIT_SHARES_SUCCESS,
+            Sv2Message::SubmitSharesError(_) => MESSAGE_TYPE_SUBMIT_SHARES_ERROR,
+            Sv2Message::NewMiningJob(_) => MESSAGE_TYPE_NEW_MINING_JOB,
+            Sv2Message::NewExtendedMiningJob(_) => MESSAGE_TYPE_NEW_EXTENDED_MINING_JOB,
+            Sv2Message::MiningSetNewPrevHash(_) => MESSAGE_TYPE_MINING_SET_NEW_PREV_HASH,
+            Sv2Message::SetTarget(_) => MESSAGE_TYPE_SET_TARGET,
+            Sv2Message::SetCustomMiningJob(_) => MESSAGE_TYPE_SET_CUSTOM_MINING_JOB,
+            Sv2Message::SetCustomMiningJobSuccess(_) => MESSAGE_TYPE_SET_CUSTOM_MINING_JOB_SUCCESS,
+            Sv2Message::SetCustomMiningJobError(_) => MESSAGE_TYPE_SET_CUSTOM_MINING_JOB_ERROR,
+            Sv2Message::Reconnect(_) => MESSAGE_TYPE_RECONNECT,
+            Sv2Message::SetGroupChannel(_) => MESSAGE_TYPE_SET_GROUP_CHANNEL,
+            Sv2Message::AllocateMiningJobToken(_) => MESSAGE_TYPE_ALLOCATE_MINING_JOB_TOKEN,
+            Sv2Message::AllocateMiningJobTokenSuccess(_) => {
+                MESSAGE_TYPE_ALLOCATE_MINING_JOB_TOKEN_SUCCESS
+            }
+            Sv2Message::IdentifyTransactions(_) => MESSAGE_TYPE_IDENTIFY_TRANSACTIONS,
+            Sv2Message::IdentifyTransactionsSuccess(_) => {
+                MESSAGE_TYPE_IDENTIFY_TRANSACTIONS_SUCCESS
+            }
+            Sv2Message::ProvideMissingTransactions(_) => MESSAGE_TYPE_PROVIDE_MISSING_TRANSACTIONS,
+            Sv2Message::ProvideMissingTransactionsSuccess(_) => {
+                MESSAGE_TYPE_PROVIDE_MISSING_TRANSACTIONS_SUCCESS
+            }
+            Sv2Message::DeclareMiningJob(_) => MESSAGE_TYPE_DECLARE_MINING_JOB,
+            Sv2Message::DeclareMiningJobSuccess(_) => MESSAGE_TYPE_DECLARE_MINING_JOB_SUCCESS,
+            Sv2Message::DeclareMiningJobError(_) => MESSAGE_TYPE_DECLARE_MINING_JOB_ERROR,
+            Sv2Message::SubmitSolutionJd(_) => MESSAGE_TYPE_SUBMIT_SOLUTION_JD,
         }
     }
 
@@ -91,6 +218,46 @@ impl<'a> Sv2Message<'a> {
             Sv2Message::SetupConnection(_) => CHANNEL_BIT_SETUP_CONNECTION,
             Sv2Message::SetupConnectionError(_) => CHANNEL_BIT_SETUP_CONNECTION_ERROR,
             Sv2Message::SetupConnectionSuccess(_) => CHANNEL_BIT_SETUP_CONNECTION_SUCCESS,
+            Sv2Message::OpenStandardMiningChannel(_) => CHANNEL_BIT_OPEN_STANDARD_MINING_CHANNEL,
+            Sv2Message::OpenStandardMiningChannelSuccess(_) => {
+                CHANNEL_BIT_OPEN_STANDARD_MINING_CHANNEL_SUCCESS
+            }
+            Sv2Message::OpenExtendedMiningChannel(_) => CHANNEL_BIT_OPEN_EXTENDED_MINING_CHANNEL,
+            Sv2Message::OpenExtendedMiningChannelSuccess(_) => {
+                CHANNEL_BIT_OPEN_EXTENDED_MINING_CHANNEL_SUCCES
+            }
+            Sv2Message::OpenMiningChannelError(_) => CHANNEL_BIT_OPEN_MINING_CHANNEL_ERROR,
+            Sv2Message::UpdateChannel(_) => CHANNEL_BIT_UPDATE_CHANNEL,
+            Sv2Message::UpdateChannelError(_) => CHANNEL_BIT_UPDATE_CHANNEL_ERROR,
+            Sv2Message::CloseChannel(_) => CHANNEL_BIT_CLOSE_CHANNEL,
+            Sv2Message::SetExtranoncePrefix(_) => CHANNEL_BIT_SET_EXTRANONCE_PREFIX,
+            Sv2Message::SubmitSharesStandard(_) => CHANNEL_BIT_SUBMIT_SHARES_STANDARD,
+            Sv2Message::SubmitSharesExtended(_) => CHANNEL_BIT_SUBMIT_SHARES_EXTENDED,
+            Sv2Message::SubmitSharesSuccess(_) => CHANNEL_BIT_SUBMIT_SHARES_SUCCESS,
+            Sv2Message::SubmitSharesError(_) => CHANNEL_BIT_SUBMIT_SHARES_ERROR,
+            Sv2Message::NewMiningJob(_) => CHANNEL_BIT_NEW_MINING_JOB,
+            Sv2Message::NewExtendedMiningJob(_) => CHANNEL_BIT_NEW_EXTENDED_MINING_JOB,
+            Sv2Message::MiningSetNewPrevHash(_) => CHANNEL_BIT_MINING_SET_NEW_PREV_HASH,
+            Sv2Message::SetTarget(_) => CHANNEL_BIT_SET_TARGET,
+            Sv2Message::SetCustomMiningJob(_) => CHANNEL_BIT_SET_CUSTOM_MINING_JOB,
+            Sv2Message::SetCustomMiningJobSuccess(_) => CHANNEL_BIT_SET_CUSTOM_MINING_JOB_SUCCESS,
+            Sv2Message::SetCustomMiningJobError(_) => CHANNEL_BIT_SET_CUSTOM_MINING_JOB_ERROR,
+            Sv2Message::Reconnect(_) => CHANNEL_BIT_RECONNECT,
+            Sv2Message::SetGroupChannel(_) => CHANNEL_BIT_SET_GROUP_CHANNEL,
+            Sv2Message::AllocateMiningJobToken(_) => CHANNEL_BIT_ALLOCATE_MINING_JOB_TOKEN,
+            Sv2Message::AllocateMiningJobTokenSuccess(_) => {
+                CHANNEL_BIT_ALLOCATE_MINING_JOB_TOKEN_SUCCESS
+            }
+            Sv2Message::IdentifyTransactions(_) => CHANNEL_BIT_IDENTIFY_TRANSACTIONS,
+            Sv2Message::IdentifyTransactionsSuccess(_) => CHANNEL_BIT_IDENTIFY_TRANSACTIONS_SUCCESS,
+            Sv2Message::ProvideMissingTransactions(_) => CHANNEL_BIT_PROVIDE_MISSING_TRANSACTIONS,
+            Sv2Message::ProvideMissingTransactionsSuccess(_) => {
+                CHANNEL_BIT_PROVIDE_MISSING_TRANSACTIONS_SUCCESS
+            }
+            Sv2Message::DeclareMiningJob(_) => CHANNEL_BIT_DECLARE_MINING_JOB,
+            Sv2Message::DeclareMiningJobSuccess(_) => CHANNEL_BIT_DECLARE_MINING_JOB_SUCCESS,
+            Sv2Message::DeclareMiningJobError(_) => CHANNEL_BIT_DECLARE_MINING_JOB_ERROR,
+            Sv2Message::SubmitSolutionJd(_) => CHANNEL_BIT_SUBMIT_SOLUTION_JD,
         }
     }
 }
@@ -113,6 +280,38 @@ pub enum CSv2Message {
     ChannelEndpointChanged(ChannelEndpointChanged),
     SetupConnection(CSetupConnection),
     SetupConnectionError(CSetupConnectionError),
+    OpenStandardMiningChannel(COpenStandardMiningChannel),
+    OpenStandardMiningChannelSuccess(COpenStandardMiningChannelSuccess),
+    OpenExtendedMiningChannel(COpenExtendedMiningChannel),
+    OpenExtendedMiningChannelSuccess(COpenExtendedMiningChannelSuccess),
+    OpenMiningChannelError(COpenMiningChannelError),
+    UpdateChannel(CUpdateChannel),
+    UpdateChannelError(CUpdateChannelError),
+    CloseChannel(CCloseChannel),
+    SetExtranoncePrefix(CSetExtranoncePrefix),
+    SubmitSharesStandard(SubmitSharesStandard),
+    SubmitSharesExtended(CSubmitSharesExtended),
+    SubmitSharesSuccess(SubmitSharesSuccess),
+    SubmitSharesError(CSubmitSharesError),
+    NewMiningJob(CNewMiningJob),
+    NewExtendedMiningJob(CNewExtendedMiningJob),
+    MiningSetNewPrevHash(CMiningSetNewPrevHash),
+    SetTarget(CSetTarget),
+    SetCustomMiningJob(CSetCustomMiningJob),
+    SetCustomMiningJobSuccess(SetCustomMiningJobSuccess),
+    SetCustomMiningJobError(CSetCustomMiningJobError),
+    Reconnect(CReconnect),
+    SetGroupChannel(CSetGroupChannel),
+    AllocateMiningJobToken(CAllocateMiningJobToken),
+    AllocateMiningJobTokenSuccess(CAllocateMiningJobTokenSuccess),
+    IdentifyTransactions(IdentifyTransactions),
+    IdentifyTransactionsSuccess(CIdentifyTransactionsSuccess),
+    ProvideMissingTransactions(CProvideMissingTransactions),
+    ProvideMissingTransactionsSuccess(CProvideMissingTransactionsSuccess),
+    DeclareMiningJob(CDeclareMiningJob),
+    DeclareMiningJobSuccess(CDeclareMiningJobSuccess),
+    DeclareMiningJobError(CDeclareMiningJobError),
+    SubmitSolutionJd(CSubmitSolutionJd),
     SetupConnectionSuccess(SetupConnectionSuccess),
 }
 
@@ -130,6 +329,38 @@ pub extern "C" fn drop_sv2_message(s: CSv2Message) {
         CSv2Message::SetupConnection(a) => drop(a),
         CSv2Message::SetupConnectionError(a) => drop(a),
         CSv2Message::SetupConnectionSuccess(_) => (),
+        CSv2Message::OpenStandardMiningChannel(a) => drop(a),
+        CSv2Message::OpenStandardMiningChannelSuccess(a) => drop(a),
+        CSv2Message::OpenExtendedMiningChannel(a) => drop(a),
+        CSv2Message::OpenExtendedMiningChannelSuccess(a) => drop(a),
+        CSv2Message::OpenMiningChannelError(a) => drop(a),
+        CSv2Message::UpdateChannel(a) => drop(a),
+        CSv2Message::UpdateChannelError(a) => drop(a),
+        CSv2Message::CloseChannel(a) => drop(a),
+        CSv2Message::SetExtranoncePrefix(a) => drop(a),
+        CSv2Message::SubmitSharesStandard(_) => (),
+        CSv2Message::SubmitSharesExtended(a) => drop(a),
+        CSv2Message::SubmitSharesSuccess(_) => (),
+        CSv2Message::SubmitSharesError(a) => drop(a),
+        CSv2Message::NewMiningJob(a) => drop(a),
+        CSv2Message::NewExtendedMiningJob(a) => drop(a),
+        CSv2Message::MiningSetNewPrevHash(a) => drop(a),
+        CSv2Message::SetTarget(a) => drop(a),
+        CSv2Message::SetCustomMiningJob(a) => drop(a),
+        CSv2Message::SetCustomMiningJobSuccess(_) => (),
+        CSv2Message::SetCustomMiningJobError(a) => drop(a),
+        CSv2Message::Reconnect(a) => drop(a),
+        CSv2Message::SetGroupChannel(a) => drop(a),
+        CSv2Message::AllocateMiningJobToken(a) => drop(a),
+        CSv2Message::AllocateMiningJobTokenSuccess(a) => drop(a),
+        CSv2Message::IdentifyTransactions(_) => (),
+        CSv2Message::IdentifyTransactionsSuccess(a) => drop(a),
+        CSv2Message::ProvideMissingTransactions(a) => drop(a),
+        CSv2Message::ProvideMissingTransactionsSuccess(a) => drop(a),
+        CSv2Message::DeclareMiningJob(a) => drop(a),
+        CSv2Message::DeclareMiningJobSuccess(a) => drop(a),
+        CSv2Message::DeclareMiningJobError(a) => drop(a),
+        CSv2Message::SubmitSolutionJd(a) => drop(a),
     }
 }
 
@@ -167,6 +398,48 @@ impl<'a> From<Sv2Message<'a>> for CSv2Message {
             Sv2Message::SetupConnection(a) => Self::SetupConnection(a.into()),
             Sv2Message::SetupConnectionError(a) => Self::SetupConnectionError(a.into()),
             Sv2Message::SetupConnectionSuccess(a) => Self::SetupConnectionSuccess(a),
+            Sv2Message::OpenStandardMiningChannel(a) => Self::OpenStandardMiningChannel(a.into()),
+            Sv2Message::OpenStandardMiningChannelSuccess(a) => {
+                Self::OpenStandardMiningChannelSuccess(a.into())
+            }
+            Sv2Message::OpenExtendedMiningChannel(a) => Self::OpenExtendedMiningChannel(a.into()),
+            Sv2Message::OpenExtendedMiningChannelSuccess(a) => {
+                Self::OpenExtendedMiningChannelSuccess(a.into())
+            }
+            Sv2Message::OpenMiningChannelError(a) => Self::OpenMiningChannelError(a.into()),
+            Sv2Message::UpdateChannel(a) => Self::UpdateChannel(a.into()),
+            Sv2Message::UpdateChannelError(a) => Self::UpdateChannelError(a.into()),
+            Sv2Message::CloseChannel(a) => Self::CloseChannel(a.into()),
+            Sv2Message::SetExtranoncePrefix(a) => Self::SetExtranoncePrefix(a.into()),
+            Sv2Message::SubmitSharesStandard(a) => Self::SubmitSharesStandard(a),
+            Sv2Message::SubmitSharesExtended(a) => Self::SubmitSharesExtended(a.into()),
+            Sv2Message::SubmitSharesSuccess(a) => Self::SubmitSharesSuccess(a),
+            Sv2Message::SubmitSharesError(a) => Self::SubmitSharesError(a.into()),
+            Sv2Message::NewMiningJob(a) => Self::NewMiningJob(a.into()),
+            Sv2Message::NewExtendedMiningJob(a) => Self::NewExtendedMiningJob(a.into()),
+            Sv2Message::MiningSetNewPrevHash(a) => Self::MiningSetNewPrevHash(a.into()),
+            Sv2Message::SetTarget(a) => Self::SetTarget(a.into()),
+            Sv2Message::SetCustomMiningJob(a) => Self::SetCustomMiningJob(a.into()),
+            Sv2Message::SetCustomMiningJobSuccess(a) => Self::SetCustomMiningJobSuccess(a),
+            Sv2Message::SetCustomMiningJobError(a) => Self::SetCustomMiningJobError(a.into()),
+            Sv2Message::Reconnect(a) => Self::Reconnect(a.into()),
+            Sv2Message::SetGroupChannel(a) => Self::SetGroupChannel(a.into()),
+            Sv2Message::AllocateMiningJobToken(a) => Self::AllocateMiningJobToken(a.into()),
+            Sv2Message::AllocateMiningJobTokenSuccess(a) => {
+                Self::AllocateMiningJobTokenSuccess(a.into())
+            }
+            Sv2Message::IdentifyTransactions(a) => Self::IdentifyTransactions(a),
+            Sv2Message::IdentifyTransactionsSuccess(a) => {
+                Self::IdentifyTransactionsSuccess(a.into())
+            }
+            Sv2Message::ProvideMissingTransactions(a) => Self::ProvideMissingTransactions(a.into()),
+            Sv2Message::ProvideMissingTransactionsSuccess(a) => {
+                Self::ProvideMissingTransactionsSuccess(a.into())
+            }
+            Sv2Message::DeclareMiningJob(a) => Self::DeclareMiningJob(a.into()),
+            Sv2Message::DeclareMiningJobSuccess(a) => Self::DeclareMiningJobSuccess(a.into()),
+            Sv2Message::DeclareMiningJobError(a) => Self::DeclareMiningJobError(a.into()),
+            Sv2Message::SubmitSolutionJd(a) => Self::SubmitSolutionJd(a.into()),
         }
     }
 }
@@ -194,6 +467,86 @@ impl<'a> CSv2Message {
             CSv2Message::SetupConnection(v) => {
                 Ok(Sv2Message::SetupConnection(v.to_rust_rep_mut()?))
             }
+            CSv2Message::OpenStandardMiningChannel(v) => {
+                Ok(Sv2Message::OpenStandardMiningChannel(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::OpenStandardMiningChannelSuccess(v) => Ok(
+                Sv2Message::OpenStandardMiningChannelSuccess(v.to_rust_rep_mut()?),
+            ),
+            CSv2Message::OpenExtendedMiningChannel(v) => {
+                Ok(Sv2Message::OpenExtendedMiningChannel(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::OpenExtendedMiningChannelSuccess(v) => Ok(
+                Sv2Message::OpenExtendedMiningChannelSuccess(v.to_rust_rep_mut()?),
+            ),
+            CSv2Message::OpenMiningChannelError(v) => {
+                Ok(Sv2Message::OpenMiningChannelError(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::UpdateChannel(v) => Ok(Sv2Message::UpdateChannel(v.to_rust_rep_mut()?)),
+            CSv2Message::UpdateChannelError(v) => {
+                Ok(Sv2Message::UpdateChannelError(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::CloseChannel(v) => Ok(Sv2Message::CloseChannel(v.to_rust_rep_mut()?)),
+            CSv2Message::SetExtranoncePrefix(v) => {
+                Ok(Sv2Message::SetExtranoncePrefix(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::SubmitSharesStandard(v) => Ok(Sv2Message::SubmitSharesStandard(v.clone())),
+            CSv2Message::SubmitSharesExtended(v) => {
+                Ok(Sv2Message::SubmitSharesExtended(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::SubmitSharesSuccess(v) => Ok(Sv2Message::SubmitSharesSuccess(v.clone())),
+            CSv2Message::SubmitSharesError(v) => {
+                Ok(Sv2Message::SubmitSharesError(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::NewMiningJob(v) => Ok(Sv2Message::NewMiningJob(v.to_rust_rep_mut()?)),
+            CSv2Message::NewExtendedMiningJob(v) => {
+                Ok(Sv2Message::NewExtendedMiningJob(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::MiningSetNewPrevHash(v) => {
+                Ok(Sv2Message::MiningSetNewPrevHash(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::SetTarget(v) => Ok(Sv2Message::SetTarget(v.to_rust_rep_mut()?)),
+            CSv2Message::SetCustomMiningJob(v) => {
+                Ok(Sv2Message::SetCustomMiningJob(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::SetCustomMiningJobSuccess(v) => {
+                Ok(Sv2Message::SetCustomMiningJobSuccess(v.clone()))
+            }
+            CSv2Message::SetCustomMiningJobError(v) => {
+                Ok(Sv2Message::SetCustomMiningJobError(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::Reconnect(v) => Ok(Sv2Message::Reconnect(v.to_rust_rep_mut()?)),
+            CSv2Message::SetGroupChannel(v) => {
+                Ok(Sv2Message::SetGroupChannel(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::AllocateMiningJobToken(v) => {
+                Ok(Sv2Message::AllocateMiningJobToken(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::AllocateMiningJobTokenSuccess(v) => Ok(
+                Sv2Message::AllocateMiningJobTokenSuccess(v.to_rust_rep_mut()?),
+            ),
+            CSv2Message::IdentifyTransactions(v) => Ok(Sv2Message::IdentifyTransactions(v.clone())),
+            CSv2Message::IdentifyTransactionsSuccess(v) => Ok(
+                Sv2Message::IdentifyTransactionsSuccess(v.to_rust_rep_mut()?),
+            ),
+            CSv2Message::ProvideMissingTransactions(v) => {
+                Ok(Sv2Message::ProvideMissingTransactions(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::ProvideMissingTransactionsSuccess(v) => Ok(
+                Sv2Message::ProvideMissingTransactionsSuccess(v.to_rust_rep_mut()?),
+            ),
+            CSv2Message::DeclareMiningJob(v) => {
+                Ok(Sv2Message::DeclareMiningJob(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::DeclareMiningJobSuccess(v) => {
+                Ok(Sv2Message::DeclareMiningJobSuccess(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::DeclareMiningJobError(v) => {
+                Ok(Sv2Message::DeclareMiningJobError(v.to_rust_rep_mut()?))
+            }
+            CSv2Message::SubmitSolutionJd(v) => {
+                Ok(Sv2Message::SubmitSolutionJd(v.to_rust_rep_mut()?))
+            }
         }
     }
 }
@@ -212,6 +565,38 @@ impl<'decoder> From<Sv2Message<'decoder>> for EncodableField<'decoder> {
             Sv2Message::SetupConnection(a) => a.into(),
             Sv2Message::SetupConnectionError(a) => a.into(),
             Sv2Message::SetupConnectionSuccess(a) => a.into(),
+            Sv2Message::OpenStandardMiningChannel(a) => a.into(),
+            Sv2Message::OpenStandardMiningChannelSuccess(a) => a.into(),
+            Sv2Message::OpenExtendedMiningChannel(a) => a.into(),
+            Sv2Message::OpenExtendedMiningChannelSuccess(a) => a.into(),
+            Sv2Message::OpenMiningChannelError(a) => a.into(),
+            Sv2Message::UpdateChannel(a) => a.into(),
+            Sv2Message::UpdateChannelError(a) => a.into(),
+            Sv2Message::CloseChannel(a) => a.into(),
+            Sv2Message::SetExtranoncePrefix(a) => a.into(),
+            Sv2Message::SubmitSharesStandard(a) => a.into(),
+            Sv2Message::SubmitSharesExtended(a) => a.into(),
+            Sv2Message::SubmitSharesSuccess(a) => a.into(),
+            Sv2Message::SubmitSharesError(a) => a.into(),
+            Sv2Message::NewMiningJob(a) => a.into(),
+            Sv2Message::NewExtendedMiningJob(a) => a.into(),
+            Sv2Message::MiningSetNewPrevHash(a) => a.into(),
+            Sv2Message::SetTarget(a) => a.into(),
+            Sv2Message::SetCustomMiningJob(a) => a.into(),
+            Sv2Message::SetCustomMiningJobSuccess(a) => a.into(),
+            Sv2Message::SetCustomMiningJobError(a) => a.into(),
+            Sv2Message::Reconnect(a) => a.into(),
+            Sv2Message::SetGroupChannel(a) => a.into(),
+            Sv2Message::AllocateMiningJobToken(a) => a.into(),
+            Sv2Message::AllocateMiningJobTokenSuccess(a) => a.into(),
+            Sv2Message::IdentifyTransactions(a) => a.into(),
+            Sv2Message::IdentifyTransactionsSuccess(a) => a.into(),
+            Sv2Message::ProvideMissingTransactions(a) => a.into(),
+            Sv2Message::ProvideMissingTransactionsSuccess(a) => a.into(),
+            Sv2Message::DeclareMiningJob(a) => a.into(),
+            Sv2Message::DeclareMiningJobSuccess(a) => a.into(),
+            Sv2Message::DeclareMiningJobError(a) => a.into(),
+            Sv2Message::SubmitSolutionJd(a) => a.into(),
         }
     }
 }
@@ -230,6 +615,38 @@ impl binary_sv2::GetSize for Sv2Message<'_> {
             Sv2Message::SetupConnection(a) => a.get_size(),
             Sv2Message::SetupConnectionError(a) => a.get_size(),
             Sv2Message::SetupConnectionSuccess(a) => a.get_size(),
+            Sv2Message::OpenStandardMiningChannel(a) => a.get_size(),
+            Sv2Message::OpenStandardMiningChannelSuccess(a) => a.get_size(),
+            Sv2Message::OpenExtendedMiningChannel(a) => a.get_size(),
+            Sv2Message::OpenExtendedMiningChannelSuccess(a) => a.get_size(),
+            Sv2Message::OpenMiningChannelError(a) => a.get_size(),
+            Sv2Message::UpdateChannel(a) => a.get_size(),
+            Sv2Message::UpdateChannelError(a) => a.get_size(),
+            Sv2Message::CloseChannel(a) => a.get_size(),
+            Sv2Message::SetExtranoncePrefix(a) => a.get_size(),
+            Sv2Message::SubmitSharesStandard(a) => a.get_size(),
+            Sv2Message::SubmitSharesExtended(a) => a.get_size(),
+            Sv2Message::SubmitSharesSuccess(a) => a.get_size(),
+            Sv2Message::SubmitSharesError(a) => a.get_size(),
+            Sv2Message::NewMiningJob(a) => a.get_size(),
+            Sv2Message::NewExtendedMiningJob(a) => a.get_size(),
+            Sv2Message::MiningSetNewPrevHash(a) => a.get_size(),
+            Sv2Message::SetTarget(a) => a.get_size(),
+            Sv2Message::SetCustomMiningJob(a) => a.get_size(),
+            Sv2Message::SetCustomMiningJobSuccess(a) => a.get_size(),
+            Sv2Message::SetCustomMiningJobError(a) => a.get_size(),
+            Sv2Message::Reconnect(a) => a.get_size(),
+            Sv2Message::SetGroupChannel(a) => a.get_size(),
+            Sv2Message::AllocateMiningJobToken(a) => a.get_size(),
+            Sv2Message::AllocateMiningJobTokenSuccess(a) => a.get_size(),
+            Sv2Message::IdentifyTransactions(a) => a.get_size(),
+            Sv2Message::IdentifyTransactionsSuccess(a) => a.get_size(),
+            Sv2Message::ProvideMissingTransactions(a) => a.get_size(),
+            Sv2Message::ProvideMissingTransactionsSuccess(a) => a.get_size(),
+            Sv2Message::DeclareMiningJob(a) => a.get_size(),
+            Sv2Message::DeclareMiningJobSuccess(a) => a.get_size(),
+            Sv2Message::DeclareMiningJobError(a) => a.get_size(),
+            Sv2Message::SubmitSolutionJd(a) => a.get_size(),
         }
     }
 }
@@ -295,6 +712,134 @@ impl<'a> TryFrom<(u8, &'a mut [u8])> for Sv2Message<'a> {
                 let message: SubmitSolution = from_bytes(v.1)?;
                 Ok(Sv2Message::SubmitSolution(message))
             }
+            MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL => {
+                let message: OpenStandardMiningChannel<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::OpenStandardMiningChannel(message))
+            }
+            MESSAGE_TYPE_OPEN_STANDARD_MINING_CHANNEL_SUCCESS => {
+                let message: OpenStandardMiningChannelSuccess<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::OpenStandardMiningChannelSuccess(message))
+            }
+            MESSAGE_TYPE_OPEN_EXTENDED_MINING_CHANNEL => {
+                let message: OpenExtendedMiningChannel<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::OpenExtendedMiningChannel(message))
+            }
+            MESSAGE_TYPE_OPEN_EXTENDED_MINING_CHANNEL_SUCCES => {
+                let message: OpenExtendedMiningChannelSuccess<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::OpenExtendedMiningChannelSuccess(message))
+            }
+            MESSAGE_TYPE_OPEN_MINING_CHANNEL_ERROR => {
+                let message: OpenMiningChannelError<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::OpenMiningChannelError(message))
+            }
+            MESSAGE_TYPE_UPDATE_CHANNEL => {
+                let message: UpdateChannel<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::UpdateChannel(message))
+            }
+            MESSAGE_TYPE_UPDATE_CHANNEL_ERROR => {
+                let message: UpdateChannelError<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::UpdateChannelError(message))
+            }
+            MESSAGE_TYPE_CLOSE_CHANNEL => {
+                let message: CloseChannel<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::CloseChannel(message))
+            }
+            MESSAGE_TYPE_SET_EXTRANONCE_PREFIX => {
+                let message: SetExtranoncePrefix<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::SetExtranoncePrefix(message))
+            }
+            MESSAGE_TYPE_SUBMIT_SHARES_STANDARD => {
+                let message: SubmitSharesStandard = from_bytes(v.1)?;
+                Ok(Sv2Message::SubmitSharesStandard(message))
+            }
+            MESSAGE_TYPE_SUBMIT_SHARES_EXTENDED => {
+                let message: SubmitSharesExtended<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::SubmitSharesExtended(message))
+            }
+            MESSAGE_TYPE_SUBMIT_SHARES_SUCCESS => {
+                let message: SubmitSharesSuccess = from_bytes(v.1)?;
+                Ok(Sv2Message::SubmitSharesSuccess(message))
+            }
+            MESSAGE_TYPE_SUBMIT_SHARES_ERROR => {
+                let message: SubmitSharesError<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::SubmitSharesError(message))
+            }
+            MESSAGE_TYPE_NEW_MINING_JOB => {
+                let message: NewMiningJob<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::NewMiningJob(message))
+            }
+            MESSAGE_TYPE_NEW_EXTENDED_MINING_JOB => {
+                let message: NewExtendedMiningJob<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::NewExtendedMiningJob(message))
+            }
+            MESSAGE_TYPE_MINING_SET_NEW_PREV_HASH => {
+                let message: MiningSetNewPrevHash<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::MiningSetNewPrevHash(message))
+            }
+            MESSAGE_TYPE_SET_TARGET => {
+                let message: SetTarget<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::SetTarget(message))
+            }
+            MESSAGE_TYPE_SET_CUSTOM_MINING_JOB => {
+                let message: SetCustomMiningJob<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::SetCustomMiningJob(message))
+            }
+            MESSAGE_TYPE_SET_CUSTOM_MINING_JOB_SUCCESS => {
+                let message: SetCustomMiningJobSuccess = from_bytes(v.1)?;
+                Ok(Sv2Message::SetCustomMiningJobSuccess(message))
+            }
+            MESSAGE_TYPE_SET_CUSTOM_MINING_JOB_ERROR => {
+                let message: SetCustomMiningJobError<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::SetCustomMiningJobError(message))
+            }
+            MESSAGE_TYPE_RECONNECT => {
+                let message: Reconnect<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::Reconnect(message))
+            }
+            MESSAGE_TYPE_SET_GROUP_CHANNEL => {
+                let message: SetGroupChannel<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::SetGroupChannel(message))
+            }
+            MESSAGE_TYPE_ALLOCATE_MINING_JOB_TOKEN => {
+                let message: AllocateMiningJobToken<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::AllocateMiningJobToken(message))
+            }
+            MESSAGE_TYPE_ALLOCATE_MINING_JOB_TOKEN_SUCCESS => {
+                let message: AllocateMiningJobTokenSuccess<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::AllocateMiningJobTokenSuccess(message))
+            }
+            MESSAGE_TYPE_IDENTIFY_TRANSACTIONS => {
+                let message: IdentifyTransactions = from_bytes(v.1)?;
+                Ok(Sv2Message::IdentifyTransactions(message))
+            }
+            MESSAGE_TYPE_IDENTIFY_TRANSACTIONS_SUCCESS => {
+                let message: IdentifyTransactionsSuccess<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::IdentifyTransactionsSuccess(message))
+            }
+            MESSAGE_TYPE_PROVIDE_MISSING_TRANSACTIONS => {
+                let message: ProvideMissingTransactions<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::ProvideMissingTransactions(message))
+            }
+            MESSAGE_TYPE_PROVIDE_MISSING_TRANSACTIONS_SUCCESS => {
+                let message: ProvideMissingTransactionsSuccess<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::ProvideMissingTransactionsSuccess(message))
+            }
+            MESSAGE_TYPE_DECLARE_MINING_JOB => {
+                let message: DeclareMiningJob<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::DeclareMiningJob(message))
+            }
+            MESSAGE_TYPE_DECLARE_MINING_JOB_SUCCESS => {
+                let message: DeclareMiningJobSuccess<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::DeclareMiningJobSuccess(message))
+            }
+            MESSAGE_TYPE_DECLARE_MINING_JOB_ERROR => {
+                let message: DeclareMiningJobError<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::DeclareMiningJobError(message))
+            }
+            MESSAGE_TYPE_SUBMIT_SOLUTION_JD => {
+                let message: SubmitSolutionJd<'a> = from_bytes(v.1)?;
+                Ok(Sv2Message::SubmitSolutionJd(message))
+            }
             _ => Err(Error::UnknownMessageType(msg_type)),
         }
     }
@@ -719,6 +1264,78 @@ mod tests {
         assert_eq!(expect, actual);
     }
 
+    #[test]
+    fn test_message_type_submit_shares_standard() {
+        let expect = MESSAGE_TYPE_SUBMIT_SHARES_STANDARD;
+
+        let submit_shares = SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 0,
+            job_id: 2,
+            nonce: 0xdeadbeef,
+            ntime: 0x29ab5f49,
+            version: 0x20000000,
+        };
+
+        let sv2_message = Sv2Message::SubmitSharesStandard(submit_shares);
+        assert_eq!(expect, sv2_message.message_type());
+        assert_eq!(
+            CHANNEL_BIT_SUBMIT_SHARES_STANDARD,
+            sv2_message.channel_bit()
+        );
+    }
+
+    #[test]
+    fn test_message_type_mining_set_new_prev_hash() {
+        let expect = MESSAGE_TYPE_MINING_SET_NEW_PREV_HASH;
+
+        let mut u256 = [0_u8; 32];
+        let prev_hash: binary_sv2::U256 = (&mut u256[..]).try_into().unwrap();
+
+        let set_new_prev_hash = MiningSetNewPrevHash {
+            channel_id: 1,
+            job_id: 2,
+            prev_hash,
+            min_ntime: 0x29ab5f49,
+            nbits: 0x1d00ffff,
+        };
+
+        let sv2_message = Sv2Message::MiningSetNewPrevHash(set_new_prev_hash);
+        assert_eq!(expect, sv2_message.message_type());
+        // Must not be confused with the template distribution SetNewPrevHash
+        assert_ne!(MESSAGE_TYPE_SET_NEW_PREV_HASH, sv2_message.message_type());
+    }
+
+    #[test]
+    fn test_message_type_identify_transactions() {
+        let expect = MESSAGE_TYPE_IDENTIFY_TRANSACTIONS;
+
+        let identify_transactions = IdentifyTransactions { request_id: 1 };
+
+        let sv2_message = Sv2Message::IdentifyTransactions(identify_transactions);
+        assert_eq!(expect, sv2_message.message_type());
+    }
+
+    #[test]
+    fn test_c_submit_shares_standard_round_trip() {
+        let submit_shares = SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 3,
+            job_id: 2,
+            nonce: 0xdeadbeef,
+            ntime: 0x29ab5f49,
+            version: 0x20000000,
+        };
+        let mut c_message: CSv2Message = Sv2Message::SubmitSharesStandard(submit_shares).into();
+        match c_message.to_rust_rep_mut().unwrap() {
+            Sv2Message::SubmitSharesStandard(m) => {
+                assert_eq!(m.sequence_number, 3);
+                assert_eq!(m.nonce, 0xdeadbeef);
+            }
+            _ => panic!(),
+        }
+    }
+
     #[test]
     #[ignore]
     fn test_next_frame() {
@@ -0,0 +1,404 @@
+//! Noise NX handshake and encrypted framing for C callers.
+//!
+//! A C client or server allocates a [`NoiseStateWrapper`] for each connection, drives the
+//! handshake with [`noise_step_0`]/[`noise_step_2`] (initiator) or [`noise_step_1`] (responder)
+//! exchanging the returned bytes verbatim over the socket, and once the state is in transport
+//! mode uses [`encode_encrypted`] and [`next_encrypted_frame`] in place of [`crate::encode`] and
+//! [`crate::next_frame`].
+use core::convert::{TryFrom, TryInto};
+use std::time::Duration;
+
+use binary_sv2::binary_codec_sv2::CVec;
+use codec_sv2::{
+    CError, Frame, HandshakeRole, Initiator, NoiseEncoder, Responder, StandardNoiseDecoder,
+    StandardSv2Frame, State,
+};
+use const_sv2::{
+    EXTENSION_TYPE_NO_EXTENSION, INITIATOR_EXPECTED_HANDSHAKE_MESSAGE_SIZE,
+    RESPONDER_EXPECTED_HANDSHAKE_MESSAGE_SIZE,
+};
+
+use crate::{CResult, CSv2Message, Sv2Error, Sv2Message};
+
+#[derive(Debug)]
+pub struct NoiseStateWrapper(State);
+
+impl NoiseStateWrapper {
+    fn is_transport(&self) -> bool {
+        matches!(self.0, State::Transport(_))
+    }
+}
+
+/// Creates the handshake state for a Noise initiator (the downstream side).
+///
+/// `authority_public_key` points to the 32 bytes x-only public key of the upstream authority, or
+/// is null when the responder certificate should not be checked. Returns null if the key is
+/// invalid.
+///
+/// # Safety
+///
+/// `authority_public_key` must be null or point to 32 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn new_noise_initiator(
+    authority_public_key: *const u8,
+) -> *mut NoiseStateWrapper {
+    let initiator = if authority_public_key.is_null() {
+        Initiator::without_pk()
+    } else {
+        let mut key = [0_u8; 32];
+        key.copy_from_slice(std::slice::from_raw_parts(authority_public_key, 32));
+        Initiator::from_raw_k(key)
+    };
+    match initiator {
+        Ok(initiator) => Box::into_raw(Box::new(NoiseStateWrapper(State::initialized(
+            HandshakeRole::Initiator(initiator),
+        )))),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Creates the handshake state for a Noise responder (the upstream side).
+///
+/// `public_key` and `private_key` point to the 32 bytes authority key pair and
+/// `cert_validity_secs` is the validity of the certificate sent to the initiator. Returns null if
+/// the keys do not match.
+///
+/// # Safety
+///
+/// `public_key` and `private_key` must point to 32 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn new_noise_responder(
+    public_key: *const u8,
+    private_key: *const u8,
+    cert_validity_secs: u64,
+) -> *mut NoiseStateWrapper {
+    let mut public = [0_u8; 32];
+    public.copy_from_slice(std::slice::from_raw_parts(public_key, 32));
+    let mut private = [0_u8; 32];
+    private.copy_from_slice(std::slice::from_raw_parts(private_key, 32));
+    match Responder::from_authority_kp(&public, &private, Duration::from_secs(cert_validity_secs)) {
+        Ok(responder) => Box::into_raw(Box::new(NoiseStateWrapper(State::initialized(
+            HandshakeRole::Responder(responder),
+        )))),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn free_noise_state(state: *mut NoiseStateWrapper) {
+    let state = unsafe { Box::from_raw(state) };
+    drop(state);
+}
+
+/// Initiator only: returns the first handshake message (the ElligatorSwift encoded ephemeral
+/// key) that must be sent to the responder.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn noise_step_0(state: *mut NoiseStateWrapper) -> CResult<CVec, Sv2Error> {
+    let mut state = unsafe { Box::from_raw(state) };
+    let result = state
+        .0
+        .step_0()
+        .map(|frame| frame.get_payload_when_handshaking().as_slice().into())
+        .map_err(|e| Sv2Error::CodecError(e.into()));
+    Box::into_raw(state);
+    result.into()
+}
+
+/// Responder only: consumes the initiator message received from the socket and returns the
+/// handshake reply that must be sent back. On success the state is in transport mode.
+///
+/// # Safety
+///
+/// `message` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn noise_step_1(
+    state: *mut NoiseStateWrapper,
+    message: *const u8,
+    len: usize,
+) -> CResult<CVec, Sv2Error> {
+    let message = std::slice::from_raw_parts(message, len);
+    let message: [u8; RESPONDER_EXPECTED_HANDSHAKE_MESSAGE_SIZE] = match message.try_into() {
+        Ok(m) => m,
+        Err(_) => return CResult::Err(Sv2Error::MissingBytes),
+    };
+    let mut state = Box::from_raw(state);
+    let result = match state.0.step_1(message) {
+        Ok((frame, transport)) => {
+            state.0 = transport;
+            Ok(frame.get_payload_when_handshaking().as_slice().into())
+        }
+        Err(e) => Err(Sv2Error::CodecError(e.into())),
+    };
+    Box::into_raw(state);
+    result.into()
+}
+
+/// Initiator only: consumes the responder reply received from the socket. Returns `true` once
+/// the state is in transport mode.
+///
+/// # Safety
+///
+/// `message` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn noise_step_2(
+    state: *mut NoiseStateWrapper,
+    message: *const u8,
+    len: usize,
+) -> CResult<bool, Sv2Error> {
+    let message = std::slice::from_raw_parts(message, len);
+    let message: [u8; INITIATOR_EXPECTED_HANDSHAKE_MESSAGE_SIZE] = match message.try_into() {
+        Ok(m) => m,
+        Err(_) => return CResult::Err(Sv2Error::MissingBytes),
+    };
+    let mut state = Box::from_raw(state);
+    let result = match state.0.step_2(message) {
+        Ok(transport) => {
+            state.0 = transport;
+            Ok(true)
+        }
+        Err(e) => Err(Sv2Error::CodecError(e.into())),
+    };
+    Box::into_raw(state);
+    result.into()
+}
+
+pub struct NoiseEncoderWrapper {
+    encoder: NoiseEncoder<Sv2Message<'static>>,
+    free: bool,
+}
+
+#[no_mangle]
+pub extern "C" fn new_noise_encoder() -> *mut NoiseEncoderWrapper {
+    let encoder: NoiseEncoder<Sv2Message<'static>> = NoiseEncoder::new();
+    let s = Box::new(NoiseEncoderWrapper {
+        encoder,
+        free: true,
+    });
+    Box::into_raw(s)
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn flush_noise_encoder(encoder: *mut NoiseEncoderWrapper) {
+    let mut encoder = unsafe { Box::from_raw(encoder) };
+    encoder.free = true;
+    Box::into_raw(encoder);
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn free_noise_encoder(encoder: *mut NoiseEncoderWrapper) {
+    let encoder = unsafe { Box::from_raw(encoder) };
+    drop(encoder);
+}
+
+fn encode_encrypted_(
+    message: &'static mut CSv2Message,
+    encoder: &mut NoiseEncoderWrapper,
+    state: &mut NoiseStateWrapper,
+) -> Result<CVec, Sv2Error> {
+    if !state.is_transport() {
+        return Err(Sv2Error::CodecError(CError::UnexpectedNoiseState));
+    }
+    let message: Sv2Message = message.to_rust_rep_mut()?;
+    let m_type = message.message_type();
+    let c_bit = message.channel_bit();
+    let frame = StandardSv2Frame::<Sv2Message<'static>>::from_message(
+        message.clone(),
+        m_type,
+        EXTENSION_TYPE_NO_EXTENSION,
+        c_bit,
+    )
+    .ok_or(Sv2Error::PayloadTooBig(
+        format!("{}", message).as_bytes().into(),
+    ))?;
+    encoder
+        .encoder
+        .encode(frame.into(), &mut state.0)
+        .map_err(|e| Sv2Error::CodecError(e.into()))
+        .map(|x| x.as_slice().into())
+}
+
+/// Same as [`crate::encode`] but the returned frame is encrypted with the transport keys of
+/// `state`. Fails if the handshake is not completed.
+///
+/// # Safety
+///
+#[no_mangle]
+pub unsafe extern "C" fn encode_encrypted(
+    message: &'static mut CSv2Message,
+    encoder: *mut NoiseEncoderWrapper,
+    state: *mut NoiseStateWrapper,
+) -> CResult<CVec, Sv2Error> {
+    let mut encoder = Box::from_raw(encoder);
+    if encoder.free {
+        let mut state = Box::from_raw(state);
+        let result = encode_encrypted_(message, &mut encoder, &mut state);
+        encoder.free = false;
+        Box::into_raw(state);
+        Box::into_raw(encoder);
+        result.into()
+    } else {
+        Box::into_raw(encoder);
+        CResult::Err(Sv2Error::EncoderBusy)
+    }
+}
+
+pub struct NoiseDecoderWrapper(StandardNoiseDecoder<Sv2Message<'static>>);
+
+#[no_mangle]
+pub extern "C" fn new_noise_decoder() -> *mut NoiseDecoderWrapper {
+    let s = Box::new(NoiseDecoderWrapper(StandardNoiseDecoder::new()));
+    Box::into_raw(s)
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn free_noise_decoder(decoder: *mut NoiseDecoderWrapper) {
+    let decoder = unsafe { Box::from_raw(decoder) };
+    drop(decoder);
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn get_noise_writable(decoder: *mut NoiseDecoderWrapper) -> CVec {
+    let mut decoder = unsafe { Box::from_raw(decoder) };
+    let writable = decoder.0.writable();
+    let res = CVec::as_shared_buffer(writable);
+    Box::into_raw(decoder);
+    res
+}
+
+/// Same as [`crate::next_frame`] for encrypted frames. Fails if the handshake is not completed.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn next_encrypted_frame(
+    decoder: *mut NoiseDecoderWrapper,
+    state: *mut NoiseStateWrapper,
+) -> CResult<CSv2Message, Sv2Error> {
+    let mut state = unsafe { Box::from_raw(state) };
+    if !state.is_transport() {
+        Box::into_raw(state);
+        return CResult::Err(Sv2Error::CodecError(CError::UnexpectedNoiseState));
+    }
+    let mut decoder = unsafe { Box::from_raw(decoder) };
+
+    let result = match decoder.0.next_frame(&mut state.0) {
+        Ok(Frame::Sv2(mut f)) => {
+            let msg_type = match f.get_header() {
+                Some(header) => header.msg_type(),
+                None => {
+                    Box::into_raw(decoder);
+                    Box::into_raw(state);
+                    return CResult::Err(Sv2Error::InvalidSv2Frame);
+                }
+            };
+            let payload = f.payload();
+            let len = payload.len();
+            let ptr = payload.as_mut_ptr();
+            let payload = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+            Sv2Message::try_from((msg_type, payload))
+                .map(|x| x.into())
+                .map_err(|_| Sv2Error::Unknown)
+        }
+        Ok(Frame::HandShake(_)) => Err(Sv2Error::InvalidSv2Frame),
+        Err(codec_sv2::Error::MissingBytes(_)) => Err(Sv2Error::MissingBytes),
+        Err(e) => Err(Sv2Error::CodecError(e.into())),
+    };
+    Box::into_raw(decoder);
+    Box::into_raw(state);
+    result.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common_messages_sv2::ChannelEndpointChanged;
+
+    const AUTHORITY_PUBLIC_K: [u8; 32] = [
+        0x24, 0xee, 0x3c, 0x38, 0x04, 0xa1, 0xaa, 0xa4, 0xc0, 0x3b, 0x80, 0xea, 0x19, 0xf7, 0xa5,
+        0x86, 0x3c, 0x91, 0x6e, 0x89, 0x94, 0xb7, 0xdb, 0x94, 0xa3, 0xba, 0xd7, 0xee, 0x09, 0x2b,
+        0x6c, 0xe7,
+    ];
+    const AUTHORITY_PRIVATE_K: [u8; 32] = [
+        0x65, 0x99, 0x5e, 0xb1, 0x96, 0x31, 0xf4, 0x78, 0xa4, 0x6f, 0xfa, 0x5c, 0xf1, 0xe5, 0x45,
+        0x09, 0x1e, 0xfe, 0x95, 0x0e, 0xae, 0xac, 0x74, 0x82, 0xff, 0xdc, 0x06, 0xeb, 0x6a, 0x89,
+        0xf6, 0x97,
+    ];
+
+    fn unwrap<T>(r: CResult<T, Sv2Error>) -> T {
+        match r {
+            CResult::Ok(v) => v,
+            CResult::Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn handshake_and_encrypted_round_trip() {
+        unsafe {
+            let initiator = new_noise_initiator(AUTHORITY_PUBLIC_K.as_ptr());
+            let responder = new_noise_responder(
+                AUTHORITY_PUBLIC_K.as_ptr(),
+                AUTHORITY_PRIVATE_K.as_ptr(),
+                3600,
+            );
+            assert!(!initiator.is_null() && !responder.is_null());
+
+            let first = unwrap(noise_step_0(initiator));
+            let second = unwrap(noise_step_1(responder, first.data, first.len));
+            assert!(unwrap(noise_step_2(initiator, second.data, second.len)));
+
+            let encoder = new_noise_encoder();
+            let message = Box::leak(Box::new(CSv2Message::ChannelEndpointChanged(
+                ChannelEndpointChanged { channel_id: 7 },
+            )));
+            let encrypted = unwrap(encode_encrypted(message, encoder, initiator));
+            flush_noise_encoder(encoder);
+            let encrypted = std::slice::from_raw_parts(encrypted.data, encrypted.len).to_vec();
+
+            let decoder = new_noise_decoder();
+            let mut read = 0;
+            let decoded = loop {
+                let mut writable = get_noise_writable(decoder);
+                let end = read + writable.len;
+                writable
+                    .as_mut_slice()
+                    .copy_from_slice(&encrypted[read..end]);
+                read = end;
+                match next_encrypted_frame(decoder, responder) {
+                    CResult::Ok(m) => break m,
+                    CResult::Err(Sv2Error::MissingBytes) => continue,
+                    CResult::Err(e) => panic!("{}", e),
+                }
+            };
+            match decoded {
+                CSv2Message::ChannelEndpointChanged(m) => assert_eq!(m.channel_id, 7),
+                _ => panic!(),
+            }
+
+            free_noise_decoder(decoder);
+            free_noise_encoder(encoder);
+            free_noise_state(initiator);
+            free_noise_state(responder);
+        }
+    }
+
+    #[test]
+    fn encode_fails_before_handshake() {
+        unsafe {
+            let initiator = new_noise_initiator(std::ptr::null());
+            let encoder = new_noise_encoder();
+            let message = Box::leak(Box::new(CSv2Message::ChannelEndpointChanged(
+                ChannelEndpointChanged { channel_id: 7 },
+            )));
+            match encode_encrypted(message, encoder, initiator) {
+                CResult::Err(Sv2Error::CodecError(CError::UnexpectedNoiseState)) => (),
+                _ => panic!(),
+            }
+            free_noise_encoder(encoder);
+            free_noise_state(initiator);
+        }
+    }
+}
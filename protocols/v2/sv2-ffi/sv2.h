@@ -451,6 +451,361 @@ void free_submit_solution(CSubmitSolution s);
 #include <ostream>
 #include <new>
 
+/// # SubmitSharesStandard (Client -> Server)
+///
+/// Client sends result of its hashing work to the server.
+struct SubmitSharesStandard {
+  /// Channel identification.
+  uint32_t channel_id;
+  /// Unique sequential identifier of the submit within the channel.
+  uint32_t sequence_number;
+  /// Identifier of the job as provided by *NewMiningJob* or
+  /// *NewExtendedMiningJob* message.
+  uint32_t job_id;
+  /// Nonce leading to the hash being submitted.
+  uint32_t nonce;
+  /// The nTime field in the block header. This MUST be greater than or equal
+  /// to the header_timestamp field in the latest SetNewPrevHash message
+  /// and lower than or equal to that value plus the number of seconds since
+  /// the receipt of that message.
+  uint32_t ntime;
+  /// Full nVersion field.
+  uint32_t version;
+};
+
+/// # SubmitShares.Success (Server -> Client)
+///
+/// Response to SubmitShares or SubmitSharesExtended, accepting results from the miner.
+/// Because it is a common case that shares submission is successful, this response can be
+/// provided for multiple SubmitShare messages aggregated together.
+///
+/// The server doesn’t have to double check that the sequence numbers sent by a client are
+/// actually increasing. It can simply use the last one received when sending a response. It is the
+/// client’s responsibility to keep the sequence numbers correct/useful.
+struct SubmitSharesSuccess {
+  /// Channel identifier.
+  uint32_t channel_id;
+  /// Most recent sequence number with a correct result.
+  uint32_t last_sequence_number;
+  /// Count of new submits acknowledged within this batch.
+  uint32_t new_submits_accepted_count;
+  /// Sum of shares acknowledged within this batch.
+  uint64_t new_shares_sum;
+};
+
+/// # SetCustomMiningJob.Success (Server -> Client)
+///
+/// Response from the server when it accepts the custom mining job. Client can start to mine on
+/// the job immediately (by using the job_id provided within this response).
+///
+struct SetCustomMiningJobSuccess {
+  /// Extended channel identifier.
+  uint32_t channel_id;
+  /// Client-specified identifier for pairing responses. Value from the request
+  /// MUST be provided by upstream in the response message.
+  uint32_t request_id;
+  /// Server’s identification of the mining job.
+  uint32_t job_id;
+};
+
+struct CCloseChannel {
+  uint32_t channel_id;
+  CVec reason_code;
+};
+
+/// C representation of [`NewMiningJob`], `min_ntime` is meaningful only when `has_min_ntime` is
+/// true.
+struct CNewMiningJob {
+  uint32_t channel_id;
+  uint32_t job_id;
+  bool has_min_ntime;
+  uint32_t min_ntime;
+  uint32_t version;
+  CVec merkle_root;
+};
+
+/// C representation of [`NewExtendedMiningJob`], `min_ntime` is meaningful only when
+/// `has_min_ntime` is true.
+struct CNewExtendedMiningJob {
+  uint32_t channel_id;
+  uint32_t job_id;
+  bool has_min_ntime;
+  uint32_t min_ntime;
+  uint32_t version;
+  bool version_rolling_allowed;
+  CVec2 merkle_path;
+  CVec coinbase_tx_prefix;
+  CVec coinbase_tx_suffix;
+};
+
+struct COpenStandardMiningChannel {
+  uint32_t request_id;
+  CVec user_identity;
+  float nominal_hash_rate;
+  CVec max_target;
+};
+
+struct COpenStandardMiningChannelSuccess {
+  uint32_t request_id;
+  uint32_t channel_id;
+  CVec target;
+  CVec extranonce_prefix;
+  uint32_t group_channel_id;
+};
+
+struct COpenExtendedMiningChannel {
+  uint32_t request_id;
+  CVec user_identity;
+  float nominal_hash_rate;
+  CVec max_target;
+  uint16_t min_extranonce_size;
+};
+
+struct COpenExtendedMiningChannelSuccess {
+  uint32_t request_id;
+  uint32_t channel_id;
+  CVec target;
+  uint16_t extranonce_size;
+  CVec extranonce_prefix;
+};
+
+struct COpenMiningChannelError {
+  uint32_t request_id;
+  CVec error_code;
+};
+
+struct CReconnect {
+  CVec new_host;
+  uint16_t new_port;
+};
+
+struct CSetCustomMiningJob {
+  uint32_t channel_id;
+  uint32_t request_id;
+  CVec token;
+  uint32_t version;
+  CVec prev_hash;
+  uint32_t min_ntime;
+  uint32_t nbits;
+  uint32_t coinbase_tx_version;
+  CVec coinbase_prefix;
+  uint32_t coinbase_tx_input_n_sequence;
+  uint64_t coinbase_tx_value_remaining;
+  CVec coinbase_tx_outputs;
+  uint32_t coinbase_tx_locktime;
+  CVec2 merkle_path;
+  uint16_t extranonce_size;
+};
+
+struct CSetCustomMiningJobError {
+  uint32_t channel_id;
+  uint32_t request_id;
+  CVec error_code;
+};
+
+struct CSetExtranoncePrefix {
+  uint32_t channel_id;
+  CVec extranonce_prefix;
+};
+
+/// C representation of [`SetGroupChannel`], `channel_ids` are serialized as consecutive
+/// little-endian `u32`.
+struct CSetGroupChannel {
+  uint32_t group_channel_id;
+  CVec channel_ids;
+};
+
+/// C representation of the mining [`SetNewPrevHash`]. It is prefixed with `Mining` so that it
+/// does not clash with the Template Distribution `CSetNewPrevHash` in the C headers.
+struct CMiningSetNewPrevHash {
+  uint32_t channel_id;
+  uint32_t job_id;
+  CVec prev_hash;
+  uint32_t min_ntime;
+  uint32_t nbits;
+};
+
+struct CSetTarget {
+  uint32_t channel_id;
+  CVec maximum_target;
+};
+
+struct CSubmitSharesExtended {
+  uint32_t channel_id;
+  uint32_t sequence_number;
+  uint32_t job_id;
+  uint32_t nonce;
+  uint32_t ntime;
+  uint32_t version;
+  CVec extranonce;
+};
+
+struct CSubmitSharesError {
+  uint32_t channel_id;
+  uint32_t sequence_number;
+  CVec error_code;
+};
+
+struct CUpdateChannel {
+  uint32_t channel_id;
+  float nominal_hash_rate;
+  CVec maximum_target;
+};
+
+struct CUpdateChannelError {
+  uint32_t channel_id;
+  CVec error_code;
+};
+
+extern "C" {
+
+void _c_export_submit_shares_standard(SubmitSharesStandard _a);
+
+void _c_export_submit_shares_success(SubmitSharesSuccess _a);
+
+void _c_export_set_custom_mining_job_success(SetCustomMiningJobSuccess _a);
+
+void free_close_channel(CCloseChannel s);
+
+void free_new_mining_job(CNewMiningJob s);
+
+void free_new_extended_mining_job(CNewExtendedMiningJob s);
+
+void free_open_standard_mining_channel(COpenStandardMiningChannel s);
+
+void free_open_standard_mining_channel_success(COpenStandardMiningChannelSuccess s);
+
+void free_open_extended_mining_channel(COpenExtendedMiningChannel s);
+
+void free_open_extended_mining_channel_success(COpenExtendedMiningChannelSuccess s);
+
+void free_open_mining_channel_error(COpenMiningChannelError s);
+
+void free_reconnect(CReconnect s);
+
+void free_set_custom_mining_job(CSetCustomMiningJob s);
+
+void free_set_custom_mining_job_error(CSetCustomMiningJobError s);
+
+void free_set_extranonce_prefix(CSetExtranoncePrefix s);
+
+void free_set_group_channel(CSetGroupChannel s);
+
+void free_mining_set_new_prev_hash(CMiningSetNewPrevHash s);
+
+void free_set_target(CSetTarget s);
+
+void free_submit_shares_extended(CSubmitSharesExtended s);
+
+void free_submit_shares_error(CSubmitSharesError s);
+
+void free_update_channel(CUpdateChannel s);
+
+void free_update_channel_error(CUpdateChannelError s);
+
+} // extern "C"
+#include <cstdarg>
+#include <cstdint>
+#include <cstdlib>
+#include <ostream>
+#include <new>
+
+/// TODO: comment
+struct IdentifyTransactions {
+  uint32_t request_id;
+};
+
+struct CAllocateMiningJobToken {
+  CVec user_identifier;
+  uint32_t request_id;
+};
+
+struct CAllocateMiningJobTokenSuccess {
+  uint32_t request_id;
+  CVec mining_job_token;
+  uint32_t coinbase_output_max_additional_size;
+  CVec coinbase_output;
+  bool async_mining_allowed;
+};
+
+struct CDeclareMiningJob {
+  uint32_t request_id;
+  CVec mining_job_token;
+  uint32_t version;
+  CVec coinbase_prefix;
+  CVec coinbase_suffix;
+  uint64_t tx_short_hash_nonce;
+  CVec2 tx_short_hash_list;
+  CVec tx_hash_list_hash;
+  CVec excess_data;
+};
+
+struct CDeclareMiningJobSuccess {
+  uint32_t request_id;
+  CVec new_mining_job_token;
+};
+
+struct CDeclareMiningJobError {
+  uint32_t request_id;
+  CVec error_code;
+  CVec error_details;
+};
+
+struct CIdentifyTransactionsSuccess {
+  uint32_t request_id;
+  CVec2 tx_data_hashes;
+};
+
+/// C representation of [`ProvideMissingTransactions`], `unknown_tx_position_list` is serialized
+/// as consecutive little-endian `u16`.
+struct CProvideMissingTransactions {
+  uint32_t request_id;
+  CVec unknown_tx_position_list;
+};
+
+struct CProvideMissingTransactionsSuccess {
+  uint32_t request_id;
+  CVec2 transaction_list;
+};
+
+struct CSubmitSolutionJd {
+  CVec extranonce;
+  CVec prev_hash;
+  uint32_t ntime;
+  uint32_t nonce;
+  uint32_t nbits;
+  uint32_t version;
+};
+
+extern "C" {
+
+void _c_export_identify_transactions(IdentifyTransactions _a);
+
+void free_allocate_mining_job_token(CAllocateMiningJobToken s);
+
+void free_allocate_mining_job_token_success(CAllocateMiningJobTokenSuccess s);
+
+void free_declare_mining_job(CDeclareMiningJob s);
+
+void free_declare_mining_job_success(CDeclareMiningJobSuccess s);
+
+void free_declare_mining_job_error(CDeclareMiningJobError s);
+
+void free_identify_transactions_success(CIdentifyTransactionsSuccess s);
+
+void free_provide_missing_transactions(CProvideMissingTransactions s);
+
+void free_provide_missing_transactions_success(CProvideMissingTransactionsSuccess s);
+
+void free_submit_solution_jd(CSubmitSolutionJd s);
+
+} // extern "C"
+#include <cstdarg>
+#include <cstdint>
+#include <cstdlib>
+#include <ostream>
+#include <new>
+
 /// C-compatible enumeration of possible errors in the `codec_sv2` module.
 ///
 /// This enum mirrors the [`Error`] enum but is designed to be used in C code through FFI. It
@@ -509,6 +864,12 @@ struct DecoderWrapper;
 
 struct EncoderWrapper;
 
+struct NoiseDecoderWrapper;
+
+struct NoiseEncoderWrapper;
+
+struct NoiseStateWrapper;
+
 struct CSv2Message {
   enum class Tag {
     CoinbaseOutputDataSize,
@@ -521,6 +882,38 @@ struct CSv2Message {
     ChannelEndpointChanged,
     SetupConnection,
     SetupConnectionError,
+    OpenStandardMiningChannel,
+    OpenStandardMiningChannelSuccess,
+    OpenExtendedMiningChannel,
+    OpenExtendedMiningChannelSuccess,
+    OpenMiningChannelError,
+    UpdateChannel,
+    UpdateChannelError,
+    CloseChannel,
+    SetExtranoncePrefix,
+    SubmitSharesStandard,
+    SubmitSharesExtended,
+    SubmitSharesSuccess,
+    SubmitSharesError,
+    NewMiningJob,
+    NewExtendedMiningJob,
+    MiningSetNewPrevHash,
+    SetTarget,
+    SetCustomMiningJob,
+    SetCustomMiningJobSuccess,
+    SetCustomMiningJobError,
+    Reconnect,
+    SetGroupChannel,
+    AllocateMiningJobToken,
+    AllocateMiningJobTokenSuccess,
+    IdentifyTransactions,
+    IdentifyTransactionsSuccess,
+    ProvideMissingTransactions,
+    ProvideMissingTransactionsSuccess,
+    DeclareMiningJob,
+    DeclareMiningJobSuccess,
+    DeclareMiningJobError,
+    SubmitSolutionJd,
     SetupConnectionSuccess,
   };
 
@@ -564,6 +957,134 @@ struct CSv2Message {
     CSetupConnectionError _0;
   };
 
+  struct OpenStandardMiningChannel_Body {
+    COpenStandardMiningChannel _0;
+  };
+
+  struct OpenStandardMiningChannelSuccess_Body {
+    COpenStandardMiningChannelSuccess _0;
+  };
+
+  struct OpenExtendedMiningChannel_Body {
+    COpenExtendedMiningChannel _0;
+  };
+
+  struct OpenExtendedMiningChannelSuccess_Body {
+    COpenExtendedMiningChannelSuccess _0;
+  };
+
+  struct OpenMiningChannelError_Body {
+    COpenMiningChannelError _0;
+  };
+
+  struct UpdateChannel_Body {
+    CUpdateChannel _0;
+  };
+
+  struct UpdateChannelError_Body {
+    CUpdateChannelError _0;
+  };
+
+  struct CloseChannel_Body {
+    CCloseChannel _0;
+  };
+
+  struct SetExtranoncePrefix_Body {
+    CSetExtranoncePrefix _0;
+  };
+
+  struct SubmitSharesStandard_Body {
+    SubmitSharesStandard _0;
+  };
+
+  struct SubmitSharesExtended_Body {
+    CSubmitSharesExtended _0;
+  };
+
+  struct SubmitSharesSuccess_Body {
+    SubmitSharesSuccess _0;
+  };
+
+  struct SubmitSharesError_Body {
+    CSubmitSharesError _0;
+  };
+
+  struct NewMiningJob_Body {
+    CNewMiningJob _0;
+  };
+
+  struct NewExtendedMiningJob_Body {
+    CNewExtendedMiningJob _0;
+  };
+
+  struct MiningSetNewPrevHash_Body {
+    CMiningSetNewPrevHash _0;
+  };
+
+  struct SetTarget_Body {
+    CSetTarget _0;
+  };
+
+  struct SetCustomMiningJob_Body {
+    CSetCustomMiningJob _0;
+  };
+
+  struct SetCustomMiningJobSuccess_Body {
+    SetCustomMiningJobSuccess _0;
+  };
+
+  struct SetCustomMiningJobError_Body {
+    CSetCustomMiningJobError _0;
+  };
+
+  struct Reconnect_Body {
+    CReconnect _0;
+  };
+
+  struct SetGroupChannel_Body {
+    CSetGroupChannel _0;
+  };
+
+  struct AllocateMiningJobToken_Body {
+    CAllocateMiningJobToken _0;
+  };
+
+  struct AllocateMiningJobTokenSuccess_Body {
+    CAllocateMiningJobTokenSuccess _0;
+  };
+
+  struct IdentifyTransactions_Body {
+    IdentifyTransactions _0;
+  };
+
+  struct IdentifyTransactionsSuccess_Body {
+    CIdentifyTransactionsSuccess _0;
+  };
+
+  struct ProvideMissingTransactions_Body {
+    CProvideMissingTransactions _0;
+  };
+
+  struct ProvideMissingTransactionsSuccess_Body {
+    CProvideMissingTransactionsSuccess _0;
+  };
+
+  struct DeclareMiningJob_Body {
+    CDeclareMiningJob _0;
+  };
+
+  struct DeclareMiningJobSuccess_Body {
+    CDeclareMiningJobSuccess _0;
+  };
+
+  struct DeclareMiningJobError_Body {
+    CDeclareMiningJobError _0;
+  };
+
+  struct SubmitSolutionJd_Body {
+    CSubmitSolutionJd _0;
+  };
+
   struct SetupConnectionSuccess_Body {
     SetupConnectionSuccess _0;
   };
@@ -580,6 +1101,38 @@ struct CSv2Message {
     ChannelEndpointChanged_Body channel_endpoint_changed;
     SetupConnection_Body setup_connection;
     SetupConnectionError_Body setup_connection_error;
+    OpenStandardMiningChannel_Body open_standard_mining_channel;
+    OpenStandardMiningChannelSuccess_Body open_standard_mining_channel_success;
+    OpenExtendedMiningChannel_Body open_extended_mining_channel;
+    OpenExtendedMiningChannelSuccess_Body open_extended_mining_channel_success;
+    OpenMiningChannelError_Body open_mining_channel_error;
+    UpdateChannel_Body update_channel;
+    UpdateChannelError_Body update_channel_error;
+    CloseChannel_Body close_channel;
+    SetExtranoncePrefix_Body set_extranonce_prefix;
+    SubmitSharesStandard_Body submit_shares_standard;
+    SubmitSharesExtended_Body submit_shares_extended;
+    SubmitSharesSuccess_Body submit_shares_success;
+    SubmitSharesError_Body submit_shares_error;
+    NewMiningJob_Body new_mining_job;
+    NewExtendedMiningJob_Body new_extended_mining_job;
+    MiningSetNewPrevHash_Body mining_set_new_prev_hash;
+    SetTarget_Body set_target;
+    SetCustomMiningJob_Body set_custom_mining_job;
+    SetCustomMiningJobSuccess_Body set_custom_mining_job_success;
+    SetCustomMiningJobError_Body set_custom_mining_job_error;
+    Reconnect_Body reconnect;
+    SetGroupChannel_Body set_group_channel;
+    AllocateMiningJobToken_Body allocate_mining_job_token;
+    AllocateMiningJobTokenSuccess_Body allocate_mining_job_token_success;
+    IdentifyTransactions_Body identify_transactions;
+    IdentifyTransactionsSuccess_Body identify_transactions_success;
+    ProvideMissingTransactions_Body provide_missing_transactions;
+    ProvideMissingTransactionsSuccess_Body provide_missing_transactions_success;
+    DeclareMiningJob_Body declare_mining_job;
+    DeclareMiningJobSuccess_Body declare_mining_job_success;
+    DeclareMiningJobError_Body declare_mining_job_error;
+    SubmitSolutionJd_Body submit_solution_jd;
     SetupConnectionSuccess_Body setup_connection_success;
   };
 };
@@ -664,4 +1217,75 @@ CVec get_writable(DecoderWrapper *decoder);
 
 CResult<CSv2Message, Sv2Error> next_frame(DecoderWrapper *decoder);
 
+/// Creates the handshake state for a Noise initiator (the downstream side).
+///
+/// `authority_public_key` points to the 32 bytes x-only public key of the upstream authority, or
+/// is null when the responder certificate should not be checked. Returns null if the key is
+/// invalid.
+///
+/// # Safety
+///
+/// `authority_public_key` must be null or point to 32 readable bytes.
+NoiseStateWrapper *new_noise_initiator(const uint8_t *authority_public_key);
+
+/// Creates the handshake state for a Noise responder (the upstream side).
+///
+/// `public_key` and `private_key` point to the 32 bytes authority key pair and
+/// `cert_validity_secs` is the validity of the certificate sent to the initiator. Returns null if
+/// the keys do not match.
+///
+/// # Safety
+///
+/// `public_key` and `private_key` must point to 32 readable bytes.
+NoiseStateWrapper *new_noise_responder(const uint8_t *public_key,
+                                       const uint8_t *private_key,
+                                       uint64_t cert_validity_secs);
+
+void free_noise_state(NoiseStateWrapper *state);
+
+/// Initiator only: returns the first handshake message (the ElligatorSwift encoded ephemeral
+/// key) that must be sent to the responder.
+CResult<CVec, Sv2Error> noise_step_0(NoiseStateWrapper *state);
+
+/// Responder only: consumes the initiator message received from the socket and returns the
+/// handshake reply that must be sent back. On success the state is in transport mode.
+///
+/// # Safety
+///
+/// `message` must point to `len` readable bytes.
+CResult<CVec, Sv2Error> noise_step_1(NoiseStateWrapper *state, const uint8_t *message, uintptr_t len);
+
+/// Initiator only: consumes the responder reply received from the socket. Returns `true` once
+/// the state is in transport mode.
+///
+/// # Safety
+///
+/// `message` must point to `len` readable bytes.
+CResult<bool, Sv2Error> noise_step_2(NoiseStateWrapper *state, const uint8_t *message, uintptr_t len);
+
+NoiseEncoderWrapper *new_noise_encoder();
+
+void flush_noise_encoder(NoiseEncoderWrapper *encoder);
+
+void free_noise_encoder(NoiseEncoderWrapper *encoder);
+
+/// Same as [`crate::encode`] but the returned frame is encrypted with the transport keys of
+/// `state`. Fails if the handshake is not completed.
+///
+/// # Safety
+///
+CResult<CVec, Sv2Error> encode_encrypted(CSv2Message *message,
+                                         NoiseEncoderWrapper *encoder,
+                                         NoiseStateWrapper *state);
+
+NoiseDecoderWrapper *new_noise_decoder();
+
+void free_noise_decoder(NoiseDecoderWrapper *decoder);
+
+CVec get_noise_writable(NoiseDecoderWrapper *decoder);
+
+/// Same as [`crate::next_frame`] for encrypted frames. Fails if the handshake is not completed.
+CResult<CSv2Message, Sv2Error> next_encrypted_frame(NoiseDecoderWrapper *decoder,
+                                                    NoiseStateWrapper *state);
+
 } // extern "C"
@@ -4,6 +4,7 @@ use super::{
     upstream_sv2::Upstream as UpstreamMiningNode,
 };
 use async_channel::{Receiver, SendError, Sender};
+use metrics_sv2::{ChannelKind, Metrics};
 use roles_logic_sv2::{
     channel_logic::channel_factory::{OnNewShare, PoolChannelFactory, Share},
     common_messages_sv2::{SetupConnection, SetupConnectionSuccess},
@@ -49,6 +50,10 @@ pub struct DownstreamMiningNode {
     // used to retreive the job id of the share that we send upstream
     last_template_id: u64,
     pub jd: Option<Arc<Mutex<JobDeclarator>>>,
+    metrics: Arc<Metrics>,
+    // user identity and nominal hashrate of the last OpenExtendedMiningChannel, reported in the
+    // metrics when the channel is opened
+    channel_request: Option<(String, f32)>,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -163,6 +168,7 @@ impl DownstreamMiningNode {
         tx_status: status::Sender,
         miner_coinbase_output: Vec<TxOut>,
         jd: Option<Arc<Mutex<JobDeclarator>>>,
+        metrics: Arc<Metrics>,
     ) -> Self {
         Self {
             receiver,
@@ -179,6 +185,8 @@ impl DownstreamMiningNode {
             // Is upated in the message handler that si called earlier in the main loop.
             last_template_id: 0,
             jd,
+            metrics,
+            channel_request: None,
         }
     }
 
@@ -211,7 +219,17 @@ impl DownstreamMiningNode {
                 let incoming: StdFrame = message.try_into().unwrap();
                 Self::next(self_mutex, incoming).await;
             }
-            let tx_status = self_mutex.safe_lock(|s| s.tx_status.clone()).unwrap();
+            let tx_status = self_mutex
+                .safe_lock(|s| {
+                    s.metrics.downstream_disconnected();
+                    if s.status.have_channel() {
+                        for channel_id in s.status.get_channel().get_extended_channels_ids() {
+                            s.metrics.channel_closed(channel_id);
+                        }
+                    }
+                    s.tx_status.clone()
+                })
+                .unwrap();
             let err = Error::DownstreamDown;
             let status = status::Status {
                 state: State::DownstreamShutdown(err.into()),
@@ -237,6 +255,7 @@ impl DownstreamMiningNode {
                     self_mutex
                         .safe_lock(|s| {
                             s.status.set_channel(factory);
+                            s.on_channel_opened();
                         })
                         .unwrap();
                 })
@@ -251,6 +270,18 @@ impl DownstreamMiningNode {
         }
     }
 
+    fn on_channel_opened(&mut self) {
+        let (user_identity, hash_rate) = self.channel_request.take().unwrap_or_default();
+        for channel_id in self.status.get_channel().get_extended_channels_ids() {
+            self.metrics.channel_opened(
+                channel_id,
+                ChannelKind::Extended,
+                &user_identity,
+                hash_rate,
+            );
+        }
+    }
+
     /// Parse the received message and relay it to the right upstream
     pub async fn next(self_mutex: &Arc<Mutex<Self>>, mut incoming: StdFrame) {
         let message_type = incoming.get_header().unwrap().msg_type();
@@ -352,6 +383,31 @@ impl DownstreamMiningNode {
         }
     }
 
+    /// Asks the downstream to connect to another endpoint, returns false if the connection is
+    /// already closed
+    pub async fn reconnect(self_mutex: &Arc<Mutex<Self>>, reconnect: Reconnect<'static>) -> bool {
+        let message = MiningDeviceMessages::Mining(Mining::Reconnect(reconnect));
+        let frame: StdFrame = match message.try_into() {
+            Ok(frame) => frame,
+            Err(e) => {
+                error!("Can not encode Reconnect: {:?}", e);
+                return false;
+            }
+        };
+        let sender = self_mutex.safe_lock(|self_| self_.sender.clone()).unwrap();
+        sender.send(frame.into()).await.is_ok()
+    }
+
+    /// False once the downstream closed the connection
+    pub fn is_connected(&self) -> bool {
+        !self.receiver.is_closed()
+    }
+
+    /// Queue of the solutions found by the downstream that are sent to the Template Provider
+    pub fn solution_sender(&self) -> Sender<SubmitSolution<'static>> {
+        self.solution_sender.clone()
+    }
+
     pub async fn on_new_template(
         self_mutex: &Arc<Mutex<Self>>,
         mut new_template: NewTemplate<'static>,
@@ -361,6 +417,7 @@ impl DownstreamMiningNode {
             super::IS_NEW_TEMPLATE_HANDLED.store(true, std::sync::atomic::Ordering::Release);
             return Ok(());
         }
+        let received_at = std::time::Instant::now();
         let mut pool_out = &pool_output[0..];
         let pool_output =
             TxOut::consensus_decode(&mut pool_out).expect("Upstream sent an invalid coinbase");
@@ -392,6 +449,9 @@ impl DownstreamMiningNode {
             let frame: StdFrame = message.try_into().unwrap();
             Self::send(self_mutex, frame).await.unwrap();
         }
+        self_mutex
+            .safe_lock(|s| s.metrics.observe_template_latency(received_at.elapsed()))
+            .unwrap();
         // See coment on the definition of the global for memory
         // ordering
         super::IS_NEW_TEMPLATE_HANDLED.store(true, std::sync::atomic::Ordering::Release);
@@ -405,6 +465,7 @@ impl DownstreamMiningNode {
         if !self_mutex.safe_lock(|s| s.status.have_channel()).unwrap() {
             return Ok(());
         }
+        let received_at = std::time::Instant::now();
         let job_id = self_mutex
             .safe_lock(|s| {
                 let channel = s.status.get_channel();
@@ -428,6 +489,9 @@ impl DownstreamMiningNode {
         let message = MiningDeviceMessages::Mining(Mining::SetNewPrevHash(to_send));
         let frame = message.try_into().unwrap();
         Self::send(self_mutex, frame).await.unwrap();
+        self_mutex
+            .safe_lock(|s| s.metrics.observe_prev_hash_latency(received_at.elapsed()))
+            .unwrap();
         Ok(())
     }
 }
@@ -463,6 +527,10 @@ impl
         &mut self,
         m: OpenExtendedMiningChannel,
     ) -> Result<SendTo<UpstreamMiningNode>, Error> {
+        let user_identity = std::str::from_utf8(m.user_identity.as_ref())
+            .unwrap_or("Unknown identity")
+            .to_string();
+        self.channel_request = Some((user_identity, m.nominal_hash_rate));
         if !self.status.is_solo_miner() {
             // Safe unwrap alreay checked if it cointains upstream with is_solo_miner
             Ok(SendTo::RelaySameMessageToRemote(
@@ -506,6 +574,7 @@ impl
             );
             match messages_res {
                 Ok(messages) => {
+                    self.on_channel_opened();
                     let messages = messages.into_iter().map(SendTo::Respond).collect();
                     Ok(SendTo::Multiple(messages))
                 }
@@ -557,9 +626,12 @@ impl
         {
             OnNewShare::SendErrorDownstream(s) => {
                 error!("Share do not meet downstream target");
+                let error_code = std::str::from_utf8(s.error_code.as_ref()).unwrap_or("unknown");
+                self.metrics.share_rejected(error_code);
                 Ok(SendTo::Respond(Mining::SubmitSharesError(s)))
             }
             OnNewShare::SendSubmitShareUpstream((m, Some(template_id))) => {
+                self.metrics.share_accepted();
                 if !self.status.is_solo_miner() {
                     match m {
                         Share::Extended(share) => {
@@ -581,6 +653,8 @@ impl
                 coinbase,
                 extranonce,
             )) => {
+                self.metrics.share_accepted();
+                self.metrics.block_found();
                 match share {
                     Share::Extended(share) => {
                         let solution_sender = self.solution_sender.clone();
@@ -622,7 +696,10 @@ impl
             // second tuple elements can not be None but must be Some(template_id)
             OnNewShare::ShareMeetBitcoinTarget(_) => unreachable!(),
             OnNewShare::SendSubmitShareUpstream(_) => unreachable!(),
-            OnNewShare::ShareMeetDownstreamTarget => Ok(SendTo::None(None)),
+            OnNewShare::ShareMeetDownstreamTarget => {
+                self.metrics.share_accepted();
+                Ok(SendTo::None(None))
+            }
         }
     }
 
@@ -680,6 +757,7 @@ pub async fn listen_for_downstream_mining(
     tx_status: status::Sender,
     miner_coinbase_output: Vec<TxOut>,
     jd: Option<Arc<Mutex<JobDeclarator>>>,
+    metrics: Arc<Metrics>,
 ) -> Result<Arc<Mutex<DownstreamMiningNode>>, Error> {
     info!("Listening for downstream mining connections on {}", address);
     let listner = TcpListener::bind(address).await.unwrap();
@@ -695,6 +773,7 @@ pub async fn listen_for_downstream_mining(
             Connection::new(stream, HandshakeRole::Responder(responder))
                 .await
                 .expect("impossible to connect");
+        metrics.downstream_connected();
         let node = DownstreamMiningNode::new(
             receiver,
             sender,
@@ -705,6 +784,7 @@ pub async fn listen_for_downstream_mining(
             tx_status,
             miner_coinbase_output,
             jd,
+            metrics,
         );
 
         let mut incoming: StdFrame = node.receiver.recv().await.unwrap().try_into().unwrap();
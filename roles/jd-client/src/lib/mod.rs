@@ -6,15 +6,20 @@ pub mod status;
 pub mod template_receiver;
 pub mod upstream_sv2;
 
-use std::{sync::atomic::AtomicBool, time::Duration};
+use std::{
+    sync::atomic::AtomicBool,
+    time::{Duration, Instant},
+};
 
+use downstream::DownstreamMiningNode;
 use job_declarator::JobDeclarator;
 use proxy_config::ProxyConfig;
 use template_receiver::TemplateRx;
 
 use async_channel::{bounded, unbounded};
 use futures::{select, FutureExt};
-use roles_logic_sv2::utils::Mutex;
+use metrics_sv2::Metrics;
+use roles_logic_sv2::{mining_sv2::Reconnect, utils::Mutex};
 use std::{
     net::{IpAddr, SocketAddr},
     str::FromStr,
@@ -57,16 +62,20 @@ pub static IS_NEW_TEMPLATE_HANDLED: AtomicBool = AtomicBool::new(true);
 pub struct JobDeclaratorClient {
     /// Configuration of the proxy server [`JobDeclaratorClient`] is connected to.
     config: ProxyConfig,
+    metrics: Arc<Metrics>,
 }
 
 impl JobDeclaratorClient {
     pub fn new(config: ProxyConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            metrics: Arc::new(Metrics::new()),
+        }
     }
 
     pub async fn start(self) {
         let mut upstream_index = 0;
-        let mut interrupt_signal_future = Box::pin(tokio::signal::ctrl_c().fuse());
+        let mut interrupt_signal_future = Box::pin(status::shutdown_signal().fuse());
 
         // Channel used to manage failed tasks
         let (tx_status, rx_status) = unbounded();
@@ -75,16 +84,30 @@ impl JobDeclaratorClient {
 
         let proxy_config = &self.config;
 
+        if let Some(metrics_address) = proxy_config.metrics_address.clone() {
+            let metrics = self.metrics.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = metrics_sv2::serve(metrics, metrics_address).await {
+                    error!("Metrics endpoint stopped: {}", e);
+                }
+            });
+        }
+
+        let mut first_connection = true;
         loop {
             let task_collector = task_collector.clone();
             let tx_status = tx_status.clone();
-            if let Some(upstream) = proxy_config.upstreams.get(upstream_index) {
+            if !first_connection {
+                self.metrics.upstream_reconnected();
+            }
+            first_connection = false;
+            let downstream = if let Some(upstream) = proxy_config.upstreams.get(upstream_index) {
                 self.initialize_jd(tx_status.clone(), task_collector.clone(), upstream.clone())
-                    .await;
+                    .await
             } else {
                 self.initialize_jd_as_solo_miner(tx_status.clone(), task_collector.clone())
-                    .await;
-            }
+                    .await
+            };
             // Check all tasks if is_finished() is true, if so exit
             loop {
                 let task_status = select! {
@@ -99,6 +122,7 @@ impl JobDeclaratorClient {
                                 // we also shut down in case of error
                             },
                         }
+                        Self::shutdown(downstream.clone(), &proxy_config.shutdown, task_collector.clone()).await;
                         std::process::exit(0);
                     }
                 };
@@ -154,11 +178,68 @@ impl JobDeclaratorClient {
         }
     }
 
+    /// Asks the downstream to reconnect to the fallback endpoint (if any) and waits, until
+    /// `config.timeout_secs`, for it to leave and for the pending solutions to be sent to the
+    /// Template Provider. The tasks are aborted after that.
+    async fn shutdown(
+        downstream: Option<Arc<Mutex<DownstreamMiningNode>>>,
+        config: &proxy_config::ShutdownConfig,
+        task_collector: Arc<Mutex<Vec<AbortHandle>>>,
+    ) {
+        info!("Shutting down");
+        let deadline = Instant::now() + Duration::from_secs(config.timeout_secs);
+        if let Some(downstream) = downstream {
+            if let Some(fallback_host) = &config.fallback_host {
+                match fallback_host.clone().into_bytes().try_into() {
+                    Ok(new_host) => {
+                        let reconnect = Reconnect {
+                            new_host,
+                            new_port: config.fallback_port,
+                        };
+                        if DownstreamMiningNode::reconnect(&downstream, reconnect).await {
+                            info!(
+                                "Asked the downstream to reconnect to {}:{}",
+                                fallback_host, config.fallback_port
+                            );
+                        }
+                    }
+                    Err(e) => error!("Invalid fallback host {}: {:?}", fallback_host, e),
+                }
+                while Instant::now() < deadline
+                    && downstream.safe_lock(|d| d.is_connected()).unwrap_or(false)
+                {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+            let solution_sender = downstream.safe_lock(|d| d.solution_sender()).ok();
+            if let Some(solution_sender) = solution_sender {
+                // the channel is closed when the connection with the Template Provider is down
+                while Instant::now() < deadline
+                    && !solution_sender.is_empty()
+                    && !solution_sender.is_closed()
+                {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                if !solution_sender.is_empty() {
+                    error!(
+                        "{} solutions have not been sent to the Template Provider",
+                        solution_sender.len()
+                    );
+                }
+            }
+        }
+        let _ = task_collector.safe_lock(|s| {
+            for handle in s {
+                handle.abort();
+            }
+        });
+    }
+
     async fn initialize_jd_as_solo_miner(
         &self,
         tx_status: async_channel::Sender<status::Status<'static>>,
         task_collector: Arc<Mutex<Vec<AbortHandle>>>,
-    ) {
+    ) -> Option<Arc<Mutex<DownstreamMiningNode>>> {
         let proxy_config = &self.config;
         let timeout = proxy_config.timeout;
         let miner_tx_out = proxy_config::get_coinbase_output(proxy_config).unwrap();
@@ -186,6 +267,7 @@ impl JobDeclaratorClient {
             status::Sender::Downstream(tx_status.clone()),
             miner_tx_out.clone(),
             None,
+            self.metrics.clone(),
         )
         .await
         .unwrap();
@@ -200,7 +282,7 @@ impl JobDeclaratorClient {
             recv_solution,
             status::Sender::TemplateReceiver(tx_status.clone()),
             None,
-            downstream,
+            downstream.clone(),
             task_collector,
             Arc::new(Mutex::new(PoolChangerTrigger::new(timeout))),
             miner_tx_out.clone(),
@@ -208,6 +290,7 @@ impl JobDeclaratorClient {
             false,
         )
         .await;
+        Some(downstream)
     }
 
     async fn initialize_jd(
@@ -215,7 +298,7 @@ impl JobDeclaratorClient {
         tx_status: async_channel::Sender<status::Status<'static>>,
         task_collector: Arc<Mutex<Vec<AbortHandle>>>,
         upstream_config: proxy_config::Upstream,
-    ) {
+    ) -> Option<Arc<Mutex<DownstreamMiningNode>>> {
         let proxy_config = &self.config;
         let timeout = proxy_config.timeout;
         let test_only_do_not_send_solution_to_tp = proxy_config
@@ -311,7 +394,7 @@ impl JobDeclaratorClient {
                         state: status::State::UpstreamShutdown(e),
                     })
                     .await;
-                return;
+                return None;
             }
         };
 
@@ -328,6 +411,7 @@ impl JobDeclaratorClient {
             status::Sender::Downstream(tx_status.clone()),
             vec![],
             Some(jd.clone()),
+            self.metrics.clone(),
         )
         .await
         .unwrap();
@@ -337,7 +421,7 @@ impl JobDeclaratorClient {
             recv_solution,
             status::Sender::TemplateReceiver(tx_status.clone()),
             Some(jd.clone()),
-            downstream,
+            downstream.clone(),
             task_collector,
             Arc::new(Mutex::new(PoolChangerTrigger::new(timeout))),
             vec![],
@@ -345,6 +429,7 @@ impl JobDeclaratorClient {
             test_only_do_not_send_solution_to_tp,
         )
         .await;
+        Some(downstream)
     }
 }
 
@@ -10,6 +10,15 @@ pub struct CoinbaseOutput {
     output_script_value: String,
 }
 
+impl CoinbaseOutput {
+    pub fn new(output_script_type: String, output_script_value: String) -> Self {
+        Self {
+            output_script_type,
+            output_script_value,
+        }
+    }
+}
+
 impl TryFrom<&CoinbaseOutput> for CoinbaseOutput_ {
     type Error = Error;
 
@@ -44,6 +53,32 @@ pub struct ProxyConfig {
     pub timeout: Duration,
     pub coinbase_outputs: Vec<CoinbaseOutput>,
     pub test_only_do_not_send_solution_to_tp: Option<bool>,
+    /// Address where the Prometheus metrics are served, eg `0.0.0.0:9184`
+    #[serde(default)]
+    pub metrics_address: Option<String>,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+}
+
+/// What the JDC does with its downstream when it is stopped
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// Host that the downstream is asked to reconnect to, when not set it is only disconnected
+    pub fallback_host: Option<String>,
+    pub fallback_port: u16,
+    /// Max number of seconds spent draining the downstream and flushing the solutions
+    pub timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            fallback_host: None,
+            fallback_port: 34265,
+            timeout_secs: 10,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -46,6 +46,21 @@ pub struct Status<'a> {
     pub state: State<'a>,
 }
 
+/// Resolves when the process is asked to stop, by Ctrl-C or, on unix, by SIGTERM
+pub async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res,
+            _ = sigterm.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
 async fn send_status(
     sender: &Sender,
     e: error::Error<'static>,
@@ -20,7 +20,7 @@ pub enum JdsError {
     Framing(codec_sv2::framing_sv2::Error),
     PoisonLock(String),
     Custom(String),
-    Sv2ProtocolError((u32, Mining<'static>)),
+    Sv2ProtocolError(Box<(u32, Mining<'static>)>),
     MempoolError(JdsMempoolError),
     ImpossibleToReconstructBlock(String),
     NoLastDeclaredJob,
@@ -113,7 +113,7 @@ impl<T> From<PoisonError<MutexGuard<'_, T>>> for JdsError {
 
 impl From<(u32, Mining<'static>)> for JdsError {
     fn from(e: (u32, Mining<'static>)) -> Self {
-        JdsError::Sv2ProtocolError(e)
+        JdsError::Sv2ProtocolError(Box::new(e))
     }
 }
 
@@ -3,48 +3,178 @@ use roles_logic_sv2::{
     handlers::{job_declaration::ParseClientJobDeclarationMessages, SendTo_},
     job_declaration_sv2::{
         AllocateMiningJobToken, AllocateMiningJobTokenSuccess, DeclareMiningJob,
-        DeclareMiningJobError, DeclareMiningJobSuccess, IdentifyTransactionsSuccess,
-        ProvideMissingTransactions, ProvideMissingTransactionsSuccess, SubmitSolutionJd,
+        DeclareMiningJobSuccess, IdentifyTransactionsSuccess, ProvideMissingTransactions,
+        ProvideMissingTransactionsSuccess, SubmitSolutionJd,
     },
     parsers::JobDeclaration,
+    utils::{u256_to_block_hash, Mutex},
 };
-use std::{convert::TryInto, io::Cursor};
+use std::{convert::TryInto, io::Cursor, sync::Arc};
 use stratum_common::bitcoin::{Transaction, Txid};
 pub type SendTo = SendTo_<JobDeclaration<'static>, ()>;
-use super::{signed_token, TransactionState};
+use super::{
+    super::{
+        job_state::{self, JobState},
+        mempool::{ChainTip, JDsMempool, TxStats},
+    },
+    signed_token,
+    validation::{self, JobValidationError, JobValidator},
+    TransactionState,
+};
 use roles_logic_sv2::{errors::Error, parsers::PoolMessages as AllMessages};
 use stratum_common::bitcoin::consensus::Decodable;
-use tracing::info;
+use tracing::{error, info};
 
 use super::JobDeclaratorDownstream;
 
 impl JobDeclaratorDownstream {
-    fn verify_job(&mut self, message: &DeclareMiningJob) -> bool {
-        // Convert token from B0255 to u32
-        let four_byte_array: [u8; 4] = message
-            .mining_job_token
-            .clone()
-            .to_vec()
-            .as_slice()
-            .try_into()
-            .unwrap();
-        let token_u32 = u32::from_le_bytes(four_byte_array);
-        // TODO Function to implement, it must be checked if the requested job has:
-        // 1. right coinbase
-        // 2. right version field
-        // 3. right prev-hash
-        // 4. right nbits
-        self.token_to_job_map.contains_key(&(token_u32))
+    /// Checks the token and everything that does not depend on the job transactions, returns the
+    /// chain tip the job has been validated against.
+    fn verify_job(&mut self, message: &DeclareMiningJob) -> Result<ChainTip, JobValidationError> {
+        let token = job_state::token_of(message).ok_or(JobValidationError::InvalidToken)?;
+        let is_token_valid = self
+            .job_state
+            .safe_lock(|x| x.is_token_valid(token))
+            .unwrap_or(false);
+        if !is_token_valid {
+            return Err(JobValidationError::InvalidToken);
+        }
+        let chain_tip = self.mempool.safe_lock(|x| x.chain_tip).ok().flatten();
+        self.validator.validate_job(message, chain_tip.as_ref())?;
+        chain_tip.ok_or(JobValidationError::ChainTipUnknown)
+    }
+
+    /// Looks for a job, declared before a restart of the JDS, that is solved by `solution`. When
+    /// found it becomes the declared job of this downstream and the transactions that were
+    /// provided by the downstream are put back in the mempool.
+    fn restore_declared_job(&mut self, solution: &SubmitSolutionJd) -> bool {
+        let prev_hash = u256_to_block_hash(solution.prev_hash.clone().into_static());
+        let declared_job = self
+            .job_state
+            .safe_lock(|x| x.jobs_on_tip(&prev_hash, solution.nbits))
+            .unwrap_or_default()
+            .into_iter()
+            .find(|job| job.is_solved_by(solution));
+        let job_state::DeclaredJob {
+            job,
+            transactions,
+            provided_transactions,
+            ..
+        } = match declared_job {
+            Some(declared_job) => declared_job,
+            None => return false,
+        };
+        info!(
+            "Restored declared job {} from the job state",
+            job.request_id
+        );
+        let _ = self.mempool.safe_lock(|x| {
+            for transaction in provided_transactions {
+                x.add_transaction(transaction);
+            }
+        });
+        self.declared_mining_job = (
+            Some(job),
+            transactions
+                .into_iter()
+                .map(TransactionState::PresentInMempool)
+                .collect(),
+            Vec::new(),
+        );
+        self.declared_job_chain_tip = None;
+        true
     }
 }
 
+/// Stores an accepted job in the job state shared by all the downstreams
+fn record_declared_job(
+    job_state: &Arc<Mutex<JobState>>,
+    declared_job: &DeclareMiningJob,
+    transactions_with_state: &[TransactionState],
+    chain_tip: &ChainTip,
+    provided_transactions: Vec<Transaction>,
+) {
+    let transactions = transactions_with_state
+        .iter()
+        .filter_map(|tx| match tx {
+            TransactionState::PresentInMempool(txid) => Some(*txid),
+            TransactionState::Missing => None,
+        })
+        .collect();
+    let _ = job_state.safe_lock(|x| {
+        x.on_job_declared(
+            declared_job.clone().into_static(),
+            (chain_tip.prev_hash, chain_tip.n_bits),
+            transactions,
+            provided_transactions,
+        )
+    });
+}
+
+/// Weight and sigops of the job transactions known by the JDS, together with the sum of their
+/// fees. Transactions that are missing are skipped. For the ones that are in the mempool only as
+/// txid, the stats reported by the node's block template are used when available, otherwise only
+/// the weight of the mempool entry is counted.
+fn transactions_stats(
+    mempool: &Arc<Mutex<JDsMempool>>,
+    transactions_with_state: &[TransactionState],
+) -> (Vec<TxStats>, u64) {
+    mempool
+        .safe_lock(|x| {
+            let mut fees = 0;
+            let stats = transactions_with_state
+                .iter()
+                .filter_map(|tx| match tx {
+                    TransactionState::PresentInMempool(txid) => {
+                        let entry = x.mempool.get(txid)?;
+                        fees += entry.fee;
+                        match &entry.tx {
+                            Some(tx) => Some(validation::tx_stats(tx)),
+                            None => x.template_tx_stats.get(txid).copied().or(Some(TxStats {
+                                weight: entry.weight,
+                                sigops: 0,
+                            })),
+                        }
+                    }
+                    TransactionState::Missing => None,
+                })
+                .collect();
+            (stats, fees)
+        })
+        .unwrap_or_default()
+}
+
+fn check_job_transactions(
+    validator: &JobValidator,
+    declared_job: &DeclareMiningJob,
+    chain_tip: &ChainTip,
+    transactions: &[TxStats],
+    fees: u64,
+) -> Result<(), JobValidationError> {
+    let coinbase = validation::decode_coinbase(
+        &declared_job.coinbase_prefix.to_vec(),
+        &declared_job.coinbase_suffix.to_vec(),
+    )?;
+    validator.check_pool_payout(&coinbase, chain_tip.height, fees)?;
+    validation::check_block_limits(chain_tip, &coinbase, transactions)
+}
+
+fn job_rejected(request_id: u32, e: JobValidationError) -> SendTo {
+    info!("Declared mining job {} rejected: {}", request_id, e);
+    SendTo::Respond(JobDeclaration::DeclareMiningJobError(
+        e.to_message(request_id),
+    ))
+}
+
 impl ParseClientJobDeclarationMessages for JobDeclaratorDownstream {
     fn handle_allocate_mining_job_token(
         &mut self,
         message: AllocateMiningJobToken,
     ) -> Result<SendTo, Error> {
-        let token = self.tokens.next();
-        self.token_to_job_map.insert(token, None);
+        let token = self
+            .job_state
+            .safe_lock(|x| x.allocate_token())
+            .map_err(|e| Error::PoisonLock(e.to_string()))?;
         let message_success = AllocateMiningJobTokenSuccess {
             request_id: message.request_id,
             mining_job_token: token.to_le_bytes().to_vec().try_into().unwrap(),
@@ -67,79 +197,83 @@ impl ParseClientJobDeclarationMessages for JobDeclaratorDownstream {
         // jds mempool, and will be non-empty in the ProvideMissingTransactionsSuccess message
         let mut known_transactions: Vec<Txid> = vec![];
         self.tx_hash_list_hash = Some(message.tx_hash_list_hash.clone().into_static());
-        if self.verify_job(&message) {
-            let short_hash_list: Vec<ShortTxId> = message
-                .tx_short_hash_list
-                .inner_as_ref()
-                .iter()
-                .map(|x| x.to_vec().try_into().unwrap())
-                .collect();
-            let nonce = message.tx_short_hash_nonce;
-            // TODO return None when we have a collision handle that case as weel
-            let short_id_mempool = self
-                .mempool
-                .safe_lock(|x| x.to_short_ids(nonce))
-                .unwrap()
-                .unwrap();
-            let mut transactions_with_state =
-                vec![TransactionState::Missing; short_hash_list.len()];
-            let mut missing_txs: Vec<u16> = Vec::new();
-
-            for (i, sid) in short_hash_list.iter().enumerate() {
-                let sid_: [u8; 6] = sid.to_vec().try_into().unwrap();
-                match short_id_mempool.get(&sid_) {
-                    Some(tx_data) => {
-                        transactions_with_state[i] = TransactionState::PresentInMempool(tx_data.id);
-                        known_transactions.push(tx_data.id);
-                    }
-                    None => {
-                        transactions_with_state[i] = TransactionState::Missing;
-                        missing_txs.push(i as u16);
-                    }
+        let chain_tip = match self.verify_job(&message) {
+            Ok(chain_tip) => chain_tip,
+            Err(e) => return Ok(job_rejected(message.request_id, e)),
+        };
+        let short_hash_list: Vec<ShortTxId> = message
+            .tx_short_hash_list
+            .inner_as_ref()
+            .iter()
+            .map(|x| x.to_vec().try_into().unwrap())
+            .collect();
+        let nonce = message.tx_short_hash_nonce;
+        // TODO return None when we have a collision handle that case as weel
+        let short_id_mempool = self
+            .mempool
+            .safe_lock(|x| x.to_short_ids(nonce))
+            .unwrap()
+            .unwrap();
+        let mut transactions_with_state = vec![TransactionState::Missing; short_hash_list.len()];
+        let mut missing_txs: Vec<u16> = Vec::new();
+
+        for (i, sid) in short_hash_list.iter().enumerate() {
+            let sid_: [u8; 6] = sid.to_vec().try_into().unwrap();
+            match short_id_mempool.get(&sid_) {
+                Some(tx_data) => {
+                    transactions_with_state[i] = TransactionState::PresentInMempool(tx_data.id);
+                    known_transactions.push(tx_data.id);
+                }
+                None => {
+                    transactions_with_state[i] = TransactionState::Missing;
+                    missing_txs.push(i as u16);
                 }
             }
-            self.declared_mining_job = (
-                Some(message.clone().into_static()),
-                transactions_with_state,
-                missing_txs.clone(),
+        }
+        let (stats, fees) = transactions_stats(&self.mempool, &transactions_with_state);
+        if let Err(e) = check_job_transactions(&self.validator, &message, &chain_tip, &stats, fees)
+        {
+            return Ok(job_rejected(message.request_id, e));
+        }
+        self.declared_job_chain_tip = Some(chain_tip);
+        self.declared_mining_job = (
+            Some(message.clone().into_static()),
+            transactions_with_state,
+            missing_txs.clone(),
+        );
+        // here we send the transactions that we want to be stored in jds mempool with full data
+
+        self.add_txs_to_mempool
+            .add_txs_to_mempool_inner
+            .known_transactions
+            .append(&mut known_transactions);
+
+        if missing_txs.is_empty() {
+            record_declared_job(
+                &self.job_state,
+                &message,
+                &self.declared_mining_job.1,
+                &chain_tip,
+                Vec::new(),
             );
-            // here we send the transactions that we want to be stored in jds mempool with full data
-
-            self.add_txs_to_mempool
-                .add_txs_to_mempool_inner
-                .known_transactions
-                .append(&mut known_transactions);
-
-            if missing_txs.is_empty() {
-                let message_success = DeclareMiningJobSuccess {
-                    request_id: message.request_id,
-                    new_mining_job_token: signed_token(
-                        message.tx_hash_list_hash.clone(),
-                        &self.public_key.clone(),
-                        &self.private_key.clone(),
-                    ),
-                };
-                let message_enum_success = JobDeclaration::DeclareMiningJobSuccess(message_success);
-                Ok(SendTo::Respond(message_enum_success))
-            } else {
-                let message_provide_missing_transactions = ProvideMissingTransactions {
-                    request_id: message.request_id,
-                    unknown_tx_position_list: missing_txs.into(),
-                };
-                let message_enum_provide_missing_transactions =
-                    JobDeclaration::ProvideMissingTransactions(
-                        message_provide_missing_transactions,
-                    );
-                Ok(SendTo::Respond(message_enum_provide_missing_transactions))
-            }
+            let message_success = DeclareMiningJobSuccess {
+                request_id: message.request_id,
+                new_mining_job_token: signed_token(
+                    message.tx_hash_list_hash.clone(),
+                    &self.public_key.clone(),
+                    &self.private_key.clone(),
+                ),
+            };
+            let message_enum_success = JobDeclaration::DeclareMiningJobSuccess(message_success);
+            Ok(SendTo::Respond(message_enum_success))
         } else {
-            let message_error = DeclareMiningJobError {
+            let message_provide_missing_transactions = ProvideMissingTransactions {
                 request_id: message.request_id,
-                error_code: Vec::new().try_into().unwrap(),
-                error_details: Vec::new().try_into().unwrap(),
+                unknown_tx_position_list: missing_txs.into(),
             };
-            let message_enum_error = JobDeclaration::DeclareMiningJobError(message_error);
-            Ok(SendTo::Respond(message_enum_error))
+            let message_enum_provide_missing_transactions =
+                JobDeclaration::ProvideMissingTransactions(message_provide_missing_transactions);
+            Ok(SendTo::Respond(message_enum_provide_missing_transactions))
         }
     }
 
@@ -162,6 +296,8 @@ impl ParseClientJobDeclarationMessages for JobDeclaratorDownstream {
                 let id = declared_job.request_id;
                 // check request_id in order to ignore old ProvideMissingTransactionsSuccess (see issue #860)
                 if id == message.request_id {
+                    let (mut stats, fees) =
+                        transactions_stats(&self.mempool, transactions_with_state);
                     for (i, tx) in message.transaction_list.inner_as_ref().iter().enumerate() {
                         let mut cursor = Cursor::new(tx);
                         let transaction =
@@ -182,17 +318,39 @@ impl ParseClientJobDeclarationMessages for JobDeclaratorDownstream {
                         transactions_with_state[index] =
                             TransactionState::PresentInMempool(transaction.txid());
                     }
+                    stats.extend(unknown_transactions.iter().map(validation::tx_stats));
+                    let chain_tip = match self.declared_job_chain_tip {
+                        Some(chain_tip) => chain_tip,
+                        None => return Ok(job_rejected(id, JobValidationError::ChainTipUnknown)),
+                    };
+                    if let Err(e) = check_job_transactions(
+                        &self.validator,
+                        declared_job,
+                        &chain_tip,
+                        &stats,
+                        fees,
+                    ) {
+                        return Ok(job_rejected(id, e));
+                    }
+                    let provided_transactions = unknown_transactions.clone();
                     self.add_txs_to_mempool
                         .add_txs_to_mempool_inner
                         .unknown_transactions
                         .append(&mut unknown_transactions);
                     // if there still a missing transaction return an error
-                    for tx_with_state in transactions_with_state {
+                    for tx_with_state in transactions_with_state.iter() {
                         match tx_with_state {
                             TransactionState::PresentInMempool(_) => continue,
                             TransactionState::Missing => return Err(Error::JDSMissingTransactions),
                         }
                     }
+                    record_declared_job(
+                        &self.job_state,
+                        declared_job,
+                        transactions_with_state,
+                        &chain_tip,
+                        provided_transactions,
+                    );
                     // TODO check it
                     let tx_hash_list_hash = self.tx_hash_list_hash.clone().unwrap().into_static();
                     let message_success = DeclareMiningJobSuccess {
@@ -214,6 +372,22 @@ impl ParseClientJobDeclarationMessages for JobDeclaratorDownstream {
     }
 
     fn handle_submit_solution(&mut self, message: SubmitSolutionJd<'_>) -> Result<SendTo, Error> {
+        // no job has been declared on this connection, the JDS may have been restarted after
+        // the downstream declared the job
+        if self.declared_mining_job.0.is_none() && !self.restore_declared_job(&message) {
+            error!("Solution rejected: no declared job is solved by it");
+            return Ok(SendTo::None(None));
+        }
+        // the coinbase of the declared job commits to the height of the block that extends the
+        // chain tip the job was validated against, so the solution must be built on that tip. A
+        // restored job has been found by the tip of the solution.
+        if let Some(chain_tip) = self.declared_job_chain_tip {
+            let prev_hash = u256_to_block_hash(message.prev_hash.clone().into_static());
+            if let Err(e) = validation::check_chain_tip(&chain_tip, prev_hash, message.nbits) {
+                error!("Solution rejected: {}", e);
+                return Ok(SendTo::None(None));
+            }
+        }
         let m = JobDeclaration::SubmitSolution(message.clone().into_static());
 
         Ok(SendTo::None(Some(m)))
@@ -1,13 +1,19 @@
 pub mod message_handler;
-use super::{error::JdsError, mempool::JDsMempool, status, Configuration, EitherFrame, StdFrame};
+pub mod validation;
+use super::{
+    error::JdsError,
+    job_state::JobState,
+    mempool::{ChainTip, JDsMempool},
+    status, Configuration, EitherFrame, StdFrame,
+};
 use async_channel::{Receiver, Sender};
 use binary_sv2::{B0255, U256};
 use codec_sv2::{HandshakeRole, Responder};
 use core::panic;
 use error_handling::handle_result;
 use key_utils::{Secp256k1PublicKey, Secp256k1SecretKey, SignatureService};
+use metrics_sv2::Metrics;
 use network_helpers_sv2::noise_connection_tokio::Connection;
-use nohash_hasher::BuildNoHashHasher;
 use roles_logic_sv2::{
     common_messages_sv2::{
         Protocol, SetupConnection, SetupConnectionError, SetupConnectionSuccess,
@@ -15,11 +21,12 @@ use roles_logic_sv2::{
     handlers::job_declaration::{ParseClientJobDeclarationMessages, SendTo},
     job_declaration_sv2::{DeclareMiningJob, SubmitSolutionJd},
     parsers::{JobDeclaration, PoolMessages as JdsMessages},
-    utils::{Id, Mutex},
+    utils::Mutex,
 };
-use std::{collections::HashMap, convert::TryInto, sync::Arc};
+use std::{convert::TryInto, sync::Arc};
 use tokio::{net::TcpListener, time::Duration};
 use tracing::{debug, error, info};
+use validation::JobValidator;
 
 use stratum_common::bitcoin::{
     consensus::{encode::serialize, Encodable},
@@ -54,8 +61,7 @@ pub struct JobDeclaratorDownstream {
     #[allow(dead_code)]
     // TODO: use coinbase output
     coinbase_output: Vec<u8>,
-    token_to_job_map: HashMap<u32, Option<u8>, BuildNoHashHasher<u32>>,
-    tokens: Id,
+    job_state: Arc<Mutex<JobState>>,
     public_key: Secp256k1PublicKey,
     private_key: Secp256k1SecretKey,
     mempool: Arc<Mutex<JDsMempool>>,
@@ -65,11 +71,16 @@ pub struct JobDeclaratorDownstream {
         Vec<TransactionState>,
         Vec<u16>,
     ),
+    // chain tip on which the last declared job was validated
+    declared_job_chain_tip: Option<ChainTip>,
     tx_hash_list_hash: Option<U256<'static>>,
     add_txs_to_mempool: AddTrasactionsToMempool,
+    validator: JobValidator,
+    metrics: Arc<Metrics>,
 }
 
 impl JobDeclaratorDownstream {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         async_mining_allowed: bool,
         receiver: Receiver<EitherFrame>,
@@ -77,35 +88,42 @@ impl JobDeclaratorDownstream {
         config: &Configuration,
         mempool: Arc<Mutex<JDsMempool>>,
         sender_add_txs_to_mempool: Sender<AddTrasactionsToMempoolInner>,
+        job_state: Arc<Mutex<JobState>>,
+        metrics: Arc<Metrics>,
     ) -> Self {
         let mut coinbase_output = vec![];
-        // TODO: use next variables
-        let token_to_job_map = HashMap::with_hasher(BuildNoHashHasher::default());
-        let tokens = Id::new();
         let add_txs_to_mempool_inner = AddTrasactionsToMempoolInner {
             known_transactions: vec![],
             unknown_transactions: vec![],
         };
-        super::get_coinbase_output(config).expect("Invalid coinbase output in config")[0]
+        let pool_outputs =
+            super::get_coinbase_output(config).expect("Invalid coinbase output in config");
+        pool_outputs[0]
             .consensus_encode(&mut coinbase_output)
             .expect("Invalid coinbase output in config");
+        let validator = JobValidator::new(
+            pool_outputs.into_iter().map(|o| o.script_pubkey).collect(),
+            config.version_rolling_mask,
+        );
 
         Self {
             async_mining_allowed,
             receiver,
             sender,
             coinbase_output,
-            token_to_job_map,
-            tokens,
+            job_state,
             public_key: config.authority_public_key,
             private_key: config.authority_secret_key,
             mempool,
             declared_mining_job: (None, Vec::new(), Vec::new()),
+            declared_job_chain_tip: None,
             tx_hash_list_hash: None,
             add_txs_to_mempool: AddTrasactionsToMempool {
                 add_txs_to_mempool_inner,
                 sender_add_txs_to_mempool,
             },
+            validator,
+            metrics,
         }
     }
 
@@ -137,7 +155,7 @@ impl JobDeclaratorDownstream {
         for tx_with_state in transactions_with_state.iter().enumerate() {
             if let TransactionState::PresentInMempool(txid) = tx_with_state.1 {
                 let tx = mempool
-                    .safe_lock(|x| x.mempool.get(txid).cloned())
+                    .safe_lock(|x| x.mempool.get(txid).map(|entry| entry.tx.clone()))
                     .map_err(|e| JdsError::PoisonLock(e.to_string()))?
                     .ok_or(Box::new(JdsError::ImpossibleToReconstructBlock(
                         "Txid not found in jds mempool".to_string(),
@@ -203,7 +221,9 @@ impl JobDeclaratorDownstream {
         tx_status: status::Sender,
         new_block_sender: Sender<String>,
     ) {
-        let recv = self_mutex.safe_lock(|s| s.receiver.clone()).unwrap();
+        let (recv, metrics) = self_mutex
+            .safe_lock(|s| (s.receiver.clone(), s.metrics.clone()))
+            .unwrap();
         tokio::spawn(async move {
             loop {
                 match recv.recv().await {
@@ -314,6 +334,7 @@ impl JobDeclaratorDownstream {
                                                         }
                                                     };
                                                 let _ = new_block_sender.send(hexdata).await;
+                                                metrics.block_found();
                                             }
                                             Err(error) => {
                                                 error!("Missing transactions: {:?}", error);
@@ -339,6 +360,7 @@ impl JobDeclaratorDownstream {
                                                         ) {
                                                             Ok(hexdata) => {
                                                                 let _ = new_block_sender.send(hexdata).await;
+                                                                metrics.block_found();
                                                             },
                                                             Err(e) => {
                                                                 handle_result!(
@@ -400,6 +422,7 @@ impl JobDeclaratorDownstream {
                     }
                 }
             }
+            metrics.downstream_disconnected();
         });
     }
 }
@@ -431,6 +454,8 @@ impl JobDeclarator {
         mempool: Arc<Mutex<JDsMempool>>,
         new_block_sender: Sender<String>,
         sender_add_txs_to_mempool: Sender<AddTrasactionsToMempoolInner>,
+        job_state: Arc<Mutex<JobState>>,
+        metrics: Arc<Metrics>,
     ) {
         let self_ = Arc::new(Mutex::new(Self {}));
         info!("JD INITIALIZED");
@@ -441,9 +466,12 @@ impl JobDeclarator {
             mempool,
             new_block_sender,
             sender_add_txs_to_mempool,
+            job_state,
+            metrics,
         )
         .await;
     }
+    #[allow(clippy::too_many_arguments)]
     async fn accept_incoming_connection(
         _self_: Arc<Mutex<JobDeclarator>>,
         config: Configuration,
@@ -451,6 +479,8 @@ impl JobDeclarator {
         mempool: Arc<Mutex<JDsMempool>>,
         new_block_sender: Sender<String>,
         sender_add_txs_to_mempool: Sender<AddTrasactionsToMempoolInner>,
+        job_state: Arc<Mutex<JobState>>,
+        metrics: Arc<Metrics>,
     ) {
         let listener = TcpListener::bind(&config.listen_jd_address).await.unwrap();
 
@@ -502,7 +532,10 @@ impl JobDeclarator {
                                         &config,
                                         mempool.clone(),
                                         sender_add_txs_to_mempool.clone(), // each downstream has its own sender (multi producer single consumer)
+                                        job_state.clone(),
+                                        metrics.clone(),
                                     )));
+                                metrics.downstream_connected();
 
                                 JobDeclaratorDownstream::start(
                                     jddownstream,
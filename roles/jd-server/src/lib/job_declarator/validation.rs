@@ -0,0 +1,532 @@
+//! Checks that a `DeclareMiningJob` describes a block the pool is willing to mine on.
+//!
+//! A declared job is accepted only if:
+//! 1. its coinbase pays the pool outputs at least the block subsidy plus the fees of the job
+//!    transactions that are in the JDS mempool
+//! 2. the version differs from the node's block template version only in the rolling bits
+//! 3. the BIP34 height in the coinbase is the one of the block that extends the current chain tip
+//! 4. the block respects the weight and sigops limits of the chain tip
+//!
+//! `DeclareMiningJob` does not carry the prev hash and nbits of the job, they are compared with
+//! the chain tip by [`check_chain_tip`] when the solution is submitted.
+//!
+//! Jobs are rejected while the JDS does not know the chain tip (e.g. the node is not reachable):
+//! the height in the coinbase is chosen by the miner, it can not be used to compute the subsidy
+//! that the pool is owed.
+//!
+//! Every failure maps to a `DeclareMiningJobError` error code, see
+//! [`JobValidationError::error_code`].
+use super::super::mempool::{ChainTip, TxStats};
+use roles_logic_sv2::job_declaration_sv2::{DeclareMiningJob, DeclareMiningJobError};
+use std::{convert::TryInto, fmt};
+use stratum_common::bitcoin::{
+    blockdata::{
+        opcodes::all::{OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY, OP_CHECKSIG, OP_CHECKSIGVERIFY},
+        script::Instruction,
+    },
+    consensus::deserialize,
+    BlockHash, Script, Transaction,
+};
+
+/// Bits that miners are allowed to roll when the config does not say otherwise (BIP320)
+pub const DEFAULT_VERSION_ROLLING_MASK: u32 = 0x1fff_e000;
+
+const WITNESS_SCALE_FACTOR: u64 = 4;
+const BLOCK_HEADER_SIZE: u64 = 80;
+const MAX_MULTISIG_PUBKEYS: u64 = 20;
+const INITIAL_SUBSIDY: u64 = 50 * 100_000_000;
+const HALVING_INTERVAL: u64 = 210_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobValidationError {
+    InvalidToken,
+    ChainTipUnknown,
+    InvalidCoinbase(String),
+    StaleChainTip {
+        expected_height: u64,
+        height: u64,
+    },
+    ChainTipMismatch {
+        prev_hash: BlockHash,
+        n_bits: u32,
+        tip_prev_hash: BlockHash,
+        tip_n_bits: u32,
+    },
+    InsufficientPoolPayout {
+        expected: u64,
+        paid: u64,
+    },
+    InvalidVersion {
+        version: u32,
+        template_version: u32,
+    },
+    BlockWeightExceeded {
+        weight: u64,
+        limit: u64,
+    },
+    BlockSigopsExceeded {
+        sigops: u64,
+        limit: u64,
+    },
+}
+
+impl JobValidationError {
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            JobValidationError::InvalidToken => "invalid-mining-job-token",
+            JobValidationError::ChainTipUnknown => "chain-tip-unknown",
+            JobValidationError::InvalidCoinbase(_) => "invalid-coinbase",
+            JobValidationError::StaleChainTip { .. } => "stale-chain-tip",
+            JobValidationError::ChainTipMismatch { .. } => "stale-chain-tip",
+            JobValidationError::InsufficientPoolPayout { .. } => "insufficient-pool-payout",
+            JobValidationError::InvalidVersion { .. } => "invalid-job-version",
+            JobValidationError::BlockWeightExceeded { .. } => "block-weight-exceeded",
+            JobValidationError::BlockSigopsExceeded { .. } => "block-sigops-exceeded",
+        }
+    }
+
+    pub fn to_message(&self, request_id: u32) -> DeclareMiningJobError<'static> {
+        DeclareMiningJobError {
+            request_id,
+            error_code: self
+                .error_code()
+                .to_string()
+                .into_bytes()
+                .try_into()
+                .unwrap(),
+            error_details: self
+                .to_string()
+                .into_bytes()
+                .try_into()
+                .unwrap_or_else(|_| Vec::new().try_into().unwrap()),
+        }
+    }
+}
+
+impl fmt::Display for JobValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use JobValidationError::*;
+        match self {
+            InvalidToken => write!(f, "Unknown mining job token"),
+            ChainTipUnknown => write!(f, "JDS does not know the current chain tip"),
+            InvalidCoinbase(e) => write!(f, "Invalid coinbase: {}", e),
+            StaleChainTip {
+                expected_height,
+                height,
+            } => write!(
+                f,
+                "Job is for height {} but the next block has height {}",
+                height, expected_height
+            ),
+            ChainTipMismatch {
+                prev_hash,
+                n_bits,
+                tip_prev_hash,
+                tip_n_bits,
+            } => write!(
+                f,
+                "Prev hash {} and nbits {:#x} do not match the chain tip {} with nbits {:#x}",
+                prev_hash, n_bits, tip_prev_hash, tip_n_bits
+            ),
+            InsufficientPoolPayout { expected, paid } => write!(
+                f,
+                "Coinbase pays {} sats to the pool outputs, at least {} are expected",
+                paid, expected
+            ),
+            InvalidVersion {
+                version,
+                template_version,
+            } => write!(
+                f,
+                "Version {:#010x} differs from {:#010x} outside of the rolling bits",
+                version, template_version
+            ),
+            BlockWeightExceeded { weight, limit } => {
+                write!(f, "Block weight {} exceeds the limit {}", weight, limit)
+            }
+            BlockSigopsExceeded { sigops, limit } => {
+                write!(
+                    f,
+                    "Block sigops cost {} exceeds the limit {}",
+                    sigops, limit
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JobValidator {
+    pool_outputs: Vec<Script>,
+    version_rolling_mask: u32,
+}
+
+impl JobValidator {
+    pub fn new(pool_outputs: Vec<Script>, version_rolling_mask: u32) -> Self {
+        Self {
+            pool_outputs,
+            version_rolling_mask,
+        }
+    }
+
+    /// Runs every check that does not need the job transactions, see [`check_block_limits`] and
+    /// [`JobValidator::check_pool_payout`] for the other ones. `chain_tip` is `None` when the JDS
+    /// does not know it yet, the job is then rejected.
+    pub fn validate_job(
+        &self,
+        message: &DeclareMiningJob,
+        chain_tip: Option<&ChainTip>,
+    ) -> Result<(), JobValidationError> {
+        let chain_tip = chain_tip.ok_or(JobValidationError::ChainTipUnknown)?;
+        let coinbase = decode_coinbase(
+            &message.coinbase_prefix.to_vec(),
+            &message.coinbase_suffix.to_vec(),
+        )?;
+
+        let height = bip34_height(&coinbase)?;
+        if height != chain_tip.height {
+            return Err(JobValidationError::StaleChainTip {
+                expected_height: chain_tip.height,
+                height,
+            });
+        }
+
+        self.check_version(message.version, chain_tip.version)?;
+        self.check_pool_payout(&coinbase, chain_tip.height, 0)
+    }
+
+    /// `fees` are the fees of the job transactions known by the JDS, the fees of the other
+    /// transactions can not be checked.
+    pub fn check_pool_payout(
+        &self,
+        coinbase: &Transaction,
+        height: u64,
+        fees: u64,
+    ) -> Result<(), JobValidationError> {
+        let expected = block_subsidy(height) + fees;
+        let paid: u64 = coinbase
+            .output
+            .iter()
+            .filter(|output| self.pool_outputs.contains(&output.script_pubkey))
+            .map(|output| output.value)
+            .sum();
+        if paid < expected {
+            return Err(JobValidationError::InsufficientPoolPayout { expected, paid });
+        }
+        Ok(())
+    }
+
+    fn check_version(&self, version: u32, template_version: u32) -> Result<(), JobValidationError> {
+        if (version ^ template_version) & !self.version_rolling_mask != 0 {
+            return Err(JobValidationError::InvalidVersion {
+                version,
+                template_version,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Checks that a job is built on `chain_tip`, `prev_hash` and `n_bits` are the ones of the
+/// submitted solution.
+pub fn check_chain_tip(
+    chain_tip: &ChainTip,
+    prev_hash: BlockHash,
+    n_bits: u32,
+) -> Result<(), JobValidationError> {
+    if prev_hash != chain_tip.prev_hash || n_bits != chain_tip.n_bits {
+        return Err(JobValidationError::ChainTipMismatch {
+            prev_hash,
+            n_bits,
+            tip_prev_hash: chain_tip.prev_hash,
+            tip_n_bits: chain_tip.n_bits,
+        });
+    }
+    Ok(())
+}
+
+/// Checks weight and sigops cost of a block made of `coinbase` and of transactions with the
+/// given stats.
+pub fn check_block_limits(
+    chain_tip: &ChainTip,
+    coinbase: &Transaction,
+    transactions: &[TxStats],
+) -> Result<(), JobValidationError> {
+    let tx_count = transactions.len() as u64 + 1;
+    let mut weight = (BLOCK_HEADER_SIZE + compact_size_len(tx_count)) * WITNESS_SCALE_FACTOR
+        + coinbase.weight() as u64;
+    let mut sigops = tx_stats(coinbase).sigops;
+    for tx in transactions {
+        weight += tx.weight;
+        sigops += tx.sigops;
+    }
+    if weight > chain_tip.weight_limit {
+        return Err(JobValidationError::BlockWeightExceeded {
+            weight,
+            limit: chain_tip.weight_limit,
+        });
+    }
+    if sigops > chain_tip.sigop_limit {
+        return Err(JobValidationError::BlockSigopsExceeded {
+            sigops,
+            limit: chain_tip.sigop_limit,
+        });
+    }
+    Ok(())
+}
+
+/// Stats of a transaction for which the JDS has the full data. The sigops cost only counts the
+/// legacy sigops, P2SH and witness sigops would need the spent outputs. Transactions that are in
+/// the node's block template use the exact stats reported by the node instead.
+pub fn tx_stats(tx: &Transaction) -> TxStats {
+    let scripts = tx
+        .input
+        .iter()
+        .map(|input| &input.script_sig)
+        .chain(tx.output.iter().map(|output| &output.script_pubkey));
+    let legacy_sigops: u64 = scripts.map(legacy_sigops).sum();
+    TxStats {
+        weight: tx.weight() as u64,
+        sigops: legacy_sigops * WITNESS_SCALE_FACTOR,
+    }
+}
+
+fn legacy_sigops(script: &Script) -> u64 {
+    script
+        .instructions()
+        .filter_map(|instruction| match instruction {
+            Ok(Instruction::Op(op)) if op == OP_CHECKSIG || op == OP_CHECKSIGVERIFY => Some(1),
+            Ok(Instruction::Op(op)) if op == OP_CHECKMULTISIG || op == OP_CHECKMULTISIGVERIFY => {
+                Some(MAX_MULTISIG_PUBKEYS)
+            }
+            _ => None,
+        })
+        .sum()
+}
+
+/// Rebuilds the coinbase from prefix and suffix. The extranonce is not known when the job is
+/// declared, so its bytes are zeroed, the extranonce is the last part of the script sig and its
+/// length is what is missing in the prefix to reach the script sig length.
+pub fn decode_coinbase(prefix: &[u8], suffix: &[u8]) -> Result<Transaction, JobValidationError> {
+    let invalid = |e: &str| JobValidationError::InvalidCoinbase(e.to_string());
+    // tx version
+    let mut index = 4;
+    // segwit marker and flag
+    if prefix.get(index) == Some(&0) && prefix.get(index + 1) == Some(&1) {
+        index += 2;
+    }
+    let (inputs, len) = read_compact_size(prefix, index).ok_or_else(|| invalid("no inputs"))?;
+    if inputs != 1 {
+        return Err(invalid("coinbase must have exactly one input"));
+    }
+    // prev OutPoint
+    index += len + 32 + 4;
+    let (script_len, len) =
+        read_compact_size(prefix, index).ok_or_else(|| invalid("script sig not in prefix"))?;
+    index += len;
+    let script_in_prefix = prefix.len().saturating_sub(index) as u64;
+    if prefix.len() < index || script_in_prefix > script_len {
+        return Err(invalid("prefix longer than the script sig"));
+    }
+    let extranonce_len = (script_len - script_in_prefix) as usize;
+    let coinbase = [prefix, &vec![0; extranonce_len][..], suffix].concat();
+    let coinbase: Transaction =
+        deserialize(&coinbase).map_err(|e| JobValidationError::InvalidCoinbase(e.to_string()))?;
+    if !coinbase.is_coin_base() {
+        return Err(invalid("first input is not a coinbase input"));
+    }
+    Ok(coinbase)
+}
+
+/// Height committed in the coinbase script sig as required by BIP34
+pub fn bip34_height(coinbase: &Transaction) -> Result<u64, JobValidationError> {
+    let invalid = || JobValidationError::InvalidCoinbase("missing BIP34 height".to_string());
+    let script = coinbase.input[0].script_sig.as_bytes();
+    match script.first().copied() {
+        Some(0) => Ok(0),
+        // OP_1 to OP_16
+        Some(op @ 0x51..=0x60) => Ok((op - 0x50) as u64),
+        Some(len @ 1..=8) => {
+            let bytes = script.get(1..1 + len as usize).ok_or_else(invalid)?;
+            Ok(bytes
+                .iter()
+                .rev()
+                .fold(0, |height, byte| (height << 8) | *byte as u64))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+pub fn block_subsidy(height: u64) -> u64 {
+    let halvings = height / HALVING_INTERVAL;
+    if halvings >= 64 {
+        0
+    } else {
+        INITIAL_SUBSIDY >> halvings
+    }
+}
+
+fn read_compact_size(bytes: &[u8], index: usize) -> Option<(u64, usize)> {
+    let le = |n: usize| {
+        bytes.get(index + 1..index + 1 + n).map(|b| {
+            b.iter()
+                .rev()
+                .fold(0, |acc, byte| (acc << 8) | *byte as u64)
+        })
+    };
+    match *bytes.get(index)? {
+        0xfd => Some((le(2)?, 3)),
+        0xfe => Some((le(4)?, 5)),
+        0xff => Some((le(8)?, 9)),
+        n => Some((n as u64, 1)),
+    }
+}
+
+fn compact_size_len(n: u64) -> u64 {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stratum_common::bitcoin::{
+        consensus::serialize, hashes::Hash, BlockHash, OutPoint, PackedLockTime, Sequence, TxIn,
+        TxOut, Witness,
+    };
+
+    fn chain_tip(height: u64) -> ChainTip {
+        ChainTip {
+            prev_hash: BlockHash::all_zeros(),
+            n_bits: 0x1d00ffff,
+            height,
+            version: 0x2000_0000,
+            weight_limit: 4_000_000,
+            sigop_limit: 80_000,
+        }
+    }
+
+    // returns prefix and suffix of a coinbase with a 8 bytes extranonce
+    fn coinbase_parts(height: u64, pool_script: &Script, value: u64) -> (Vec<u8>, Vec<u8>) {
+        let mut script_sig = vec![3];
+        script_sig.extend_from_slice(&height.to_le_bytes()[..3]);
+        script_sig.extend_from_slice(&[0; 8]);
+        let coinbase = Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: script_sig.into(),
+                sequence: Sequence(u32::MAX),
+                witness: Witness::from_vec(vec![vec![0; 32]]),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: pool_script.clone(),
+            }],
+        };
+        let encoded = serialize(&coinbase);
+        // version + segwit bytes + inputs + outpoint + script len + bip34 bytes
+        let prefix_len = 4 + 2 + 1 + 36 + 1 + 4;
+        (
+            encoded[..prefix_len].to_vec(),
+            encoded[prefix_len + 8..].to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_validate_job_coinbase() {
+        let pool_script = Script::from(vec![0x51]);
+        let validator = JobValidator::new(vec![pool_script.clone()], DEFAULT_VERSION_ROLLING_MASK);
+        let tip = chain_tip(840_000);
+
+        let (prefix, suffix) = coinbase_parts(840_000, &pool_script, 312_500_000);
+        let coinbase = decode_coinbase(&prefix, &suffix).unwrap();
+        assert_eq!(bip34_height(&coinbase).unwrap(), 840_000);
+        assert!(check_block_limits(&tip, &coinbase, &[]).is_ok());
+
+        assert!(validator
+            .check_pool_payout(&coinbase, tip.height, 0)
+            .is_ok());
+        assert!(validator
+            .check_pool_payout(&coinbase, tip.height, 1)
+            .is_err());
+
+        let (prefix, suffix) = coinbase_parts(840_000, &pool_script, 312_499_999);
+        let coinbase = decode_coinbase(&prefix, &suffix).unwrap();
+        assert_eq!(
+            validator.check_pool_payout(&coinbase, tip.height, 0),
+            Err(JobValidationError::InsufficientPoolPayout {
+                expected: 312_500_000,
+                paid: 312_499_999
+            })
+        );
+
+        assert!(validator.check_version(0x2000_e000, tip.version).is_ok());
+        assert!(validator.check_version(0x2000_0004, tip.version).is_err());
+        assert!(validator.check_version(0x4000_0000, tip.version).is_err());
+    }
+
+    #[test]
+    fn test_job_is_rejected_without_chain_tip() {
+        let pool_script = Script::from(vec![0x51]);
+        let validator = JobValidator::new(vec![pool_script.clone()], DEFAULT_VERSION_ROLLING_MASK);
+        // the coinbase pays nothing, as if the height was after the last halving
+        let (prefix, suffix) = coinbase_parts(64 * HALVING_INTERVAL, &pool_script, 0);
+        let job = DeclareMiningJob {
+            request_id: 1,
+            mining_job_token: vec![0; 4].try_into().unwrap(),
+            version: 0x2000_0000,
+            coinbase_prefix: prefix.try_into().unwrap(),
+            coinbase_suffix: suffix.try_into().unwrap(),
+            tx_short_hash_nonce: 0,
+            tx_short_hash_list: vec![].into(),
+            tx_hash_list_hash: [0; 32].into(),
+            excess_data: vec![].try_into().unwrap(),
+        };
+        let error = validator.validate_job(&job, None).unwrap_err();
+        assert_eq!(error, JobValidationError::ChainTipUnknown);
+        assert_eq!(error.error_code(), "chain-tip-unknown");
+        assert!(matches!(
+            validator.validate_job(&job, Some(&chain_tip(840_000))),
+            Err(JobValidationError::StaleChainTip { .. })
+        ));
+    }
+
+    #[test]
+    fn test_block_limits() {
+        let pool_script = Script::from(vec![0x51]);
+        let (prefix, suffix) = coinbase_parts(1, &pool_script, 0);
+        let coinbase = decode_coinbase(&prefix, &suffix).unwrap();
+        let tip = chain_tip(1);
+        let heavy = TxStats {
+            weight: 3_999_800,
+            sigops: 0,
+        };
+        assert!(matches!(
+            check_block_limits(&tip, &coinbase, &[heavy]),
+            Err(JobValidationError::BlockWeightExceeded { .. })
+        ));
+        let many_sigops = TxStats {
+            weight: 400,
+            sigops: 80_004,
+        };
+        assert!(matches!(
+            check_block_limits(&tip, &coinbase, &[many_sigops]),
+            Err(JobValidationError::BlockSigopsExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_chain_tip() {
+        let tip = chain_tip(1);
+        assert!(check_chain_tip(&tip, BlockHash::all_zeros(), 0x1d00ffff).is_ok());
+        assert!(check_chain_tip(&tip, BlockHash::all_zeros(), 0x207fffff).is_err());
+        let prev_hash = BlockHash::from_inner([1; 32]);
+        assert!(check_chain_tip(&tip, prev_hash, 0x1d00ffff).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! Tokens and declared jobs of the JDS.
+//!
+//! Tokens are allocated by the JDS for every downstream and declared jobs are what the
+//! downstreams built with them. Both are kept in the [`JobState`], shared by all the downstream
+//! connections, and written through a [`JobStore`] with an expiry. This way a restarted JDS
+//! still knows which tokens it issued, and when a downstream submits a solution for a job
+//! declared before the restart the job, with its transactions, can be recovered and the block
+//! reconstructed.
+//!
+//! `SetCustomMiningJob` is validated by the pool with the signature returned in
+//! `DeclareMiningJobSuccess`, that only depends on the authority key so it stays valid across
+//! restarts.
+
+pub mod store;
+
+use super::error::JdsError;
+use nohash_hasher::BuildNoHashHasher;
+use roles_logic_sv2::{
+    job_declaration_sv2::{DeclareMiningJob, SubmitSolutionJd},
+    utils::u256_to_block_hash,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use store::{FileJobStore, JobStore, MemoryJobStore};
+use stratum_common::bitcoin::{
+    consensus::deserialize, util::hash::bitcoin_merkle_root, BlockHash, BlockHeader, Transaction,
+    TxMerkleNode, Txid,
+};
+use tracing::{info, warn};
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct JobStateConfig {
+    /// File where tokens and declared jobs are persisted. When not set they are kept in memory
+    /// only and are lost on restart.
+    pub path: Option<PathBuf>,
+    /// Seconds an allocated token can be used to declare a job
+    pub token_expiry_secs: u64,
+    /// Seconds a declared job can be used to submit a solution
+    pub job_expiry_secs: u64,
+}
+
+impl Default for JobStateConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            token_expiry_secs: 3600,
+            job_expiry_secs: 3600,
+        }
+    }
+}
+
+/// A job accepted by the JDS
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclaredJob {
+    pub token: u32,
+    /// Unix timestamp in seconds
+    pub declared_at: u64,
+    pub job: DeclareMiningJob<'static>,
+    /// Prev hash and nbits of the chain tip the job has been validated against
+    pub chain_tip: (BlockHash, u32),
+    /// Ids of the job transactions, in block order
+    pub transactions: Vec<Txid>,
+    /// Transactions provided by the downstream with `ProvideMissingTransactionsSuccess`, they
+    /// are usually not in the node mempool so their data is kept with the job
+    pub provided_transactions: Vec<Transaction>,
+}
+
+impl DeclaredJob {
+    /// Returns true if `solution` is a valid proof of work for this job, that is if the header
+    /// built with the job coinbase and transactions meets the target in `solution.nbits`
+    pub fn is_solved_by(&self, solution: &SubmitSolutionJd) -> bool {
+        let coinbase = [
+            self.job.coinbase_prefix.to_vec(),
+            solution.extranonce.to_vec(),
+            self.job.coinbase_suffix.to_vec(),
+        ]
+        .concat();
+        let coinbase: Transaction = match deserialize(&coinbase) {
+            Ok(coinbase) => coinbase,
+            Err(_) => return false,
+        };
+        let hashes = std::iter::once(coinbase.txid())
+            .chain(self.transactions.iter().copied())
+            .map(|txid| TxMerkleNode::from_hash(txid.as_hash()));
+        let merkle_root = match bitcoin_merkle_root(hashes) {
+            Some(merkle_root) => merkle_root,
+            None => return false,
+        };
+        let header = BlockHeader {
+            version: solution.version as i32,
+            prev_blockhash: u256_to_block_hash(solution.prev_hash.clone().into_static()),
+            merkle_root,
+            time: solution.ntime,
+            bits: solution.nbits,
+            nonce: solution.nonce,
+        };
+        header.validate_pow(&header.target()).is_ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStateEntry {
+    Token { token: u32, expires_at: u64 },
+    Job(Box<DeclaredJob>),
+}
+
+/// Tokens and declared jobs of every downstream of the JDS
+#[derive(Debug)]
+pub struct JobState {
+    store: Box<dyn JobStore>,
+    // token -> expiry timestamp
+    tokens: HashMap<u32, u64, BuildNoHashHasher<u32>>,
+    // token -> last job declared with it
+    jobs: HashMap<u32, DeclaredJob, BuildNoHashHasher<u32>>,
+    next_token: u32,
+    token_expiry_secs: u64,
+    job_expiry_secs: u64,
+}
+
+impl JobState {
+    pub fn new(config: &JobStateConfig) -> Result<Self, JdsError> {
+        let store: Box<dyn JobStore> = match &config.path {
+            Some(path) => Box::new(FileJobStore::open(path)?),
+            None => Box::new(MemoryJobStore::default()),
+        };
+        Self::with_store(store, config.token_expiry_secs, config.job_expiry_secs)
+    }
+
+    /// Builds the state on top of any [`JobStore`], restoring the entries that are not expired.
+    /// The store is compacted so that expired entries do not pile up across restarts.
+    pub fn with_store(
+        mut store: Box<dyn JobStore>,
+        token_expiry_secs: u64,
+        job_expiry_secs: u64,
+    ) -> Result<Self, JdsError> {
+        let entries = store.load()?;
+        let mut state = Self {
+            store,
+            tokens: HashMap::with_hasher(BuildNoHashHasher::default()),
+            jobs: HashMap::with_hasher(BuildNoHashHasher::default()),
+            next_token: 0,
+            token_expiry_secs,
+            job_expiry_secs,
+        };
+        for entry in entries {
+            match entry {
+                JobStateEntry::Token { token, expires_at } => {
+                    // tokens must never be reused, also the expired ones
+                    state.next_token = state.next_token.max(token.wrapping_add(1));
+                    state.tokens.insert(token, expires_at);
+                }
+                JobStateEntry::Job(job) => {
+                    state.jobs.insert(job.token, *job);
+                }
+            }
+        }
+        state.remove_expired(now());
+        if !state.tokens.is_empty() || !state.jobs.is_empty() {
+            info!(
+                "Restored {} tokens and {} declared jobs",
+                state.tokens.len(),
+                state.jobs.len()
+            );
+        }
+        let mut entries: Vec<JobStateEntry> = state
+            .tokens
+            .iter()
+            .map(|(token, expires_at)| JobStateEntry::Token {
+                token: *token,
+                expires_at: *expires_at,
+            })
+            .collect();
+        // keep the last allocated token even if expired, it is needed to not reuse tokens
+        if state.next_token > 0 && !state.tokens.contains_key(&(state.next_token - 1)) {
+            entries.push(JobStateEntry::Token {
+                token: state.next_token - 1,
+                expires_at: 0,
+            });
+        }
+        entries.extend(
+            state
+                .jobs
+                .values()
+                .map(|job| JobStateEntry::Job(Box::new(job.clone()))),
+        );
+        state.store.rewrite(&entries)?;
+        Ok(state)
+    }
+
+    /// Returns a new token, unique across all the downstreams and restarts
+    pub fn allocate_token(&mut self) -> u32 {
+        let now = now();
+        self.remove_expired(now);
+        let token = self.next_token;
+        self.next_token = self.next_token.wrapping_add(1);
+        let expires_at = now + self.token_expiry_secs;
+        self.tokens.insert(token, expires_at);
+        if let Err(e) = self
+            .store
+            .append(&JobStateEntry::Token { token, expires_at })
+        {
+            warn!("Failed to persist mining job token {}: {}", token, e);
+        }
+        token
+    }
+
+    pub fn is_token_valid(&self, token: u32) -> bool {
+        self.tokens
+            .get(&token)
+            .is_some_and(|expires_at| *expires_at > now())
+    }
+
+    /// Records a job accepted by the JDS, `transactions` are the ids of all the job
+    /// transactions and `provided_transactions` the ones the downstream had to provide
+    pub fn on_job_declared(
+        &mut self,
+        job: DeclareMiningJob<'static>,
+        chain_tip: (BlockHash, u32),
+        transactions: Vec<Txid>,
+        provided_transactions: Vec<Transaction>,
+    ) {
+        let token = match token_of(&job) {
+            Some(token) => token,
+            None => return,
+        };
+        let now = now();
+        self.remove_expired(now);
+        let job = DeclaredJob {
+            token,
+            declared_at: now,
+            job,
+            chain_tip,
+            transactions,
+            provided_transactions,
+        };
+        if let Err(e) = self
+            .store
+            .append(&JobStateEntry::Job(Box::new(job.clone())))
+        {
+            warn!(
+                "Failed to persist declared job {}: {}",
+                job.job.request_id, e
+            );
+        }
+        self.jobs.insert(token, job);
+    }
+
+    /// Not expired jobs validated on the chain tip with `prev_hash` and `n_bits`, most recent
+    /// first
+    pub fn jobs_on_tip(&self, prev_hash: &BlockHash, n_bits: u32) -> Vec<DeclaredJob> {
+        let now = now();
+        let mut jobs: Vec<DeclaredJob> = self
+            .jobs
+            .values()
+            .filter(|job| job.declared_at + self.job_expiry_secs > now)
+            .filter(|job| job.chain_tip == (*prev_hash, n_bits))
+            .cloned()
+            .collect();
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.declared_at));
+        jobs
+    }
+
+    fn remove_expired(&mut self, now: u64) {
+        let job_expiry_secs = self.job_expiry_secs;
+        self.tokens.retain(|_, expires_at| *expires_at > now);
+        self.jobs
+            .retain(|_, job| job.declared_at + job_expiry_secs > now);
+    }
+}
+
+/// Token of a declared job, `None` if the token was not allocated by the JDS
+pub fn token_of(job: &DeclareMiningJob) -> Option<u32> {
+    let token: [u8; 4] = job.mining_job_token.to_vec().as_slice().try_into().ok()?;
+    Some(u32::from_le_bytes(token))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stratum_common::bitcoin::hashes::Hash;
+
+    fn declared_job(token: u32, declared_at: u64) -> DeclaredJob {
+        DeclaredJob {
+            token,
+            declared_at,
+            job: DeclareMiningJob {
+                request_id: token,
+                mining_job_token: token.to_le_bytes().to_vec().try_into().unwrap(),
+                version: 0x2000_0000,
+                coinbase_prefix: vec![1, 2, 3].try_into().unwrap(),
+                coinbase_suffix: vec![4, 5].try_into().unwrap(),
+                tx_short_hash_nonce: 0,
+                tx_short_hash_list: vec![].into(),
+                tx_hash_list_hash: [0; 32].into(),
+                excess_data: vec![].try_into().unwrap(),
+            },
+            chain_tip: (BlockHash::all_zeros(), 0x207fffff),
+            transactions: vec![],
+            provided_transactions: vec![],
+        }
+    }
+
+    #[test]
+    fn test_state_is_restored_without_expired_entries() {
+        let mut store = MemoryJobStore::default();
+        let now = now();
+        store
+            .append(&JobStateEntry::Token {
+                token: 7,
+                expires_at: now - 1,
+            })
+            .unwrap();
+        store
+            .append(&JobStateEntry::Token {
+                token: 3,
+                expires_at: now + 60,
+            })
+            .unwrap();
+        let job = declared_job(3, now);
+        store
+            .append(&JobStateEntry::Job(Box::new(job.clone())))
+            .unwrap();
+        store
+            .append(&JobStateEntry::Job(Box::new(declared_job(7, now - 120))))
+            .unwrap();
+
+        let mut state = JobState::with_store(Box::new(store), 60, 60).unwrap();
+        assert!(state.is_token_valid(3));
+        assert!(!state.is_token_valid(7));
+        // the expired token is not reused
+        assert_eq!(state.allocate_token(), 8);
+        assert_eq!(
+            state.jobs_on_tip(&BlockHash::all_zeros(), 0x207fffff),
+            vec![job]
+        );
+        assert!(state
+            .jobs_on_tip(&BlockHash::all_zeros(), 0x1d00ffff)
+            .is_empty());
+    }
+}
@@ -0,0 +1,248 @@
+//! Storage backends for the [`JobState`](super::JobState).
+//!
+//! A store appends entries, gives them back in the same order when the JDS starts and can be
+//! rewritten with only the live entries. [`FileJobStore`] is the on-disk backend,
+//! [`MemoryJobStore`] keeps everything in memory and is used when no path is configured.
+
+use super::{super::error::JdsError, DeclaredJob, JobStateEntry};
+use roles_logic_sv2::job_declaration_sv2::DeclareMiningJob;
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use stratum_common::bitcoin::{
+    consensus::{deserialize, serialize},
+    BlockHash, Transaction, Txid,
+};
+use tracing::warn;
+
+pub trait JobStore: Send + std::fmt::Debug {
+    /// Persists `entry` after the already stored ones
+    fn append(&mut self, entry: &JobStateEntry) -> Result<(), JdsError>;
+    /// Returns every stored entry in insertion order
+    fn load(&mut self) -> Result<Vec<JobStateEntry>, JdsError>;
+    /// Replaces the stored entries with `entries`
+    fn rewrite(&mut self, entries: &[JobStateEntry]) -> Result<(), JdsError>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryJobStore {
+    entries: Vec<JobStateEntry>,
+}
+
+impl JobStore for MemoryJobStore {
+    fn append(&mut self, entry: &JobStateEntry) -> Result<(), JdsError> {
+        self.entries.push(entry.clone());
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<Vec<JobStateEntry>, JdsError> {
+        Ok(self.entries.clone())
+    }
+
+    fn rewrite(&mut self, entries: &[JobStateEntry]) -> Result<(), JdsError> {
+        self.entries = entries.to_vec();
+        Ok(())
+    }
+}
+
+/// Append only log, one entry per line:
+///
+/// ```text
+/// token <token> <expires_at>
+/// job <token> <declared_at> <prev_hash> <nbits> <hex job> <txid,...|-> <hex tx,...|->
+/// ```
+///
+/// The job is the binary encoded `DeclareMiningJob`, the last field are the transactions
+/// provided by the downstream, consensus encoded.
+#[derive(Debug)]
+pub struct FileJobStore {
+    path: PathBuf,
+    file: File,
+}
+
+impl FileJobStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, JdsError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file })
+    }
+}
+
+impl JobStore for FileJobStore {
+    fn append(&mut self, entry: &JobStateEntry) -> Result<(), JdsError> {
+        writeln!(self.file, "{}", encode_entry(entry)?)?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<Vec<JobStateEntry>, JdsError> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let mut entries = Vec::new();
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match decode_entry(&line) {
+                Some(entry) => entries.push(entry),
+                // A crash while writing can leave the last line truncated
+                None => warn!(
+                    "Skipping malformed line {} of job state {:?}",
+                    n + 1,
+                    self.path
+                ),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn rewrite(&mut self, entries: &[JobStateEntry]) -> Result<(), JdsError> {
+        // write to a temporary file first so that a crash does not lose the current entries
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            for entry in entries {
+                writeln!(tmp, "{}", encode_entry(entry)?)?;
+            }
+            tmp.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+fn encode_entry(entry: &JobStateEntry) -> Result<String, JdsError> {
+    Ok(match entry {
+        JobStateEntry::Token { token, expires_at } => format!("token {} {}", token, expires_at),
+        JobStateEntry::Job(job) => {
+            let (prev_hash, n_bits) = job.chain_tip;
+            format!(
+                "job {} {} {} {} {} {} {}",
+                job.token,
+                job.declared_at,
+                prev_hash,
+                n_bits,
+                hex::encode(binary_sv2::to_bytes(job.job.clone())?),
+                encode_list(job.transactions.iter().map(|txid| txid.to_string())),
+                encode_list(
+                    job.provided_transactions
+                        .iter()
+                        .map(|tx| hex::encode(serialize(tx)))
+                ),
+            )
+        }
+    })
+}
+
+fn encode_list<I: Iterator<Item = String>>(items: I) -> String {
+    let list = items.collect::<Vec<String>>().join(",");
+    if list.is_empty() {
+        "-".to_string()
+    } else {
+        list
+    }
+}
+
+fn decode_entry(line: &str) -> Option<JobStateEntry> {
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.as_slice() {
+        ["token", token, expires_at] => Some(JobStateEntry::Token {
+            token: token.parse().ok()?,
+            expires_at: expires_at.parse().ok()?,
+        }),
+        ["job", token, declared_at, prev_hash, n_bits, job, transactions, provided] => {
+            let chain_tip = (BlockHash::from_str(prev_hash).ok()?, n_bits.parse().ok()?);
+            let mut job = hex::decode(job).ok()?;
+            let job = binary_sv2::from_bytes::<DeclareMiningJob>(&mut job)
+                .ok()?
+                .into_static();
+            let transactions = decode_list(transactions, |txid| Txid::from_str(txid).ok())?;
+            let provided_transactions = decode_list(provided, |tx| {
+                deserialize::<Transaction>(&hex::decode(tx).ok()?).ok()
+            })?;
+            Some(JobStateEntry::Job(Box::new(DeclaredJob {
+                token: token.parse().ok()?,
+                declared_at: declared_at.parse().ok()?,
+                job,
+                chain_tip,
+                transactions,
+                provided_transactions,
+            })))
+        }
+        _ => None,
+    }
+}
+
+fn decode_list<T, F: Fn(&str) -> Option<T>>(list: &str, decode: F) -> Option<Vec<T>> {
+    match list {
+        "-" => Some(vec![]),
+        list => list.split(',').map(decode).collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryInto;
+    use stratum_common::bitcoin::{hashes::Hash, PackedLockTime, TxIn, TxOut};
+
+    #[test]
+    fn test_file_store_roundtrip() {
+        let path =
+            std::env::temp_dir().join(format!("jds-job-state-test-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let provided = Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn::default()],
+            output: vec![TxOut::default()],
+        };
+        let job = DeclaredJob {
+            token: 1,
+            declared_at: 1700000000,
+            job: DeclareMiningJob {
+                request_id: 4,
+                mining_job_token: 1_u32.to_le_bytes().to_vec().try_into().unwrap(),
+                version: 0x2000_0000,
+                coinbase_prefix: vec![1, 0, 0, 0, 1].try_into().unwrap(),
+                coinbase_suffix: vec![0xff; 10].try_into().unwrap(),
+                tx_short_hash_nonce: 42,
+                tx_short_hash_list: vec![vec![1, 2, 3, 4, 5, 6].try_into().unwrap()].into(),
+                tx_hash_list_hash: [7; 32].into(),
+                excess_data: vec![].try_into().unwrap(),
+            },
+            chain_tip: (BlockHash::all_zeros(), 0x207fffff),
+            transactions: vec![provided.txid(), Txid::all_zeros()],
+            provided_transactions: vec![provided],
+        };
+        let entries = vec![
+            JobStateEntry::Token {
+                token: 1,
+                expires_at: 1700003600,
+            },
+            JobStateEntry::Job(Box::new(job.clone())),
+            JobStateEntry::Job(Box::new(DeclaredJob {
+                chain_tip: (BlockHash::from_inner([1; 32]), 0x1d00ffff),
+                transactions: vec![],
+                provided_transactions: vec![],
+                ..job
+            })),
+        ];
+        {
+            let mut store = FileJobStore::open(&path).unwrap();
+            for entry in &entries {
+                store.append(entry).unwrap();
+            }
+        }
+        let mut store = FileJobStore::open(&path).unwrap();
+        assert_eq!(store.load().unwrap(), entries);
+        store.rewrite(&entries[..1]).unwrap();
+        store.append(&entries[1]).unwrap();
+        assert_eq!(store.load().unwrap(), entries[..2]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
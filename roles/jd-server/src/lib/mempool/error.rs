@@ -8,6 +8,7 @@ pub enum JdsMempoolError {
     NoClient,
     Rpc(RpcError),
     PoisonLock(String),
+    Source(String),
 }
 
 impl From<RpcError> for JdsMempoolError {
@@ -40,5 +41,9 @@ pub fn handle_error(err: &JdsMempoolError) {
             error!("{:?}", err);
             error!("Poison lock error)");
         }
+        JdsMempoolError::Source(_) => {
+            error!("{:?}", err);
+            error!("Unable to get the mempool from the configured mempool source");
+        }
     }
 }
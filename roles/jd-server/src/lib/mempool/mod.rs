@@ -1,4 +1,5 @@
 pub mod error;
+pub mod source;
 use super::job_declarator::AddTrasactionsToMempoolInner;
 use crate::mempool::error::JdsMempoolError;
 use async_channel::Receiver;
@@ -6,8 +7,12 @@ use bitcoin::blockdata::transaction::Transaction;
 use hashbrown::HashMap;
 use roles_logic_sv2::utils::Mutex;
 use rpc_sv2::{mini_rpc_client, mini_rpc_client::RpcError};
+use source::{MempoolEntry, MempoolSource};
 use std::{convert::TryInto, str::FromStr, sync::Arc};
-use stratum_common::{bitcoin, bitcoin::hash_types::Txid};
+use stratum_common::{
+    bitcoin,
+    bitcoin::hash_types::{BlockHash, Txid},
+};
 
 #[derive(Clone, Debug)]
 pub struct TransactionWithHash {
@@ -15,12 +20,36 @@ pub struct TransactionWithHash {
     pub tx: Option<Transaction>,
 }
 
+/// The chain tip as seen by the node the JDS is connected to, together with the limits that a
+/// block built on top of it must respect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainTip {
+    pub prev_hash: BlockHash,
+    pub n_bits: u32,
+    /// Height of the next block
+    pub height: u64,
+    pub version: u32,
+    pub weight_limit: u64,
+    pub sigop_limit: u64,
+}
+
+/// Weight and sigop cost of a transaction, as reported by the node in `getblocktemplate`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TxStats {
+    pub weight: u64,
+    pub sigops: u64,
+}
+
 #[derive(Clone, Debug)]
 pub struct JDsMempool {
-    pub mempool: HashMap<Txid, Option<Transaction>>,
+    pub mempool: HashMap<Txid, MempoolEntry>,
+    pub chain_tip: Option<ChainTip>,
+    // used to get weight and sigops of transactions for which the JDS has only the txid
+    pub template_tx_stats: HashMap<Txid, TxStats>,
     auth: mini_rpc_client::Auth,
     url: String,
     new_block_receiver: Receiver<String>,
+    source: Option<Arc<dyn MempoolSource>>,
 }
 
 impl JDsMempool {
@@ -34,6 +63,25 @@ impl JDsMempool {
         }
     }
 
+    /// Sets where the mempool is fetched from, without a source the mempool is filled only with
+    /// the transactions provided by the downstreams.
+    pub fn set_source(&mut self, source: Arc<dyn MempoolSource>) {
+        self.source = Some(source);
+    }
+
+    pub fn has_source(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Sum of the fees of the given transactions, the ones that are not in the mempool count as 0
+    pub fn fees(&self, txids: &[Txid]) -> u64 {
+        txids
+            .iter()
+            .filter_map(|txid| self.mempool.get(txid))
+            .map(|entry| entry.fee)
+            .sum()
+    }
+
     /// This function is used only for debug purposes and should not be used
     /// in production code.
     #[cfg(debug_assertions)]
@@ -45,17 +93,18 @@ impl JDsMempool {
 
     pub fn new(
         url: String,
-        username: String,
-        password: String,
+        auth: mini_rpc_client::Auth,
         new_block_receiver: Receiver<String>,
     ) -> Self {
-        let auth = mini_rpc_client::Auth::new(username, password);
-        let empty_mempool: HashMap<Txid, Option<Transaction>> = HashMap::new();
+        let empty_mempool: HashMap<Txid, MempoolEntry> = HashMap::new();
         JDsMempool {
             mempool: empty_mempool,
+            chain_tip: None,
+            template_tx_stats: HashMap::new(),
             auth,
             url,
             new_block_receiver,
+            source: None,
         }
     }
 
@@ -68,51 +117,54 @@ impl JDsMempool {
     ) -> Result<(), JdsMempoolError> {
         let txids = add_txs_to_mempool_inner.known_transactions;
         let transactions = add_txs_to_mempool_inner.unknown_transactions;
-        let client = self_
-            .safe_lock(|a| a.get_client())?
+        let source = self_
+            .safe_lock(|a| a.source.clone())?
             .ok_or(JdsMempoolError::NoClient)?;
         // fill in the mempool the transactions id in the mempool with the full transactions
         // retrieved from the jd client
-        for txid in txids {
-            if let Some(None) = self_
-                .safe_lock(|a| a.mempool.get(&txid).cloned())
-                .map_err(|e| JdsMempoolError::PoisonLock(e.to_string()))?
-            {
-                let transaction = client
-                    .get_raw_transaction(&txid.to_string(), None)
-                    .await
-                    .map_err(JdsMempoolError::Rpc)?;
-                let _ =
-                    self_.safe_lock(|a| a.mempool.insert(transaction.txid(), Some(transaction)));
+        let missing_data: Vec<Txid> = self_.safe_lock(|a| {
+            txids
+                .into_iter()
+                .filter(|txid| matches!(a.mempool.get(txid), Some(entry) if entry.tx.is_none()))
+                .collect()
+        })?;
+        let retrieved = source.transactions(&missing_data).await?;
+
+        // fill in the mempool the retrieved transactions and the transactions given in input
+        self_.safe_lock(|a| {
+            for transaction in retrieved.into_iter().chain(transactions) {
+                a.add_transaction(transaction);
             }
-        }
+        })?;
+        Ok(())
+    }
 
-        // fill in the mempool the transactions given in input
-        for transaction in transactions {
-            let _ = self_.safe_lock(|a| a.mempool.insert(transaction.txid(), Some(transaction)));
+    pub fn add_transaction(&mut self, transaction: Transaction) {
+        match self.mempool.get_mut(&transaction.txid()) {
+            Some(entry) => entry.tx = Some(transaction),
+            None => {
+                self.mempool.insert(
+                    transaction.txid(),
+                    MempoolEntry::from_transaction(transaction),
+                );
+            }
         }
-        Ok(())
     }
 
     pub async fn update_mempool(self_: Arc<Mutex<Self>>) -> Result<(), JdsMempoolError> {
-        let mut mempool_ordered: HashMap<Txid, Option<Transaction>> = HashMap::new();
-
-        let client = self_
-            .safe_lock(|x| x.get_client())?
+        let source = self_
+            .safe_lock(|x| x.source.clone())?
             .ok_or(JdsMempoolError::NoClient)?;
 
-        let mempool: Vec<String> = client.get_raw_mempool().await?;
-        for id in &mempool {
-            let key_id = Txid::from_str(id)
-                .map_err(|err| JdsMempoolError::Rpc(RpcError::Deserialization(err.to_string())))?;
-
-            let tx = self_.safe_lock(|x| match x.mempool.get(&key_id) {
-                Some(entry) => entry.clone(),
-                None => None,
-            })?;
-
-            mempool_ordered.insert(key_id, tx);
-        }
+        let mut mempool_ordered = source.entries().await?;
+        // keep the transactions data that has already been retrieved
+        self_.safe_lock(|x| {
+            for (txid, entry) in mempool_ordered.iter_mut() {
+                if entry.tx.is_none() {
+                    entry.tx = x.mempool.get(txid).and_then(|known| known.tx.clone());
+                }
+            }
+        })?;
 
         if mempool_ordered.is_empty() {
             Err(JdsMempoolError::EmptyMempool)
@@ -122,6 +174,43 @@ impl JDsMempool {
         }
     }
 
+    pub async fn update_chain_tip(self_: Arc<Mutex<Self>>) -> Result<(), JdsMempoolError> {
+        let client = self_
+            .safe_lock(|x| x.get_client())?
+            .ok_or(JdsMempoolError::NoClient)?;
+
+        let template = client.get_block_template().await?;
+        let prev_hash = BlockHash::from_str(&template.previousblockhash)
+            .map_err(|err| JdsMempoolError::Rpc(RpcError::Deserialization(err.to_string())))?;
+        let n_bits = u32::from_str_radix(&template.bits, 16)
+            .map_err(|err| JdsMempoolError::Rpc(RpcError::Deserialization(err.to_string())))?;
+        let chain_tip = ChainTip {
+            prev_hash,
+            n_bits,
+            height: template.height,
+            version: template.version as u32,
+            weight_limit: template.weightlimit,
+            sigop_limit: template.sigoplimit,
+        };
+        let mut template_tx_stats = HashMap::new();
+        for tx in &template.transactions {
+            let txid = Txid::from_str(&tx.txid)
+                .map_err(|err| JdsMempoolError::Rpc(RpcError::Deserialization(err.to_string())))?;
+            template_tx_stats.insert(
+                txid,
+                TxStats {
+                    weight: tx.weight,
+                    sigops: tx.sigops,
+                },
+            );
+        }
+        self_.safe_lock(|x| {
+            x.chain_tip = Some(chain_tip);
+            x.template_tx_stats = template_tx_stats;
+        })?;
+        Ok(())
+    }
+
     pub async fn on_submit(self_: Arc<Mutex<Self>>) -> Result<(), JdsMempoolError> {
         let new_block_receiver: Receiver<String> =
             self_.safe_lock(|x| x.new_block_receiver.clone())?;
@@ -147,7 +236,7 @@ impl JDsMempool {
                 .unwrap();
             let tx_data = TransactionWithHash {
                 id: *tx.0,
-                tx: tx.1.clone(),
+                tx: tx.1.tx.clone(),
             };
             if ret.insert(s_id, tx_data.clone()).is_none() {
                 continue;
@@ -0,0 +1,167 @@
+use super::{super::error::JdsMempoolError, MempoolEntry, MempoolSource, SourceFuture};
+use hashbrown::{HashMap, HashSet};
+use serde::Deserialize;
+use std::path::PathBuf;
+use stratum_common::bitcoin::{consensus::deserialize, Transaction, Txid};
+
+/// A transaction of the fixture file
+#[derive(Debug, Deserialize)]
+struct FixtureTransaction {
+    /// Consensus encoded transaction, hex encoded
+    hex: String,
+    /// Fee in sats
+    #[serde(default)]
+    fee: u64,
+}
+
+/// Reads the mempool from a JSON file like:
+///
+/// ```json
+/// [
+///     { "hex": "0200000001...", "fee": 1000 },
+///     { "hex": "0200000001...", "fee": 250 }
+/// ]
+/// ```
+///
+/// The file is read again on every update, so tests can change the mempool while the JDS is
+/// running. Ancestors are the fixture transactions spent, directly or not, by a transaction.
+#[derive(Debug, Clone)]
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn read(&self) -> Result<HashMap<Txid, MempoolEntry>, JdsMempoolError> {
+        let content = std::fs::read_to_string(&self.path)
+            .map_err(|e| JdsMempoolError::Source(format!("Can not read {:?}: {}", self.path, e)))?;
+        let fixture: Vec<FixtureTransaction> = serde_json::from_str(&content).map_err(|e| {
+            JdsMempoolError::Source(format!("Invalid mempool fixture {:?}: {}", self.path, e))
+        })?;
+        let mut entries = HashMap::with_capacity(fixture.len());
+        for fixture_tx in fixture {
+            let tx: Transaction = hex::decode(&fixture_tx.hex)
+                .ok()
+                .and_then(|bytes| deserialize(&bytes).ok())
+                .ok_or_else(|| {
+                    JdsMempoolError::Source(format!(
+                        "Invalid transaction in mempool fixture {:?}",
+                        self.path
+                    ))
+                })?;
+            let mut entry = MempoolEntry::from_transaction(tx.clone());
+            entry.fee = fixture_tx.fee;
+            entries.insert(tx.txid(), entry);
+        }
+        let ancestors: HashMap<Txid, HashSet<Txid>> = entries
+            .keys()
+            .map(|txid| (*txid, find_ancestors(&entries, txid)))
+            .collect();
+        for (txid, ancestors) in ancestors {
+            let (vsize, fees) = ancestors
+                .iter()
+                .filter_map(|ancestor| entries.get(ancestor))
+                .fold((0, 0), |(vsize, fees), a| (vsize + a.vsize(), fees + a.fee));
+            if let Some(entry) = entries.get_mut(&txid) {
+                entry.ancestor_count += ancestors.len() as u64;
+                entry.ancestor_vsize += vsize;
+                entry.ancestor_fees = entry.fee + fees;
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn find_ancestors(entries: &HashMap<Txid, MempoolEntry>, txid: &Txid) -> HashSet<Txid> {
+    let mut ancestors = HashSet::new();
+    let mut to_visit = vec![*txid];
+    while let Some(txid) = to_visit.pop() {
+        let parents = entries
+            .get(&txid)
+            .and_then(|entry| entry.tx.as_ref())
+            .map(|tx| tx.input.iter().map(|input| input.previous_output.txid))
+            .into_iter()
+            .flatten();
+        for parent in parents {
+            if entries.contains_key(&parent) && ancestors.insert(parent) {
+                to_visit.push(parent);
+            }
+        }
+    }
+    ancestors
+}
+
+impl MempoolSource for FileSource {
+    fn entries(&self) -> SourceFuture<'_, HashMap<Txid, MempoolEntry>> {
+        Box::pin(async move { self.read() })
+    }
+
+    fn transactions<'a>(&'a self, txids: &'a [Txid]) -> SourceFuture<'a, Vec<Transaction>> {
+        Box::pin(async move {
+            let entries = self.read()?;
+            Ok(txids
+                .iter()
+                .filter_map(|txid| entries.get(txid).and_then(|entry| entry.tx.clone()))
+                .collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stratum_common::bitcoin::{
+        consensus::serialize, OutPoint, PackedLockTime, Script, Sequence, TxIn, TxOut, Witness,
+    };
+
+    fn spending(previous_output: OutPoint) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output,
+                script_sig: Script::new(),
+                sequence: Sequence(u32::MAX),
+                witness: Witness::default(),
+            }],
+            output: vec![TxOut {
+                value: 1000,
+                script_pubkey: Script::from(vec![0x51]),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_source_ancestors() {
+        let parent = spending(OutPoint::null());
+        let child = spending(OutPoint::new(parent.txid(), 0));
+        let grandchild = spending(OutPoint::new(child.txid(), 0));
+        let fixture = format!(
+            "[{{\"hex\": \"{}\", \"fee\": 100}}, {{\"hex\": \"{}\", \"fee\": 200}}, {{\"hex\": \"{}\", \"fee\": 300}}]",
+            hex::encode(serialize(&parent)),
+            hex::encode(serialize(&child)),
+            hex::encode(serialize(&grandchild)),
+        );
+        let path =
+            std::env::temp_dir().join(format!("jds-mempool-fixture-{}.json", std::process::id()));
+        std::fs::write(&path, fixture).unwrap();
+
+        let source = FileSource::new(path.clone());
+        let entries = source.entries().await.unwrap();
+        let grandchild_entry = &entries[&grandchild.txid()];
+        assert_eq!(grandchild_entry.ancestor_count, 3);
+        assert_eq!(grandchild_entry.ancestor_fees, 600);
+        assert_eq!(
+            grandchild_entry.ancestor_vsize,
+            3 * grandchild_entry.vsize()
+        );
+        assert_eq!(entries[&parent.txid()].ancestor_count, 1);
+
+        let transactions = source.transactions(&[child.txid()]).await.unwrap();
+        assert_eq!(transactions, vec![child]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,119 @@
+//! Backends the [`JDsMempool`](super::JDsMempool) gets its transactions from.
+//!
+//! - [`RpcSource`] polls the node with `getrawmempool` and fetches transactions in batches
+//! - [`ZmqSource`] (feature `zmq`) follows the node `rawtx` and `hashblock` notifications and only
+//!   uses RPC to resync after a block and to get fees of the new transactions
+//! - [`FileSource`] reads the mempool from a JSON fixture, it is meant for tests
+pub mod file;
+pub mod rpc;
+#[cfg(feature = "zmq")]
+pub mod zmq_subscriber;
+
+pub use file::FileSource;
+pub use rpc::RpcSource;
+#[cfg(feature = "zmq")]
+pub use zmq_subscriber::ZmqSource;
+
+use super::error::JdsMempoolError;
+use hashbrown::HashMap;
+use rpc_sv2::mini_rpc_client::MiniRpcClient;
+use serde::Deserialize;
+use std::{fmt::Debug, future::Future, path::PathBuf, pin::Pin, sync::Arc};
+use stratum_common::bitcoin::{Transaction, Txid};
+
+const WITNESS_SCALE_FACTOR: u64 = 4;
+
+pub type SourceFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, JdsMempoolError>> + Send + 'a>>;
+
+pub trait MempoolSource: Send + Sync + Debug {
+    /// Returns every transaction currently in the mempool. Entries can come without the
+    /// transaction data, that is fetched with [`MempoolSource::transactions`] only when needed.
+    fn entries(&self) -> SourceFuture<'_, HashMap<Txid, MempoolEntry>>;
+
+    /// Returns the transactions with the given ids, the ones unknown to the source are omitted
+    fn transactions<'a>(&'a self, txids: &'a [Txid]) -> SourceFuture<'a, Vec<Transaction>>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MempoolEntry {
+    pub tx: Option<Transaction>,
+    /// Base fee in sats
+    pub fee: u64,
+    pub weight: u64,
+    /// Number of in-mempool ancestors, the transaction itself included
+    pub ancestor_count: u64,
+    /// Virtual size of the in-mempool ancestors, the transaction itself included
+    pub ancestor_vsize: u64,
+    /// Fees of the in-mempool ancestors, the transaction itself included
+    pub ancestor_fees: u64,
+}
+
+impl MempoolEntry {
+    /// Entry of a transaction for which only the data is known (eg provided by a downstream),
+    /// the fee can not be computed without the spent outputs so it is set to 0.
+    pub fn from_transaction(tx: Transaction) -> Self {
+        let weight = tx.weight() as u64;
+        let vsize = weight.div_ceil(WITNESS_SCALE_FACTOR);
+        Self {
+            tx: Some(tx),
+            fee: 0,
+            weight,
+            ancestor_count: 1,
+            ancestor_vsize: vsize,
+            ancestor_fees: 0,
+        }
+    }
+
+    pub fn vsize(&self) -> u64 {
+        self.weight.div_ceil(WITNESS_SCALE_FACTOR)
+    }
+
+    /// Fee rate in sat/vB
+    pub fn fee_rate(&self) -> f64 {
+        self.fee as f64 / self.vsize().max(1) as f64
+    }
+
+    /// Fee rate in sat/vB of the package made by the transaction and its in-mempool ancestors
+    pub fn ancestor_fee_rate(&self) -> f64 {
+        self.ancestor_fees as f64 / self.ancestor_vsize.max(1) as f64
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MempoolSourceConfig {
+    #[default]
+    Rpc,
+    Zmq {
+        address: String,
+    },
+    File {
+        path: PathBuf,
+    },
+}
+
+impl MempoolSourceConfig {
+    /// Builds the configured source, `client` is the RPC client of the JDS if one is configured.
+    /// Returns `None` when the RPC source is selected and there is no RPC client.
+    pub fn build(
+        &self,
+        client: Option<MiniRpcClient>,
+    ) -> Result<Option<Arc<dyn MempoolSource>>, JdsMempoolError> {
+        match self {
+            MempoolSourceConfig::Rpc => {
+                Ok(client.map(|client| Arc::new(RpcSource::new(client)) as Arc<dyn MempoolSource>))
+            }
+            #[cfg(feature = "zmq")]
+            MempoolSourceConfig::Zmq { address } => {
+                let rpc = RpcSource::new(client.ok_or(JdsMempoolError::NoClient)?);
+                Ok(Some(Arc::new(ZmqSource::new(address, rpc)?)))
+            }
+            #[cfg(not(feature = "zmq"))]
+            MempoolSourceConfig::Zmq { .. } => Err(JdsMempoolError::Source(
+                "jd-server has been built without the zmq feature".to_string(),
+            )),
+            MempoolSourceConfig::File { path } => Ok(Some(Arc::new(FileSource::new(path.clone())))),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use super::{super::error::JdsMempoolError, MempoolEntry, MempoolSource, SourceFuture};
+use hashbrown::HashMap;
+use rpc_sv2::mini_rpc_client::{self, MiniRpcClient, RpcError};
+use std::str::FromStr;
+use stratum_common::bitcoin::{Transaction, Txid};
+
+#[derive(Clone, Debug)]
+pub struct RpcSource {
+    client: MiniRpcClient,
+}
+
+impl RpcSource {
+    pub fn new(client: MiniRpcClient) -> Self {
+        Self { client }
+    }
+
+    /// Fetches the mempool entries of the given transactions in a single batch, transactions
+    /// that are not in the node's mempool are omitted.
+    pub async fn entries_of(
+        &self,
+        txids: &[Txid],
+    ) -> Result<HashMap<Txid, MempoolEntry>, JdsMempoolError> {
+        let ids: Vec<String> = txids.iter().map(|txid| txid.to_string()).collect();
+        let entries = self.client.get_mempool_entries(&ids).await?;
+        Ok(txids
+            .iter()
+            .zip(entries)
+            .filter_map(|(txid, entry)| Some((*txid, to_mempool_entry(entry?))))
+            .collect())
+    }
+}
+
+impl MempoolSource for RpcSource {
+    fn entries(&self) -> SourceFuture<'_, HashMap<Txid, MempoolEntry>> {
+        Box::pin(async move {
+            let mempool = self.client.get_raw_mempool_verbose().await?;
+            let mut entries = HashMap::with_capacity(mempool.len());
+            for (id, entry) in mempool {
+                let txid = Txid::from_str(&id).map_err(|err| {
+                    JdsMempoolError::Rpc(RpcError::Deserialization(err.to_string()))
+                })?;
+                entries.insert(txid, to_mempool_entry(entry));
+            }
+            Ok(entries)
+        })
+    }
+
+    fn transactions<'a>(&'a self, txids: &'a [Txid]) -> SourceFuture<'a, Vec<Transaction>> {
+        Box::pin(async move {
+            let ids: Vec<String> = txids.iter().map(|txid| txid.to_string()).collect();
+            Ok(self.client.get_raw_transactions(&ids).await?)
+        })
+    }
+}
+
+fn to_mempool_entry(entry: mini_rpc_client::MempoolEntry) -> MempoolEntry {
+    MempoolEntry {
+        tx: None,
+        fee: btc_to_sats(entry.fees.base),
+        weight: entry.weight,
+        ancestor_count: entry.ancestorcount,
+        ancestor_vsize: entry.ancestorsize,
+        ancestor_fees: btc_to_sats(entry.fees.ancestor),
+    }
+}
+
+fn btc_to_sats(btc: f64) -> u64 {
+    (btc * 100_000_000.0).round() as u64
+}
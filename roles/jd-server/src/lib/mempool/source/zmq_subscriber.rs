@@ -0,0 +1,123 @@
+use super::{super::error::JdsMempoolError, MempoolEntry, MempoolSource, RpcSource, SourceFuture};
+use hashbrown::HashMap;
+use roles_logic_sv2::utils::Mutex;
+use std::{sync::Arc, thread};
+use stratum_common::bitcoin::{consensus::deserialize, Transaction, Txid};
+use tracing::{error, warn};
+
+#[derive(Debug, Default)]
+struct ZmqState {
+    entries: HashMap<Txid, MempoolEntry>,
+    // transactions received since the last call to `entries`
+    new_transactions: Vec<Transaction>,
+    // set when a block is connected, confirmed transactions can only be removed by a resync
+    resync: bool,
+}
+
+/// Follows the node `rawtx` and `hashblock` zmq notifications. The mempool is fetched with RPC
+/// only at start and after every new block, in between only the entries of the new transactions
+/// are asked to the node. Transactions evicted or replaced between two blocks are dropped only at
+/// the next resync.
+#[derive(Debug)]
+pub struct ZmqSource {
+    rpc: RpcSource,
+    state: Arc<Mutex<ZmqState>>,
+}
+
+impl ZmqSource {
+    /// Connects to `address` (eg `tcp://127.0.0.1:28332`), the node must publish both `rawtx`
+    /// and `hashblock` on it.
+    pub fn new(address: &str, rpc: RpcSource) -> Result<Self, JdsMempoolError> {
+        let context = zmq::Context::new();
+        let socket = context
+            .socket(zmq::SUB)
+            .map_err(|e| JdsMempoolError::Source(e.to_string()))?;
+        socket
+            .connect(address)
+            .map_err(|e| JdsMempoolError::Source(e.to_string()))?;
+        for topic in [&b"rawtx"[..], &b"hashblock"[..]] {
+            socket
+                .set_subscribe(topic)
+                .map_err(|e| JdsMempoolError::Source(e.to_string()))?;
+        }
+        let state = Arc::new(Mutex::new(ZmqState {
+            resync: true,
+            ..Default::default()
+        }));
+        let state_cloned = state.clone();
+        // zmq sockets are blocking, so they get their own thread
+        thread::spawn(move || loop {
+            let message = match socket.recv_multipart(0) {
+                Ok(message) => message,
+                Err(e) => {
+                    error!("Zmq mempool source stopped: {}", e);
+                    let _ = state_cloned.safe_lock(|s| s.resync = true);
+                    break;
+                }
+            };
+            match message.first().map(|topic| &topic[..]) {
+                Some(b"rawtx") => match message.get(1).map(|tx| deserialize::<Transaction>(tx)) {
+                    Some(Ok(tx)) => {
+                        let _ = state_cloned.safe_lock(|s| s.new_transactions.push(tx));
+                    }
+                    _ => warn!("Received an invalid rawtx zmq notification"),
+                },
+                Some(b"hashblock") => {
+                    let _ = state_cloned.safe_lock(|s| s.resync = true);
+                }
+                _ => (),
+            }
+        });
+        Ok(Self { rpc, state })
+    }
+}
+
+impl MempoolSource for ZmqSource {
+    fn entries(&self) -> SourceFuture<'_, HashMap<Txid, MempoolEntry>> {
+        Box::pin(async move {
+            let (resync, new_transactions) = self.state.safe_lock(|s| {
+                let resync = s.resync;
+                s.resync = false;
+                (resync, std::mem::take(&mut s.new_transactions))
+            })?;
+            if resync {
+                let entries = self.rpc.entries().await;
+                if entries.is_err() {
+                    let _ = self.state.safe_lock(|s| s.resync = true);
+                }
+                let entries = entries?;
+                self.state.safe_lock(|s| s.entries = entries.clone())?;
+                return Ok(entries);
+            }
+            let txids: Vec<Txid> = new_transactions.iter().map(|tx| tx.txid()).collect();
+            let mut new_entries = self.rpc.entries_of(&txids).await?;
+            for tx in new_transactions {
+                if let Some(entry) = new_entries.get_mut(&tx.txid()) {
+                    entry.tx = Some(tx);
+                }
+            }
+            Ok(self.state.safe_lock(|s| {
+                s.entries.extend(new_entries);
+                s.entries.clone()
+            })?)
+        })
+    }
+
+    fn transactions<'a>(&'a self, txids: &'a [Txid]) -> SourceFuture<'a, Vec<Transaction>> {
+        Box::pin(async move {
+            let (mut known, missing) = self.state.safe_lock(|s| {
+                let mut known = Vec::new();
+                let mut missing = Vec::new();
+                for txid in txids {
+                    match s.entries.get(txid).and_then(|entry| entry.tx.clone()) {
+                        Some(tx) => known.push(tx),
+                        None => missing.push(*txid),
+                    }
+                }
+                (known, missing)
+            })?;
+            known.extend(self.rpc.transactions(&missing).await?);
+            Ok(known)
+        })
+    }
+}
@@ -1,14 +1,18 @@
 pub mod error;
 pub mod job_declarator;
+pub mod job_state;
 pub mod mempool;
 pub mod status;
 
 use async_channel::{bounded, unbounded, Receiver, Sender};
 use error_handling::handle_result;
 use job_declarator::JobDeclarator;
-use mempool::error::JdsMempoolError;
+use job_state::{JobState, JobStateConfig};
+use mempool::{error::JdsMempoolError, source::MempoolSourceConfig};
+use metrics_sv2::Metrics;
 use roles_logic_sv2::utils::Mutex;
-use std::{ops::Sub, sync::Arc};
+use rpc_sv2::mini_rpc_client::Auth;
+use std::{ops::Sub, path::PathBuf, sync::Arc};
 use tokio::{select, task};
 use tracing::{error, info, warn};
 
@@ -20,7 +24,7 @@ use roles_logic_sv2::{
 use serde::Deserialize;
 use std::{
     convert::{TryFrom, TryInto},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use stratum_common::bitcoin::{Script, TxOut};
 
@@ -30,26 +34,50 @@ pub type EitherFrame = StandardEitherFrame<Message>;
 
 pub struct JobDeclaratorServer {
     config: Configuration,
+    metrics: Arc<Metrics>,
 }
 
 impl JobDeclaratorServer {
     pub fn new(config: Configuration) -> Self {
-        Self { config }
+        Self {
+            config,
+            metrics: Arc::new(Metrics::new()),
+        }
     }
     pub async fn start(&self) {
         let config = self.config.clone();
+        if let Some(metrics_address) = config.metrics_address.clone() {
+            let metrics = self.metrics.clone();
+            task::spawn(async move {
+                if let Err(e) = metrics_sv2::serve(metrics, metrics_address).await {
+                    error!("Metrics endpoint stopped: {}", e);
+                }
+            });
+        }
         let url = config.core_rpc_url.clone() + ":" + &config.core_rpc_port.clone().to_string();
-        let username = config.core_rpc_user.clone();
-        let password = config.core_rpc_pass.clone();
         // TODO should we manage what to do when the limit is reaced?
         let (new_block_sender, new_block_receiver): (Sender<String>, Receiver<String>) =
             bounded(10);
-        let mempool = Arc::new(Mutex::new(mempool::JDsMempool::new(
-            url.clone(),
-            username,
-            password,
-            new_block_receiver,
-        )));
+        let mut jds_mempool =
+            mempool::JDsMempool::new(url.clone(), config.core_rpc_auth(), new_block_receiver);
+        match config.mempool_source.build(jds_mempool.get_client()) {
+            Ok(Some(source)) => jds_mempool.set_source(source),
+            Ok(None) => (),
+            Err(err) => {
+                mempool::error::handle_error(&err);
+                return;
+            }
+        }
+        // tokens and declared jobs are shared by all the downstreams
+        let job_state = match JobState::new(&config.job_state) {
+            Ok(job_state) => Arc::new(Mutex::new(job_state)),
+            Err(err) => {
+                error!("Failed to load the job state: {}", err);
+                return;
+            }
+        };
+        let has_mempool_source = jds_mempool.has_source();
+        let mempool = Arc::new(Mutex::new(jds_mempool));
         let mempool_update_interval = config.mempool_update_interval;
         let mempool_cloned_ = mempool.clone();
         let (status_tx, status_rx) = unbounded();
@@ -57,12 +85,12 @@ impl JobDeclaratorServer {
         let mut last_empty_mempool_warning =
             std::time::Instant::now().sub(std::time::Duration::from_secs(60));
 
-        // TODO if the jd-server is launched with core_rpc_url empty, the following flow is never
-        // taken. Consequentally new_block_receiver in JDsMempool::on_submit is never read, possibly
-        // reaching the channel bound. The new_block_sender is given as input to JobDeclarator::start()
-        if url.contains("http") {
+        // aborted on shutdown
+        let mut tasks = Vec::new();
+
+        if has_mempool_source {
             let sender_update_mempool = sender.clone();
-            task::spawn(async move {
+            tasks.push(task::spawn(async move {
                 loop {
                     let update_mempool_result: Result<(), mempool::error::JdsMempoolError> =
                         mempool::JDsMempool::update_mempool(mempool_cloned_.clone()).await;
@@ -87,17 +115,40 @@ impl JobDeclaratorServer {
                                 mempool::error::handle_error(&err);
                                 handle_result!(sender_update_mempool, Err(err));
                             }
+                            JdsMempoolError::Source(_) => {
+                                mempool::error::handle_error(&err);
+                                handle_result!(sender_update_mempool, Err(err));
+                            }
                         }
                     }
                     tokio::time::sleep(mempool_update_interval).await;
                     // DO NOT REMOVE THIS LINE
                     //let _transactions = mempool::JDsMempool::_get_transaction_list(mempool_cloned_.clone());
                 }
-            });
+            }));
+        }
+
+        // TODO if the jd-server is launched with core_rpc_url empty, the following flow is never
+        // taken. Consequentally new_block_receiver in JDsMempool::on_submit is never read, possibly
+        // reaching the channel bound. The new_block_sender is given as input to JobDeclarator::start()
+        if url.contains("http") {
+            let mempool_cloned = mempool.clone();
+            let sender_update_chain_tip = sender.clone();
+            tasks.push(task::spawn(async move {
+                loop {
+                    if let Err(err) =
+                        mempool::JDsMempool::update_chain_tip(mempool_cloned.clone()).await
+                    {
+                        mempool::error::handle_error(&err);
+                        handle_result!(sender_update_chain_tip, Err(err));
+                    }
+                    tokio::time::sleep(mempool_update_interval).await;
+                }
+            }));
 
             let mempool_cloned = mempool.clone();
             let sender_submit_solution = sender.clone();
-            task::spawn(async move {
+            tasks.push(task::spawn(async move {
                 loop {
                     let result = mempool::JDsMempool::on_submit(mempool_cloned.clone()).await;
                     if let Err(err) = result {
@@ -117,23 +168,28 @@ impl JobDeclaratorServer {
                         }
                     }
                 }
-            });
+            }));
         };
 
         let cloned = config.clone();
         let mempool_cloned = mempool.clone();
         let (sender_add_txs_to_mempool, receiver_add_txs_to_mempool) = unbounded();
-        task::spawn(async move {
+        let metrics = self.metrics.clone();
+        // blocks still queued when the shutdown starts are submitted before exiting
+        let pending_blocks = new_block_sender.clone();
+        let listener = task::spawn(async move {
             JobDeclarator::start(
                 cloned,
                 sender,
                 mempool_cloned,
                 new_block_sender,
                 sender_add_txs_to_mempool,
+                job_state,
+                metrics,
             )
             .await
         });
-        task::spawn(async move {
+        tasks.push(task::spawn(async move {
             loop {
                 if let Ok(add_transactions_to_mempool) = receiver_add_txs_to_mempool.recv().await {
                     let mempool_cloned = mempool.clone();
@@ -154,14 +210,14 @@ impl JobDeclaratorServer {
                     });
                 }
             }
-        });
+        }));
 
         // Start the error handling loop
         // See `./status.rs` and `utils/error_handling` for information on how this operates
         loop {
             let task_status = select! {
                 task_status = status_rx.recv() => task_status,
-                interrupt_signal = tokio::signal::ctrl_c() => {
+                interrupt_signal = status::shutdown_signal() => {
                     match interrupt_signal {
                         Ok(()) => {
                             info!("Interrupt received");
@@ -196,6 +252,28 @@ impl JobDeclaratorServer {
                 }
             }
         }
+
+        info!("Shutting down");
+        listener.abort();
+        let deadline = Instant::now() + Duration::from_secs(config.shutdown_timeout_secs);
+        // the receiving side is dropped if the task submitting the blocks is gone
+        while Instant::now() < deadline && !pending_blocks.is_empty() && !pending_blocks.is_closed()
+        {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        if !pending_blocks.is_empty() {
+            warn!("{} blocks have not been submitted", pending_blocks.len());
+        }
+        for task in tasks {
+            task.abort();
+            if tokio::time::timeout_at(deadline.into(), task)
+                .await
+                .is_err()
+            {
+                warn!("Shutdown deadline expired before every task stopped");
+                break;
+            }
+        }
     }
 }
 
@@ -255,10 +333,28 @@ pub struct Configuration {
     pub coinbase_outputs: Vec<CoinbaseOutput>,
     pub core_rpc_url: String,
     pub core_rpc_port: u16,
+    #[serde(default)]
     pub core_rpc_user: String,
+    #[serde(default)]
     pub core_rpc_pass: String,
+    /// Cookie file of the node, used instead of `core_rpc_user` and `core_rpc_pass` when set
+    #[serde(default)]
+    pub core_rpc_cookie_file: Option<PathBuf>,
     #[serde(deserialize_with = "duration_from_toml")]
     pub mempool_update_interval: Duration,
+    /// Version bits that a declared job may change with respect to the node's block template
+    #[serde(default = "default_version_rolling_mask")]
+    pub version_rolling_mask: u32,
+    #[serde(default)]
+    pub mempool_source: MempoolSourceConfig,
+    #[serde(default)]
+    pub job_state: JobStateConfig,
+    /// Address where the Prometheus metrics are served, eg `0.0.0.0:9184`
+    #[serde(default)]
+    pub metrics_address: Option<String>,
+    /// Max number of seconds spent submitting the pending blocks when stopping
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -301,7 +397,20 @@ impl Configuration {
             core_rpc_port: core_rpc.port,
             core_rpc_user: core_rpc.user,
             core_rpc_pass: core_rpc.pass,
+            core_rpc_cookie_file: None,
             mempool_update_interval,
+            version_rolling_mask: default_version_rolling_mask(),
+            mempool_source: MempoolSourceConfig::default(),
+            job_state: JobStateConfig::default(),
+            metrics_address: None,
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+        }
+    }
+
+    pub fn core_rpc_auth(&self) -> Auth {
+        match &self.core_rpc_cookie_file {
+            Some(path) => Auth::cookie_file(path.clone()),
+            None => Auth::new(self.core_rpc_user.clone(), self.core_rpc_pass.clone()),
         }
     }
 }
@@ -310,6 +419,14 @@ fn default_true() -> bool {
     true
 }
 
+fn default_version_rolling_mask() -> u32 {
+    job_declarator::validation::DEFAULT_VERSION_ROLLING_MASK
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    10
+}
+
 fn duration_from_toml<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -356,7 +473,7 @@ mod tests {
         let config_path = config_path.to_str().unwrap();
 
         let settings = Config::builder()
-            .add_source(File::new(&config_path, FileFormat::Toml))
+            .add_source(File::new(config_path, FileFormat::Toml))
             .build()
             .expect("Failed to build config");
 
@@ -46,9 +46,11 @@ async fn send_status(
 ) -> error_handling::ErrorBranch {
     match sender {
         Sender::Downstream(tx) => match e {
-            JdsError::Sv2ProtocolError((id, Mining::OpenMiningChannelError(_))) => {
+            JdsError::Sv2ProtocolError(ref e)
+                if matches!(**e, (_, Mining::OpenMiningChannelError(_))) =>
+            {
                 tx.send(Status {
-                    state: State::DownstreamInstanceDropped(id),
+                    state: State::DownstreamInstanceDropped(e.0),
                 })
                 .await
                 .unwrap_or(());
@@ -94,6 +96,21 @@ async fn send_status(
     outcome
 }
 
+/// Resolves when the process is asked to stop, by Ctrl-C or, on unix, by SIGTERM
+pub async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res,
+            _ = sigterm.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
 // this is called by `error_handling::handle_result!`
 pub async fn handle_error(sender: &Sender, e: JdsError) -> error_handling::ErrorBranch {
     tracing::debug!("Error: {:?}", &e);
@@ -390,13 +407,13 @@ mod tests {
         let sender = Sender::Downstream(tx);
         let inner: [u8; 32] = rand::random();
         let value = inner.to_vec().try_into().unwrap();
-        let error = JdsError::Sv2ProtocolError((
+        let error = JdsError::Sv2ProtocolError(Box::new((
             12,
             Mining::OpenMiningChannelError(OpenMiningChannelError {
                 request_id: 1,
                 error_code: value,
             }),
-        ));
+        )));
         let error_string = "12";
         handle_error(&sender, error).await;
         match rx.recv().await {
@@ -1,14 +1,15 @@
-use std::{convert::TryInto, sync::Arc};
+use std::{convert::TryInto, sync::Arc, time::Duration};
 
 use async_channel::{Receiver, SendError, Sender};
-use tokio::{net::TcpListener, sync::oneshot::Receiver as TokioReceiver};
+use tokio::{net::TcpListener, sync::oneshot::Receiver as TokioReceiver, task::JoinHandle};
 use tracing::{info, warn};
 
 use codec_sv2::{StandardEitherFrame, StandardSv2Frame};
+use metrics_sv2::ChannelKind;
 use network_helpers_sv2::plain_connection_tokio::PlainConnection;
 use roles_logic_sv2::{
     common_messages_sv2::{SetupConnection, SetupConnectionSuccess},
-    common_properties::{CommonDownstreamData, IsDownstream, IsMiningDownstream},
+    common_properties::{CommonDownstreamData, IsDownstream, IsMiningDownstream, IsMiningUpstream},
     errors::Error,
     handlers::{
         common::{ParseDownstreamCommonMessages, SendTo as SendToCommon},
@@ -20,7 +21,10 @@ use roles_logic_sv2::{
     utils::Mutex,
 };
 
-use super::upstream_mining::{ProxyRemoteSelector, StdFrame as UpstreamFrame, UpstreamMiningNode};
+use super::{
+    upstream_mining::{ProxyRemoteSelector, StdFrame as UpstreamFrame, UpstreamMiningNode},
+    ShutdownConfig,
+};
 
 pub type Message = MiningDeviceMessages<'static>;
 pub type StdFrame = StandardSv2Frame<Message>;
@@ -37,6 +41,10 @@ pub struct DownstreamMiningNode {
     sender: Sender<EitherFrame>,
     pub status: DownstreamMiningNodeStatus,
     upstream: Option<Arc<Mutex<UpstreamMiningNode>>>,
+    // OpenStandardMiningChannel sent by the downstream, its user identity and nominal hashrate are
+    // reported in the metrics when the channel is opened and it is sent again when the upstream
+    // migrates to another endpoint
+    channel_request: Option<OpenStandardMiningChannel<'static>>,
 }
 
 #[derive(Debug)]
@@ -121,6 +129,25 @@ impl DownstreamMiningNodeStatus {
             DownstreamMiningNodeStatus::ChannelOpened(..) => panic!("Channel already opened"),
         }
     }
+
+    /// Goes back to paired returning the id of the channel that was opened, if any
+    fn close_channel(&mut self) -> Option<u32> {
+        match self {
+            DownstreamMiningNodeStatus::ChannelOpened(channel) => {
+                let (data, channel_id) = match channel {
+                    Channel::DownstreamHomUpstreamGroup {
+                        data, channel_id, ..
+                    } => (*data, *channel_id),
+                    Channel::DownstreamHomUpstreamExtended { data, channel_id } => {
+                        (*data, *channel_id)
+                    }
+                };
+                let _ = std::mem::replace(self, Self::Paired(data));
+                Some(channel_id)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl PartialEq for DownstreamMiningNode {
@@ -138,10 +165,43 @@ impl DownstreamMiningNode {
     pub fn open_channel_for_down_hom_up_group(&mut self, channel_id: u32, group_id: u32) {
         self.status
             .open_channel_for_down_hom_up_group(channel_id, group_id);
+        self.on_channel_opened(channel_id);
     }
     pub fn open_channel_for_down_hom_up_extended(&mut self, channel_id: u32, group_id: u32) {
         self.status
             .open_channel_for_down_hom_up_extended(channel_id, group_id);
+        self.on_channel_opened(channel_id);
+    }
+
+    fn on_channel_opened(&mut self, channel_id: u32) {
+        let (user_identity, hash_rate) = match &self.channel_request {
+            Some(req) => (
+                std::str::from_utf8(req.user_identity.as_ref())
+                    .unwrap_or("Unknown identity")
+                    .to_string(),
+                req.nominal_hash_rate,
+            ),
+            None => Default::default(),
+        };
+        super::METRICS.channel_opened(channel_id, ChannelKind::Standard, &user_identity, hash_rate);
+    }
+
+    /// Closes the channel opened with the upstream, the downstream stays connected and can open it
+    /// again with [`DownstreamMiningNode::channel_request`]
+    pub fn close_channel(&mut self) {
+        if let Some(channel_id) = self.status.close_channel() {
+            super::METRICS.channel_closed(channel_id);
+        }
+    }
+
+    /// The OpenStandardMiningChannel received from the downstream, if any
+    pub fn channel_request(&self) -> Option<OpenStandardMiningChannel<'static>> {
+        self.channel_request.clone()
+    }
+
+    /// Pairs the downstream with another upstream, the channel must be closed before
+    pub fn set_upstream(&mut self, upstream: Arc<Mutex<UpstreamMiningNode>>) {
+        self.upstream = Some(upstream);
     }
 
     pub fn new(receiver: Receiver<EitherFrame>, sender: Sender<EitherFrame>, id: u32) -> Self {
@@ -151,6 +211,7 @@ impl DownstreamMiningNode {
             status: DownstreamMiningNodeStatus::Initializing,
             upstream: None,
             id,
+            channel_request: None,
         }
     }
 
@@ -174,6 +235,7 @@ impl DownstreamMiningNode {
                 .await
                 .unwrap();
             }
+            super::METRICS.downstream_connected();
             let receiver = self_mutex
                 .safe_lock(|self_| self_.receiver.clone())
                 .unwrap();
@@ -276,15 +338,50 @@ impl DownstreamMiningNode {
         }
     }
 
+    /// Asks the downstream to connect to another endpoint, returns false if the connection is
+    /// already closed
+    pub async fn reconnect(self_mutex: Arc<Mutex<Self>>, reconnect: Reconnect<'static>) -> bool {
+        let message = MiningDeviceMessages::Mining(Mining::Reconnect(reconnect));
+        let frame: StdFrame = message.try_into().unwrap();
+        let sender = self_mutex.safe_lock(|self_| self_.sender.clone()).unwrap();
+        sender.send(frame.into()).await.is_ok()
+    }
+
+    /// Drops the connection with the downstream
+    pub fn disconnect(self_mutex: Arc<Mutex<Self>>) {
+        self_mutex.safe_lock(|self_| self_.sender.close()).unwrap();
+        Self::exit(self_mutex);
+    }
+
     pub fn exit(self_: Arc<Mutex<Self>>) {
-        if let Some(up) = self_.safe_lock(|s| s.upstream.clone()).unwrap() {
-            UpstreamMiningNode::remove_dowstream(up, &self_);
+        let upstream = self_.safe_lock(|s| s.upstream.clone()).unwrap();
+        if let Some(up) = &upstream {
+            UpstreamMiningNode::remove_dowstream(up.clone(), &self_);
         };
-        self_
+        let hash_rate = self_
             .safe_lock(|s| {
-                s.receiver.close();
+                // exit is called both by the upstream and when the receiver is closed, only the
+                // first call is accounted
+                if s.receiver.close() {
+                    super::METRICS.downstream_disconnected();
+                    if let DownstreamMiningNodeStatus::ChannelOpened(channel) = &s.status {
+                        let channel_id = match channel {
+                            Channel::DownstreamHomUpstreamGroup { channel_id, .. } => channel_id,
+                            Channel::DownstreamHomUpstreamExtended { channel_id, .. } => channel_id,
+                        };
+                        super::METRICS.channel_closed(*channel_id);
+                    }
+                    s.channel_request
+                        .as_ref()
+                        .map(|r| r.nominal_hash_rate as u64)
+                } else {
+                    None
+                }
             })
             .unwrap();
+        if let (Some(up), Some(hash_rate)) = (upstream, hash_rate) {
+            up.safe_lock(|u| u.remove_hash_rate(hash_rate)).unwrap();
+        }
     }
 }
 
@@ -316,6 +413,7 @@ impl
         req: OpenStandardMiningChannel,
         up: Option<Arc<Mutex<UpstreamMiningNode>>>,
     ) -> Result<SendTo<UpstreamMiningNode>, Error> {
+        self.channel_request = Some(req.clone().into_static());
         let channel_id = up
             .as_ref()
             .expect("No upstream initialized")
@@ -326,6 +424,7 @@ impl
         up.as_ref()
             .expect("No upstream initialized")
             .safe_lock(|up| {
+                up.add_hash_rate(req.nominal_hash_rate as u64);
                 if up.channel_kind.is_extended() {
                     let messages = up.open_standard_channel_down(
                         req.request_id.as_u32(),
@@ -373,7 +472,16 @@ impl
         // done by GroupChannel not here
         match &self.status {
             DownstreamMiningNodeStatus::Initializing => todo!(),
-            DownstreamMiningNodeStatus::Paired(_) => todo!(),
+            // The channel is closed while the upstream migrates to another endpoint, the share
+            // belongs to a job of the previous upstream
+            DownstreamMiningNodeStatus::Paired(_) => {
+                let error = SubmitSharesError {
+                    channel_id: m.channel_id,
+                    sequence_number: m.sequence_number,
+                    error_code: "stale-share".to_string().into_bytes().try_into().unwrap(),
+                };
+                Ok(SendTo::Respond(Mining::SubmitSharesError(error)))
+            }
             DownstreamMiningNodeStatus::ChannelOpened(Channel::DownstreamHomUpstreamGroup {
                 ..
             }) => {
@@ -440,11 +548,14 @@ impl
 pub async fn listen_for_downstream_mining(
     listener: TcpListener,
     mut shutdown_rx: TokioReceiver<()>,
+    shutdown_config: ShutdownConfig,
 ) {
     let mut ids = roles_logic_sv2::utils::Id::new();
+    let mut downstreams: Vec<(Arc<Mutex<DownstreamMiningNode>>, JoinHandle<()>)> = Vec::new();
     loop {
         tokio::select! {
             accept_result = listener.accept() => {
+                downstreams.retain(|(_, handle)| !handle.is_finished());
                 let (stream, _) = accept_result.expect("failed to accept downstream connection");
                 let (receiver, sender): (Receiver<EitherFrame>, Sender<EitherFrame>) =
                     PlainConnection::new(stream).await;
@@ -468,7 +579,8 @@ pub async fn listen_for_downstream_mining(
 
                 if let SendToCommon::RelayNewMessageToRemote(_, relay_msg) = common_msg {
                     if let roles_logic_sv2::parsers::CommonMessages::SetupConnectionSuccess(setup_msg) = relay_msg {
-                        DownstreamMiningNode::start(node, setup_msg).await;
+                        let handle = tokio::spawn(DownstreamMiningNode::start(node.clone(), setup_msg));
+                        downstreams.push((node, handle));
                     }
                 } else {
                     warn!("Received unexpected message from downstream");
@@ -476,12 +588,50 @@ pub async fn listen_for_downstream_mining(
             }
             _ = &mut shutdown_rx => {
                 info!("Closing listener");
+                drain_downstreams(downstreams, &shutdown_config).await;
                 return;
             }
         }
     }
 }
 
+/// Asks the downstreams to reconnect to the fallback endpoint, when there is one, and gives them
+/// `timeout_secs` to leave. The connections still open after that are dropped.
+async fn drain_downstreams(
+    downstreams: Vec<(Arc<Mutex<DownstreamMiningNode>>, JoinHandle<()>)>,
+    config: &ShutdownConfig,
+) {
+    let mut deadline = tokio::time::Instant::now();
+    if let Some(fallback_host) = &config.fallback_host {
+        match fallback_host.clone().into_bytes().try_into() {
+            Ok(new_host) => {
+                let reconnect = Reconnect {
+                    new_host,
+                    new_port: config.fallback_port,
+                };
+                let mut reconnected = 0;
+                for (downstream, _) in &downstreams {
+                    if DownstreamMiningNode::reconnect(downstream.clone(), reconnect.clone()).await
+                    {
+                        reconnected += 1;
+                    }
+                }
+                info!(
+                    "Asked {} downstreams to reconnect to {}:{}",
+                    reconnected, fallback_host, config.fallback_port
+                );
+                deadline += Duration::from_secs(config.timeout_secs);
+            }
+            Err(e) => warn!("Invalid fallback host {}: {:?}", fallback_host, e),
+        }
+    }
+    for (downstream, handle) in downstreams {
+        if tokio::time::timeout_at(deadline, handle).await.is_err() {
+            DownstreamMiningNode::disconnect(downstream);
+        }
+    }
+}
+
 impl IsDownstream for DownstreamMiningNode {
     fn get_downstream_mining_data(&self) -> CommonDownstreamData {
         match self.status {
@@ -498,4 +648,10 @@ impl IsDownstream for DownstreamMiningNode {
         }
     }
 }
-impl IsMiningDownstream for DownstreamMiningNode {}
+impl IsMiningDownstream for DownstreamMiningNode {
+    fn paired_upstream_id(&self) -> Option<u32> {
+        self.upstream
+            .as_ref()
+            .map(|up| up.safe_lock(|up| up.get_id()).unwrap())
+    }
+}
@@ -1,11 +1,13 @@
 pub mod downstream_mining;
 pub mod error;
 pub mod upstream_mining;
+pub mod upstream_selection;
 
-use once_cell::sync::OnceCell;
+use metrics_sv2::Metrics;
+use once_cell::sync::{Lazy, OnceCell};
 use roles_logic_sv2::{
     routing_logic::{CommonRoutingLogic, MiningProxyRoutingLogic, MiningRoutingLogic},
-    selectors::GeneralMiningSelector,
+    selectors::{GeneralMiningSelector, UpstreamSelectionStrategy},
     utils::{GroupId, Id, Mutex},
 };
 use serde::Deserialize;
@@ -26,6 +28,8 @@ type RLogic = MiningProxyRoutingLogic<
 /// So it make sense to use shared mutable memory to lower the complexity of the codebase and to
 /// have some performance gain.
 pub static ROUTING_LOGIC: OnceCell<Mutex<RLogic>> = OnceCell::new();
+/// Metrics of the proxy, served at `metrics_address` when it is configured
+pub static METRICS: Lazy<Arc<Metrics>> = Lazy::new(|| Arc::new(Metrics::new()));
 static MIN_EXTRANONCE_SIZE: u16 = 6;
 static EXTRANONCE_RANGE_1_LENGTH: usize = 4;
 
@@ -43,6 +47,14 @@ pub async fn initialize_upstreams(min_version: u16, max_version: u16) {
         .unwrap();
 }
 
+fn add_upstream(upstream: Arc<Mutex<UpstreamMiningNode>>) {
+    ROUTING_LOGIC
+        .get()
+        .expect("BUG: ROUTING_LOGIC has not been set yet")
+        .safe_lock(|rl| rl.upstream_selector.upstreams.push(upstream))
+        .unwrap();
+}
+
 fn remove_upstream(id: u32) {
     let upstreams = ROUTING_LOGIC
         .get()
@@ -88,6 +100,9 @@ pub struct UpstreamMiningValues {
     port: u16,
     pub_key: key_utils::Secp256k1PublicKey,
     channel_kind: ChannelKind,
+    /// Percentage of the downstream hashrate sent to this upstream with the `Weighted` strategy
+    #[serde(default)]
+    weight: Option<f32>,
 }
 
 #[derive(Debug, Deserialize, Clone, Copy)]
@@ -106,6 +121,99 @@ pub struct Configuration {
     downstream_share_per_minute: f32,
     expected_total_downstream_hr: f32,
     reconnect: bool,
+    /// Address where the Prometheus metrics are served, eg `0.0.0.0:9184`
+    #[serde(default)]
+    pub metrics_address: Option<String>,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub selection: SelectionConfig,
+}
+
+/// How the downstreams are split between the upstreams
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub enum SelectionStrategy {
+    /// The upstream with less hashrate
+    LeastLoaded,
+    /// The first available upstream in the order of `upstreams`
+    Priority,
+    /// Split by the `weight` of each upstream
+    Weighted,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SelectionConfig {
+    pub strategy: SelectionStrategy,
+    /// Seconds between two attempts to connect again to the unavailable upstreams
+    pub reprobe_interval_secs: u64,
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        Self {
+            strategy: SelectionStrategy::LeastLoaded,
+            reprobe_interval_secs: 30,
+        }
+    }
+}
+
+impl SelectionConfig {
+    /// The id of each upstream is its index in `upstreams`
+    pub fn strategy(&self, upstreams: &[UpstreamMiningValues]) -> UpstreamSelectionStrategy {
+        match self.strategy {
+            SelectionStrategy::LeastLoaded => UpstreamSelectionStrategy::LeastLoaded,
+            SelectionStrategy::Priority => {
+                UpstreamSelectionStrategy::Priority((0..upstreams.len() as u32).collect())
+            }
+            SelectionStrategy::Weighted => UpstreamSelectionStrategy::Weighted(
+                upstreams
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, upstream)| {
+                        upstream.weight.map(|weight| (index as u32, weight))
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// What the proxy does with its downstreams when it is stopped
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// Host that the downstreams are asked to reconnect to, when not set they are only
+    /// disconnected
+    pub fallback_host: Option<String>,
+    pub fallback_port: u16,
+    /// Max number of seconds that the downstreams have to leave after the `Reconnect`
+    pub timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            fallback_host: None,
+            fallback_port: 34255,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Resolves when the process is asked to stop, by Ctrl-C or, on unix, by SIGTERM
+pub async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res,
+            _ = sigterm.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
 }
 pub async fn initialize_r_logic(
     upstreams: &[UpstreamMiningValues],
@@ -138,7 +246,8 @@ pub async fn initialize_r_logic(
 
         upstream_mining_nodes.push(upstream);
     }
-    let upstream_selector = GeneralMiningSelector::new(upstream_mining_nodes);
+    let mut upstream_selector = GeneralMiningSelector::new(upstream_mining_nodes);
+    upstream_selector.set_strategy(config.selection.strategy(upstreams));
     MiningProxyRoutingLogic {
         upstream_selector,
         downstream_id_generator: Id::new(),
@@ -1,7 +1,12 @@
 #![allow(dead_code)]
 
 use core::convert::TryInto;
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_channel::{Receiver, SendError, Sender};
 use async_recursion::async_recursion;
@@ -34,7 +39,7 @@ use stratum_common::bitcoin::TxOut;
 
 use super::{
     downstream_mining::{Channel, DownstreamMiningNode, StdFrame as DownstreamFrame},
-    EXTRANONCE_RANGE_1_LENGTH,
+    EXTRANONCE_RANGE_1_LENGTH, METRICS,
 };
 
 pub type Message = PoolMessages<'static>;
@@ -189,6 +194,12 @@ pub struct UpstreamMiningNode {
         HashMap<u32, Vec<(Arc<Mutex<DownstreamMiningNode>>, u32)>, BuildNoHashHasher<u32>>,
     downstream_hash_rate: f32,
     reconnect: bool,
+    // Endpoint received in a Reconnect, the connection is moved there after that the message has
+    // been handled
+    pending_reconnect: Option<(String, u16)>,
+    // Incremented each time that the connection is replaced after a Reconnect, so that the task
+    // relaying the messages of the previous connection do not treat its closing as a failure
+    connection_generation: u32,
 }
 
 /// It assume that endpoint NEVER change flags and version!
@@ -230,6 +241,8 @@ impl UpstreamMiningNode {
             job_up_to_down_ids: HashMap::with_hasher(BuildNoHashHasher::default()),
             downstream_hash_rate,
             reconnect,
+            pending_reconnect: None,
+            connection_generation: 0,
         }
     }
     fn on_p_hash(
@@ -296,7 +309,10 @@ impl UpstreamMiningNode {
                     Self::connect(self_mutex.clone()).await.unwrap();
                     // It assume that enpoint NEVER change flags and version!
                     match Self::setup_connection(self_mutex).await {
-                        Ok(()) => Ok(()),
+                        Ok(()) => {
+                            METRICS.upstream_reconnected();
+                            Ok(())
+                        }
                         Err(()) => panic!(),
                     }
                 }
@@ -402,7 +418,7 @@ impl UpstreamMiningNode {
                     .unwrap();
                 Self::send(self_mutex.clone(), frame)
                     .await
-                    .map_err(|e| (error!("Failed to send {:?}", e)))?;
+                    .map_err(|e| error!("Failed to send {:?}", e))?;
 
                 let cloned = self_mutex.clone();
                 let mut response = task::spawn(async { Self::receive(cloned).await })
@@ -431,6 +447,7 @@ impl UpstreamMiningNode {
         //_downstreams: HashMap<u32, Downstream>,
         receiver: Receiver<EitherFrame>,
     ) {
+        let generation = self_.safe_lock(|s| s.connection_generation).unwrap();
         task::spawn(async move {
             loop {
                 if let Ok(message) = receiver.recv().await {
@@ -438,7 +455,9 @@ impl UpstreamMiningNode {
                     let incoming: StdFrame = m;
                     Self::next(self_.clone(), incoming).await;
                 } else {
-                    Self::exit(self_);
+                    if self_.safe_lock(|s| s.connection_generation).unwrap() == generation {
+                        Self::exit(self_);
+                    }
                     break;
                 }
             }
@@ -493,22 +512,44 @@ impl UpstreamMiningNode {
             }
             DownstreamMiningNode::exit(d);
         }
+        let flags = self_
+            .safe_lock(|s| {
+                let flags = s.sv2_connection.map(|c| c.setup_connection_flags);
+                s.reset();
+                flags
+            })
+            .unwrap();
         if self_.safe_lock(|s| s.reconnect).unwrap() {
-            self_.safe_lock(|s| s.connection = None).unwrap();
-            let flags = self_
-                .safe_lock(|s| s.sv2_connection.unwrap().setup_connection_flags)
-                .unwrap();
-            self_.safe_lock(|s| s.sv2_connection = None).unwrap();
-            self_.safe_lock(|s| s.channel_kind.reset()).unwrap();
             tokio::task::spawn(async move {
                 tokio::time::sleep(std::time::Duration::from_secs(10)).await;
-                Self::setup_flag_and_version(self_, Some(flags), 2, 2)
+                Self::setup_flag_and_version(self_, flags, 2, 2)
                     .await
                     .unwrap();
+                METRICS.upstream_reconnected();
             });
         }
     }
 
+    /// Drops the connection and the state related to it, so that the node can connect again from
+    /// scratch. The hashrate of the downstreams is kept as they can stay with the node.
+    pub fn reset(&mut self) {
+        self.connection_generation = self.connection_generation.wrapping_add(1);
+        if let Some(connection) = self.connection.take() {
+            connection.sender.close();
+            connection.receiver.close();
+        }
+        self.sv2_connection = None;
+        self.channel_kind.reset();
+        self.job_up_to_down_ids.clear();
+        self.request_id_mapper = RequestIdMapper::new();
+        self.downstream_selector = ProxyRemoteSelector::new();
+    }
+
+    /// Downstreams with a channel opened with the node
+    pub fn get_downstreams(&self) -> Vec<Arc<Mutex<DownstreamMiningNode>>> {
+        self.downstream_selector.get_all_downstreams()
+    }
+
     async fn match_next_message(
         self_mutex: Arc<Mutex<Self>>,
         to_send: Result<SendTo<DownstreamMiningNode>, Error>,
@@ -575,6 +616,7 @@ impl UpstreamMiningNode {
     }
 
     pub async fn next(self_mutex: Arc<Mutex<Self>>, mut incoming: StdFrame) {
+        let received_at = Instant::now();
         let message_type = incoming.get_header().unwrap().msg_type();
         let payload = incoming.payload();
 
@@ -586,7 +628,149 @@ impl UpstreamMiningNode {
             payload,
             routing_logic,
         );
-        Self::match_next_message(self_mutex, next_message_to_send, incoming).await;
+        Self::match_next_message(self_mutex.clone(), next_message_to_send, incoming).await;
+        if let Some((new_host, new_port)) = self_mutex
+            .safe_lock(|s| s.pending_reconnect.take())
+            .unwrap()
+        {
+            task::spawn(Self::migrate(self_mutex, new_host, new_port));
+        }
+        match message_type {
+            const_sv2::MESSAGE_TYPE_NEW_EXTENDED_MINING_JOB => {
+                METRICS.observe_template_latency(received_at.elapsed())
+            }
+            const_sv2::MESSAGE_TYPE_MINING_SET_NEW_PREV_HASH => {
+                METRICS.observe_prev_hash_latency(received_at.elapsed())
+            }
+            _ => (),
+        }
+    }
+
+    /// Moves the connection to the endpoint received in a Reconnect. Downstreams stay connected,
+    /// their channels are closed and opened again with the new endpoint.
+    async fn migrate(self_mutex: Arc<Mutex<Self>>, new_host: String, new_port: u16) {
+        let current = self_mutex.safe_lock(|s| s.address).unwrap();
+        // An empty host and a 0 port mean that the current ones must be used
+        let port = match new_port {
+            0 => current.port(),
+            port => port,
+        };
+        let address = if new_host.is_empty() {
+            Some(SocketAddr::new(current.ip(), port))
+        } else {
+            match tokio::net::lookup_host((new_host.as_str(), port)).await {
+                Ok(mut addresses) => addresses.next(),
+                Err(e) => {
+                    error!("Impossible to resolve {}: {}", new_host, e);
+                    None
+                }
+            }
+        };
+        let address = match address {
+            Some(address) => address,
+            None => {
+                error!(
+                    "Ignoring Reconnect to {}:{}, staying connected to {}",
+                    new_host, port, current
+                );
+                return;
+            }
+        };
+        info!("Upstream {} asked to reconnect to {}", current, address);
+
+        // Channels are closed before resetting the upstream so that the shares received in the
+        // meantime are rejected by the downstreams instead of reaching the new upstream
+        let downstreams = self_mutex
+            .safe_lock(|s| s.downstream_selector.get_all_downstreams())
+            .unwrap();
+        for downstream in &downstreams {
+            downstream.safe_lock(|d| d.close_channel()).unwrap();
+        }
+        let flags = self_mutex
+            .safe_lock(|s| {
+                let flags = s.sv2_connection.map(|c| c.setup_connection_flags);
+                s.reset();
+                s.address = address;
+                flags
+            })
+            .unwrap();
+
+        if let Err(e) = Self::setup_flag_and_version(self_mutex.clone(), flags, 2, 2).await {
+            error!("Impossible to reconnect to {}: {:?}", address, e);
+            for downstream in downstreams {
+                DownstreamMiningNode::exit(downstream);
+            }
+            super::remove_upstream(self_mutex.safe_lock(|s| s.id).unwrap());
+            return;
+        }
+        METRICS.upstream_reconnected();
+        for downstream in downstreams {
+            Self::reopen_channel(self_mutex.clone(), downstream).await;
+        }
+    }
+
+    /// Sends again the OpenStandardMiningChannel of a downstream whose channel has been closed,
+    /// after a [`UpstreamMiningNode::migrate`] or when the downstream is moved to this node
+    pub async fn reopen_channel(
+        self_mutex: Arc<Mutex<Self>>,
+        downstream: Arc<Mutex<DownstreamMiningNode>>,
+    ) {
+        let mut request = match downstream.safe_lock(|d| d.channel_request()).unwrap() {
+            Some(request) => request,
+            // The downstream did not open a channel yet
+            None => return,
+        };
+        let (is_extended, request_id) = self_mutex
+            .safe_lock(|s| {
+                let request_id = s
+                    .request_id_mapper
+                    .on_open_channel(request.get_request_id_as_u32());
+                s.downstream_selector
+                    .on_open_standard_channel_request(request_id, downstream.clone());
+                (s.channel_kind.is_extended(), request_id)
+            })
+            .unwrap();
+        request.update_id(request_id);
+        if is_extended {
+            let messages = self_mutex
+                .safe_lock(|s| {
+                    let channel_id = s.channel_ids.safe_lock(|ids| ids.next()).unwrap();
+                    s.open_standard_channel_down(
+                        request_id,
+                        request.nominal_hash_rate,
+                        true,
+                        channel_id,
+                    )
+                })
+                .unwrap();
+            for message in messages {
+                if let Mining::OpenStandardMiningChannelSuccess(m) = &message {
+                    downstream
+                        .safe_lock(|d| {
+                            d.open_channel_for_down_hom_up_extended(
+                                m.channel_id,
+                                m.group_channel_id,
+                            )
+                        })
+                        .unwrap();
+                }
+                let message = MiningDeviceMessages::Mining(message);
+                let frame: DownstreamFrame = message.try_into().unwrap();
+                if DownstreamMiningNode::send(downstream.clone(), frame)
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        } else {
+            // The success is handled as for a new channel by
+            // handle_open_standard_mining_channel_success
+            let message = PoolMessages::Mining(Mining::OpenStandardMiningChannel(request));
+            if let Err(e) = Self::send(self_mutex, message.try_into().unwrap()).await {
+                error!("Impossible to reopen downstream channel: {:?}", e);
+            }
+        }
     }
 
     #[async_recursion]
@@ -765,6 +949,9 @@ impl UpstreamMiningNode {
             match share {
                 OnNewShare::SendErrorDownstream(e) => {
                     tracing::error!("Received invalid share");
+                    METRICS.share_rejected(
+                        std::str::from_utf8(e.error_code.as_ref()).unwrap_or("unknown"),
+                    );
                     Ok(Mining::SubmitSharesError(e))
                 }
                 OnNewShare::SendSubmitShareUpstream((s, _)) => match s {
@@ -777,6 +964,7 @@ impl UpstreamMiningNode {
                                 .await
                                 .unwrap();
                         });
+                        METRICS.share_accepted();
                         let success = SubmitSharesSuccess {
                             channel_id: share_.channel_id,
                             last_sequence_number: share_.sequence_number,
@@ -805,6 +993,8 @@ impl UpstreamMiningNode {
                                 .unwrap();
                             // The below channel should never be full is ok to block
                             sender.send_blocking(solution).unwrap();
+                            METRICS.share_accepted();
+                            METRICS.block_found();
 
                             let message = Mining::SubmitSharesExtended(s);
                             let message = PoolMessages::Mining(message);
@@ -836,6 +1026,7 @@ impl UpstreamMiningNode {
                 // second tuple elements can not be None but must be Some(template_id)
                 OnNewShare::ShareMeetBitcoinTarget(..) => unreachable!(),
                 OnNewShare::ShareMeetDownstreamTarget => {
+                    METRICS.share_accepted();
                     let success = SubmitSharesSuccess {
                         channel_id: share_.channel_id,
                         last_sequence_number: share_.sequence_number,
@@ -989,7 +1180,12 @@ impl
             .downstream_selector
             .downstream_from_channel_id(m.channel_id)
         {
-            Some(d) => Ok(SendTo::RelaySameMessageToRemote(d.clone())),
+            Some(d) => {
+                // shares relayed from a downstream in a group channel, the ones of an extended
+                // channel are accounted when they are checked by the proxy
+                METRICS.shares_accepted(m.new_submits_accepted_count);
+                Ok(SendTo::RelaySameMessageToRemote(d.clone()))
+            }
             None => {
                 info!("Share success");
                 Ok(SendTo::None(None))
@@ -999,8 +1195,15 @@ impl
 
     fn handle_submit_shares_error(
         &mut self,
-        _m: SubmitSharesError,
+        m: SubmitSharesError,
     ) -> Result<SendTo<DownstreamMiningNode>, Error> {
+        if self
+            .downstream_selector
+            .downstream_from_channel_id(m.channel_id)
+            .is_some()
+        {
+            METRICS.share_rejected(std::str::from_utf8(m.error_code.as_ref()).unwrap_or("unknown"));
+        }
         Ok(SendTo::None(None))
     }
 
@@ -1162,17 +1365,98 @@ impl
 
     fn handle_set_custom_mining_job_error(
         &mut self,
-        _m: SetCustomMiningJobError,
+        m: SetCustomMiningJobError,
     ) -> Result<SendTo<DownstreamMiningNode>, Error> {
-        todo!("560")
+        error!(
+            "Custom mining job {} rejected by upstream: {}",
+            m.request_id,
+            std::str::from_utf8(m.error_code.as_ref()).unwrap_or("unknown")
+        );
+        Ok(SendTo::None(None))
     }
 
-    fn handle_set_target(&mut self, _m: SetTarget) -> Result<SendTo<DownstreamMiningNode>, Error> {
-        todo!("570")
+    fn handle_set_target(&mut self, m: SetTarget) -> Result<SendTo<DownstreamMiningNode>, Error> {
+        match &mut self.channel_kind {
+            // Channels are opened by upstream, the proxy only relay the target to the downstream
+            // of the channel or to every downstream of the group
+            ChannelKind::Group(_) => {
+                if let Some(downstream) = self
+                    .downstream_selector
+                    .downstream_from_channel_id(m.channel_id)
+                {
+                    return Ok(SendTo::RelaySameMessageToRemote(downstream));
+                }
+                let downstreams = self
+                    .downstream_selector
+                    .get_downstreams_in_channel(m.channel_id)
+                    .ok_or(Error::NoDownstreamsConnected)?;
+                let mut res = vec![];
+                for downstream in downstreams {
+                    if let Channel::DownstreamHomUpstreamGroup { channel_id, .. } =
+                        downstream.safe_lock(|d| d.get_channel().clone()).unwrap()
+                    {
+                        let message = Mining::SetTarget(SetTarget {
+                            channel_id,
+                            maximum_target: m.maximum_target.clone().into_static(),
+                        });
+                        res.push(SendTo::RelayNewMessageToRemote(downstream.clone(), message));
+                    }
+                }
+                Ok(SendTo::Multiple(res))
+            }
+            // The target is the one of the extended channel opened by the proxy. Downstream
+            // channels with an harder target are updated to the new one, otherwise the shares
+            // that upstream accepts but that do not meet their target would be lost.
+            ChannelKind::Extended(Some(factory)) => {
+                if m.channel_id != factory.get_this_channel_id() {
+                    error!("Received SetTarget for unknown channel {}", m.channel_id);
+                    return Ok(SendTo::None(None));
+                }
+                let upstream_target: Target = m.maximum_target.clone().into();
+                factory.set_target(&mut upstream_target.clone());
+                let mut res = vec![];
+                for downstream in self.downstream_selector.get_all_downstreams() {
+                    let channel_id = match downstream
+                        .safe_lock(|d| match &d.status {
+                            super::downstream_mining::DownstreamMiningNodeStatus::ChannelOpened(
+                                Channel::DownstreamHomUpstreamExtended { channel_id, .. },
+                            ) => Some(*channel_id),
+                            _ => None,
+                        })
+                        .unwrap()
+                    {
+                        Some(channel_id) => channel_id,
+                        None => continue,
+                    };
+                    match factory.channel_target(channel_id) {
+                        Some(target) if target < upstream_target => {
+                            factory.update_target_for_channel(channel_id, upstream_target.clone());
+                            let message = Mining::SetTarget(SetTarget {
+                                channel_id,
+                                maximum_target: upstream_target.clone().into(),
+                            });
+                            res.push(SendTo::RelayNewMessageToRemote(downstream, message));
+                        }
+                        _ => (),
+                    }
+                }
+                Ok(SendTo::Multiple(res))
+            }
+            ChannelKind::Extended(None) => panic!("Factory not initialized"),
+        }
     }
 
-    fn handle_reconnect(&mut self, _m: Reconnect) -> Result<SendTo<DownstreamMiningNode>, Error> {
-        todo!("580")
+    fn handle_reconnect(&mut self, m: Reconnect) -> Result<SendTo<DownstreamMiningNode>, Error> {
+        let new_host = match std::str::from_utf8(m.new_host.as_ref()) {
+            Ok(new_host) => new_host.to_string(),
+            Err(_) => {
+                error!("Received Reconnect with an invalid host");
+                return Ok(SendTo::None(None));
+            }
+        };
+        // The connection can not be replaced from here, see UpstreamMiningNode::next
+        self.pending_reconnect = Some((new_host, m.new_port));
+        Ok(SendTo::None(None))
     }
 
     fn get_request_id_mapper(&mut self) -> Option<Arc<Mutex<RequestIdMapper>>> {
@@ -1208,7 +1492,9 @@ pub async fn scan(
         })
         .collect();
     for task in spawn_tasks {
-        task.await.unwrap();
+        if let Err(e) = task.await {
+            error!("{:?}", e)
+        }
     }
     res.safe_lock(|r| r.clone()).unwrap()
 }
@@ -1245,6 +1531,9 @@ impl IsMiningUpstream<DownstreamMiningNode, ProxyRemoteSelector> for UpstreamMin
     fn add_hash_rate(&mut self, to_add: u64) {
         self.total_hash_rate += to_add;
     }
+    fn remove_hash_rate(&mut self, to_remove: u64) {
+        self.total_hash_rate = self.total_hash_rate.saturating_sub(to_remove);
+    }
     fn get_opened_channels(&mut self) -> &mut Vec<UpstreamChannel> {
         todo!()
     }
@@ -1303,4 +1592,38 @@ mod tests {
         assert!(actual.channel_id_to_job_dispatcher.is_empty());
         assert_eq!(actual.request_id_mapper, RequestIdMapper::new());
     }
+
+    #[test]
+    fn reconnect_is_deferred_to_next() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
+        let mut upstream = UpstreamMiningNode::new(
+            0,
+            address,
+            [0; 32],
+            super::super::ChannelKind::Group,
+            Arc::new(Mutex::new(GroupId::new())),
+            Arc::new(Mutex::new(Id::new())),
+            10.0,
+            None,
+            None,
+            100_000.0,
+            false,
+        );
+        let reconnect = Reconnect {
+            new_host: "pool.example.com"
+                .to_string()
+                .into_bytes()
+                .try_into()
+                .unwrap(),
+            new_port: 3334,
+        };
+
+        let res = upstream.handle_reconnect(reconnect).unwrap();
+
+        assert!(matches!(res, SendTo::None(None)));
+        assert_eq!(
+            upstream.pending_reconnect,
+            Some(("pool.example.com".to_string(), 3334))
+        );
+    }
 }
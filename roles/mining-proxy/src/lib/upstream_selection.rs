@@ -0,0 +1,125 @@
+//! Keeps the downstreams split between the upstreams as the selection strategy says: the
+//! unavailable upstreams are probed again periodically and, when one of them comes back or the
+//! strategy changes, the downstream channels are moved between the upstreams.
+use std::{sync::Arc, time::Duration};
+
+use roles_logic_sv2::{
+    common_properties::IsMiningUpstream, selectors::UpstreamSelectionStrategy, utils::Mutex,
+};
+use tracing::info;
+
+use super::{
+    downstream_mining::DownstreamMiningNode,
+    upstream_mining::{scan, UpstreamMiningNode},
+    METRICS, ROUTING_LOGIC,
+};
+
+/// Tries to connect to the unavailable upstreams every `interval`, the ones that answer are used
+/// again for the new downstreams and the existing downstreams are rebalanced.
+pub async fn reprobe_upstreams(interval: Duration, min_version: u16, max_version: u16) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let unavailable = ROUTING_LOGIC
+            .get()
+            .expect("BUG: ROUTING_LOGIC has not been set yet")
+            .safe_lock(|r_logic| r_logic.upstream_selector.unavailable_upstreams())
+            .unwrap();
+        if unavailable.is_empty() {
+            continue;
+        }
+        for upstream in &unavailable {
+            upstream.safe_lock(|u| u.reset()).unwrap();
+        }
+        let available = scan(unavailable, min_version, max_version).await;
+        if available.is_empty() {
+            continue;
+        }
+        for upstream in available {
+            info!(
+                "Upstream {} is available again",
+                upstream.safe_lock(|u| u.get_id()).unwrap()
+            );
+            super::add_upstream(upstream);
+            METRICS.upstream_reconnected();
+        }
+        rebalance().await;
+    }
+}
+
+/// Replaces the selection strategy, eg with new weights, and rebalances the downstreams
+pub async fn update_strategy(strategy: UpstreamSelectionStrategy) {
+    ROUTING_LOGIC
+        .get()
+        .expect("BUG: ROUTING_LOGIC has not been set yet")
+        .safe_lock(|r_logic| r_logic.upstream_selector.set_strategy(strategy))
+        .unwrap();
+    rebalance().await;
+}
+
+/// Assigns again every downstream channel, the biggest first, as if the downstreams were
+/// connecting now, and moves the ones that end up on another upstream.
+pub async fn rebalance() {
+    let (upstreams, strategy) = ROUTING_LOGIC
+        .get()
+        .expect("BUG: ROUTING_LOGIC has not been set yet")
+        .safe_lock(|r_logic| {
+            (
+                r_logic.upstream_selector.upstreams.clone(),
+                r_logic.upstream_selector.strategy().clone(),
+            )
+        })
+        .unwrap();
+    let mut downstreams = vec![];
+    let mut loads = Vec::with_capacity(upstreams.len());
+    for upstream in &upstreams {
+        let (id, upstream_downstreams) = upstream
+            .safe_lock(|u| (u.get_id(), u.get_downstreams()))
+            .unwrap();
+        loads.push((id, 0));
+        for downstream in upstream_downstreams {
+            let hash_rate = downstream
+                .safe_lock(|d| d.channel_request().map(|r| r.nominal_hash_rate as u64))
+                .unwrap();
+            if let Some(hash_rate) = hash_rate {
+                downstreams.push((downstream, upstream.clone(), hash_rate));
+            }
+        }
+    }
+    downstreams.sort_by_key(|downstream| std::cmp::Reverse(downstream.2));
+
+    let mut moved = 0;
+    for (downstream, from, hash_rate) in downstreams {
+        let index = match strategy.pick(&loads) {
+            Some(index) => index,
+            None => return,
+        };
+        loads[index].1 += hash_rate;
+        if !Arc::ptr_eq(&from, &upstreams[index]) {
+            move_downstream(downstream, from, upstreams[index].clone(), hash_rate).await;
+            moved += 1;
+        }
+    }
+    if moved > 0 {
+        info!("Moved {} downstreams to rebalance the upstreams", moved);
+    }
+}
+
+/// Closes the channel of the downstream with `from` and opens it again with `to`, the downstream
+/// stays connected with the proxy
+async fn move_downstream(
+    downstream: Arc<Mutex<DownstreamMiningNode>>,
+    from: Arc<Mutex<UpstreamMiningNode>>,
+    to: Arc<Mutex<UpstreamMiningNode>>,
+    hash_rate: u64,
+) {
+    UpstreamMiningNode::remove_dowstream(from.clone(), &downstream);
+    from.safe_lock(|u| u.remove_hash_rate(hash_rate)).unwrap();
+    downstream
+        .safe_lock(|d| {
+            d.close_channel();
+            d.set_upstream(to.clone());
+        })
+        .unwrap();
+    to.safe_lock(|u| u.add_hash_rate(hash_rate)).unwrap();
+    UpstreamMiningNode::reopen_channel(to, downstream).await;
+}
@@ -18,7 +18,7 @@
 //! A Downstream that signal the incapacity to handle group channels can open only one channel.
 //!
 #![allow(special_module_name)]
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use tokio::{net::TcpListener, sync::oneshot};
 use tracing::{error, info};
@@ -92,6 +92,40 @@ mod args {
     }
 }
 
+fn load_config(config_path: &str) -> Result<Configuration, String> {
+    let settings = Config::builder()
+        .add_source(File::new(config_path, FileFormat::Toml))
+        .build()
+        .map_err(|e| format!("Failed to build config: {}", e))?;
+    settings
+        .try_deserialize::<Configuration>()
+        .map_err(|e| format!("Failed to deserialize config: {}", e))
+}
+
+/// On SIGHUP the selection strategy and the weights of the upstreams are read again from the
+/// config file and the downstreams are rebalanced. The list of upstreams must not change.
+#[cfg(unix)]
+async fn reload_selection_on_sighup(config_path: String) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(e) => {
+            error!("Unable to listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+    while sighup.recv().await.is_some() {
+        match load_config(&config_path) {
+            Ok(config) => {
+                info!("Reloading the upstream selection strategy");
+                let strategy = config.selection.strategy(&config.upstreams);
+                lib::upstream_selection::update_strategy(strategy).await;
+            }
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
 /// 1. the proxy scan all the upstreams and map them
 /// 2. downstream open a connection with proxy
 /// 3. downstream send SetupConnection
@@ -115,19 +149,10 @@ async fn main() {
 
     let config_path = args.config_path.to_str().expect("Invalid config path");
 
-    let config: Configuration = match Config::builder()
-        .add_source(File::new(config_path, FileFormat::Toml))
-        .build()
-    {
-        Ok(settings) => match settings.try_deserialize::<Configuration>() {
-            Ok(c) => c,
-            Err(e) => {
-                error!("Failed to deserialize config: {}", e);
-                return;
-            }
-        },
+    let config = match load_config(config_path) {
+        Ok(c) => c,
         Err(e) => {
-            error!("Failed to build config: {}", e);
+            error!("{}", e);
             return;
         }
     };
@@ -139,8 +164,23 @@ async fn main() {
         ))
         .expect("BUG: Failed to set ROUTING_LOGIC");
 
+    if let Some(metrics_address) = config.metrics_address.clone() {
+        tokio::task::spawn(async move {
+            if let Err(e) = metrics_sv2::serve(lib::METRICS.clone(), metrics_address).await {
+                error!("Metrics endpoint stopped: {}", e);
+            }
+        });
+    }
+
     info!("Initializing upstream scanner");
     lib::initialize_upstreams(config.min_supported_version, config.max_supported_version).await;
+    tokio::task::spawn(lib::upstream_selection::reprobe_upstreams(
+        Duration::from_secs(config.selection.reprobe_interval_secs),
+        config.min_supported_version,
+        config.max_supported_version,
+    ));
+    #[cfg(unix)]
+    tokio::task::spawn(reload_selection_on_sighup(config_path.to_string()));
     info!("Initializing downstream listener");
 
     let socket = SocketAddr::new(
@@ -155,10 +195,14 @@ async fn main() {
 
     let (_, res) = tokio::join!(
         // Wait for downstream connection
-        lib::downstream_mining::listen_for_downstream_mining(listener, shutdown_rx),
+        lib::downstream_mining::listen_for_downstream_mining(
+            listener,
+            shutdown_rx,
+            config.shutdown.clone()
+        ),
         // handle SIGTERM/QUIT / ctrl+c
         tokio::spawn(async {
-            tokio::signal::ctrl_c()
+            lib::shutdown_signal()
                 .await
                 .expect("Failed to listen to signals");
             let _ = shutdown_tx.send(());
@@ -0,0 +1,332 @@
+//! Local HTTP/JSON API to manage a running pool.
+//!
+//! Every request must carry `Authorization: Bearer <auth_token>`. The API exposes:
+//!
+//! * `GET /downstreams`: connected downstreams, their channels with target and hashrate
+//! * `POST /downstreams/<id>/disconnect`: drops the connection of a downstream
+//! * `POST /downstreams/<id>/ban`: drops every connection from the downstream's ip and refuses the
+//!   new ones
+//! * `GET /bans` and `DELETE /bans/<ip>`: lists and lifts the bans
+//! * `POST /downstreams/<id>/reconnect` and `POST /reconnect`: sends `Reconnect` to one or to every
+//!   downstream, the body is `{"new_host": "...", "new_port": 34254}`
+//! * `PUT /coinbase_outputs`: replaces the `coinbase_outputs`, the body has the same format of the
+//!   config file
+//! * `PUT /pool_signature`: replaces the pool signature, the body is `{"pool_signature": "..."}`
+//! * `GET /payouts` and `GET /balances`: the blocks recorded by the share ledger and the amounts
+//!   credited to every user by PPS
+//!
+//! Changes to the coinbase are used from the next template and are not written to the config
+//! file.
+
+use super::{
+    error::PoolError,
+    mining_pool::{CoinbaseOutput, Pool},
+};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    body::{Bytes, Incoming},
+    header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use roles_logic_sv2::utils::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, net::IpAddr, sync::Arc};
+use tokio::net::TcpListener;
+use tracing::{debug, info, warn};
+
+// Bodies are small JSON documents
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AdminApiConfig {
+    /// Address of the API, it should not be reachable from outside the pool's host
+    pub listen_address: String,
+    /// Token expected in the `Authorization: Bearer` header of every request
+    pub auth_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DownstreamInfo {
+    pub id: u32,
+    pub address: String,
+    pub header_only: bool,
+    pub channels: Vec<ChannelInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelInfo {
+    pub channel_id: u32,
+    pub user_identity: Option<String>,
+    /// Big endian hex of the channel target
+    pub target: Option<String>,
+    pub difficulty: Option<f64>,
+    /// Estimated hashrate in h/s
+    pub hashrate: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReconnectRequest {
+    /// Empty to reconnect to the same host
+    #[serde(default)]
+    new_host: String,
+    /// 0 to reconnect to the same port
+    #[serde(default)]
+    new_port: u16,
+}
+
+#[derive(Debug, Deserialize)]
+struct PoolSignatureRequest {
+    pool_signature: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum Route {
+    Downstreams,
+    Disconnect(u32),
+    Ban(u32),
+    Bans,
+    Unban(IpAddr),
+    Reconnect(Option<u32>),
+    CoinbaseOutputs,
+    PoolSignature,
+    Payouts,
+    Balances,
+}
+
+impl Route {
+    fn parse(method: &Method, path: &str) -> Option<Self> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (method, segments.as_slice()) {
+            (&Method::GET, ["downstreams"]) => Some(Route::Downstreams),
+            (&Method::POST, ["downstreams", id, "disconnect"]) => {
+                Some(Route::Disconnect(id.parse().ok()?))
+            }
+            (&Method::POST, ["downstreams", id, "ban"]) => Some(Route::Ban(id.parse().ok()?)),
+            (&Method::POST, ["downstreams", id, "reconnect"]) => {
+                Some(Route::Reconnect(Some(id.parse().ok()?)))
+            }
+            (&Method::POST, ["reconnect"]) => Some(Route::Reconnect(None)),
+            (&Method::GET, ["bans"]) => Some(Route::Bans),
+            (&Method::DELETE, ["bans", ip]) => Some(Route::Unban(ip.parse().ok()?)),
+            (&Method::PUT, ["coinbase_outputs"]) => Some(Route::CoinbaseOutputs),
+            (&Method::PUT, ["pool_signature"]) => Some(Route::PoolSignature),
+            (&Method::GET, ["payouts"]) => Some(Route::Payouts),
+            (&Method::GET, ["balances"]) => Some(Route::Balances),
+            _ => None,
+        }
+    }
+}
+
+/// Serves the admin API of `pool` until the listener fails
+pub async fn serve(pool: Arc<Mutex<Pool>>, config: AdminApiConfig) -> std::io::Result<()> {
+    let listener = TcpListener::bind(&config.listen_address).await?;
+    info!("Admin API listening on: {}", config.listen_address);
+    let auth_token = Arc::new(config.auth_token);
+    loop {
+        let (stream, address) = listener.accept().await?;
+        let pool = pool.clone();
+        let auth_token = auth_token.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let pool = pool.clone();
+                let auth_token = auth_token.clone();
+                async move { Ok::<_, Infallible>(respond(pool, &auth_token, request).await) }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Admin connection from {} closed: {}", address, e);
+            }
+        });
+    }
+}
+
+async fn respond(
+    pool: Arc<Mutex<Pool>>,
+    auth_token: &str,
+    request: Request<Incoming>,
+) -> Response<Full<Bytes>> {
+    if !is_authorized(request.headers().get(AUTHORIZATION), auth_token) {
+        return error_response(StatusCode::UNAUTHORIZED, "Invalid or missing token");
+    }
+    let route = match Route::parse(request.method(), request.uri().path()) {
+        Some(route) => route,
+        None => return error_response(StatusCode::NOT_FOUND, "Unknown endpoint"),
+    };
+    let body = match Limited::new(request.into_body(), MAX_BODY_SIZE)
+        .collect()
+        .await
+    {
+        Ok(body) => body.to_bytes(),
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    match handle(pool, route, &body).await {
+        Ok(response) => response,
+        Err(PoolError::PoisonLock(e)) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    }
+}
+
+async fn handle(
+    pool: Arc<Mutex<Pool>>,
+    route: Route,
+    body: &[u8],
+) -> Result<Response<Full<Bytes>>, PoolError> {
+    match route {
+        Route::Downstreams => Ok(json_response(&Pool::downstreams_info(&pool)?)),
+        Route::Disconnect(id) => match Pool::disconnect_downstream(&pool, id)? {
+            true => {
+                info!("Admin API: disconnected downstream {}", id);
+                Ok(json_response(&serde_json::json!({ "disconnected": id })))
+            }
+            false => Ok(unknown_downstream(id)),
+        },
+        Route::Ban(id) => match Pool::ban_downstream(&pool, id)? {
+            Some(ip) => {
+                warn!("Admin API: banned {} of downstream {}", ip, id);
+                Ok(json_response(&serde_json::json!({ "banned": ip })))
+            }
+            None => Ok(unknown_downstream(id)),
+        },
+        Route::Bans => Ok(json_response(&pool.safe_lock(|p| p.banned())?)),
+        Route::Unban(ip) => match pool.safe_lock(|p| p.unban(&ip))? {
+            true => {
+                info!("Admin API: lifted ban of {}", ip);
+                Ok(json_response(&serde_json::json!({ "unbanned": ip })))
+            }
+            false => Ok(error_response(
+                StatusCode::NOT_FOUND,
+                &format!("{} is not banned", ip),
+            )),
+        },
+        Route::Reconnect(id) => {
+            let request: ReconnectRequest = parse_body(body)?;
+            let reconnected =
+                Pool::reconnect_downstreams(pool, id, request.new_host, request.new_port).await?;
+            match (id, reconnected) {
+                (Some(id), 0) => Ok(unknown_downstream(id)),
+                _ => {
+                    info!("Admin API: sent Reconnect to {} downstreams", reconnected);
+                    Ok(json_response(
+                        &serde_json::json!({ "reconnected": reconnected }),
+                    ))
+                }
+            }
+        }
+        Route::CoinbaseOutputs => {
+            let coinbase_outputs: Vec<CoinbaseOutput> = parse_body(body)?;
+            pool.safe_lock(|p| p.update_coinbase_outputs(coinbase_outputs))??;
+            info!("Admin API: updated the coinbase outputs");
+            Ok(json_response(&serde_json::json!({ "updated": true })))
+        }
+        Route::PoolSignature => {
+            let request: PoolSignatureRequest = parse_body(body)?;
+            pool.safe_lock(|p| p.update_pool_signature(request.pool_signature))??;
+            info!("Admin API: updated the pool signature");
+            Ok(json_response(&serde_json::json!({ "updated": true })))
+        }
+        Route::Payouts => {
+            let share_ledger = pool.safe_lock(|p| p.share_ledger())?;
+            let payouts = share_ledger.safe_lock(|l| l.payouts().to_vec())?;
+            Ok(json_response(&payouts))
+        }
+        Route::Balances => {
+            let share_ledger = pool.safe_lock(|p| p.share_ledger())?;
+            let balances = share_ledger.safe_lock(|l| l.balances())?;
+            Ok(json_response(&balances))
+        }
+    }
+}
+
+fn is_authorized(header: Option<&HeaderValue>, auth_token: &str) -> bool {
+    let token = match header
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+    {
+        Some(token) => token,
+        None => return false,
+    };
+    // compares every byte so that the time taken does not leak how much of the token is right
+    token.len() == auth_token.len()
+        && token
+            .bytes()
+            .zip(auth_token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn parse_body<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, PoolError> {
+    serde_json::from_slice(body).map_err(|e| PoolError::Custom(format!("Invalid body: {}", e)))
+}
+
+fn json_response<T: Serialize>(value: &T) -> Response<Full<Bytes>> {
+    match serde_json::to_vec(value) {
+        Ok(body) => {
+            let mut response = Response::new(Full::new(Bytes::from(body)));
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            response
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn error_response(status: StatusCode, error: &str) -> Response<Full<Bytes>> {
+    let mut response = json_response(&serde_json::json!({ "error": error }));
+    *response.status_mut() = status;
+    response
+}
+
+fn unknown_downstream(id: u32) -> Response<Full<Bytes>> {
+    error_response(
+        StatusCode::NOT_FOUND,
+        &format!("Downstream {} is not connected", id),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_route_parse() {
+        assert_eq!(
+            Route::parse(&Method::GET, "/downstreams"),
+            Some(Route::Downstreams)
+        );
+        assert_eq!(
+            Route::parse(&Method::POST, "/downstreams/3/ban"),
+            Some(Route::Ban(3))
+        );
+        assert_eq!(
+            Route::parse(&Method::POST, "/downstreams/3/reconnect/"),
+            Some(Route::Reconnect(Some(3)))
+        );
+        assert_eq!(
+            Route::parse(&Method::DELETE, "/bans/10.0.0.1"),
+            Some(Route::Unban("10.0.0.1".parse().unwrap()))
+        );
+        assert_eq!(
+            Route::parse(&Method::GET, "/balances"),
+            Some(Route::Balances)
+        );
+        assert_eq!(Route::parse(&Method::GET, "/downstreams/3/ban"), None);
+        assert_eq!(Route::parse(&Method::POST, "/downstreams/x/ban"), None);
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        let header = HeaderValue::from_static("Bearer secret");
+        assert!(is_authorized(Some(&header), "secret"));
+        assert!(!is_authorized(Some(&header), "secreT"));
+        assert!(!is_authorized(Some(&header), "secret2"));
+        assert!(!is_authorized(None, "secret"));
+        let header = HeaderValue::from_static("secret");
+        assert!(!is_authorized(Some(&header), "secret"));
+    }
+}
@@ -0,0 +1,494 @@
+//! Submits the blocks found by the pool to a bitcoin node with `submitblock`.
+//!
+//! `SubmitSolution` only reaches the Template Provider, which may be a plain template source that
+//! never submits the block. When `block_submission` is configured the pool also asks the Template
+//! Provider for the transactions of the solved template (`RequestTransactionData`), assembles the
+//! block and submits it over RPC. The outcome (accepted, stale, rejected with the node's reason)
+//! is logged and recorded in the metrics.
+//!
+//! The coinbase of `SubmitSolution` has no witness. When it commits to the witnesses of the block
+//! the witness reserved value is added back to its input, taken from the `excess_data` of
+//! `RequestTransactionDataSuccess` or `[0; 32]` if the Template Provider does not send it.
+
+use super::error::{PoolError, PoolResult};
+use metrics_sv2::Metrics;
+use roles_logic_sv2::{
+    template_distribution_sv2::{
+        NewTemplate, RequestTransactionData, RequestTransactionDataError,
+        RequestTransactionDataSuccess, SetNewPrevHash, SubmitSolution,
+    },
+    utils::{u256_to_block_hash, Mutex},
+};
+use rpc_sv2::mini_rpc_client::{Auth, MiniRpcClient};
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use stratum_common::bitcoin::{
+    blockdata::block::BlockHeader,
+    consensus::encode::{deserialize, serialize},
+    hash_types::{BlockHash, TxMerkleNode},
+    hashes::Hash,
+    Block, Transaction, Witness,
+};
+use tokio::task;
+use tracing::{error, info, warn};
+
+/// Start of the script of the output with the witness commitment (BIP141)
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlockSubmissionConfig {
+    pub core_rpc_url: String,
+    pub core_rpc_port: u16,
+    #[serde(default)]
+    pub core_rpc_user: String,
+    #[serde(default)]
+    pub core_rpc_pass: String,
+    /// Cookie file of the node, used instead of `core_rpc_user` and `core_rpc_pass` when set
+    #[serde(default)]
+    pub core_rpc_cookie_file: Option<PathBuf>,
+}
+
+/// Outcome of a block submission
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockSubmission {
+    Accepted,
+    /// The block does not extend the node's tip anymore
+    Stale,
+    /// Reason given by the node, eg `high-hash` or `bad-txnmrklroot`
+    Rejected(String),
+    /// The block could not be built or the node could not be reached
+    Failed(&'static str),
+}
+
+impl BlockSubmission {
+    /// Maps the result of `submitblock`, `None` means that the block has been accepted
+    pub fn from_submit_block_result(result: Option<String>) -> Self {
+        match result.as_deref() {
+            // the Template Provider may have already submitted the block
+            None | Some("duplicate") => BlockSubmission::Accepted,
+            Some("inconclusive") | Some("duplicate-inconclusive") => BlockSubmission::Stale,
+            Some(reason) => BlockSubmission::Rejected(reason.to_string()),
+        }
+    }
+
+    fn result(&self) -> &'static str {
+        match self {
+            BlockSubmission::Accepted => "accepted",
+            BlockSubmission::Stale => "stale",
+            BlockSubmission::Rejected(_) => "rejected",
+            BlockSubmission::Failed(_) => "failed",
+        }
+    }
+
+    fn reason(&self) -> &str {
+        match self {
+            BlockSubmission::Accepted | BlockSubmission::Stale => "",
+            BlockSubmission::Rejected(reason) => reason.as_str(),
+            BlockSubmission::Failed(reason) => reason,
+        }
+    }
+}
+
+/// Header fields that the templates built on the same chain tip have in common
+#[derive(Debug, Clone, Copy)]
+struct ChainTip {
+    prev_hash: BlockHash,
+    n_bits: u32,
+}
+
+#[derive(Debug)]
+struct PendingBlock {
+    solution: SubmitSolution<'static>,
+    tip: ChainTip,
+}
+
+#[derive(Debug)]
+pub struct BlockSubmitter {
+    client: MiniRpcClient,
+    metrics: Arc<Metrics>,
+    chain_tip: Option<ChainTip>,
+    // template id -> tip the template builds on, only for the templates of the current tip
+    templates: HashMap<u64, ChainTip>,
+    // template id -> solution waiting for the transactions of the template
+    pending: HashMap<u64, PendingBlock>,
+}
+
+impl BlockSubmitter {
+    pub fn new(config: &BlockSubmissionConfig, metrics: Arc<Metrics>) -> Self {
+        let url = format!("{}:{}", config.core_rpc_url, config.core_rpc_port);
+        let auth = match &config.core_rpc_cookie_file {
+            Some(path) => Auth::cookie_file(path.clone()),
+            None => Auth::new(config.core_rpc_user.clone(), config.core_rpc_pass.clone()),
+        };
+        Self {
+            client: MiniRpcClient::new(url, auth),
+            metrics,
+            chain_tip: None,
+            templates: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Future templates get their tip from the `SetNewPrevHash` that activates them
+    pub fn on_new_template(&mut self, m: &NewTemplate) {
+        if m.future_template {
+            return;
+        }
+        if let Some(tip) = self.chain_tip {
+            self.templates.insert(m.template_id, tip);
+        }
+    }
+
+    pub fn on_new_prev_hash(&mut self, m: &SetNewPrevHash<'static>) {
+        let tip = ChainTip {
+            prev_hash: u256_to_block_hash(m.prev_hash.clone()),
+            n_bits: m.n_bits,
+        };
+        // templates of the previous tip can not produce a valid block anymore
+        self.templates.clear();
+        self.templates.insert(m.template_id, tip);
+        self.chain_tip = Some(tip);
+    }
+
+    /// Keeps the solution until the transactions of its template are received, returns the
+    /// request to send to the Template Provider. Solutions of templates that are not on the
+    /// current tip are recorded as stale.
+    pub fn on_solution(
+        &mut self,
+        solution: SubmitSolution<'static>,
+    ) -> Option<RequestTransactionData> {
+        let template_id = solution.template_id;
+        match self.templates.get(&template_id) {
+            Some(tip) => {
+                let tip = *tip;
+                self.pending
+                    .insert(template_id, PendingBlock { solution, tip });
+                Some(RequestTransactionData { template_id })
+            }
+            None => {
+                warn!(
+                    "Solution for template {} that is not on the current tip",
+                    template_id
+                );
+                record(&self.metrics, &BlockSubmission::Stale);
+                None
+            }
+        }
+    }
+
+    /// Builds the block of the solution waiting for these transactions and submits it from a
+    /// new task
+    pub fn on_transaction_data(
+        self_: Arc<Mutex<Self>>,
+        m: RequestTransactionDataSuccess<'static>,
+    ) -> PoolResult<()> {
+        let (pending, client, metrics) = self_.safe_lock(|s| {
+            (
+                s.pending.remove(&m.template_id),
+                s.client.clone(),
+                s.metrics.clone(),
+            )
+        })?;
+        let pending = match pending {
+            Some(pending) => pending,
+            None => {
+                warn!("Unexpected transaction data for template {}", m.template_id);
+                return Ok(());
+            }
+        };
+        match build_block(
+            &pending,
+            m.transaction_list.to_vec(),
+            m.excess_data.inner_as_ref(),
+        ) {
+            Ok(block) => {
+                task::spawn(async move {
+                    let outcome = submit(&client, &block).await;
+                    record(&metrics, &outcome);
+                });
+            }
+            Err(e) => {
+                error!(
+                    "Can not build the block of template {}: {}",
+                    m.template_id, e
+                );
+                record(&metrics, &BlockSubmission::Failed("invalid-block"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The Template Provider does not have the template anymore, the block is lost
+    pub fn on_transaction_data_error(&mut self, m: &RequestTransactionDataError) {
+        if self.pending.remove(&m.template_id).is_some() {
+            error!(
+                "Can not get the transactions of template {}: {}",
+                m.template_id,
+                String::from_utf8_lossy(m.error_code.inner_as_ref())
+            );
+            record(&self.metrics, &BlockSubmission::Failed("transaction-data"));
+        }
+    }
+}
+
+/// `transactions` are the serialized transactions of the template, without the coinbase.
+/// `excess_data` is the witness reserved value when the Template Provider sends it.
+fn build_block(
+    pending: &PendingBlock,
+    transactions: Vec<Vec<u8>>,
+    excess_data: &[u8],
+) -> PoolResult<Block> {
+    let solution = &pending.solution;
+    let mut txdata = Vec::with_capacity(transactions.len() + 1);
+    let mut coinbase: Transaction = deserialize(solution.coinbase_tx.inner_as_ref())
+        .map_err(|e| PoolError::Custom(format!("Invalid coinbase: {}", e)))?;
+    let has_witness_commitment = coinbase.output.iter().any(|output| {
+        output.script_pubkey.len() >= 38 && output.script_pubkey[..6] == WITNESS_COMMITMENT_HEADER
+    });
+    if has_witness_commitment {
+        let witness_reserved_value = match excess_data.len() {
+            32 => excess_data.to_vec(),
+            _ => vec![0; 32],
+        };
+        let input = coinbase
+            .input
+            .first_mut()
+            .ok_or_else(|| PoolError::Custom("Coinbase without inputs".to_string()))?;
+        if input.witness.is_empty() {
+            input.witness = Witness::from_vec(vec![witness_reserved_value]);
+        }
+    }
+    txdata.push(coinbase);
+    for transaction in transactions {
+        let transaction: Transaction = deserialize(&transaction)
+            .map_err(|e| PoolError::Custom(format!("Invalid transaction: {}", e)))?;
+        txdata.push(transaction);
+    }
+    let mut block = Block {
+        header: BlockHeader {
+            version: solution.version as i32,
+            prev_blockhash: pending.tip.prev_hash,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: solution.header_timestamp,
+            bits: pending.tip.n_bits,
+            nonce: solution.header_nonce,
+        },
+        txdata,
+    };
+    // txdata is never empty
+    block.header.merkle_root = block.compute_merkle_root().unwrap();
+    Ok(block)
+}
+
+async fn submit(client: &MiniRpcClient, block: &Block) -> BlockSubmission {
+    info!("Submitting block {} to the node", block.block_hash());
+    match client.submit_block(hex::encode(serialize(block))).await {
+        Ok(result) => BlockSubmission::from_submit_block_result(result),
+        Err(e) => {
+            error!("Can not submit block {}: {:?}", block.block_hash(), e);
+            BlockSubmission::Failed("rpc")
+        }
+    }
+}
+
+fn record(metrics: &Metrics, outcome: &BlockSubmission) {
+    match outcome {
+        BlockSubmission::Accepted => info!("Block accepted by the node"),
+        BlockSubmission::Stale => warn!("Stale block"),
+        BlockSubmission::Rejected(reason) => error!("Block rejected by the node: {}", reason),
+        BlockSubmission::Failed(reason) => error!("Block submission failed: {}", reason),
+    }
+    metrics.block_submitted(outcome.result(), outcome.reason());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use binary_sv2::{B064K, U256};
+    use std::convert::TryInto;
+    use stratum_common::bitcoin::{
+        OutPoint, PackedLockTime, Script, Sequence, TxIn, TxOut, Witness,
+    };
+
+    fn transaction(value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::from(vec![0x01, 0x01]),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    fn new_prev_hash(template_id: u64) -> SetNewPrevHash<'static> {
+        let prev_hash: U256 = [7_u8; 32].to_vec().try_into().unwrap();
+        let target: U256 = [0xff_u8; 32].to_vec().try_into().unwrap();
+        SetNewPrevHash {
+            template_id,
+            prev_hash,
+            header_timestamp: 1,
+            n_bits: 0x207fffff,
+            target,
+        }
+    }
+
+    fn new_template(template_id: u64, future_template: bool) -> NewTemplate<'static> {
+        NewTemplate {
+            template_id,
+            future_template,
+            version: 0x20000000,
+            coinbase_tx_version: 2,
+            coinbase_prefix: vec![].try_into().unwrap(),
+            coinbase_tx_input_sequence: u32::MAX,
+            coinbase_tx_value_remaining: 0,
+            coinbase_tx_outputs_count: 0,
+            coinbase_tx_outputs: vec![].try_into().unwrap(),
+            coinbase_tx_locktime: 0,
+            merkle_path: vec![].into(),
+        }
+    }
+
+    fn solution(template_id: u64) -> SubmitSolution<'static> {
+        solution_with_coinbase(template_id, &transaction(50))
+    }
+
+    fn solution_with_coinbase(template_id: u64, coinbase: &Transaction) -> SubmitSolution<'static> {
+        let coinbase_tx: B064K = serialize(coinbase).try_into().unwrap();
+        SubmitSolution {
+            template_id,
+            version: 0x20000000,
+            header_timestamp: 2,
+            header_nonce: 3,
+            coinbase_tx,
+        }
+    }
+
+    fn submitter() -> BlockSubmitter {
+        let config = BlockSubmissionConfig {
+            core_rpc_url: "http://127.0.0.1".to_string(),
+            core_rpc_port: 18332,
+            core_rpc_user: "user".to_string(),
+            core_rpc_pass: "pass".to_string(),
+            core_rpc_cookie_file: None,
+        };
+        BlockSubmitter::new(&config, Arc::new(Metrics::new()))
+    }
+
+    #[test]
+    fn test_submit_block_result() {
+        assert_eq!(
+            BlockSubmission::from_submit_block_result(None),
+            BlockSubmission::Accepted
+        );
+        assert_eq!(
+            BlockSubmission::from_submit_block_result(Some("duplicate".to_string())),
+            BlockSubmission::Accepted
+        );
+        assert_eq!(
+            BlockSubmission::from_submit_block_result(Some("inconclusive".to_string())),
+            BlockSubmission::Stale
+        );
+        assert_eq!(
+            BlockSubmission::from_submit_block_result(Some("high-hash".to_string())),
+            BlockSubmission::Rejected("high-hash".to_string())
+        );
+    }
+
+    #[test]
+    fn test_solutions_of_previous_tip_are_stale() {
+        let mut submitter = submitter();
+        submitter.on_new_template(&new_template(1, true));
+        submitter.on_new_prev_hash(&new_prev_hash(1));
+        submitter.on_new_template(&new_template(2, false));
+        assert!(submitter.on_solution(solution(1)).is_some());
+        assert!(submitter.on_solution(solution(2)).is_some());
+
+        submitter.on_new_template(&new_template(3, true));
+        submitter.on_new_prev_hash(&new_prev_hash(3));
+        assert!(submitter.on_solution(solution(2)).is_none());
+        assert!(submitter
+            .metrics
+            .encode()
+            .contains("sv2_blocks_submitted_total{result=\"stale\",reason=\"\"} 1"));
+    }
+
+    #[test]
+    fn test_build_block() {
+        let mut submitter = submitter();
+        submitter.on_new_prev_hash(&new_prev_hash(1));
+        let request = submitter.on_solution(solution(1)).unwrap();
+        let pending = submitter.pending.remove(&request.template_id).unwrap();
+
+        let block = build_block(&pending, vec![serialize(&transaction(1))], &[]).unwrap();
+
+        assert_eq!(block.txdata.len(), 2);
+        assert_eq!(block.txdata[0], transaction(50));
+        assert!(block.check_merkle_root());
+        assert_eq!(block.header.prev_blockhash, pending.tip.prev_hash);
+        assert_eq!(block.header.bits, 0x207fffff);
+        assert_eq!(block.header.time, 2);
+        assert_eq!(block.header.nonce, 3);
+    }
+
+    #[test]
+    fn test_build_block_with_witness_commitment() {
+        let mut segwit_transaction = transaction(1);
+        segwit_transaction.input[0].previous_output.vout = 0;
+        segwit_transaction.input[0].witness = Witness::from_vec(vec![vec![2; 72], vec![3; 33]]);
+        let witness_reserved_value = [5_u8; 32];
+        // the witness root does not depend on the coinbase
+        let witness_root = Block {
+            header: BlockHeader {
+                version: 0x20000000,
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 0,
+                bits: 0,
+                nonce: 0,
+            },
+            txdata: vec![transaction(50), segwit_transaction.clone()],
+        }
+        .witness_root()
+        .unwrap();
+        let commitment = Block::compute_witness_commitment(&witness_root, &witness_reserved_value);
+        let mut commitment_script = WITNESS_COMMITMENT_HEADER.to_vec();
+        commitment_script.extend_from_slice(&commitment[..]);
+        let mut coinbase = transaction(50);
+        coinbase.output.push(TxOut {
+            value: 0,
+            script_pubkey: Script::from(commitment_script),
+        });
+
+        let mut submitter = submitter();
+        submitter.on_new_prev_hash(&new_prev_hash(1));
+        let request = submitter
+            .on_solution(solution_with_coinbase(1, &coinbase))
+            .unwrap();
+        let pending = submitter.pending.remove(&request.template_id).unwrap();
+
+        let block = build_block(
+            &pending,
+            vec![serialize(&segwit_transaction)],
+            &witness_reserved_value,
+        )
+        .unwrap();
+        assert!(block.check_merkle_root());
+        assert!(block.check_witness_commitment());
+        assert_eq!(
+            block.txdata[0].input[0].witness.to_vec(),
+            vec![witness_reserved_value.to_vec()]
+        );
+
+        // without excess data the witness reserved value is all zeros
+        let block = build_block(&pending, vec![serialize(&segwit_transaction)], &[]).unwrap();
+        assert!(!block.check_witness_commitment());
+        assert_eq!(block.txdata[0].input[0].witness.to_vec(), vec![vec![0; 32]]);
+    }
+}
@@ -19,7 +19,7 @@ pub enum PoolError {
     PoisonLock(String),
     ComponentShutdown(String),
     Custom(String),
-    Sv2ProtocolError((u32, Mining<'static>)),
+    Sv2ProtocolError(Box<(u32, Mining<'static>)>),
 }
 
 impl std::fmt::Display for PoolError {
@@ -107,6 +107,6 @@ impl<T> From<PoisonError<MutexGuard<'_, T>>> for PoolError {
 
 impl From<(u32, Mining<'static>)> for PoolError {
     fn from(e: (u32, Mining<'static>)) -> Self {
-        PoolError::Sv2ProtocolError(e)
+        PoolError::Sv2ProtocolError(Box::new(e))
     }
 }
@@ -1,4 +1,5 @@
 use super::super::mining_pool::Downstream;
+use metrics_sv2::ChannelKind;
 use roles_logic_sv2::{
     errors::Error,
     handlers::mining::{ParseDownstreamMiningMessages, SendTo, SupportedChannelTypes},
@@ -35,6 +36,9 @@ impl ParseDownstreamMiningMessages<(), NullDownstreamMiningSelector, NoRouting>
         _m: Option<Arc<Mutex<()>>>,
     ) -> Result<SendTo<()>, Error> {
         let header_only = self.downstream_data.header_only;
+        let user_identity = std::str::from_utf8(incoming.user_identity.as_ref())
+            .unwrap_or("Unknown identity")
+            .to_string();
         let reposnses = self
             .channel_factory
             .safe_lock(|factory| {
@@ -57,6 +61,15 @@ impl ParseDownstreamMiningMessages<(), NullDownstreamMiningSelector, NoRouting>
             .map_err(|e| roles_logic_sv2::Error::PoisonLock(e.to_string()))??;
         let mut result = vec![];
         for response in reposnses {
+            if let Mining::OpenStandardMiningChannelSuccess(success) = &response {
+                self.register_channel(
+                    success.channel_id,
+                    ChannelKind::Standard,
+                    user_identity.clone(),
+                    incoming.nominal_hash_rate,
+                    &success.target,
+                )?;
+            }
             result.push(SendTo::Respond(response.into_static()))
         }
         Ok(SendTo::Multiple(result))
@@ -69,12 +82,26 @@ impl ParseDownstreamMiningMessages<(), NullDownstreamMiningSelector, NoRouting>
         let request_id = m.request_id;
         let hash_rate = m.nominal_hash_rate;
         let min_extranonce_size = m.min_extranonce_size;
+        let user_identity = std::str::from_utf8(m.user_identity.as_ref())
+            .unwrap_or("Unknown identity")
+            .to_string();
         let messages_res = self
             .channel_factory
             .safe_lock(|s| s.new_extended_channel(request_id, hash_rate, min_extranonce_size))
             .map_err(|e| roles_logic_sv2::Error::PoisonLock(e.to_string()))?;
         match messages_res {
             Ok(messages) => {
+                for message in &messages {
+                    if let Mining::OpenExtendedMiningChannelSuccess(success) = message {
+                        self.register_channel(
+                            success.channel_id,
+                            ChannelKind::Extended,
+                            user_identity.clone(),
+                            hash_rate,
+                            &success.target,
+                        )?;
+                    }
+                }
                 let messages = messages.into_iter().map(SendTo::Respond).collect();
                 Ok(SendTo::Multiple(messages))
             }
@@ -83,13 +110,15 @@ impl ParseDownstreamMiningMessages<(), NullDownstreamMiningSelector, NoRouting>
     }
 
     fn handle_update_channel(&mut self, m: UpdateChannel) -> Result<SendTo<()>, Error> {
-        let maximum_target =
-            roles_logic_sv2::utils::hash_rate_to_target(m.nominal_hash_rate.into(), 10.0)?;
-        self.channel_factory
-            .safe_lock(|s| s.update_target_for_channel(m.channel_id, maximum_target.clone().into()))
-            .unwrap_or_else(|_| {
-                std::process::exit(1);
-            });
+        let maximum_target = self
+            .channel_factory
+            .safe_lock(|s| s.update_channel_hashrate(m.channel_id, m.nominal_hash_rate))
+            .map_err(|e| roles_logic_sv2::Error::PoisonLock(e.to_string()))??;
+        self.metrics
+            .set_channel_hashrate(m.channel_id, m.nominal_hash_rate);
+        self.share_ledger
+            .safe_lock(|l| l.update_channel_target(m.channel_id, &maximum_target))
+            .map_err(|e| roles_logic_sv2::Error::PoisonLock(e.to_string()))?;
         let set_target = SetTarget {
             channel_id: m.channel_id,
             maximum_target,
@@ -108,6 +137,7 @@ impl ParseDownstreamMiningMessages<(), NullDownstreamMiningSelector, NoRouting>
         match res {
             Ok(res) => match res  {
                 roles_logic_sv2::channel_logic::channel_factory::OnNewShare::SendErrorDownstream(m) => {
+                    self.on_share_rejected(&m);
                     Ok(SendTo::Respond(Mining::SubmitSharesError(m)))
                 }
                 roles_logic_sv2::channel_logic::channel_factory::OnNewShare::SendSubmitShareUpstream(_) => unreachable!(),
@@ -124,22 +154,25 @@ impl ParseDownstreamMiningMessages<(), NullDownstreamMiningSelector, NoRouting>
                         // TODO we can block everything with the below (looks like this will infinite loop??)
                         while self.solution_sender.try_send(solution.clone()).is_err() {};
                     }
+                    let new_shares_sum = self.account_share(m.channel_id)?;
+                    self.on_block_found(t_id)?;
                     let success = SubmitSharesSuccess {
                         channel_id: m.channel_id,
                         last_sequence_number: m.sequence_number,
                         new_submits_accepted_count: 1,
-                        new_shares_sum: 0,
+                        new_shares_sum,
                     };
 
                     Ok(SendTo::Respond(Mining::SubmitSharesSuccess(success)))
 
                 },
                 roles_logic_sv2::channel_logic::channel_factory::OnNewShare::ShareMeetDownstreamTarget => {
-                 let success = SubmitSharesSuccess {
+                    let new_shares_sum = self.account_share(m.channel_id)?;
+                    let success = SubmitSharesSuccess {
                         channel_id: m.channel_id,
                         last_sequence_number: m.sequence_number,
                         new_submits_accepted_count: 1,
-                        new_shares_sum: 0,
+                        new_shares_sum,
                     };
                     Ok(SendTo::Respond(Mining::SubmitSharesSuccess(success)))
                 },
@@ -159,6 +192,7 @@ impl ParseDownstreamMiningMessages<(), NullDownstreamMiningSelector, NoRouting>
         match res {
             Ok(res) => match res  {
                 roles_logic_sv2::channel_logic::channel_factory::OnNewShare::SendErrorDownstream(m) => {
+                    self.on_share_rejected(&m);
                     Ok(SendTo::Respond(Mining::SubmitSharesError(m)))
                 }
                 roles_logic_sv2::channel_logic::channel_factory::OnNewShare::SendSubmitShareUpstream(_) => unreachable!(),
@@ -175,22 +209,25 @@ impl ParseDownstreamMiningMessages<(), NullDownstreamMiningSelector, NoRouting>
                         // TODO we can block everything with the below (looks like this will infinite loop??)
                         while self.solution_sender.try_send(solution.clone()).is_err() {};
                     }
+                    let new_shares_sum = self.account_share(m.channel_id)?;
+                    self.on_block_found(t_id)?;
                     let success = SubmitSharesSuccess {
                         channel_id: m.channel_id,
                         last_sequence_number: m.sequence_number,
                         new_submits_accepted_count: 1,
-                        new_shares_sum: 0,
+                        new_shares_sum,
                     };
 
                     Ok(SendTo::Respond(Mining::SubmitSharesSuccess(success)))
 
                 },
                 roles_logic_sv2::channel_logic::channel_factory::OnNewShare::ShareMeetDownstreamTarget => {
-                let success = SubmitSharesSuccess {
+                    let new_shares_sum = self.account_share(m.channel_id)?;
+                    let success = SubmitSharesSuccess {
                         channel_id: m.channel_id,
                         last_sequence_number: m.sequence_number,
                         new_submits_accepted_count: 1,
-                        new_shares_sum: 0,
+                        new_shares_sum,
                     };
                     Ok(SendTo::Respond(Mining::SubmitSharesSuccess(success)))
                 },
@@ -1,5 +1,8 @@
 use super::{
+    admin::{AdminApiConfig, ChannelInfo, DownstreamInfo},
+    block_submitter::BlockSubmissionConfig,
     error::{PoolError, PoolResult},
+    share_ledger::{target_to_difficulty, ShareLedger, ShareLedgerConfig},
     status,
 };
 use async_channel::{Receiver, Sender};
@@ -7,15 +10,19 @@ use binary_sv2::U256;
 use codec_sv2::{HandshakeRole, Responder, StandardEitherFrame, StandardSv2Frame};
 use error_handling::handle_result;
 use key_utils::{Secp256k1PublicKey, Secp256k1SecretKey, SignatureService};
+use metrics_sv2::{ChannelKind, Metrics};
 use network_helpers_sv2::noise_connection_tokio::Connection;
 use nohash_hasher::BuildNoHashHasher;
 use roles_logic_sv2::{
-    channel_logic::channel_factory::PoolChannelFactory,
+    channel_logic::{channel_factory::PoolChannelFactory, vardiff::VardiffConfig},
     common_properties::{CommonDownstreamData, IsDownstream, IsMiningDownstream},
     errors::Error,
     handlers::mining::{ParseDownstreamMiningMessages, SendTo},
     job_creator::JobsCreators,
-    mining_sv2::{ExtendedExtranonce, SetNewPrevHash as SetNPH},
+    mining_sv2::{
+        user_identity_from_tlv, ExtendedExtranonce, Reconnect, SetNewPrevHash as SetNPH,
+        SubmitSharesError,
+    },
     parsers::{Mining, PoolMessages},
     routing_logic::MiningRoutingLogic,
     template_distribution_sv2::{NewTemplate, SetNewPrevHash, SubmitSolution},
@@ -23,13 +30,15 @@ use roles_logic_sv2::{
 };
 use serde::Deserialize;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::{TryFrom, TryInto},
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
     sync::Arc,
+    time::{Duration, Instant},
 };
 use stratum_common::{
-    bitcoin::{Script, TxOut},
+    bitcoin::{consensus::encode::serialize, Address, Script, TxOut},
     secp256k1,
 };
 use tokio::{net::TcpListener, task};
@@ -60,10 +69,147 @@ pub fn get_coinbase_output(config: &Configuration) -> Result<Vec<TxOut>, Error>
     }
 }
 
+// The coinbase script is at most 100 bytes, 5 of them are used by the BIP34 block height and 32
+// by the extranonce
+const MAX_POOL_SIGNATURE_LEN: usize = 63;
+
+// How often the shutdown checks whether the downstreams left and the solutions were sent
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Weights of the pool outputs are expressed in millionths of the coinbase value
+const WEIGHT_SCALE: u64 = 1_000_000;
+
+// Biggest output paying a standard address (P2WSH or P2TR): 8 bytes of value, 1 byte of script
+// length and 34 bytes of script
+const MAX_PAYOUT_OUTPUT_SIZE: u32 = 43;
+
+/// Builds the outputs that the pool adds to every coinbase. The `value` of each output is its
+/// weight in the split of the template's `coinbase_tx_value_remaining` (see
+/// `roles_logic_sv2::job_creator::split_coinbase_value`):
+///
+/// 1. the optional `pool_fee_output` receives its `percentage` of the value
+/// 2. if `share_ledger.max_coinbase_payouts` is not 0, the users in the PPLNS window whose
+///    `user_identity` is a bitcoin address (optionally followed by `.worker_name`) are paid
+///    directly, at most `max_coinbase_payouts` of them, biggest first
+/// 3. what is left is split between the `coinbase_outputs` by their `percentage`. When no
+///    percentage is configured the first output receives everything.
+///
+/// When the outputs are built for `template_id` the PPLNS split is remembered by the ledger, so
+/// that the users paid by the coinbase are not credited again when a block is found.
+pub fn get_pool_outputs(
+    config: &Configuration,
+    ledger: &mut ShareLedger,
+    template_id: Option<u64>,
+) -> PoolResult<Vec<TxOut>> {
+    let mut outputs = get_coinbase_output(config)?;
+    let mut to_distribute = WEIGHT_SCALE;
+
+    let fee_output = match &config.pool_fee_output {
+        Some(fee) => {
+            let weight = percentage_to_weight(fee.percentage.unwrap_or(0.0))?;
+            let coinbase_output: CoinbaseOutput_ = fee.try_into()?;
+            to_distribute -= weight;
+            Some(TxOut {
+                value: weight,
+                script_pubkey: coinbase_output.try_into()?,
+            })
+        }
+        None => None,
+    };
+
+    let mut payouts: Vec<TxOut> = vec![];
+    let mut window_splits: Vec<(String, u64)> = vec![];
+    let mut paid: Vec<String> = vec![];
+    if config.share_ledger.max_coinbase_payouts > 0 {
+        window_splits = ledger.pplns_split(to_distribute);
+        let mut splits: Vec<(&String, Script, u64)> = window_splits
+            .iter()
+            .filter_map(|(user, weight)| Some((user, payout_script(user)?, *weight)))
+            .collect();
+        splits.sort_by_key(|split| std::cmp::Reverse(split.2));
+        splits.truncate(config.share_ledger.max_coinbase_payouts);
+        for (user, script_pubkey, weight) in splits {
+            to_distribute -= weight;
+            paid.push(user.clone());
+            payouts.push(TxOut {
+                value: weight,
+                script_pubkey,
+            });
+        }
+    }
+
+    let percentages: Vec<f64> = config
+        .coinbase_outputs
+        .iter()
+        .map(|o| o.percentage.unwrap_or(0.0))
+        .collect();
+    let total_percentage: f64 = percentages.iter().sum();
+    if total_percentage > 0.0 {
+        for (output, percentage) in outputs.iter_mut().zip(percentages) {
+            output.value = (to_distribute as f64 * percentage / total_percentage) as u64;
+        }
+    } else {
+        outputs[0].value = to_distribute;
+    }
+
+    outputs.extend(fee_output);
+    outputs.append(&mut payouts);
+    if let (Some(template_id), false) = (template_id, window_splits.is_empty()) {
+        let total_weight = outputs.iter().map(|o| o.value).sum();
+        ledger.on_coinbase_payouts(template_id, &window_splits, total_weight, &paid);
+    }
+    Ok(outputs)
+}
+
+/// Max number of bytes that the pool outputs can add to the coinbase, to be sent to the
+/// Template Provider in `CoinbaseOutputDataSize`
+pub fn get_coinbase_output_data_size(config: &Configuration) -> PoolResult<u32> {
+    let mut outputs = get_coinbase_output(config)?;
+    if let Some(fee) = &config.pool_fee_output {
+        let coinbase_output: CoinbaseOutput_ = fee.try_into()?;
+        outputs.push(TxOut {
+            value: 0,
+            script_pubkey: coinbase_output.try_into()?,
+        });
+    }
+    let payouts = config.share_ledger.max_coinbase_payouts as u32;
+    let mut size: u32 = outputs.iter().map(|o| serialize(o).len() as u32).sum();
+    size += payouts * MAX_PAYOUT_OUTPUT_SIZE;
+    // The outputs count in the coinbase goes from 1 to 3 bytes after 252 outputs
+    if outputs.len() as u32 + payouts > 252 {
+        size += 2;
+    }
+    Ok(size)
+}
+
+fn percentage_to_weight(percentage: f64) -> PoolResult<u64> {
+    if !(0.0..100.0).contains(&percentage) {
+        return Err(PoolError::Custom(format!(
+            "Invalid pool fee percentage: {}",
+            percentage
+        )));
+    }
+    Ok((percentage * (WEIGHT_SCALE / 100) as f64) as u64)
+}
+
+/// Returns the script paying `user_identity` when it is a bitcoin address, optionally followed
+/// by `.worker_name`
+fn payout_script(user_identity: &str) -> Option<Script> {
+    let address = user_identity.split('.').next()?;
+    let script = Address::from_str(address).ok()?.script_pubkey();
+    match script.len() as u32 + 9 <= MAX_PAYOUT_OUTPUT_SIZE {
+        true => Some(script),
+        false => None,
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct CoinbaseOutput {
     output_script_type: String,
     output_script_value: String,
+    /// Share of the coinbase value paid to this output
+    #[serde(default)]
+    percentage: Option<f64>,
 }
 
 impl CoinbaseOutput {
@@ -71,8 +217,14 @@ impl CoinbaseOutput {
         Self {
             output_script_type,
             output_script_value,
+            percentage: None,
         }
     }
+
+    pub fn with_percentage(mut self, percentage: f64) -> Self {
+        self.percentage = Some(percentage);
+        self
+    }
 }
 
 impl TryFrom<&CoinbaseOutput> for CoinbaseOutput_ {
@@ -96,18 +248,97 @@ pub struct Configuration {
     pub listen_address: String,
     pub tp_address: String,
     pub tp_authority_public_key: Option<Secp256k1PublicKey>,
+    /// Template Providers used, in order, when `tp_address` is not reachable
+    #[serde(default)]
+    pub backup_template_providers: Vec<TemplateProviderConfig>,
     pub authority_public_key: Secp256k1PublicKey,
     pub authority_secret_key: Secp256k1SecretKey,
     pub cert_validity_sec: u64,
     pub coinbase_outputs: Vec<CoinbaseOutput>,
+    /// Output paying the pool fee, its `percentage` is the fee
+    #[serde(default)]
+    pub pool_fee_output: Option<CoinbaseOutput>,
     pub pool_signature: String,
+    #[serde(default)]
+    pub share_ledger: ShareLedgerConfig,
+    #[serde(default)]
+    pub downstream_difficulty_config: DownstreamDifficultyConfig,
+    /// Address where the Prometheus metrics are served, eg `0.0.0.0:9184`
+    #[serde(default)]
+    pub metrics_address: Option<String>,
+    #[serde(default)]
+    pub admin_api: Option<AdminApiConfig>,
+    /// Node used to submit the blocks found by the pool, besides sending them to the Template
+    /// Provider
+    #[serde(default)]
+    pub block_submission: Option<BlockSubmissionConfig>,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
     #[cfg(feature = "test_only_allow_unencrypted")]
     pub test_only_listen_adress_plain: String,
 }
 
+/// What the pool does with its downstreams when it is stopped
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// Host that the downstreams are asked to reconnect to, when not set they are only
+    /// disconnected
+    pub fallback_host: Option<String>,
+    pub fallback_port: u16,
+    /// Max number of seconds spent draining the downstreams and flushing the solutions
+    pub timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            fallback_host: None,
+            fallback_port: 34254,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Server side vardiff, when enabled the target of every channel is periodically adjusted to
+/// the hashrate measured from its shares instead of relying only on the hashrate declared by
+/// the downstream
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DownstreamDifficultyConfig {
+    pub enabled: bool,
+    pub shares_per_minute: f32,
+    /// Min number of seconds between two target updates of the same channel
+    pub retarget_interval_secs: u64,
+    /// Lower bound for the hashrate of a channel in h/s
+    pub min_individual_miner_hashrate: f32,
+}
+
+impl Default for DownstreamDifficultyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            shares_per_minute: 6.0,
+            retarget_interval_secs: 60,
+            min_individual_miner_hashrate: 100_000_000.0,
+        }
+    }
+}
+
+impl From<&DownstreamDifficultyConfig> for VardiffConfig {
+    fn from(config: &DownstreamDifficultyConfig) -> Self {
+        Self {
+            shares_per_minute: config.shares_per_minute,
+            retarget_interval_secs: config.retarget_interval_secs,
+            min_hashrate: config.min_individual_miner_hashrate,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct TemplateProviderConfig {
-    address: String,
-    authority_public_key: Option<Secp256k1PublicKey>,
+    pub address: String,
+    pub authority_public_key: Option<Secp256k1PublicKey>,
 }
 
 impl TemplateProviderConfig {
@@ -161,15 +392,33 @@ impl Configuration {
             listen_address: pool_connection.listen_address,
             tp_address: template_provider.address,
             tp_authority_public_key: template_provider.authority_public_key,
+            backup_template_providers: Vec::new(),
             authority_public_key: authority_config.public_key,
             authority_secret_key: authority_config.secret_key,
             cert_validity_sec: pool_connection.cert_validity_sec,
             coinbase_outputs,
+            pool_fee_output: None,
             pool_signature: pool_connection.signature,
+            share_ledger: ShareLedgerConfig::default(),
+            downstream_difficulty_config: DownstreamDifficultyConfig::default(),
+            metrics_address: None,
+            admin_api: None,
+            block_submission: None,
+            shutdown: ShutdownConfig::default(),
             #[cfg(feature = "test_only_allow_unencrypted")]
             test_only_listen_adress_plain,
         }
     }
+
+    /// Every Template Provider sorted by priority, `tp_address` first
+    pub fn template_providers(&self) -> Vec<TemplateProviderConfig> {
+        let mut template_providers = vec![TemplateProviderConfig::new(
+            self.tp_address.clone(),
+            self.tp_authority_public_key,
+        )];
+        template_providers.extend(self.backup_template_providers.iter().cloned());
+        template_providers
+    }
 }
 
 #[derive(Debug)]
@@ -181,6 +430,14 @@ pub struct Downstream {
     downstream_data: CommonDownstreamData,
     solution_sender: Sender<SubmitSolution<'static>>,
     channel_factory: Arc<Mutex<PoolChannelFactory>>,
+    share_ledger: Arc<Mutex<ShareLedger>>,
+    // channels opened by this downstream
+    channel_ids: Vec<u32>,
+    metrics: Arc<Metrics>,
+    address: SocketAddr,
+    // worker that found the share being handled, set when a proxy tags its shares with the
+    // Worker-Specific Hashrate Tracking extension
+    share_worker: Option<String>,
 }
 
 /// Accept downstream connection
@@ -189,8 +446,19 @@ pub struct Pool {
     solution_sender: Sender<SubmitSolution<'static>>,
     new_template_processed: bool,
     channel_factory: Arc<Mutex<PoolChannelFactory>>,
+    share_ledger: Arc<Mutex<ShareLedger>>,
+    metrics: Arc<Metrics>,
     last_prev_hash_template_id: u64,
     status_tx: status::Sender,
+    config: Configuration,
+    // size declared to the Template Provider, the coinbase outputs can not grow past it
+    coinbase_output_data_size: u32,
+    // connections from these ips are refused
+    banned: HashSet<IpAddr>,
+    // accept loops, aborted on shutdown so that no new downstream can connect
+    listeners: Vec<task::AbortHandle>,
+    // tasks fed by the Template Provider, aborted at the end of the shutdown
+    tasks: Vec<task::JoinHandle<()>>,
 }
 
 impl Downstream {
@@ -201,6 +469,8 @@ impl Downstream {
         solution_sender: Sender<SubmitSolution<'static>>,
         pool: Arc<Mutex<Pool>>,
         channel_factory: Arc<Mutex<PoolChannelFactory>>,
+        share_ledger: Arc<Mutex<ShareLedger>>,
+        metrics: Arc<Metrics>,
         status_tx: status::Sender,
         address: SocketAddr,
     ) -> PoolResult<Arc<Mutex<Self>>> {
@@ -213,6 +483,10 @@ impl Downstream {
             false => channel_factory.safe_lock(|c| c.new_group_id())?,
             true => channel_factory.safe_lock(|c| c.new_standard_id_for_hom())?,
         };
+        metrics.downstream_connected();
+        if !downstream_data.header_only {
+            metrics.channel_opened(id, ChannelKind::Group, "", 0.0);
+        }
 
         let self_ = Arc::new(Mutex::new(Downstream {
             id,
@@ -221,6 +495,11 @@ impl Downstream {
             downstream_data,
             solution_sender,
             channel_factory,
+            share_ledger,
+            channel_ids: Vec::new(),
+            metrics,
+            address,
+            share_worker: None,
         }));
 
         let cloned = self_.clone();
@@ -265,6 +544,10 @@ impl Downstream {
                             .safe_lock(|p| p.downstreams.remove(&id))
                             .map_err(|e| PoolError::PoisonLock(e.to_string()));
                         handle_result!(status_tx, res);
+                        let res = cloned
+                            .safe_lock(|d| d.on_disconnect())
+                            .map_err(|e| PoolError::PoisonLock(e.to_string()));
+                        handle_result!(status_tx, res);
                         error!("Downstream {} disconnected", id);
                         break;
                     }
@@ -285,6 +568,13 @@ impl Downstream {
             "Received downstream message type: {:?}, payload: {:?}",
             message_type, payload
         );
+        let share_worker = match message_type {
+            const_sv2::MESSAGE_TYPE_SUBMIT_SHARES_EXTENDED => {
+                user_identity_from_tlv(payload).map(|worker| worker.to_string())
+            }
+            _ => None,
+        };
+        self_mutex.safe_lock(|d| d.share_worker = share_worker)?;
         let next_message_to_send = ParseDownstreamMiningMessages::handle_message_mining(
             self_mutex.clone(),
             message_type,
@@ -309,10 +599,10 @@ impl Downstream {
                     let downstream_id = self_
                         .safe_lock(|d| d.id)
                         .map_err(|e| Error::PoisonLock(e.to_string()))?;
-                    return Err(PoolError::Sv2ProtocolError((
+                    return Err(PoolError::Sv2ProtocolError(Box::new((
                         downstream_id,
                         message.clone(),
-                    )));
+                    ))));
                 } else {
                     Self::send(self_, message.clone()).await?;
                 }
@@ -338,6 +628,131 @@ impl Downstream {
         Ok(())
     }
 
+    /// Binds a newly opened channel to the `user_identity` that opened it, so that its shares can
+    /// be credited in the share ledger
+    fn register_channel(
+        &mut self,
+        channel_id: u32,
+        kind: ChannelKind,
+        user_identity: String,
+        nominal_hash_rate: f32,
+        target: &U256,
+    ) -> Result<(), Error> {
+        self.channel_ids.push(channel_id);
+        let hash_rate = self
+            .channel_factory
+            .safe_lock(|cf| cf.channel_hashrate(channel_id))
+            .map_err(|e| Error::PoisonLock(e.to_string()))?
+            .unwrap_or(nominal_hash_rate);
+        self.metrics
+            .channel_opened(channel_id, kind, &user_identity, hash_rate);
+        self.share_ledger
+            .safe_lock(|l| l.register_channel(channel_id, user_identity, target))
+            .map_err(|e| Error::PoisonLock(e.to_string()))
+    }
+
+    /// Records an accepted share in the share ledger and returns the work to report in
+    /// `SubmitSharesSuccess.new_shares_sum`. If the share can not be persisted the error is
+    /// logged and the share is still accepted. Shares tagged with a worker are credited to the
+    /// worker instead of the `user_identity` of the channel.
+    fn account_share(&mut self, channel_id: u32) -> Result<u64, Error> {
+        self.metrics.share_accepted();
+        let worker = self.share_worker.take();
+        let template_id = self
+            .channel_factory
+            .safe_lock(|cf| cf.share_template_id(channel_id))
+            .map_err(|e| Error::PoisonLock(e.to_string()))?;
+        let res = self
+            .share_ledger
+            .safe_lock(|l| l.on_share_accepted(channel_id, worker.as_deref(), template_id))
+            .map_err(|e| Error::PoisonLock(e.to_string()))?;
+        let work = res.unwrap_or_else(|e| {
+            error!("Failed to record share for channel {}: {}", channel_id, e);
+            0
+        });
+        if let Some(worker) = worker {
+            self.metrics.worker_work_accepted(&worker, work);
+        }
+        Ok(work)
+    }
+
+    fn on_share_rejected(&self, error: &SubmitSharesError) {
+        let error_code = std::str::from_utf8(error.error_code.as_ref()).unwrap_or("unknown");
+        self.metrics.share_rejected(error_code);
+    }
+
+    /// Closes the channels of a disconnected downstream in the metrics and in the share ledger
+    fn on_disconnect(&self) {
+        self.metrics.downstream_disconnected();
+        if !self.downstream_data.header_only {
+            self.metrics.channel_closed(self.id);
+        }
+        for channel_id in &self.channel_ids {
+            self.metrics.channel_closed(*channel_id);
+        }
+        if let Err(e) = self.share_ledger.safe_lock(|l| {
+            for channel_id in &self.channel_ids {
+                l.remove_channel(*channel_id);
+            }
+        }) {
+            error!(
+                "Failed to remove the channels of downstream {}: {}",
+                self.id, e
+            );
+        }
+    }
+
+    /// Closes the connection, the receiver task then removes the downstream from the pool
+    fn disconnect(&self) {
+        self.receiver.close();
+        self.sender.close();
+    }
+
+    /// Sends a `SetTarget` for every channel of this downstream whose target has been changed
+    /// by vardiff
+    async fn update_vardiff(self_: Arc<Mutex<Self>>) -> PoolResult<()> {
+        let (channel_ids, channel_factory, share_ledger, metrics) = self_.safe_lock(|d| {
+            (
+                d.channel_ids.clone(),
+                d.channel_factory.clone(),
+                d.share_ledger.clone(),
+                d.metrics.clone(),
+            )
+        })?;
+        for channel_id in channel_ids {
+            let set_target = channel_factory.safe_lock(|cf| cf.update_vardiff(channel_id))??;
+            if let Some(hash_rate) =
+                channel_factory.safe_lock(|cf| cf.channel_hashrate(channel_id))?
+            {
+                metrics.set_channel_hashrate(channel_id, hash_rate);
+            }
+            if let Some(set_target) = set_target {
+                share_ledger.safe_lock(|l| {
+                    l.update_channel_target(channel_id, &set_target.maximum_target)
+                })?;
+                Self::match_send_to(
+                    self_.clone(),
+                    Ok(SendTo::Respond(Mining::SetTarget(set_target))),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes and persists the payout for a block found on `template_id`
+    fn on_block_found(&self, template_id: Option<u64>) -> Result<(), Error> {
+        self.metrics.block_found();
+        let res = self
+            .share_ledger
+            .safe_lock(|l| l.on_block_found(template_id))
+            .map_err(|e| Error::PoisonLock(e.to_string()))?;
+        if let Err(e) = res {
+            error!("Failed to record payout: {}", e);
+        }
+        Ok(())
+    }
+
     async fn send(
         self_mutex: Arc<Mutex<Self>>,
         message: roles_logic_sv2::parsers::Mining<'static>,
@@ -400,6 +815,10 @@ impl Pool {
         while let Ok((stream, _)) = listner.accept().await {
             let address = stream.peer_addr().unwrap();
             debug!("New connection from {}", address);
+            if self_.safe_lock(|p| p.banned.contains(&address.ip()))? {
+                info!("Refusing connection from banned address {}", address);
+                continue;
+            }
 
             let (receiver, sender): (Receiver<EitherFrame>, Sender<EitherFrame>) =
                 network_helpers_sv2::plain_connection_tokio::PlainConnection::new(stream).await;
@@ -428,6 +847,10 @@ impl Pool {
                 "New connection from {:?}",
                 stream.peer_addr().map_err(PoolError::Io)
             );
+            if self_.safe_lock(|p| p.banned.contains(&address.ip()))? {
+                info!("Refusing connection from banned address {}", address);
+                continue;
+            }
 
             let responder = Responder::from_authority_kp(
                 &config.authority_public_key.into_bytes(),
@@ -468,6 +891,8 @@ impl Pool {
         let solution_sender = self_.safe_lock(|p| p.solution_sender.clone())?;
         let status_tx = self_.safe_lock(|s| s.status_tx.clone())?;
         let channel_factory = self_.safe_lock(|s| s.channel_factory.clone())?;
+        let share_ledger = self_.safe_lock(|s| s.share_ledger.clone())?;
+        let metrics = self_.safe_lock(|s| s.metrics.clone())?;
 
         let downstream = Downstream::new(
             receiver,
@@ -475,6 +900,8 @@ impl Pool {
             solution_sender,
             self_.clone(),
             channel_factory,
+            share_ledger,
+            metrics,
             // convert Listener variant to Downstream variant
             status_tx.listener_to_connection(),
             address,
@@ -497,13 +924,20 @@ impl Pool {
         let status_tx = self_
             .safe_lock(|s| s.status_tx.clone())
             .map_err(|e| PoolError::PoisonLock(e.to_string()))?;
+        let metrics = self_.safe_lock(|s| s.metrics.clone())?;
         while let Ok(new_prev_hash) = rx.recv().await {
+            let received_at = Instant::now();
             debug!("New prev hash received: {:?}", new_prev_hash);
             let res = self_
                 .safe_lock(|s| {
                     s.last_prev_hash_template_id = new_prev_hash.template_id;
+                    s.share_ledger.clone()
                 })
                 .map_err(|e| PoolError::PoisonLock(e.to_string()));
+            let share_ledger = handle_result!(status_tx, res);
+            let res = share_ledger
+                .safe_lock(|l| l.on_new_prev_hash(new_prev_hash.template_id, new_prev_hash.n_bits))
+                .map_err(|e| PoolError::PoisonLock(e.to_string()));
             handle_result!(status_tx, res);
 
             let job_id_res = self_
@@ -537,6 +971,7 @@ impl Pool {
                         .await;
                         handle_result!(status_tx, res);
                     }
+                    metrics.observe_prev_hash_latency(received_at.elapsed());
                     handle_result!(status_tx, sender_message_received_signal.send(()).await);
                 }
                 Err(_) => todo!(),
@@ -552,14 +987,40 @@ impl Pool {
     ) -> PoolResult<()> {
         let status_tx = self_.safe_lock(|s| s.status_tx.clone())?;
         let channel_factory = self_.safe_lock(|s| s.channel_factory.clone())?;
+        let share_ledger = self_.safe_lock(|s| s.share_ledger.clone())?;
+        let metrics = self_.safe_lock(|s| s.metrics.clone())?;
         while let Ok(mut new_template) = rx.recv().await {
+            let received_at = Instant::now();
+            // the coinbase outputs can be changed at runtime by the admin API
+            let config = self_
+                .safe_lock(|s| s.config.clone())
+                .map_err(|e| PoolError::PoisonLock(e.to_string()));
+            let config = handle_result!(status_tx, config);
             debug!(
                 "New template received, creating a new mining job(s): {:?}",
                 new_template
             );
 
+            let res = share_ledger
+                .safe_lock(|l| {
+                    l.on_new_template(
+                        new_template.template_id,
+                        new_template.coinbase_tx_value_remaining,
+                    )
+                })
+                .map_err(|e| PoolError::PoisonLock(e.to_string()));
+            handle_result!(status_tx, res);
+
+            let pool_outputs = share_ledger
+                .safe_lock(|l| get_pool_outputs(&config, l, Some(new_template.template_id)))
+                .map_err(|e| PoolError::PoisonLock(e.to_string()));
+            let pool_outputs = handle_result!(status_tx, handle_result!(status_tx, pool_outputs));
+
             let messages = channel_factory
-                .safe_lock(|cf| cf.on_new_template(&mut new_template))
+                .safe_lock(|cf| {
+                    cf.update_pool_outputs(pool_outputs);
+                    cf.on_new_template(&mut new_template)
+                })
                 .map_err(|e| PoolError::PoisonLock(e.to_string()));
             let messages = handle_result!(status_tx, messages);
             let mut messages = handle_result!(status_tx, messages);
@@ -579,6 +1040,7 @@ impl Pool {
                     }
                 }
             }
+            metrics.observe_template_latency(received_at.elapsed());
             let res = self_
                 .safe_lock(|s| s.new_template_processed = true)
                 .map_err(|e| PoolError::PoisonLock(e.to_string()));
@@ -589,6 +1051,21 @@ impl Pool {
         Ok(())
     }
 
+    /// Periodically runs vardiff on the channels of every downstream
+    async fn on_vardiff_tick(self_: Arc<Mutex<Self>>, interval: Duration) -> PoolResult<()> {
+        loop {
+            tokio::time::sleep(interval).await;
+            let downstreams = self_.safe_lock(|s| s.downstreams.clone())?;
+            for (id, downstream) in downstreams {
+                // the downstream may have just disconnected, that is not an error for the pool
+                if let Err(e) = Downstream::update_vardiff(downstream).await {
+                    warn!("Can not update the target of downstream {}: {}", id, e);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn start(
         config: Configuration,
         new_template_rx: Receiver<NewTemplate<'static>>,
@@ -596,6 +1073,8 @@ impl Pool {
         solution_sender: Sender<SubmitSolution<'static>>,
         sender_message_received_signal: Sender<()>,
         status_tx: status::Sender,
+        share_ledger: ShareLedger,
+        metrics: Arc<Metrics>,
     ) -> Arc<Mutex<Self>> {
         let extranonce_len = 32;
         let range_0 = std::ops::Range { start: 0, end: 0 };
@@ -611,7 +1090,7 @@ impl Pool {
         let creator = JobsCreators::new(extranonce_len as u8);
         let share_per_min = 1.0;
         let kind = roles_logic_sv2::channel_logic::channel_factory::ExtendedChannelKind::Pool;
-        let channel_factory = Arc::new(Mutex::new(PoolChannelFactory::new(
+        let mut channel_factory = PoolChannelFactory::new(
             ids,
             extranonces,
             creator,
@@ -619,19 +1098,49 @@ impl Pool {
             kind,
             pool_coinbase_outputs.expect("Invalid coinbase output in config"),
             config.pool_signature.clone(),
-        )));
+        );
+        let vardiff_enabled = config.downstream_difficulty_config.enabled;
+        if vardiff_enabled {
+            channel_factory.set_vardiff((&config.downstream_difficulty_config).into());
+        }
+        let channel_factory = Arc::new(Mutex::new(channel_factory));
+        let coinbase_output_data_size =
+            get_coinbase_output_data_size(&config).expect("Invalid coinbase output in config");
         let pool = Arc::new(Mutex::new(Pool {
             downstreams: HashMap::with_hasher(BuildNoHashHasher::default()),
             solution_sender,
             new_template_processed: false,
             channel_factory,
+            share_ledger: Arc::new(Mutex::new(share_ledger)),
+            metrics,
             last_prev_hash_template_id: 0,
             status_tx: status_tx.clone(),
+            config: config.clone(),
+            coinbase_output_data_size,
+            banned: HashSet::new(),
+            listeners: Vec::new(),
+            tasks: Vec::new(),
         }));
 
         let cloned = pool.clone();
         let cloned2 = pool.clone();
         let cloned3 = pool.clone();
+        let mut listeners = Vec::new();
+        let mut tasks = Vec::new();
+
+        if vardiff_enabled {
+            // ticks more often than the retarget interval so that channels opened at different
+            // times are retargeted close to their own interval
+            let interval = Duration::from_secs(
+                (config.downstream_difficulty_config.retarget_interval_secs / 4).max(1),
+            );
+            let cloned_vardiff = pool.clone();
+            tasks.push(task::spawn(async move {
+                if let Err(e) = Self::on_vardiff_tick(cloned_vardiff, interval).await {
+                    error!("Vardiff stopped: {}", e);
+                }
+            }));
+        }
 
         #[cfg(feature = "test_only_allow_unencrypted")]
         {
@@ -639,7 +1148,7 @@ impl Pool {
             let status_tx_clone_unenc = status_tx.clone();
             let config_unenc = config.clone();
 
-            task::spawn(async move {
+            let listener = task::spawn(async move {
                 if let Err(e) = Self::accept_incoming_plain_connection(cloned4, config_unenc).await
                 {
                     error!("{}", e);
@@ -656,11 +1165,12 @@ impl Pool {
                     error!("Downstream shutdown and Status Channel dropped");
                 }
             });
+            listeners.push(listener.abort_handle());
         }
 
         info!("Starting up pool listener");
         let status_tx_clone = status_tx.clone();
-        task::spawn(async move {
+        let listener = task::spawn(async move {
             if let Err(e) = Self::accept_incoming_connection(cloned, config).await {
                 error!("{}", e);
             }
@@ -676,10 +1186,11 @@ impl Pool {
                 error!("Downstream shutdown and Status Channel dropped");
             }
         });
+        listeners.push(listener.abort_handle());
 
         let cloned = sender_message_received_signal.clone();
         let status_tx_clone = status_tx.clone();
-        task::spawn(async move {
+        tasks.push(task::spawn(async move {
             if let Err(e) = Self::on_new_prev_hash(cloned2, new_prev_hash_rx, cloned).await {
                 error!("{}", e);
             }
@@ -695,10 +1206,10 @@ impl Pool {
             {
                 error!("Downstream shutdown and Status Channel dropped");
             }
-        });
+        }));
 
         let status_tx_clone = status_tx;
-        task::spawn(async move {
+        tasks.push(task::spawn(async move {
             if let Err(e) =
                 Self::on_new_template(pool, new_template_rx, sender_message_received_signal).await
             {
@@ -716,7 +1227,13 @@ impl Pool {
             {
                 error!("Downstream shutdown and Status Channel dropped");
             }
-        });
+        }));
+        cloned3
+            .safe_lock(|p| {
+                p.listeners = listeners;
+                p.tasks = tasks;
+            })
+            .expect("Pool mutex poisoned at startup");
         cloned3
     }
 
@@ -728,6 +1245,244 @@ impl Pool {
     pub fn remove_downstream(&mut self, downstream_id: u32) {
         self.downstreams.remove(&downstream_id);
     }
+
+    /// Size declared to the Template Provider in `CoinbaseOutputDataSize`
+    pub fn coinbase_output_data_size(&self) -> u32 {
+        self.coinbase_output_data_size
+    }
+
+    pub fn share_ledger(&self) -> Arc<Mutex<ShareLedger>> {
+        self.share_ledger.clone()
+    }
+
+    /// Connected downstreams and their channels, as exposed by the admin API
+    pub fn downstreams_info(self_: &Arc<Mutex<Self>>) -> PoolResult<Vec<DownstreamInfo>> {
+        let (downstreams, channel_factory, share_ledger, metrics) = self_.safe_lock(|p| {
+            (
+                p.downstreams.clone(),
+                p.channel_factory.clone(),
+                p.share_ledger.clone(),
+                p.metrics.clone(),
+            )
+        })?;
+        let mut infos = Vec::with_capacity(downstreams.len());
+        for (id, downstream) in downstreams {
+            let (address, header_only, channel_ids) = downstream.safe_lock(|d| {
+                (
+                    d.address,
+                    d.downstream_data.header_only,
+                    d.channel_ids.clone(),
+                )
+            })?;
+            let mut channels = Vec::with_capacity(channel_ids.len());
+            for channel_id in channel_ids {
+                let target: Option<U256<'static>> = channel_factory
+                    .safe_lock(|cf| cf.channel_target(channel_id))?
+                    .map(|target| target.into());
+                let user_identity = share_ledger
+                    .safe_lock(|l| l.user_identity(channel_id).map(|user| user.to_string()))?;
+                channels.push(ChannelInfo {
+                    channel_id,
+                    user_identity,
+                    target: target.as_ref().map(|target| {
+                        let mut bytes = target.to_vec();
+                        bytes.reverse();
+                        hex::encode(bytes)
+                    }),
+                    difficulty: target.as_ref().map(target_to_difficulty),
+                    hashrate: metrics.channel_hashrate(channel_id),
+                });
+            }
+            infos.push(DownstreamInfo {
+                id,
+                address: address.to_string(),
+                header_only,
+                channels,
+            });
+        }
+        infos.sort_by_key(|info| info.id);
+        Ok(infos)
+    }
+
+    /// Drops the connection of a downstream, returns false if it is not connected
+    pub fn disconnect_downstream(self_: &Arc<Mutex<Self>>, downstream_id: u32) -> PoolResult<bool> {
+        match self_.safe_lock(|p| p.downstreams.remove(&downstream_id))? {
+            Some(downstream) => {
+                downstream.safe_lock(|d| d.disconnect())?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Bans the ip of a downstream: every connection from it is dropped and the new ones are
+    /// refused. Returns the banned ip, `None` if the downstream is not connected.
+    pub fn ban_downstream(
+        self_: &Arc<Mutex<Self>>,
+        downstream_id: u32,
+    ) -> PoolResult<Option<IpAddr>> {
+        let downstream = match self_.safe_lock(|p| p.downstreams.get(&downstream_id).cloned())? {
+            Some(downstream) => downstream,
+            None => return Ok(None),
+        };
+        let ip = downstream.safe_lock(|d| d.address.ip())?;
+        let downstreams = self_.safe_lock(|p| {
+            p.banned.insert(ip);
+            p.downstreams.clone()
+        })?;
+        for (id, downstream) in downstreams {
+            if downstream.safe_lock(|d| d.address.ip())? == ip {
+                Self::disconnect_downstream(self_, id)?;
+            }
+        }
+        Ok(Some(ip))
+    }
+
+    /// Returns false if `ip` was not banned
+    pub fn unban(&mut self, ip: &IpAddr) -> bool {
+        self.banned.remove(ip)
+    }
+
+    pub fn banned(&self) -> Vec<IpAddr> {
+        let mut banned: Vec<IpAddr> = self.banned.iter().copied().collect();
+        banned.sort();
+        banned
+    }
+
+    /// Sends `Reconnect` to `downstream_id`, or to every downstream when it is `None`. Returns
+    /// the number of downstreams that have been asked to reconnect.
+    pub async fn reconnect_downstreams(
+        self_: Arc<Mutex<Self>>,
+        downstream_id: Option<u32>,
+        new_host: String,
+        new_port: u16,
+    ) -> PoolResult<usize> {
+        let downstreams: Vec<Arc<Mutex<Downstream>>> =
+            self_.safe_lock(|p| match downstream_id {
+                Some(id) => p.downstreams.get(&id).cloned().into_iter().collect(),
+                None => p.downstreams.values().cloned().collect(),
+            })?;
+        for downstream in &downstreams {
+            let message = Mining::Reconnect(Reconnect {
+                new_host: new_host.clone().into_bytes().try_into()?,
+                new_port,
+            });
+            Downstream::match_send_to(downstream.clone(), Ok(SendTo::Respond(message))).await?;
+        }
+        Ok(downstreams.len())
+    }
+
+    /// Replaces the `coinbase_outputs` of the config. The new outputs are paid from the next
+    /// template and can not need more space in the coinbase than the one declared to the
+    /// Template Provider at startup.
+    pub fn update_coinbase_outputs(
+        &mut self,
+        coinbase_outputs: Vec<CoinbaseOutput>,
+    ) -> PoolResult<()> {
+        let mut config = self.config.clone();
+        config.coinbase_outputs = coinbase_outputs;
+        let size = get_coinbase_output_data_size(&config)?;
+        if size > self.coinbase_output_data_size {
+            return Err(PoolError::Custom(format!(
+                "The coinbase outputs need {} bytes, only {} have been declared to the TP",
+                size, self.coinbase_output_data_size
+            )));
+        }
+        // the outputs are used from the next template, its split is recorded then
+        let pool_outputs = self
+            .share_ledger
+            .safe_lock(|l| get_pool_outputs(&config, l, None))??;
+        self.channel_factory
+            .safe_lock(|cf| cf.update_pool_outputs(pool_outputs))?;
+        self.config = config;
+        Ok(())
+    }
+
+    /// Replaces the signature added to the coinbase script, it is used from the next template
+    pub fn update_pool_signature(&mut self, pool_signature: String) -> PoolResult<()> {
+        if pool_signature.len() > MAX_POOL_SIGNATURE_LEN {
+            return Err(PoolError::Custom(format!(
+                "The pool signature can not be longer than {} bytes",
+                MAX_POOL_SIGNATURE_LEN
+            )));
+        }
+        self.channel_factory
+            .safe_lock(|cf| cf.update_pool_signature(pool_signature.clone()))?;
+        self.config.pool_signature = pool_signature;
+        Ok(())
+    }
+
+    /// Stops the pool: the listeners stop accepting connections, the downstreams are asked to
+    /// reconnect to the fallback endpoint (if any) and given time to leave, the solutions still
+    /// queued for the Template Provider are flushed and then the remaining downstreams are
+    /// disconnected. Everything after closing the listeners is bounded by
+    /// `config.timeout_secs`.
+    pub async fn shutdown(self_: Arc<Mutex<Self>>, config: &ShutdownConfig) -> PoolResult<()> {
+        let deadline = Instant::now() + Duration::from_secs(config.timeout_secs);
+        let (listeners, solution_sender) =
+            self_.safe_lock(|p| (std::mem::take(&mut p.listeners), p.solution_sender.clone()))?;
+        for listener in listeners {
+            listener.abort();
+        }
+        info!("Stopped accepting new downstreams");
+
+        if let Some(fallback_host) = &config.fallback_host {
+            match Self::reconnect_downstreams(
+                self_.clone(),
+                None,
+                fallback_host.clone(),
+                config.fallback_port,
+            )
+            .await
+            {
+                Ok(reconnected) => info!(
+                    "Asked {} downstreams to reconnect to {}:{}",
+                    reconnected, fallback_host, config.fallback_port
+                ),
+                Err(e) => warn!("Can not send Reconnect to every downstream: {}", e),
+            }
+            // downstreams remove themselves from the pool when they close the connection
+            while Instant::now() < deadline && !self_.safe_lock(|p| p.downstreams.is_empty())? {
+                tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+            }
+        }
+
+        // the channel is closed when the connection with the Template Provider is down
+        while Instant::now() < deadline
+            && !solution_sender.is_empty()
+            && !solution_sender.is_closed()
+        {
+            tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+        if !solution_sender.is_empty() {
+            warn!(
+                "{} solutions have not been sent to the Template Provider",
+                solution_sender.len()
+            );
+        }
+
+        let downstream_ids: Vec<u32> =
+            self_.safe_lock(|p| p.downstreams.keys().copied().collect())?;
+        for id in &downstream_ids {
+            Self::disconnect_downstream(&self_, *id)?;
+        }
+        if !downstream_ids.is_empty() {
+            info!("Disconnected {} downstreams", downstream_ids.len());
+        }
+
+        let tasks = self_.safe_lock(|p| std::mem::take(&mut p.tasks))?;
+        for task in tasks {
+            task.abort();
+            if tokio::time::timeout_at(deadline.into(), task)
+                .await
+                .is_err()
+            {
+                warn!("Shutdown deadline expired before every task stopped");
+                break;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -752,7 +1507,7 @@ mod test {
 
         // Load config
         let config: Configuration = match Config::builder()
-            .add_source(File::new(&config_path, FileFormat::Toml))
+            .add_source(File::new(config_path, FileFormat::Toml))
             .build()
         {
             Ok(settings) => match settings.try_deserialize::<Configuration>() {
@@ -784,7 +1539,7 @@ mod test {
         // build coinbase TX from 'job_creator::coinbase()'
 
         let mut bip34_bytes = get_bip_34_bytes(coinbase_prefix.try_into().unwrap());
-        let script_prefix_length = bip34_bytes.len() + config.pool_signature.as_bytes().len();
+        let script_prefix_length = bip34_bytes.len() + config.pool_signature.len();
         bip34_bytes.extend_from_slice(config.pool_signature.as_bytes());
         bip34_bytes.extend_from_slice(&vec![0; extranonce_len as usize]);
         let witness = match bip34_bytes.len() {
@@ -836,6 +1591,83 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_pool_outputs_weights() {
+        use super::super::share_ledger::{ShareLedger, ShareLedgerConfig};
+        use super::{get_pool_outputs, CoinbaseOutput};
+
+        let mut config: Configuration = Config::builder()
+            .add_source(File::new(
+                "./config-examples/pool-config-local-tp-example.toml",
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let key = "036adc3bdf21e6f9a0f0fb0066bf517e5b7909ed1563d6958a10993849a7554075";
+        config.coinbase_outputs = vec![
+            CoinbaseOutput::new("P2WPKH".to_string(), key.to_string()).with_percentage(75.0),
+            CoinbaseOutput::new("P2PKH".to_string(), key.to_string()).with_percentage(25.0),
+        ];
+        // P2TR takes the x-only key
+        config.pool_fee_output = Some(
+            CoinbaseOutput::new("P2TR".to_string(), key[2..].to_string()).with_percentage(2.0),
+        );
+        let mut ledger = ShareLedger::new(&ShareLedgerConfig::default()).unwrap();
+
+        let outputs = get_pool_outputs(&config, &mut ledger, None).unwrap();
+        let weights: Vec<u64> = outputs.iter().map(|o| o.value).collect();
+        assert_eq!(weights, vec![735_000, 245_000, 20_000]);
+
+        config.pool_fee_output =
+            Some(CoinbaseOutput::new("P2TR".to_string(), key.to_string()).with_percentage(2.0));
+        assert!(get_pool_outputs(&config, &mut ledger, None).is_err());
+    }
+
+    #[test]
+    fn test_coinbase_payouts_are_not_credited_twice() {
+        use super::super::share_ledger::{ShareLedger, ShareLedgerConfig};
+        use super::{get_pool_outputs, payout_script, CoinbaseOutput};
+        use binary_sv2::U256;
+        use roles_logic_sv2::job_creator::split_coinbase_value;
+
+        let mut config: Configuration = Config::builder()
+            .add_source(File::new(
+                "./config-examples/pool-config-local-tp-example.toml",
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let key = "036adc3bdf21e6f9a0f0fb0066bf517e5b7909ed1563d6958a10993849a7554075";
+        config.coinbase_outputs = vec![CoinbaseOutput::new("P2WPKH".to_string(), key.to_string())];
+        config.pool_fee_output = None;
+        config.share_ledger.max_coinbase_payouts = 1;
+        let alice = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+        let target: U256 = [0xff; 32].to_vec().try_into().unwrap();
+        let mut ledger = ShareLedger::new(&ShareLedgerConfig::default()).unwrap();
+        ledger.register_channel(1, format!("{}.rig1", alice), &target);
+        ledger.register_channel(2, "bob".to_string(), &target);
+        ledger.on_share_accepted(1, None, Some(7)).unwrap();
+        ledger.on_share_accepted(2, None, Some(7)).unwrap();
+        ledger.on_new_template(7, 1000);
+
+        let mut outputs = get_pool_outputs(&config, &mut ledger, Some(7)).unwrap();
+        split_coinbase_value(&mut outputs, 1000).unwrap();
+        let paid_in_coinbase = outputs[1].value;
+        assert_eq!(outputs[1].script_pubkey, payout_script(alice).unwrap());
+        assert_eq!(paid_in_coinbase, 500);
+
+        // shares accepted after the template was built do not change its split
+        ledger.on_share_accepted(1, None, Some(7)).unwrap();
+        let payout = ledger.on_block_found(Some(7)).unwrap();
+        assert_eq!(payout.splits, vec![("bob".to_string(), 500)]);
+        let recorded: u64 = payout.splits.iter().map(|(_, amount)| amount).sum();
+        assert_eq!(paid_in_coinbase + recorded, payout.reward);
+    }
+
     // copied from roles-logic-sv2::job_creator
     fn coinbase_tx_prefix(coinbase: &Transaction, script_prefix_len: usize) -> B064K<'static> {
         let encoded = coinbase.serialize();
@@ -1,15 +1,23 @@
+pub mod admin;
+pub mod block_submitter;
 pub mod error;
 pub mod mining_pool;
+pub mod share_ledger;
 pub mod status;
 pub mod template_receiver;
 
 use async_channel::{bounded, unbounded};
+use block_submitter::BlockSubmitter;
+use metrics_sv2::Metrics;
+use std::{sync::Arc, time::Duration};
 
 use error::PoolError;
-use mining_pool::{get_coinbase_output, Configuration, Pool};
+use mining_pool::{get_coinbase_output_data_size, Configuration, Pool, TemplateProviderConfig};
+use share_ledger::ShareLedger;
 use template_receiver::TemplateRx;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
+use roles_logic_sv2::utils::Mutex;
 use tokio::select;
 
 #[derive(Debug, Clone)]
@@ -29,18 +37,31 @@ impl PoolSv2 {
         let (s_prev_hash, r_prev_hash) = bounded(10);
         let (s_solution, r_solution) = bounded(10);
         let (s_message_recv_signal, r_message_recv_signal) = bounded(10);
-        let coinbase_output_result = get_coinbase_output(&config);
-        let coinbase_output_len = coinbase_output_result?.len() as u32;
-        let tp_authority_public_key = config.tp_authority_public_key;
-        TemplateRx::connect(
-            config.tp_address.parse().unwrap(),
+        let coinbase_output_len = get_coinbase_output_data_size(&config)?;
+        let share_ledger = ShareLedger::new(&config.share_ledger)?;
+        let template_providers = config.template_providers();
+        let metrics = Arc::new(Metrics::new());
+        if let Some(metrics_address) = config.metrics_address.clone() {
+            let metrics = metrics.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = metrics_sv2::serve(metrics, metrics_address).await {
+                    error!("Metrics endpoint stopped: {}", e);
+                }
+            });
+        }
+        let block_submitter = config
+            .block_submission
+            .as_ref()
+            .map(|c| Arc::new(Mutex::new(BlockSubmitter::new(c, metrics.clone()))));
+        let mut template_rx = TemplateRx::connect_to_any(
+            &template_providers,
             s_new_t,
             s_prev_hash,
             r_solution,
+            block_submitter,
             r_message_recv_signal,
             status::Sender::Upstream(status_tx.clone()),
             coinbase_output_len,
-            tp_authority_public_key,
         )
         .await?;
         let pool = Pool::start(
@@ -50,14 +71,24 @@ impl PoolSv2 {
             s_solution,
             s_message_recv_signal,
             status::Sender::DownstreamListener(status_tx),
+            share_ledger,
+            metrics.clone(),
         );
+        if let Some(admin_api) = config.admin_api.clone() {
+            let pool = pool.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = admin::serve(pool, admin_api).await {
+                    error!("Admin API stopped: {}", e);
+                }
+            });
+        }
 
         // Start the error handling loop
         // See `./status.rs` and `utils/error_handling` for information on how this operates
         loop {
             let task_status = select! {
                 task_status = status_rx.recv() => task_status,
-                interrupt_signal = tokio::signal::ctrl_c() => {
+                interrupt_signal = status::shutdown_signal() => {
                     match interrupt_signal {
                         Ok(()) => {
                             info!("Interrupt received");
@@ -67,7 +98,7 @@ impl PoolSv2 {
                             // we also shut down in case of error
                         },
                     }
-                    break Ok(());
+                    break;
                 }
             };
             let task_status: status::Status = task_status.unwrap();
@@ -79,11 +110,22 @@ impl PoolSv2 {
                         "SHUTDOWN from Downstream: {}\nTry to restart the downstream listener",
                         err
                     );
-                    break Ok(());
+                    break;
                 }
                 status::State::TemplateProviderShutdown(err) => {
-                    error!("SHUTDOWN from Upstream: {}\nTry to reconnecting or connecting to a new upstream", err);
-                    break Ok(());
+                    if template_rx.safe_lock(|t| t.is_connected()).unwrap_or(false) {
+                        // sent by a connection that has already been replaced
+                        debug!("Template Provider error on a previous connection: {}", err);
+                        continue;
+                    }
+                    error!("Lost the Template Provider: {}", err);
+                    match fail_over(&template_rx, &template_providers, &pool).await {
+                        Some(new_template_rx) => {
+                            template_rx = new_template_rx;
+                            metrics.upstream_reconnected();
+                        }
+                        None => break,
+                    }
                 }
                 status::State::Healthy(msg) => {
                     info!("HEALTHY message: {}", msg);
@@ -94,10 +136,50 @@ impl PoolSv2 {
                         .safe_lock(|p| p.remove_downstream(downstream_id))
                         .is_err()
                     {
-                        break Ok(());
+                        break;
+                    }
+                }
+            }
+        }
+
+        info!("Shutting down");
+        Pool::shutdown(pool, &config.shutdown).await
+    }
+}
+
+// Backoff between two rounds of connection attempts to the Template Providers
+const TP_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const TP_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Connects to the first reachable Template Provider, trying them in order of priority. The
+/// downstreams keep their channels while this runs and get new jobs from the first templates of
+/// the new Template Provider. Returns `None` if the pool is stopped in the meantime.
+async fn fail_over(
+    template_rx: &Arc<Mutex<TemplateRx>>,
+    template_providers: &[TemplateProviderConfig],
+    pool: &Arc<Mutex<Pool>>,
+) -> Option<Arc<Mutex<TemplateRx>>> {
+    let coinbase_output_len = pool.safe_lock(|p| p.coinbase_output_data_size()).ok()?;
+    let mut backoff = TP_MIN_BACKOFF;
+    loop {
+        select! {
+            res = TemplateRx::reconnect(template_rx, template_providers, coinbase_output_len) => {
+                match res {
+                    Ok(new_template_rx) => {
+                        if let Ok(address) = new_template_rx.safe_lock(|t| t.address()) {
+                            info!("Switched to the Template Provider at {}", address);
+                        }
+                        return Some(new_template_rx);
                     }
+                    Err(e) => warn!("No Template Provider reachable, retrying in {:?}: {}", backoff, e),
                 }
             }
+            _ = status::shutdown_signal() => return None,
+        }
+        select! {
+            _ = tokio::time::sleep(backoff) => (),
+            _ = status::shutdown_signal() => return None,
         }
+        backoff = (backoff * 2).min(TP_MAX_BACKOFF);
     }
 }
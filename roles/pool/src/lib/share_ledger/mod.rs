@@ -0,0 +1,635 @@
+//! Share accounting for the pool.
+//!
+//! Every share accepted by the `PoolChannelFactory` is recorded in the [`ShareLedger`] together
+//! with the `user_identity` of the channel that produced it and the difficulty of the channel
+//! target at submission time. With PPLNS the block reward is split between the users when a share
+//! meets the bitcoin target, with PPS every share is credited its expected value when accepted.
+//! See [`PayoutScheme`].
+//!
+//! Shares and payouts are written through a [`LedgerStore`] so that the PPLNS window and the
+//! payout history survive a pool restart. Once the store holds [`COMPACTION_RATIO`] times the
+//! entries needed to rebuild the ledger, and at least [`MIN_COMPACTION_ENTRIES`], it is rewritten
+//! with a snapshot of the window, the PPS balances and the payouts. So the store does not grow
+//! with the shares, only with the users and the blocks found.
+
+pub mod store;
+
+use super::error::PoolResult;
+use binary_sv2::U256;
+use nohash_hasher::BuildNoHashHasher;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use store::{FileLedgerStore, LedgerStore, MemoryLedgerStore};
+use tracing::{info, warn};
+
+/// The store is compacted when it holds this many times the entries of a snapshot
+pub const COMPACTION_RATIO: usize = 4;
+/// Stores with less entries are never compacted, so that small ledgers are not rewritten on
+/// every share
+pub const MIN_COMPACTION_ENTRIES: usize = 10_000;
+
+/// How the block reward is split between the users that contributed work
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum PayoutScheme {
+    /// Pay Per Last N Shares: the reward is split proportionally to the work in the last
+    /// `pplns_window_factor * network_difficulty` units of work.
+    Pplns,
+    /// Pay Per Share: every accepted share is credited its expected value,
+    /// `coinbase_value * share_difficulty / network_difficulty`, whether or not the pool finds a
+    /// block. The pool absorbs the variance.
+    Pps,
+}
+
+impl std::fmt::Display for PayoutScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayoutScheme::Pplns => write!(f, "PPLNS"),
+            PayoutScheme::Pps => write!(f, "PPS"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShareLedgerConfig {
+    /// File where shares and payouts are persisted. When not set the ledger is kept in memory
+    /// only and is lost on restart.
+    pub path: Option<PathBuf>,
+    pub payout_scheme: PayoutScheme,
+    /// Size of the PPLNS window as a multiple of the network difficulty
+    pub pplns_window_factor: f64,
+    /// Max number of users paid directly in the coinbase, 0 disables coinbase payouts
+    pub max_coinbase_payouts: usize,
+}
+
+impl Default for ShareLedgerConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            payout_scheme: PayoutScheme::Pplns,
+            pplns_window_factor: 2.0,
+            max_coinbase_payouts: 0,
+        }
+    }
+}
+
+/// A share accepted by the pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareRecord {
+    pub channel_id: u32,
+    pub user_identity: String,
+    /// Difficulty of the channel target when the share was accepted
+    pub difficulty: f64,
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+    /// Amount credited to the user for this share (in satoshis), always 0 with PPLNS
+    pub credit: u64,
+}
+
+/// The reward split computed when a block is found
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Payout {
+    /// `None` when the block was found on a custom job declared by the downstream
+    pub template_id: Option<u64>,
+    pub scheme: PayoutScheme,
+    /// Coinbase value that was split (in satoshis)
+    pub reward: u64,
+    /// (user_identity, amount in satoshis), sorted by user_identity. Always empty with PPS as
+    /// the users are credited when their shares are accepted. The users already paid by the
+    /// coinbase of the block are not included.
+    pub splits: Vec<(String, u64)>,
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+}
+
+/// What a [`LedgerStore`] persists
+#[derive(Debug, Clone, PartialEq)]
+pub enum LedgerEntry {
+    Share(ShareRecord),
+    /// PPS credits of a user, written when the store is compacted
+    Balance(String, u64),
+    Payout(Payout),
+}
+
+#[derive(Debug, Clone)]
+struct ChannelAccount {
+    user_identity: String,
+    difficulty: f64,
+}
+
+#[derive(Debug)]
+pub struct ShareLedger {
+    store: Box<dyn LedgerStore>,
+    scheme: PayoutScheme,
+    pplns_window_factor: f64,
+    channels: HashMap<u32, ChannelAccount, BuildNoHashHasher<u32>>,
+    // Shares in the PPLNS window, oldest first
+    window: VecDeque<ShareRecord>,
+    window_work: f64,
+    // Amount credited to every user by PPS (in satoshis)
+    balances: HashMap<String, u64>,
+    network_difficulty: f64,
+    // template_id -> coinbase_tx_value_remaining
+    template_values: HashMap<u64, u64, BuildNoHashHasher<u64>>,
+    // template_id -> PPLNS split of the users not paid by the coinbase of the template
+    template_splits: HashMap<u64, Vec<(String, u64)>, BuildNoHashHasher<u64>>,
+    last_template_value: u64,
+    payouts: Vec<Payout>,
+    // Entries in the store
+    stored_entries: usize,
+}
+
+impl ShareLedger {
+    /// Opens the store described by `config` and replays its content
+    pub fn new(config: &ShareLedgerConfig) -> PoolResult<Self> {
+        let store: Box<dyn LedgerStore> = match &config.path {
+            Some(path) => Box::new(FileLedgerStore::open(path)?),
+            None => Box::new(MemoryLedgerStore::default()),
+        };
+        Self::with_store(store, config.payout_scheme, config.pplns_window_factor)
+    }
+
+    /// Builds a ledger on top of any [`LedgerStore`], replaying the entries already stored
+    pub fn with_store(
+        mut store: Box<dyn LedgerStore>,
+        scheme: PayoutScheme,
+        pplns_window_factor: f64,
+    ) -> PoolResult<Self> {
+        let entries = store.load()?;
+        let mut ledger = Self {
+            store,
+            scheme,
+            pplns_window_factor,
+            channels: HashMap::with_hasher(BuildNoHashHasher::default()),
+            window: VecDeque::new(),
+            window_work: 0.0,
+            balances: HashMap::new(),
+            network_difficulty: 0.0,
+            template_values: HashMap::with_hasher(BuildNoHashHasher::default()),
+            template_splits: HashMap::with_hasher(BuildNoHashHasher::default()),
+            last_template_value: 0,
+            payouts: Vec::new(),
+            stored_entries: entries.len(),
+        };
+        let loaded = entries.len();
+        for entry in entries {
+            ledger.apply(entry);
+        }
+        if loaded > 0 {
+            info!(
+                "Share ledger restored {} entries, {} payouts",
+                loaded,
+                ledger.payouts.len()
+            );
+        }
+        Ok(ledger)
+    }
+
+    /// Called when a channel is opened, binds the channel to the user that opened it
+    pub fn register_channel(&mut self, channel_id: u32, user_identity: String, target: &U256) {
+        self.channels.insert(
+            channel_id,
+            ChannelAccount {
+                user_identity,
+                difficulty: target_to_difficulty(target),
+            },
+        );
+    }
+
+    /// Called every time the pool changes the target of a channel
+    pub fn update_channel_target(&mut self, channel_id: u32, target: &U256) {
+        if let Some(account) = self.channels.get_mut(&channel_id) {
+            account.difficulty = target_to_difficulty(target);
+        }
+    }
+
+    /// Called when a channel is closed, the shares already accepted stay in the ledger
+    pub fn remove_channel(&mut self, channel_id: u32) {
+        self.channels.remove(&channel_id);
+    }
+
+    /// Returns the user_identity that opened `channel_id`
+    pub fn user_identity(&self, channel_id: u32) -> Option<&str> {
+        self.channels
+            .get(&channel_id)
+            .map(|a| a.user_identity.as_str())
+    }
+
+    /// Updates the network difficulty used to size the PPLNS window and to value PPS shares.
+    /// Templates older than `template_id` can not be mined anymore and are forgotten.
+    pub fn on_new_prev_hash(&mut self, template_id: u64, n_bits: u32) {
+        self.network_difficulty = nbits_to_difficulty(n_bits);
+        self.template_values.retain(|id, _| *id >= template_id);
+        self.template_splits.retain(|id, _| *id >= template_id);
+        self.trim_window();
+    }
+
+    /// Remembers the value of the coinbase for `template_id` so that the right reward is split
+    /// when a block is found on one of its jobs
+    pub fn on_new_template(&mut self, template_id: u64, coinbase_tx_value_remaining: u64) {
+        self.template_values
+            .insert(template_id, coinbase_tx_value_remaining);
+        self.last_template_value = coinbase_tx_value_remaining;
+    }
+
+    /// Remembers the PPLNS split used to build the coinbase of `template_id`. `splits` are the
+    /// weights of the users in the window, out of the `total_weight` of the coinbase outputs,
+    /// and `paid` the users that the coinbase pays directly. When a block is found on the
+    /// template the other users are credited with this split, so that nobody is paid twice and
+    /// the coinbase and the ledger agree on the window.
+    pub fn on_coinbase_payouts(
+        &mut self,
+        template_id: u64,
+        splits: &[(String, u64)],
+        total_weight: u64,
+        paid: &[String],
+    ) {
+        if total_weight == 0 {
+            return;
+        }
+        let value = self.template_value(Some(template_id)) as u128;
+        let unpaid = splits
+            .iter()
+            .filter(|(user, _)| !paid.contains(user))
+            .map(|(user, weight)| {
+                (
+                    user.clone(),
+                    (value * *weight as u128 / total_weight as u128) as u64,
+                )
+            })
+            .filter(|(_, amount)| *amount > 0)
+            .collect();
+        self.template_splits.insert(template_id, unpaid);
+    }
+
+    /// Records an accepted share for `channel_id` and returns the work that must be reported in
+    /// `SubmitSharesSuccess.new_shares_sum`. The share is credited to `worker` when the proxy
+    /// that owns the channel tagged it, to the user that opened the channel otherwise.
+    /// `template_id` is the template of the job the share was mined on, `None` for custom jobs.
+    pub fn on_share_accepted(
+        &mut self,
+        channel_id: u32,
+        worker: Option<&str>,
+        template_id: Option<u64>,
+    ) -> PoolResult<u64> {
+        let account = match self.channels.get(&channel_id) {
+            Some(account) => account.clone(),
+            None => {
+                warn!("Share for unknown channel {} not accounted", channel_id);
+                return Ok(0);
+            }
+        };
+        let credit = match self.scheme {
+            PayoutScheme::Pplns => 0,
+            PayoutScheme::Pps => self.pps_credit(template_id, account.difficulty),
+        };
+        let record = ShareRecord {
+            channel_id,
+            user_identity: worker
+                .map(|worker| worker.to_string())
+                .unwrap_or(account.user_identity),
+            difficulty: account.difficulty,
+            timestamp: now(),
+            credit,
+        };
+        self.append(LedgerEntry::Share(record))?;
+        Ok(share_work(account.difficulty))
+    }
+
+    /// Called when a share meets the bitcoin target. With PPLNS splits the coinbase value of the
+    /// template between the users in the window, or between the users not paid by the coinbase
+    /// when the template has coinbase payouts (see [`Self::on_coinbase_payouts`]). With PPS the
+    /// shares have already been credited and the block is only recorded.
+    pub fn on_block_found(&mut self, template_id: Option<u64>) -> PoolResult<Payout> {
+        let reward = self.template_value(template_id);
+        let splits = match self.scheme {
+            PayoutScheme::Pplns => template_id
+                .and_then(|id| self.template_splits.get(&id).cloned())
+                .unwrap_or_else(|| self.pplns_split(reward)),
+            PayoutScheme::Pps => vec![],
+        };
+        let payout = Payout {
+            template_id,
+            scheme: self.scheme,
+            reward,
+            splits,
+            timestamp: now(),
+        };
+        self.append(LedgerEntry::Payout(payout.clone()))?;
+        info!("Block found, payout: {:?}", payout);
+        Ok(payout)
+    }
+
+    /// Splits `reward` proportionally to the work of each user in the current PPLNS window
+    pub fn pplns_split(&self, reward: u64) -> Vec<(String, u64)> {
+        let mut work: HashMap<String, f64> = HashMap::new();
+        for share in &self.window {
+            *work.entry(share.user_identity.clone()).or_insert(0.0) += share.difficulty;
+        }
+        split(reward, &work, self.window_work)
+    }
+
+    /// Expected value of a share of `difficulty` mined on `template_id`, rounded down
+    fn pps_credit(&self, template_id: Option<u64>, difficulty: f64) -> u64 {
+        if self.network_difficulty <= 0.0 {
+            warn!("Network difficulty unknown, share not credited");
+            return 0;
+        }
+        (self.template_value(template_id) as f64 * difficulty / self.network_difficulty).floor()
+            as u64
+    }
+
+    /// Coinbase value of `template_id`, the one of the last template when unknown
+    fn template_value(&self, template_id: Option<u64>) -> u64 {
+        template_id
+            .and_then(|id| self.template_values.get(&id).copied())
+            .unwrap_or(self.last_template_value)
+    }
+
+    pub fn payouts(&self) -> &[Payout] {
+        &self.payouts
+    }
+
+    /// Amount credited to every user by PPS (in satoshis), sorted by user_identity
+    pub fn balances(&self) -> Vec<(String, u64)> {
+        let mut balances: Vec<(String, u64)> = self
+            .balances
+            .iter()
+            .map(|(user, amount)| (user.clone(), *amount))
+            .collect();
+        balances.sort();
+        balances
+    }
+
+    fn append(&mut self, entry: LedgerEntry) -> PoolResult<()> {
+        self.store.append(&entry)?;
+        self.stored_entries += 1;
+        self.apply(entry);
+        self.compact_if_needed()
+    }
+
+    fn compact_if_needed(&mut self) -> PoolResult<()> {
+        // Until the first prev hash the window is not trimmed and the snapshot would not be
+        // smaller than the store
+        if self.network_difficulty <= 0.0 || self.stored_entries < MIN_COMPACTION_ENTRIES {
+            return Ok(());
+        }
+        let snapshot_len = self.balances.len() + self.window.len() + self.payouts.len();
+        if self.stored_entries < COMPACTION_RATIO * snapshot_len {
+            return Ok(());
+        }
+        // The credits of the shares in the window are already in the balances
+        let snapshot: Vec<LedgerEntry> = self
+            .balances()
+            .into_iter()
+            .map(|(user, amount)| LedgerEntry::Balance(user, amount))
+            .chain(self.window.iter().map(|share| {
+                LedgerEntry::Share(ShareRecord {
+                    credit: 0,
+                    ..share.clone()
+                })
+            }))
+            .chain(self.payouts.iter().cloned().map(LedgerEntry::Payout))
+            .collect();
+        self.store.rewrite(&snapshot)?;
+        info!(
+            "Share ledger compacted from {} to {} entries",
+            self.stored_entries,
+            snapshot.len()
+        );
+        self.stored_entries = snapshot.len();
+        Ok(())
+    }
+
+    fn apply(&mut self, entry: LedgerEntry) {
+        match entry {
+            LedgerEntry::Share(share) => {
+                self.window_work += share.difficulty;
+                if share.credit > 0 {
+                    *self
+                        .balances
+                        .entry(share.user_identity.clone())
+                        .or_insert(0) += share.credit;
+                }
+                self.window.push_back(share);
+                self.trim_window();
+            }
+            LedgerEntry::Balance(user_identity, amount) => {
+                *self.balances.entry(user_identity).or_insert(0) += amount;
+            }
+            LedgerEntry::Payout(payout) => self.payouts.push(payout),
+        }
+    }
+
+    fn trim_window(&mut self) {
+        // Until the first prev hash we don't know how big the window should be
+        if self.network_difficulty <= 0.0 {
+            return;
+        }
+        let max_work = self.pplns_window_factor * self.network_difficulty;
+        while self.window_work > max_work && self.window.len() > 1 {
+            if let Some(share) = self.window.pop_front() {
+                self.window_work -= share.difficulty;
+            }
+        }
+    }
+}
+
+/// Splits `reward` proportionally to `work / total_work`, rounding every amount down
+fn split(reward: u64, work: &HashMap<String, f64>, total_work: f64) -> Vec<(String, u64)> {
+    if total_work <= 0.0 {
+        return vec![];
+    }
+    let mut splits: Vec<(String, u64)> = work
+        .iter()
+        .map(|(user, w)| {
+            (
+                user.clone(),
+                (reward as f64 * w / total_work).floor() as u64,
+            )
+        })
+        .filter(|(_, amount)| *amount > 0)
+        .collect();
+    splits.sort();
+    splits
+}
+
+/// Work reported for a share, shares below difficulty 1 (regtest) still count as 1
+fn share_work(difficulty: f64) -> u64 {
+    difficulty.round().max(1.0) as u64
+}
+
+/// Converts a little endian U256 target into a difficulty
+pub fn target_to_difficulty(target: &U256) -> f64 {
+    let target = target
+        .inner_as_ref()
+        .iter()
+        .rev()
+        .fold(0.0, |acc, byte| acc * 256.0 + *byte as f64);
+    if target == 0.0 {
+        return f64::MAX;
+    }
+    // difficulty 1 target is 0x00000000FFFF0000...
+    let difficulty_1_target = 65535.0 * 2_f64.powi(208);
+    difficulty_1_target / target
+}
+
+/// Converts the compact nbits representation of the bitcoin target into a difficulty
+pub fn nbits_to_difficulty(n_bits: u32) -> f64 {
+    let exponent = (n_bits >> 24) as i32;
+    let mantissa = (n_bits & 0x00ff_ffff) as f64;
+    if mantissa == 0.0 {
+        return 0.0;
+    }
+    (0xffff as f64 / mantissa) * 256_f64.powi(0x1d - exponent)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn target_for_difficulty(difficulty: u64) -> U256<'static> {
+        // difficulty 1 target divided by `difficulty`, little endian
+        let mut target = [0_u8; 32];
+        let diff_1: u128 = 0xffff << 80;
+        let value = diff_1 / difficulty as u128;
+        target[16..32].copy_from_slice(&value.to_le_bytes());
+        target.to_vec().try_into().unwrap()
+    }
+
+    fn ledger(scheme: PayoutScheme) -> ShareLedger {
+        ShareLedger::with_store(Box::new(MemoryLedgerStore::default()), scheme, 2.0).unwrap()
+    }
+
+    #[test]
+    fn test_nbits_to_difficulty() {
+        assert_eq!(nbits_to_difficulty(0x1d00ffff), 1.0);
+        // regtest
+        assert!(nbits_to_difficulty(0x207fffff) < 1.0);
+    }
+
+    #[test]
+    fn test_new_shares_sum_is_weighted() {
+        let mut ledger = ledger(PayoutScheme::Pplns);
+        ledger.register_channel(1, "alice".to_string(), &target_for_difficulty(1000));
+        assert_eq!(ledger.on_share_accepted(1, None, Some(7)).unwrap(), 1000);
+        assert_eq!(ledger.on_share_accepted(2, None, Some(7)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_pplns_split() {
+        let mut ledger = ledger(PayoutScheme::Pplns);
+        ledger.on_new_template(7, 1000);
+        ledger.on_new_prev_hash(7, 0x1d00ffff);
+        ledger.register_channel(1, "alice".to_string(), &target_for_difficulty(1));
+        ledger.register_channel(2, "bob".to_string(), &target_for_difficulty(1));
+        // window is 2 * difficulty 1, the first alice share is pushed out of the window
+        ledger.on_share_accepted(1, None, Some(7)).unwrap();
+        ledger.on_share_accepted(1, None, Some(7)).unwrap();
+        ledger.on_share_accepted(2, None, Some(7)).unwrap();
+        let payout = ledger.on_block_found(Some(7)).unwrap();
+        assert_eq!(payout.reward, 1000);
+        assert_eq!(
+            payout.splits,
+            vec![("alice".to_string(), 500), ("bob".to_string(), 500)]
+        );
+    }
+
+    #[test]
+    fn test_pps_credits_every_share() {
+        let mut ledger = ledger(PayoutScheme::Pps);
+        ledger.on_new_template(7, 1000);
+        ledger.register_channel(1, "alice".to_string(), &target_for_difficulty(1));
+        ledger.register_channel(2, "bob".to_string(), &target_for_difficulty(2));
+        // before the first prev hash the share value is unknown
+        ledger.on_share_accepted(1, None, Some(7)).unwrap();
+        assert!(ledger.balances().is_empty());
+        // network difficulty is 4
+        ledger.on_new_prev_hash(7, 0x1c3fffc0);
+        ledger.on_share_accepted(1, None, Some(7)).unwrap();
+        ledger.on_share_accepted(2, None, Some(7)).unwrap();
+        assert_eq!(
+            ledger.balances(),
+            vec![("alice".to_string(), 250), ("bob".to_string(), 500)]
+        );
+        // finding a block does not change what has already been credited
+        let payout = ledger.on_block_found(Some(7)).unwrap();
+        assert!(payout.splits.is_empty());
+        ledger.on_share_accepted(1, None, Some(7)).unwrap();
+        assert_eq!(
+            ledger.balances(),
+            vec![("alice".to_string(), 500), ("bob".to_string(), 500)]
+        );
+        // shares are valued on the template of their job, not on the last one
+        ledger.on_new_template(8, 2000);
+        ledger.on_share_accepted(1, None, Some(7)).unwrap();
+        ledger.on_share_accepted(2, None, Some(8)).unwrap();
+        assert_eq!(
+            ledger.balances(),
+            vec![("alice".to_string(), 750), ("bob".to_string(), 1500)]
+        );
+    }
+
+    #[test]
+    fn test_store_is_compacted() {
+        let mut ledger = ledger(PayoutScheme::Pps);
+        ledger.on_new_template(7, 1000);
+        // network difficulty is 4, the window holds 8 shares of difficulty 1
+        ledger.on_new_prev_hash(7, 0x1c3fffc0);
+        ledger.register_channel(1, "alice".to_string(), &target_for_difficulty(1));
+        for _ in 0..3 * MIN_COMPACTION_ENTRIES {
+            ledger.on_share_accepted(1, None, Some(7)).unwrap();
+        }
+        ledger.on_block_found(Some(7)).unwrap();
+        let entries = ledger.store.load().unwrap();
+        assert_eq!(entries.len(), ledger.stored_entries);
+        assert!(entries.len() < MIN_COMPACTION_ENTRIES);
+
+        let mut store = MemoryLedgerStore::default();
+        store.rewrite(&entries).unwrap();
+        let mut restored =
+            ShareLedger::with_store(Box::new(store), PayoutScheme::Pps, 2.0).unwrap();
+        restored.on_new_prev_hash(7, 0x1c3fffc0);
+        let credit = 3 * MIN_COMPACTION_ENTRIES as u64 * 250;
+        assert_eq!(restored.balances(), vec![("alice".to_string(), credit)]);
+        assert_eq!(restored.balances(), ledger.balances());
+        assert_eq!(restored.window.len(), ledger.window.len());
+        assert_eq!(restored.window_work, ledger.window_work);
+        assert_eq!(restored.payouts(), ledger.payouts());
+    }
+
+    #[test]
+    fn test_tagged_shares_are_credited_to_the_worker() {
+        let mut ledger = ledger(PayoutScheme::Pplns);
+        ledger.on_new_template(7, 1000);
+        ledger.on_new_prev_hash(7, 0x1d00ffff);
+        ledger.register_channel(1, "translator".to_string(), &target_for_difficulty(1));
+        ledger
+            .on_share_accepted(1, Some("alice.rig1"), Some(7))
+            .unwrap();
+        ledger
+            .on_share_accepted(1, Some("bob.rig1"), Some(7))
+            .unwrap();
+        let payout = ledger.on_block_found(Some(7)).unwrap();
+        assert_eq!(
+            payout.splits,
+            vec![
+                ("alice.rig1".to_string(), 500),
+                ("bob.rig1".to_string(), 500)
+            ]
+        );
+    }
+}
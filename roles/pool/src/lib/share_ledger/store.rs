@@ -0,0 +1,285 @@
+//! Storage backends for the [`ShareLedger`](super::ShareLedger).
+//!
+//! A store appends entries, gives them back in the same order when the pool starts and can be
+//! rewritten with a snapshot of the ledger, so that it does not grow forever.
+//! [`FileLedgerStore`] is the default on-disk backend, [`MemoryLedgerStore`] keeps everything in
+//! memory and is used when no ledger path is configured.
+
+use super::{super::error::PoolResult, LedgerEntry, Payout, PayoutScheme, ShareRecord};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+// Max time an appended share can stay in the write buffer of the FileLedgerStore
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub trait LedgerStore: Send + std::fmt::Debug {
+    /// Persists `entry` after the already stored ones
+    fn append(&mut self, entry: &LedgerEntry) -> PoolResult<()>;
+    /// Returns every stored entry in insertion order
+    fn load(&mut self) -> PoolResult<Vec<LedgerEntry>>;
+    /// Replaces the stored entries with `entries`
+    fn rewrite(&mut self, entries: &[LedgerEntry]) -> PoolResult<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryLedgerStore {
+    entries: Vec<LedgerEntry>,
+}
+
+impl LedgerStore for MemoryLedgerStore {
+    fn append(&mut self, entry: &LedgerEntry) -> PoolResult<()> {
+        self.entries.push(entry.clone());
+        Ok(())
+    }
+
+    fn load(&mut self) -> PoolResult<Vec<LedgerEntry>> {
+        Ok(self.entries.clone())
+    }
+
+    fn rewrite(&mut self, entries: &[LedgerEntry]) -> PoolResult<()> {
+        self.entries = entries.to_vec();
+        Ok(())
+    }
+}
+
+/// Append only log, one entry per line:
+///
+/// ```text
+/// share <timestamp> <channel_id> <difficulty> <hex user_identity> <credit>
+/// balance <hex user_identity> <amount>
+/// payout <timestamp> <template_id|-> <PPLNS|PPS> <reward> <hex user_identity>:<amount>,...
+/// ```
+///
+/// User identities are hex encoded so that they can not break the line format. `balance` lines
+/// are only written by [`LedgerStore::rewrite`], they carry the PPS credits of the shares that
+/// are not in the snapshot anymore.
+///
+/// Shares are buffered and written at most [`FLUSH_INTERVAL`] after being appended, payouts are
+/// written right away. A crash can lose the shares of the last second.
+#[derive(Debug)]
+pub struct FileLedgerStore {
+    path: PathBuf,
+    file: BufWriter<File>,
+    last_flush: Instant,
+}
+
+impl FileLedgerStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> PoolResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+            last_flush: Instant::now(),
+        })
+    }
+}
+
+impl LedgerStore for FileLedgerStore {
+    fn append(&mut self, entry: &LedgerEntry) -> PoolResult<()> {
+        writeln!(self.file, "{}", encode_entry(entry))?;
+        if matches!(entry, LedgerEntry::Payout(_)) || self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.file.flush()?;
+            self.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn load(&mut self) -> PoolResult<Vec<LedgerEntry>> {
+        self.file.flush()?;
+        let reader = BufReader::new(File::open(&self.path)?);
+        let mut entries = Vec::new();
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match decode_entry(&line) {
+                Some(entry) => entries.push(entry),
+                // A crash while writing can leave the last line truncated
+                None => warn!(
+                    "Skipping malformed line {} of share ledger {:?}",
+                    n + 1,
+                    self.path
+                ),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn rewrite(&mut self, entries: &[LedgerEntry]) -> PoolResult<()> {
+        self.file.flush()?;
+        // write to a temporary file first so that a crash does not lose the current entries
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+            for entry in entries {
+                writeln!(tmp, "{}", encode_entry(entry))?;
+            }
+            tmp.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, &self.path)?;
+        self.file = BufWriter::new(OpenOptions::new().append(true).open(&self.path)?);
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+fn encode_entry(entry: &LedgerEntry) -> String {
+    match entry {
+        LedgerEntry::Share(share) => format!(
+            "share {} {} {} {} {}",
+            share.timestamp,
+            share.channel_id,
+            share.difficulty,
+            hex::encode(share.user_identity.as_bytes()),
+            share.credit
+        ),
+        LedgerEntry::Balance(user_identity, amount) => format!(
+            "balance {} {}",
+            hex::encode(user_identity.as_bytes()),
+            amount
+        ),
+        LedgerEntry::Payout(payout) => {
+            let template_id = payout
+                .template_id
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let splits = if payout.splits.is_empty() {
+                "-".to_string()
+            } else {
+                payout
+                    .splits
+                    .iter()
+                    .map(|(user, amount)| format!("{}:{}", hex::encode(user.as_bytes()), amount))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            };
+            format!(
+                "payout {} {} {} {} {}",
+                payout.timestamp, template_id, payout.scheme, payout.reward, splits
+            )
+        }
+    }
+}
+
+fn decode_entry(line: &str) -> Option<LedgerEntry> {
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.as_slice() {
+        ["share", timestamp, channel_id, difficulty, user, credit] => {
+            Some(LedgerEntry::Share(ShareRecord {
+                channel_id: channel_id.parse().ok()?,
+                user_identity: decode_user(user)?,
+                difficulty: difficulty.parse().ok()?,
+                timestamp: timestamp.parse().ok()?,
+                credit: credit.parse().ok()?,
+            }))
+        }
+        ["balance", user, amount] => Some(LedgerEntry::Balance(
+            decode_user(user)?,
+            amount.parse().ok()?,
+        )),
+        ["payout", timestamp, template_id, scheme, reward, splits] => {
+            let template_id = match *template_id {
+                "-" => None,
+                id => Some(id.parse().ok()?),
+            };
+            let scheme = match *scheme {
+                "PPLNS" => PayoutScheme::Pplns,
+                "PPS" => PayoutScheme::Pps,
+                _ => return None,
+            };
+            let splits = match *splits {
+                "-" => vec![],
+                splits => splits
+                    .split(',')
+                    .map(|split| {
+                        let (user, amount) = split.split_once(':')?;
+                        Some((decode_user(user)?, amount.parse().ok()?))
+                    })
+                    .collect::<Option<Vec<(String, u64)>>>()?,
+            };
+            Some(LedgerEntry::Payout(Payout {
+                template_id,
+                scheme,
+                reward: reward.parse().ok()?,
+                splits,
+                timestamp: timestamp.parse().ok()?,
+            }))
+        }
+        _ => None,
+    }
+}
+
+fn decode_user(user: &str) -> Option<String> {
+    String::from_utf8(hex::decode(user).ok()?).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_store_roundtrip() {
+        let path =
+            std::env::temp_dir().join(format!("pool-share-ledger-test-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let entries = vec![
+            LedgerEntry::Share(ShareRecord {
+                channel_id: 1,
+                user_identity: "alice worker 1".to_string(),
+                difficulty: 1024.5,
+                timestamp: 1700000000,
+                credit: 0,
+            }),
+            LedgerEntry::Share(ShareRecord {
+                channel_id: 2,
+                user_identity: "bob".to_string(),
+                difficulty: 1.0,
+                timestamp: 1700000000,
+                credit: 152,
+            }),
+            LedgerEntry::Payout(Payout {
+                template_id: Some(3),
+                scheme: PayoutScheme::Pplns,
+                reward: 625000000,
+                splits: vec![("alice worker 1".to_string(), 625000000)],
+                timestamp: 1700000001,
+            }),
+            LedgerEntry::Payout(Payout {
+                template_id: None,
+                scheme: PayoutScheme::Pps,
+                reward: 0,
+                splits: vec![],
+                timestamp: 1700000002,
+            }),
+            LedgerEntry::Balance("bob".to_string(), 304),
+        ];
+        {
+            let mut store = FileLedgerStore::open(&path).unwrap();
+            for entry in &entries {
+                store.append(entry).unwrap();
+            }
+        }
+        let mut store = FileLedgerStore::open(&path).unwrap();
+        assert_eq!(store.load().unwrap(), entries);
+        store.rewrite(&entries[3..]).unwrap();
+        store.append(&entries[0]).unwrap();
+        assert_eq!(
+            store.load().unwrap(),
+            vec![entries[3].clone(), entries[4].clone(), entries[0].clone()]
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_share_without_credit_is_rejected() {
+        assert!(decode_entry("share 1700000000 1 2 616c696365").is_none());
+        assert!(decode_entry("share 1700000000 1 2 616c696365 0").is_some());
+    }
+}
@@ -66,9 +66,11 @@ async fn send_status(
 ) -> error_handling::ErrorBranch {
     match sender {
         Sender::Downstream(tx) => match e {
-            PoolError::Sv2ProtocolError((id, Mining::OpenMiningChannelError(_))) => {
+            PoolError::Sv2ProtocolError(ref e)
+                if matches!(**e, (_, Mining::OpenMiningChannelError(_))) =>
+            {
                 tx.send(Status {
-                    state: State::DownstreamInstanceDropped(id),
+                    state: State::DownstreamInstanceDropped(e.0),
                 })
                 .await
                 .unwrap_or(());
@@ -109,6 +111,21 @@ async fn send_status(
     outcome
 }
 
+/// Resolves when the process is asked to stop, by Ctrl-C or, on unix, by SIGTERM
+pub async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res,
+            _ = sigterm.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
 // this is called by `error_handling::handle_result!`
 // todo: as described in issue #777, we should replace every generic *(_) with specific errors and cover every possible combination
 pub async fn handle_error(sender: &Sender, e: PoolError) -> error_handling::ErrorBranch {
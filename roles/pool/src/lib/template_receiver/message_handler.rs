@@ -27,17 +27,23 @@ impl ParseServerTemplateDistributionMessages for TemplateRx {
 
     fn handle_request_tx_data_success(
         &mut self,
-        _m: RequestTransactionDataSuccess,
+        m: RequestTransactionDataSuccess,
     ) -> Result<SendTo, Error> {
-        // Just ignore tx data messages this are meant for the declaretors
-        Ok(SendTo::None(None))
+        let tx_data = TemplateDistribution::RequestTransactionDataSuccess(m.into_static());
+        Ok(SendTo::RelayNewMessageToRemote(
+            Arc::new(Mutex::new(())),
+            tx_data,
+        ))
     }
 
     fn handle_request_tx_data_error(
         &mut self,
-        _m: RequestTransactionDataError,
+        m: RequestTransactionDataError,
     ) -> Result<SendTo, Error> {
-        // Just ignore tx data messages this are meant for the declaretors
-        Ok(SendTo::None(None))
+        let tx_data_error = TemplateDistribution::RequestTransactionDataError(m.into_static());
+        Ok(SendTo::RelayNewMessageToRemote(
+            Arc::new(Mutex::new(())),
+            tx_data_error,
+        ))
     }
 }
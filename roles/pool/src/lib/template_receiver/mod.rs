@@ -1,6 +1,7 @@
 use super::{
+    block_submitter::BlockSubmitter,
     error::{PoolError, PoolResult},
-    mining_pool::{EitherFrame, StdFrame},
+    mining_pool::{EitherFrame, StdFrame, TemplateProviderConfig},
     status,
 };
 use async_channel::{Receiver, Sender};
@@ -16,21 +17,28 @@ use roles_logic_sv2::{
     },
     utils::Mutex,
 };
-use std::{convert::TryInto, net::SocketAddr, sync::Arc};
+use std::{convert::TryInto, net::SocketAddr, sync::Arc, time::Duration};
 use tokio::{net::TcpStream, task};
-use tracing::info;
+use tracing::{info, warn};
 
 mod message_handler;
 mod setup_connection;
 use setup_connection::SetupConnectionHandler;
 
+// An unreachable Template Provider must not stall the failover to the next one
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct TemplateRx {
     receiver: Receiver<EitherFrame>,
     sender: Sender<EitherFrame>,
     message_received_signal: Receiver<()>,
     new_template_sender: Sender<NewTemplate<'static>>,
     new_prev_hash_sender: Sender<SetNewPrevHash<'static>>,
+    solution_receiver: Receiver<SubmitSolution<'static>>,
+    block_submitter: Option<Arc<Mutex<BlockSubmitter>>>,
     status_tx: status::Sender,
+    address: SocketAddr,
+    tasks: Vec<task::AbortHandle>,
 }
 
 impl TemplateRx {
@@ -40,12 +48,15 @@ impl TemplateRx {
         templ_sender: Sender<NewTemplate<'static>>,
         prev_h_sender: Sender<SetNewPrevHash<'static>>,
         solution_receiver: Receiver<SubmitSolution<'static>>,
+        block_submitter: Option<Arc<Mutex<BlockSubmitter>>>,
         message_received_signal: Receiver<()>,
         status_tx: status::Sender,
         coinbase_out_len: u32,
         expected_tp_authority_public_key: Option<Secp256k1PublicKey>,
-    ) -> PoolResult<()> {
-        let stream = TcpStream::connect(address).await?;
+    ) -> PoolResult<Arc<Mutex<Self>>> {
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+            .await
+            .map_err(|_| PoolError::Custom(format!("Timed out connecting to {}", address)))??;
         info!("Connected to template distribution server at {}", address);
 
         let initiator = match expected_tp_authority_public_key {
@@ -57,7 +68,9 @@ impl TemplateRx {
         let (mut receiver, mut sender, _, _) =
             Connection::new(stream, HandshakeRole::Initiator(initiator))
                 .await
-                .unwrap();
+                .map_err(|e| {
+                    PoolError::Custom(format!("Handshake with {} failed: {:?}", address, e))
+                })?;
 
         SetupConnectionHandler::setup(&mut receiver, &mut sender, address).await?;
 
@@ -67,7 +80,11 @@ impl TemplateRx {
             new_template_sender: templ_sender,
             new_prev_hash_sender: prev_h_sender,
             message_received_signal,
+            solution_receiver: solution_receiver.clone(),
+            block_submitter,
             status_tx,
+            address,
+            tasks: Vec::new(),
         }));
         let cloned = self_.clone();
 
@@ -81,25 +98,146 @@ impl TemplateRx {
 
         Self::send(self_.clone(), frame).await?;
 
-        task::spawn(async { Self::start(cloned).await });
-        task::spawn(async { Self::on_new_solution(self_, solution_receiver).await });
+        let start = task::spawn(async { Self::start(cloned).await });
+        let cloned = self_.clone();
+        let on_new_solution =
+            task::spawn(async { Self::on_new_solution(cloned, solution_receiver).await });
+        self_.safe_lock(|s| {
+            s.tasks = vec![start.abort_handle(), on_new_solution.abort_handle()];
+        })?;
 
-        Ok(())
+        Ok(self_)
+    }
+
+    /// Connects to the first reachable Template Provider of `template_providers`, which are
+    /// sorted by priority
+    #[allow(clippy::too_many_arguments)]
+    pub async fn connect_to_any(
+        template_providers: &[TemplateProviderConfig],
+        templ_sender: Sender<NewTemplate<'static>>,
+        prev_h_sender: Sender<SetNewPrevHash<'static>>,
+        solution_receiver: Receiver<SubmitSolution<'static>>,
+        block_submitter: Option<Arc<Mutex<BlockSubmitter>>>,
+        message_received_signal: Receiver<()>,
+        status_tx: status::Sender,
+        coinbase_out_len: u32,
+    ) -> PoolResult<Arc<Mutex<Self>>> {
+        let mut last_error = PoolError::Custom("No Template Provider configured".to_string());
+        for template_provider in template_providers {
+            let address = match template_provider.address.parse() {
+                Ok(address) => address,
+                Err(e) => {
+                    warn!(
+                        "Invalid Template Provider address {}: {}",
+                        template_provider.address, e
+                    );
+                    continue;
+                }
+            };
+            match Self::connect(
+                address,
+                templ_sender.clone(),
+                prev_h_sender.clone(),
+                solution_receiver.clone(),
+                block_submitter.clone(),
+                message_received_signal.clone(),
+                status_tx.clone(),
+                coinbase_out_len,
+                template_provider.authority_public_key,
+            )
+            .await
+            {
+                Ok(template_rx) => return Ok(template_rx),
+                Err(e) => {
+                    warn!(
+                        "Can not connect to the Template Provider at {}: {}",
+                        address, e
+                    );
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Drops this connection and connects to the first reachable Template Provider, the
+    /// templates and prev hashes of the new one are sent to the same channels.
+    /// `CoinbaseOutputDataSize` is declared again to the new Template Provider.
+    pub async fn reconnect(
+        self_: &Arc<Mutex<Self>>,
+        template_providers: &[TemplateProviderConfig],
+        coinbase_out_len: u32,
+    ) -> PoolResult<Arc<Mutex<Self>>> {
+        let (
+            new_template_sender,
+            new_prev_hash_sender,
+            solution_receiver,
+            block_submitter,
+            message_received_signal,
+            status_tx,
+        ) = self_.safe_lock(|s| {
+            s.disconnect();
+            (
+                s.new_template_sender.clone(),
+                s.new_prev_hash_sender.clone(),
+                s.solution_receiver.clone(),
+                s.block_submitter.clone(),
+                s.message_received_signal.clone(),
+                s.status_tx.clone(),
+            )
+        })?;
+        Self::connect_to_any(
+            template_providers,
+            new_template_sender,
+            new_prev_hash_sender,
+            solution_receiver,
+            block_submitter,
+            message_received_signal,
+            status_tx,
+            coinbase_out_len,
+        )
+        .await
+    }
+
+    /// False once the connection has been closed, by either side
+    pub fn is_connected(&self) -> bool {
+        !self.receiver.is_closed()
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Closes the connection and stops the tasks reading from it, the solutions not yet taken
+    /// are left in the channel for the next connection
+    fn disconnect(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+        self.receiver.close();
+        self.sender.close();
     }
 
     pub async fn start(self_: Arc<Mutex<Self>>) {
-        let (recv_msg_signal, receiver, new_template_sender, new_prev_hash_sender, status_tx) =
-            self_
-                .safe_lock(|s| {
-                    (
-                        s.message_received_signal.clone(),
-                        s.receiver.clone(),
-                        s.new_template_sender.clone(),
-                        s.new_prev_hash_sender.clone(),
-                        s.status_tx.clone(),
-                    )
-                })
-                .unwrap();
+        let (
+            recv_msg_signal,
+            receiver,
+            new_template_sender,
+            new_prev_hash_sender,
+            block_submitter,
+            status_tx,
+        ) = self_
+            .safe_lock(|s| {
+                (
+                    s.message_received_signal.clone(),
+                    s.receiver.clone(),
+                    s.new_template_sender.clone(),
+                    s.new_prev_hash_sender.clone(),
+                    s.block_submitter.clone(),
+                    s.status_tx.clone(),
+                )
+            })
+            .unwrap();
         loop {
             let message_from_tp = handle_result!(status_tx, receiver.recv().await);
             let mut message_from_tp: StdFrame = handle_result!(
@@ -125,14 +263,39 @@ impl TemplateRx {
                 roles_logic_sv2::handlers::SendTo_::RelayNewMessageToRemote(_, m) => match m {
                     TemplateDistribution::CoinbaseOutputDataSize(_) => todo!(),
                     TemplateDistribution::NewTemplate(m) => {
+                        if let Some(block_submitter) = &block_submitter {
+                            let res = block_submitter
+                                .safe_lock(|b| b.on_new_template(&m))
+                                .map_err(|e| PoolError::PoisonLock(e.to_string()));
+                            handle_result!(status_tx, res);
+                        }
                         let res = new_template_sender.send(m).await;
                         handle_result!(status_tx, res);
                         handle_result!(status_tx, recv_msg_signal.recv().await);
                     }
                     TemplateDistribution::RequestTransactionData(_) => todo!(),
-                    TemplateDistribution::RequestTransactionDataError(_) => todo!(),
-                    TemplateDistribution::RequestTransactionDataSuccess(_) => todo!(),
+                    TemplateDistribution::RequestTransactionDataError(m) => {
+                        if let Some(block_submitter) = &block_submitter {
+                            let res = block_submitter
+                                .safe_lock(|b| b.on_transaction_data_error(&m))
+                                .map_err(|e| PoolError::PoisonLock(e.to_string()));
+                            handle_result!(status_tx, res);
+                        }
+                    }
+                    TemplateDistribution::RequestTransactionDataSuccess(m) => {
+                        if let Some(block_submitter) = &block_submitter {
+                            let res =
+                                BlockSubmitter::on_transaction_data(block_submitter.clone(), m);
+                            handle_result!(status_tx, res);
+                        }
+                    }
                     TemplateDistribution::SetNewPrevHash(m) => {
+                        if let Some(block_submitter) = &block_submitter {
+                            let res = block_submitter
+                                .safe_lock(|b| b.on_new_prev_hash(&m))
+                                .map_err(|e| PoolError::PoisonLock(e.to_string()));
+                            handle_result!(status_tx, res);
+                        }
                         let res = new_prev_hash_sender.send(m).await;
                         handle_result!(status_tx, res);
                         handle_result!(status_tx, recv_msg_signal.recv().await);
@@ -146,6 +309,11 @@ impl TemplateRx {
                 }
             }
         }
+        // nothing reads from the connection anymore, closing it lets the status loop fail over
+        let _ = self_.safe_lock(|s| {
+            s.receiver.close();
+            s.sender.close();
+        });
     }
 
     pub async fn send(self_: Arc<Mutex<Self>>, sv2_frame: StdFrame) -> PoolResult<()> {
@@ -158,12 +326,15 @@ impl TemplateRx {
     }
 
     async fn on_new_solution(self_: Arc<Mutex<Self>>, rx: Receiver<SubmitSolution<'static>>) {
-        let status_tx = self_.safe_lock(|s| s.status_tx.clone()).unwrap();
+        let (status_tx, block_submitter) = self_
+            .safe_lock(|s| (s.status_tx.clone(), s.block_submitter.clone()))
+            .unwrap();
         while let Ok(solution) = rx.recv().await {
             info!("Sending Solution to TP: {:?}", &solution);
-            let sv2_frame_res: Result<StdFrame, _> =
-                PoolMessages::TemplateDistribution(TemplateDistribution::SubmitSolution(solution))
-                    .try_into();
+            let sv2_frame_res: Result<StdFrame, _> = PoolMessages::TemplateDistribution(
+                TemplateDistribution::SubmitSolution(solution.clone()),
+            )
+            .try_into();
             match sv2_frame_res {
                 Ok(frame) => {
                     handle_result!(status_tx, Self::send(self_.clone(), frame).await);
@@ -173,6 +344,20 @@ impl TemplateRx {
                     todo!()
                 }
             };
+            if let Some(block_submitter) = &block_submitter {
+                // the block is also submitted to the node once the transactions are received
+                let request = block_submitter
+                    .safe_lock(|b| b.on_solution(solution))
+                    .map_err(|e| PoolError::PoisonLock(e.to_string()));
+                if let Some(request) = handle_result!(status_tx, request) {
+                    let frame: Result<StdFrame, _> = PoolMessages::TemplateDistribution(
+                        TemplateDistribution::RequestTransactionData(request),
+                    )
+                    .try_into();
+                    let frame = handle_result!(status_tx, frame);
+                    handle_result!(status_tx, Self::send(self_.clone(), frame).await);
+                }
+            }
         }
     }
 }
@@ -0,0 +1,437 @@
+//! Operational metrics shared by all the SV2 roles.
+//!
+//! Every role owns an `Arc<Metrics>` and updates it where the events happen (connections,
+//! channels, shares, blocks, templates, reconnects). When a metrics address is configured the
+//! role mounts [`serve`] on it and the metrics are exposed at `/metrics` in the Prometheus text
+//! format.
+
+mod server;
+
+pub use server::serve;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelKind {
+    Standard,
+    Extended,
+    Group,
+}
+
+impl ChannelKind {
+    const ALL: [ChannelKind; 3] = [
+        ChannelKind::Standard,
+        ChannelKind::Extended,
+        ChannelKind::Group,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ChannelKind::Standard => "standard",
+            ChannelKind::Extended => "extended",
+            ChannelKind::Group => "group",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Channel {
+    kind: ChannelKind,
+    user_identity: String,
+    // h/s
+    hashrate: f32,
+}
+
+/// Sum and count of the observed latencies, exposed as a Prometheus summary
+#[derive(Debug, Default)]
+struct Latency {
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    connected_downstreams: u64,
+    channels: BTreeMap<u32, Channel>,
+    shares_accepted: u64,
+    // error code -> rejected shares
+    shares_rejected: BTreeMap<String, u64>,
+    // worker -> sum of the difficulty of its accepted shares
+    worker_work: BTreeMap<String, u64>,
+    blocks_found: u64,
+    // (result, reason) -> submitted blocks
+    blocks_submitted: BTreeMap<(String, String), u64>,
+    template_latency: Latency,
+    prev_hash_latency: Latency,
+    upstream_reconnects: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // metrics must never take a role down, so a poisoned lock is just recovered
+    fn inner(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn downstream_connected(&self) {
+        self.inner().connected_downstreams += 1;
+    }
+
+    pub fn downstream_disconnected(&self) {
+        let mut inner = self.inner();
+        inner.connected_downstreams = inner.connected_downstreams.saturating_sub(1);
+    }
+
+    /// `hashrate` is the hashrate declared by the downstream, it can be refined later with
+    /// [`Metrics::set_channel_hashrate`]
+    pub fn channel_opened(
+        &self,
+        channel_id: u32,
+        kind: ChannelKind,
+        user_identity: &str,
+        hashrate: f32,
+    ) {
+        self.inner().channels.insert(
+            channel_id,
+            Channel {
+                kind,
+                user_identity: user_identity.to_string(),
+                hashrate,
+            },
+        );
+    }
+
+    pub fn channel_closed(&self, channel_id: u32) {
+        self.inner().channels.remove(&channel_id);
+    }
+
+    /// Updates the estimated hashrate of an open channel
+    pub fn set_channel_hashrate(&self, channel_id: u32, hashrate: f32) {
+        if let Some(channel) = self.inner().channels.get_mut(&channel_id) {
+            channel.hashrate = hashrate;
+        }
+    }
+
+    /// Last hashrate set for an open channel
+    pub fn channel_hashrate(&self, channel_id: u32) -> Option<f32> {
+        self.inner()
+            .channels
+            .get(&channel_id)
+            .map(|channel| channel.hashrate)
+    }
+
+    pub fn share_accepted(&self) {
+        self.shares_accepted(1);
+    }
+
+    /// Used when the upstream acknowledges several shares with one `SubmitSharesSuccess`
+    pub fn shares_accepted(&self, count: u32) {
+        self.inner().shares_accepted += count as u64;
+    }
+
+    /// `error_code` is the one sent to the downstream in `SubmitSharesError`
+    pub fn share_rejected(&self, error_code: &str) {
+        *self
+            .inner()
+            .shares_rejected
+            .entry(error_code.to_string())
+            .or_insert(0) += 1;
+    }
+
+    /// Used for the shares that a proxy tagged with the worker that found them, `work` is the
+    /// difficulty of the share
+    pub fn worker_work_accepted(&self, worker: &str, work: u64) {
+        *self
+            .inner()
+            .worker_work
+            .entry(worker.to_string())
+            .or_insert(0) += work;
+    }
+
+    pub fn block_found(&self) {
+        self.inner().blocks_found += 1;
+    }
+
+    /// Outcome of a block submitted to the node, `result` is one of a few fixed values (eg
+    /// `accepted`, `rejected`) and `reason` details it (eg the reject reason given by the node)
+    pub fn block_submitted(&self, result: &str, reason: &str) {
+        *self
+            .inner()
+            .blocks_submitted
+            .entry((result.to_string(), reason.to_string()))
+            .or_insert(0) += 1;
+    }
+
+    /// Time from a new template to the jobs sent downstream
+    pub fn observe_template_latency(&self, latency: Duration) {
+        let mut inner = self.inner();
+        inner.template_latency.sum += latency.as_secs_f64();
+        inner.template_latency.count += 1;
+    }
+
+    /// Time from a new prev hash to the jobs sent downstream
+    pub fn observe_prev_hash_latency(&self, latency: Duration) {
+        let mut inner = self.inner();
+        inner.prev_hash_latency.sum += latency.as_secs_f64();
+        inner.prev_hash_latency.count += 1;
+    }
+
+    pub fn upstream_reconnected(&self) {
+        self.inner().upstream_reconnects += 1;
+    }
+
+    /// Renders all the metrics in the Prometheus text exposition format
+    pub fn encode(&self) -> String {
+        let inner = self.inner();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "sv2_connected_downstreams",
+            "gauge",
+            "Connected downstreams",
+        );
+        sample(
+            &mut out,
+            "sv2_connected_downstreams",
+            &[],
+            inner.connected_downstreams,
+        );
+
+        header(
+            &mut out,
+            "sv2_open_channels",
+            "gauge",
+            "Open channels by type",
+        );
+        for kind in ChannelKind::ALL.iter() {
+            let open = inner.channels.values().filter(|c| c.kind == *kind).count();
+            sample(
+                &mut out,
+                "sv2_open_channels",
+                &[("type", kind.as_str())],
+                open,
+            );
+        }
+
+        header(
+            &mut out,
+            "sv2_shares_accepted_total",
+            "counter",
+            "Accepted shares",
+        );
+        sample(
+            &mut out,
+            "sv2_shares_accepted_total",
+            &[],
+            inner.shares_accepted,
+        );
+
+        header(
+            &mut out,
+            "sv2_shares_rejected_total",
+            "counter",
+            "Rejected shares by error code",
+        );
+        for (error_code, rejected) in &inner.shares_rejected {
+            sample(
+                &mut out,
+                "sv2_shares_rejected_total",
+                &[("error_code", error_code.as_str())],
+                rejected,
+            );
+        }
+
+        header(
+            &mut out,
+            "sv2_channel_hashrate",
+            "gauge",
+            "Estimated hashrate of each channel in h/s",
+        );
+        let mut users: HashMap<&str, f64> = HashMap::new();
+        // group channels have no hashrate of their own, it is the one of their channels
+        let channels = inner
+            .channels
+            .iter()
+            .filter(|(_, channel)| channel.kind != ChannelKind::Group);
+        for (channel_id, channel) in channels {
+            sample(
+                &mut out,
+                "sv2_channel_hashrate",
+                &[
+                    ("channel_id", channel_id.to_string().as_str()),
+                    ("user", channel.user_identity.as_str()),
+                ],
+                channel.hashrate,
+            );
+            let user_hashrate = users.entry(channel.user_identity.as_str()).or_insert(0.0);
+            *user_hashrate += channel.hashrate as f64;
+        }
+
+        header(
+            &mut out,
+            "sv2_user_hashrate",
+            "gauge",
+            "Estimated hashrate of each user in h/s",
+        );
+        let mut users: Vec<(&str, f64)> = users.into_iter().collect();
+        users.sort_by(|a, b| a.0.cmp(b.0));
+        for (user, hashrate) in users {
+            sample(&mut out, "sv2_user_hashrate", &[("user", user)], hashrate);
+        }
+
+        header(
+            &mut out,
+            "sv2_worker_work_total",
+            "counter",
+            "Difficulty of the accepted shares of each worker, the hashrate is its rate * 2^32",
+        );
+        for (worker, work) in &inner.worker_work {
+            sample(
+                &mut out,
+                "sv2_worker_work_total",
+                &[("worker", worker.as_str())],
+                work,
+            );
+        }
+
+        header(
+            &mut out,
+            "sv2_blocks_found_total",
+            "counter",
+            "Blocks found",
+        );
+        sample(&mut out, "sv2_blocks_found_total", &[], inner.blocks_found);
+
+        header(
+            &mut out,
+            "sv2_blocks_submitted_total",
+            "counter",
+            "Blocks submitted to the node by result",
+        );
+        for ((result, reason), submitted) in &inner.blocks_submitted {
+            sample(
+                &mut out,
+                "sv2_blocks_submitted_total",
+                &[("result", result.as_str()), ("reason", reason.as_str())],
+                submitted,
+            );
+        }
+
+        summary(
+            &mut out,
+            "sv2_template_latency_seconds",
+            "Time from a new template to the jobs sent downstream",
+            &inner.template_latency,
+        );
+        summary(
+            &mut out,
+            "sv2_prev_hash_latency_seconds",
+            "Time from a new prev hash to the jobs sent downstream",
+            &inner.prev_hash_latency,
+        );
+
+        header(
+            &mut out,
+            "sv2_upstream_reconnects_total",
+            "counter",
+            "Reconnections to the upstream",
+        );
+        sample(
+            &mut out,
+            "sv2_upstream_reconnects_total",
+            &[],
+            inner.upstream_reconnects,
+        );
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    // writing to a String never fails
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample<V: std::fmt::Display>(out: &mut String, name: &str, labels: &[(&str, &str)], value: V) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+fn summary(out: &mut String, name: &str, help: &str, latency: &Latency) {
+    header(out, name, "summary", help);
+    sample(out, &format!("{}_sum", name), &[], latency.sum);
+    sample(out, &format!("{}_count", name), &[], latency.count);
+}
+
+fn escape(label_value: &str) -> String {
+    label_value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let metrics = Metrics::new();
+        metrics.downstream_connected();
+        metrics.channel_opened(1, ChannelKind::Extended, "alice", 100.0);
+        metrics.channel_opened(2, ChannelKind::Standard, "alice", 50.0);
+        metrics.channel_opened(3, ChannelKind::Standard, "bob\"", 10.0);
+        metrics.channel_closed(3);
+        metrics.set_channel_hashrate(2, 25.0);
+        metrics.share_accepted();
+        metrics.share_rejected("difficulty-too-low");
+        metrics.share_rejected("difficulty-too-low");
+        metrics.observe_template_latency(Duration::from_millis(500));
+        metrics.worker_work_accepted("alice.rig1", 1000);
+        metrics.worker_work_accepted("alice.rig1", 24);
+        metrics.block_submitted("rejected", "high-hash");
+
+        let encoded = metrics.encode();
+        for line in [
+            "sv2_connected_downstreams 1",
+            "sv2_open_channels{type=\"standard\"} 1",
+            "sv2_open_channels{type=\"extended\"} 1",
+            "sv2_open_channels{type=\"group\"} 0",
+            "sv2_shares_accepted_total 1",
+            "sv2_shares_rejected_total{error_code=\"difficulty-too-low\"} 2",
+            "sv2_channel_hashrate{channel_id=\"2\",user=\"alice\"} 25",
+            "sv2_user_hashrate{user=\"alice\"} 125",
+            "sv2_worker_work_total{worker=\"alice.rig1\"} 1024",
+            "sv2_blocks_submitted_total{result=\"rejected\",reason=\"high-hash\"} 1",
+            "sv2_template_latency_seconds_sum 0.5",
+            "sv2_template_latency_seconds_count 1",
+        ]
+        .iter()
+        {
+            assert!(encoded.lines().any(|l| l == *line), "missing {}", line);
+        }
+        assert!(!encoded.contains("bob"));
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\n");
+    }
+}
@@ -19,10 +19,7 @@ use const_sv2::{
     INITIATOR_EXPECTED_HANDSHAKE_MESSAGE_SIZE, RESPONDER_EXPECTED_HANDSHAKE_MESSAGE_SIZE,
 };
 use futures::lock::Mutex;
-use std::{
-    convert::TryInto,
-    sync::{atomic::AtomicBool, Arc},
-};
+use std::{convert::TryInto, sync::Arc};
 
 #[derive(Debug)]
 pub enum Error {
@@ -84,9 +81,6 @@ async fn initialize_as_downstream<
     let transport_mode = state.step_2(second_message)?;
 
     T::set_state(self_, transport_mode).await;
-    while !TRANSPORT_READY.load(std::sync::atomic::Ordering::SeqCst) {
-        std::hint::spin_loop()
-    }
     Ok(())
 }
 
@@ -111,19 +105,12 @@ async fn initialize_as_upstream<'a, Message: Serialize + Deserialize<'a> + GetSi
 
     // Create and send second handshake message
     let (second_message, transport_mode) = state.step_1(first_message)?;
-    HANDSHAKE_READY.store(false, std::sync::atomic::Ordering::SeqCst);
-    sender_outgoing.send(second_message.into()).await?;
 
-    // This sets the state to Handshake state - this prompts the task above to move the state
-    // to transport mode so that the next incoming message will be decoded correctly
-    // It is important to do this directly before sending the fourth message
+    // The remote can send transport frames as soon as it gets the second message, so the
+    // connection must be in transport mode before it is sent. The writer task encodes handshake
+    // frames without the connection state
     T::set_state(self_, transport_mode).await;
-    while !TRANSPORT_READY.load(std::sync::atomic::Ordering::SeqCst) {
-        std::hint::spin_loop()
-    }
+    sender_outgoing.send(second_message.into()).await?;
 
     Ok(())
 }
-
-static HANDSHAKE_READY: AtomicBool = AtomicBool::new(false);
-static TRANSPORT_READY: AtomicBool = AtomicBool::new(false);
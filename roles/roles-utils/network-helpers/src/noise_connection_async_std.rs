@@ -10,7 +10,9 @@ use std::{sync::Arc, time::Duration};
 use tracing::{debug, error};
 
 use binary_sv2::GetSize;
-use codec_sv2::{HandshakeRole, Initiator, Responder, StandardEitherFrame, StandardNoiseDecoder};
+use codec_sv2::{
+    Frame, HandshakeRole, Initiator, Responder, StandardEitherFrame, StandardNoiseDecoder,
+};
 
 use crate::Error;
 
@@ -21,16 +23,7 @@ pub struct Connection {
 
 impl crate::SetState for Connection {
     async fn set_state(self_: Arc<Mutex<Self>>, state: codec_sv2::State) {
-        loop {
-            if crate::HANDSHAKE_READY.load(std::sync::atomic::Ordering::SeqCst) {
-                if let Some(mut connection) = self_.try_lock() {
-                    connection.state = state;
-                    crate::TRANSPORT_READY.store(true, std::sync::atomic::Ordering::Relaxed);
-                    break;
-                };
-            }
-            task::yield_now().await;
-        }
+        self_.lock().await.state = state;
     }
 }
 
@@ -65,6 +58,7 @@ impl Connection {
 
         let cloned1 = connection.clone();
         let cloned2 = connection.clone();
+        let handshake_state = codec_sv2::State::not_initialized(&role);
 
         // RECEIVE AND PARSE INCOMING MESSAGES FROM TCP STREAM
         task::spawn(async move {
@@ -109,13 +103,20 @@ impl Connection {
         // ENCODE AND SEND INCOMING MESSAGES TO TCP STREAM
         task::spawn(async move {
             let mut encoder = codec_sv2::NoiseEncoder::<Message>::new();
+            // Handshake frames are sent in clear, the connection can already be in transport
+            // mode when the responder sends the last one
+            let mut handshake_state = handshake_state;
 
             loop {
                 let received = receiver_outgoing_cloned.recv().await;
                 match received {
                     Ok(frame) => {
                         let mut connection = cloned2.lock().await;
-                        let b = match encoder.encode(frame, &mut connection.state) {
+                        let state = match frame {
+                            Frame::HandShake(_) => &mut handshake_state,
+                            Frame::Sv2(_) => &mut connection.state,
+                        };
+                        let b = match encoder.encode(frame, state) {
                             Ok(b) => b,
                             Err(e) => {
                                 error!("Failed to encode noise frame: {:#?}", e);
@@ -140,7 +141,6 @@ impl Connection {
                         break;
                     }
                 };
-                crate::HANDSHAKE_READY.store(true, std::sync::atomic::Ordering::Relaxed);
             }
         });
 
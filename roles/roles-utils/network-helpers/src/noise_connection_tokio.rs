@@ -10,7 +10,9 @@ use tokio::{
 };
 
 use binary_sv2::GetSize;
-use codec_sv2::{HandshakeRole, Initiator, Responder, StandardEitherFrame, StandardNoiseDecoder};
+use codec_sv2::{
+    Frame, HandshakeRole, Initiator, Responder, StandardEitherFrame, StandardNoiseDecoder,
+};
 
 use tracing::{debug, error};
 
@@ -21,16 +23,7 @@ pub struct Connection {
 
 impl crate::SetState for Connection {
     async fn set_state(self_: Arc<Mutex<Self>>, state: codec_sv2::State) {
-        loop {
-            if crate::HANDSHAKE_READY.load(std::sync::atomic::Ordering::SeqCst) {
-                if let Some(mut connection) = self_.try_lock() {
-                    connection.state = state;
-                    crate::TRANSPORT_READY.store(true, std::sync::atomic::Ordering::Relaxed);
-                    break;
-                };
-            }
-            task::yield_now().await;
-        }
+        self_.lock().await.state = state;
     }
 }
 
@@ -67,6 +60,7 @@ impl Connection {
 
         let cloned1 = connection.clone();
         let cloned2 = connection.clone();
+        let handshake_state = codec_sv2::State::not_initialized(&role);
 
         // RECEIVE AND PARSE INCOMING MESSAGES FROM TCP STREAM
         let recv_task = task::spawn(async move {
@@ -119,6 +113,9 @@ impl Connection {
         // ENCODE AND SEND INCOMING MESSAGES TO TCP STREAM
         let send_task = task::spawn(async move {
             let mut encoder = codec_sv2::NoiseEncoder::<Message>::new();
+            // Handshake frames are sent in clear, the connection can already be in transport
+            // mode when the responder sends the last one
+            let mut handshake_state = handshake_state;
 
             loop {
                 let received = receiver_outgoing_cloned.recv().await;
@@ -126,8 +123,12 @@ impl Connection {
                 match received {
                     Ok(frame) => {
                         let mut connection = cloned2.lock().await;
+                        let state = match frame {
+                            Frame::HandShake(_) => &mut handshake_state,
+                            Frame::Sv2(_) => &mut connection.state,
+                        };
 
-                        let b = encoder.encode(frame, &mut connection.state).unwrap();
+                        let b = encoder.encode(frame, state).unwrap();
 
                         drop(connection);
 
@@ -158,7 +159,6 @@ impl Connection {
                         break;
                     }
                 };
-                crate::HANDSHAKE_READY.store(true, std::sync::atomic::Ordering::Relaxed);
             }
         });
 
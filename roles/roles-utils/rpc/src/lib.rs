@@ -7,5 +7,12 @@ pub struct Hash([u8; 32]);
 #[derive(Clone, Deserialize)]
 pub struct Amount(f64);
 
+impl Amount {
+    /// The amount in BTC, as returned by the node
+    pub fn to_btc(&self) -> f64 {
+        self.0
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BlockHash(Hash);
@@ -6,29 +6,59 @@ use http_body_util::{BodyExt, Full};
 use hyper::{
     body::Bytes,
     header::{AUTHORIZATION, CONTENT_TYPE},
-    Request,
+    Request, StatusCode,
 };
 use hyper_util::{
     client::legacy::{connect::HttpConnector, Client},
     rt::TokioExecutor,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
+use std::{collections::HashMap, fmt, path::PathBuf, time::Duration};
 use stratum_common::bitcoin::{consensus::encode::deserialize as consensus_decode, Transaction};
 
 use super::BlockHash;
 
+// getblocktemplate can take a few seconds on a big mempool
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_RETRIES: u32 = 2;
+// Multiplied by the number of the attempt
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+// Returned by the node while it loads the chain state
+const RPC_IN_WARMUP: i32 = -28;
+
 #[derive(Clone, Debug)]
 pub struct MiniRpcClient {
     client: Client<HttpConnector, Full<Bytes>>,
     url: String,
     auth: Auth,
+    timeout: Duration,
+    retries: u32,
 }
 
 impl MiniRpcClient {
     pub fn new(url: String, auth: Auth) -> MiniRpcClient {
         let client: Client<_, Full<Bytes>> = Client::builder(TokioExecutor::new()).build_http();
-        MiniRpcClient { client, url, auth }
+        MiniRpcClient {
+            client,
+            url,
+            auth,
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+
+    /// Max time of a single http request, including the read of the response
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Number of times a request is sent again after a timeout, a connection error or while
+    /// the node is warming up
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
     }
 
     pub async fn get_raw_transaction(
@@ -36,55 +66,170 @@ impl MiniRpcClient {
         txid: &String,
         block_hash: Option<&BlockHash>,
     ) -> Result<Transaction, RpcError> {
-        let response = match block_hash {
+        let transaction_hex: String = match block_hash {
             Some(hash) => {
-                self.send_json_rpc_request("getrawtransaction", json!([txid, false, hash]))
+                self.call_result("getrawtransaction", json!([txid, false, hash]))
+                    .await?
             }
-            None => self.send_json_rpc_request("getrawtransaction", json!([txid, false])),
-        }
-        .await;
-        match response {
-            Ok(result_hex) => {
-                let result_deserialized: JsonRpcResult<String> = serde_json::from_str(&result_hex)
-                    .map_err(|e| {
-                        RpcError::Deserialization(e.to_string()) // TODO manage message ids
-                    })?;
-                let transaction_hex: String = result_deserialized
-                    .result
-                    .ok_or_else(|| RpcError::Other("Result not found".to_string()))?;
-                let transaction_bytes = decode(transaction_hex).expect("Decoding failed");
-                Ok(consensus_decode(&transaction_bytes).expect("Deserialization failed"))
+            None => {
+                self.call_result("getrawtransaction", json!([txid, false]))
+                    .await?
             }
-            Err(error) => Err(error),
-        }
+        };
+        decode_transaction(transaction_hex)
     }
 
     pub async fn get_raw_mempool(&self) -> Result<Vec<String>, RpcError> {
-        let response = self.send_json_rpc_request("getrawmempool", json!([])).await;
-        match response {
-            Ok(result_hex) => {
-                let result_deserialized: JsonRpcResult<Vec<String>> =
-                    serde_json::from_str(&result_hex).map_err(|e| {
-                        RpcError::Deserialization(e.to_string()) // TODO manage message ids
-                    })?;
-                let mempool: Vec<String> = result_deserialized
-                    .result
-                    .ok_or_else(|| RpcError::Other("Result not found".to_string()))?;
-                Ok(mempool)
+        self.call_result("getrawmempool", json!([])).await
+    }
+
+    /// Calls `getblocktemplate` with the segwit rule, the result describes the block that would
+    /// be built on top of the current chain tip.
+    pub async fn get_block_template(&self) -> Result<BlockTemplate, RpcError> {
+        self.call_result("getblocktemplate", json!([{"rules": ["segwit"]}]))
+            .await
+    }
+
+    /// Returns `None` when the node accepted the block, otherwise the reason given by the node
+    /// (eg `duplicate`, `inconclusive`, `high-hash`).
+    pub async fn submit_block(&self, block_hex: String) -> Result<Option<String>, RpcError> {
+        self.call("submitblock", json!([block_hex])).await
+    }
+
+    /// Fetches many transactions with a single batched request, transactions that the node does
+    /// not know are omitted from the result.
+    pub async fn get_raw_transactions(
+        &self,
+        txids: &[String],
+    ) -> Result<Vec<Transaction>, RpcError> {
+        let requests = txids
+            .iter()
+            .map(|txid| ("getrawtransaction", json!([txid, false])))
+            .collect();
+        let results: Vec<Result<String, RpcError>> = self.batch(requests).await?;
+        results
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(decode_transaction)
+            .collect()
+    }
+
+    /// Returns every mempool entry, keyed by txid
+    pub async fn get_raw_mempool_verbose(&self) -> Result<HashMap<String, MempoolEntry>, RpcError> {
+        self.call_result("getrawmempool", json!([true])).await
+    }
+
+    pub async fn get_mempool_entry(&self, txid: &str) -> Result<MempoolEntry, RpcError> {
+        self.call_result("getmempoolentry", json!([txid])).await
+    }
+
+    /// Fetches the mempool entries of many transactions with a single batched request, `None`
+    /// is returned for the transactions that are not in the node's mempool.
+    pub async fn get_mempool_entries(
+        &self,
+        txids: &[String],
+    ) -> Result<Vec<Option<MempoolEntry>>, RpcError> {
+        let requests = txids
+            .iter()
+            .map(|txid| ("getmempoolentry", json!([txid])))
+            .collect();
+        let results = self.batch(requests).await?;
+        Ok(results.into_iter().map(Result::ok).collect())
+    }
+
+    /// Hash of the tip of the most-work chain, hex encoded
+    pub async fn get_best_block_hash(&self) -> Result<String, RpcError> {
+        self.call_result("getbestblockhash", json!([])).await
+    }
+
+    pub async fn get_block_header(&self, block_hash: &str) -> Result<BlockHeaderInfo, RpcError> {
+        self.call_result("getblockheader", json!([block_hash, true]))
+            .await
+    }
+
+    /// Checks whether the given raw transactions would be accepted in the node's mempool,
+    /// without adding them. The result has one entry for each transaction.
+    pub async fn test_mempool_accept(
+        &self,
+        raw_transactions: &[String],
+    ) -> Result<Vec<MempoolAcceptResult>, RpcError> {
+        self.call_result("testmempoolaccept", json!([raw_transactions]))
+            .await
+    }
+
+    pub async fn get_network_info(&self) -> Result<NetworkInfo, RpcError> {
+        self.call_result("getnetworkinfo", json!([])).await
+    }
+
+    /// Fee rate needed for a transaction to confirm within `conf_target` blocks
+    pub async fn estimate_smart_fee(
+        &self,
+        conf_target: u16,
+        mode: EstimateMode,
+    ) -> Result<SmartFeeEstimate, RpcError> {
+        self.call_result("estimatesmartfee", json!([conf_target, mode]))
+            .await
+    }
+
+    /// Sends all the requests in one http call. Results are returned in the same order of the
+    /// requests, each one with its own error (eg unknown txid).
+    pub async fn batch<T: DeserializeOwned>(
+        &self,
+        requests: Vec<(&str, serde_json::Value)>,
+    ) -> Result<Vec<Result<T, RpcError>>, RpcError> {
+        if requests.is_empty() {
+            return Ok(vec![]);
+        }
+        let len = requests.len();
+        let requests: Vec<JsonRpcRequest> = requests
+            .into_iter()
+            .enumerate()
+            .map(|(id, (method, params))| JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                method: method.to_string(),
+                params,
+                id: id as u64,
+            })
+            .collect();
+        let request_body =
+            serde_json::to_string(&requests).map_err(|e| RpcError::Serialization(e.to_string()))?;
+        let response = self.send_body(request_body).await?;
+        let responses: Vec<JsonRpcResult<T>> = serde_json::from_str(&response)
+            .map_err(|e| RpcError::Deserialization(e.to_string()))?;
+        // the node can answer in any order
+        let mut results: Vec<Result<T, RpcError>> =
+            (0..len).map(|_| Err(RpcError::MissingResult)).collect();
+        for response in responses {
+            if let Some(result) = results.get_mut(response.id as usize) {
+                *result = response
+                    .into_result()
+                    .and_then(|r| r.ok_or(RpcError::MissingResult));
             }
-            Err(error) => Err(error),
         }
+        Ok(results)
     }
 
-    pub async fn submit_block(&self, block_hex: String) -> Result<(), RpcError> {
-        let response = self
-            .send_json_rpc_request("submitblock", json!([block_hex]))
-            .await;
+    /// `None` is returned when the node answers with a `null` result
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<Option<T>, RpcError> {
+        let response = self.send_json_rpc_request(method, params).await?;
+        let response: JsonRpcResult<T> = serde_json::from_str(&response).map_err(|e| {
+            RpcError::Deserialization(e.to_string()) // TODO manage message ids
+        })?;
+        response.into_result()
+    }
 
-        match response {
-            Ok(_) => Ok(()),
-            Err(error) => Err(error),
-        }
+    async fn call_result<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, RpcError> {
+        self.call(method, params)
+            .await?
+            .ok_or(RpcError::MissingResult)
     }
 
     async fn send_json_rpc_request(
@@ -92,8 +237,6 @@ impl MiniRpcClient {
         method: &str,
         params: serde_json::Value,
     ) -> Result<String, RpcError> {
-        let client = &self.client;
-        let (username, password) = self.auth.clone().get_user_pass();
         let request = JsonRpcRequest {
             jsonrpc: "2.0".to_string(),
             method: method.to_string(),
@@ -105,6 +248,26 @@ impl MiniRpcClient {
             Ok(body) => body,
             Err(e) => return Err(RpcError::Serialization(e.to_string())),
         };
+        self.send_body(request_body).await
+    }
+
+    async fn send_body(&self, request_body: String) -> Result<String, RpcError> {
+        let mut attempt = 0;
+        loop {
+            match self.send_body_once(request_body.clone()).await {
+                Err(e) if e.is_transient() && attempt < self.retries => {
+                    attempt += 1;
+                    tokio::time::sleep(RETRY_DELAY * attempt).await;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    async fn send_body_once(&self, request_body: String) -> Result<String, RpcError> {
+        let client = &self.client;
+        // the cookie changes every time the node is restarted
+        let (username, password) = self.auth.get_user_pass()?;
 
         let req = Request::builder()
             .method("POST")
@@ -121,47 +284,196 @@ impl MiniRpcClient {
             .body(Full::<Bytes>::from(request_body))
             .map_err(|e| RpcError::Http(e.to_string()))?;
 
-        let response = client
-            .request(req)
-            .await
-            .map_err(|e| RpcError::Http(e.to_string()))?;
-
-        let status = response.status();
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .map_err(|e| RpcError::Http(e.to_string()))?
-            .to_bytes()
-            .to_vec();
+        let (status, body) = tokio::time::timeout(self.timeout, async {
+            let response = client
+                .request(req)
+                .await
+                .map_err(|e| RpcError::Http(e.to_string()))?;
+            let status = response.status();
+            let body = response
+                .into_body()
+                .collect()
+                .await
+                .map_err(|e| RpcError::Http(e.to_string()))?
+                .to_bytes()
+                .to_vec();
+            Ok::<_, RpcError>((status, body))
+        })
+        .await
+        .map_err(|_| RpcError::Timeout)??;
 
         if status.is_success() {
             String::from_utf8(body).map_err(|e| {
                 RpcError::Deserialization(e.to_string()) // TODO manage message ids
             })
+        } else if status == StatusCode::UNAUTHORIZED {
+            Err(RpcError::Auth("Wrong RPC credentials".to_string()))
         } else {
-            let error_result: Result<JsonRpcResult<_>, _> = serde_json::from_slice(&body);
+            // the node answers to failed calls with an error status and the error in the body
+            let error_result: Result<JsonRpcResult<serde_json::Value>, _> =
+                serde_json::from_slice(&body);
             match error_result {
-                Ok(error_response) => Err(error_response.into()),
-                Err(e) => Err(RpcError::Deserialization(e.to_string())),
+                Ok(JsonRpcResult {
+                    error: Some(error), ..
+                }) => Err(RpcError::JsonRpc(error)),
+                _ => Err(RpcError::Http(format!("Unexpected status {}", status))),
             }
         }
     }
 }
 
+fn decode_transaction(transaction_hex: String) -> Result<Transaction, RpcError> {
+    let transaction_bytes =
+        decode(transaction_hex).map_err(|e| RpcError::Deserialization(e.to_string()))?;
+    consensus_decode(&transaction_bytes).map_err(|e| RpcError::Deserialization(e.to_string()))
+}
+
 #[derive(Clone, Debug)]
-pub struct Auth {
-    username: String,
-    password: String,
+pub enum Auth {
+    UserPass(String, String),
+    /// Path of the cookie file written by the node when `rpcpassword` is not set, eg
+    /// `~/.bitcoin/.cookie`
+    CookieFile(PathBuf),
 }
 
 impl Auth {
-    pub fn get_user_pass(self) -> (String, String) {
-        (self.username, self.password)
+    pub fn get_user_pass(&self) -> Result<(String, String), RpcError> {
+        match self {
+            Auth::UserPass(username, password) => Ok((username.clone(), password.clone())),
+            Auth::CookieFile(path) => {
+                let cookie = std::fs::read_to_string(path).map_err(|e| {
+                    RpcError::Auth(format!("Can not read {}: {}", path.display(), e))
+                })?;
+                parse_cookie(&cookie)
+            }
+        }
     }
     pub fn new(username: String, password: String) -> Auth {
-        Auth { username, password }
+        Auth::UserPass(username, password)
     }
+    pub fn cookie_file(path: PathBuf) -> Auth {
+        Auth::CookieFile(path)
+    }
+}
+
+// The cookie is `__cookie__:<password>`
+fn parse_cookie(cookie: &str) -> Result<(String, String), RpcError> {
+    cookie
+        .trim_end()
+        .split_once(':')
+        .map(|(username, password)| (username.to_string(), password.to_string()))
+        .ok_or_else(|| RpcError::Auth("Invalid cookie file".to_string()))
+}
+
+/// Subset of the `getblocktemplate` result
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlockTemplate {
+    pub version: i32,
+    pub previousblockhash: String,
+    /// Compact target, hex encoded
+    pub bits: String,
+    pub height: u64,
+    pub coinbasevalue: u64,
+    pub sigoplimit: u64,
+    pub weightlimit: u64,
+    pub transactions: Vec<BlockTemplateTransaction>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlockTemplateTransaction {
+    pub txid: String,
+    pub fee: u64,
+    pub sigops: u64,
+    pub weight: u64,
+}
+
+/// Subset of a `getrawmempool true` / `getmempoolentry` entry
+#[derive(Debug, Deserialize, Clone)]
+pub struct MempoolEntry {
+    pub vsize: u64,
+    pub weight: u64,
+    pub ancestorcount: u64,
+    pub ancestorsize: u64,
+    pub fees: MempoolEntryFees,
+    pub depends: Vec<String>,
+}
+
+/// Fees of a mempool entry, in BTC
+#[derive(Debug, Deserialize, Clone)]
+pub struct MempoolEntryFees {
+    pub base: f64,
+    pub modified: f64,
+    pub ancestor: f64,
+}
+
+/// Result of `getblockheader` with `verbose = true`
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlockHeaderInfo {
+    pub hash: String,
+    /// -1 when the block is not in the main chain
+    pub confirmations: i64,
+    pub height: u64,
+    pub version: i32,
+    pub merkleroot: String,
+    pub time: u32,
+    pub mediantime: u32,
+    pub nonce: u32,
+    /// Compact target, hex encoded
+    pub bits: String,
+    pub difficulty: f64,
+    pub chainwork: String,
+    pub previousblockhash: Option<String>,
+    pub nextblockhash: Option<String>,
+}
+
+/// One entry of the `testmempoolaccept` result
+#[derive(Debug, Deserialize, Clone)]
+pub struct MempoolAcceptResult {
+    pub txid: String,
+    pub wtxid: Option<String>,
+    pub allowed: Option<bool>,
+    pub vsize: Option<u64>,
+    pub fees: Option<MempoolAcceptFees>,
+    #[serde(rename = "reject-reason")]
+    pub reject_reason: Option<String>,
+}
+
+/// Fees of an accepted transaction, in BTC
+#[derive(Debug, Deserialize, Clone)]
+pub struct MempoolAcceptFees {
+    pub base: f64,
+}
+
+/// Subset of the `getnetworkinfo` result
+#[derive(Debug, Deserialize, Clone)]
+pub struct NetworkInfo {
+    pub version: u64,
+    pub subversion: String,
+    pub protocolversion: u64,
+    pub connections: u64,
+    pub networkactive: bool,
+    /// BTC/kvB
+    pub relayfee: f64,
+    /// BTC/kvB
+    pub incrementalfee: f64,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum EstimateMode {
+    Unset,
+    Economical,
+    Conservative,
+}
+
+/// Result of `estimatesmartfee`
+#[derive(Debug, Deserialize, Clone)]
+pub struct SmartFeeEstimate {
+    /// BTC/kvB, missing when the node does not have enough data
+    pub feerate: Option<f64>,
+    pub errors: Option<Vec<String>>,
+    /// Number of blocks for which the estimate is valid
+    pub blocks: u64,
 }
 
 #[derive(Debug, Serialize)]
@@ -179,6 +491,15 @@ pub struct JsonRpcResult<T> {
     pub id: u64,
 }
 
+impl<T> JsonRpcResult<T> {
+    fn into_result(self) -> Result<Option<T>, RpcError> {
+        match self.error {
+            Some(error) => Err(RpcError::JsonRpc(error)),
+            None => Ok(self.result),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct JsonRpcError {
     pub code: i32,
@@ -187,17 +508,71 @@ pub struct JsonRpcError {
 
 #[derive(Debug, Deserialize)]
 pub enum RpcError {
-    // TODO this type is slightly incorrect, as the JsonRpcError evaluates a generic that is meant
-    // for the result field of JsonRpcResult struct. This should be corrected
-    JsonRpc(JsonRpcResult<JsonRpcError>),
+    /// Error returned by the node, eg unknown txid or invalid params
+    JsonRpc(JsonRpcError),
     Deserialization(String),
     Serialization(String),
     Http(String),
+    /// Wrong credentials or unreadable cookie file
+    Auth(String),
+    Timeout,
+    /// The node answered without a result
+    MissingResult,
     Other(String),
 }
 
-impl From<JsonRpcResult<JsonRpcError>> for RpcError {
-    fn from(error: JsonRpcResult<JsonRpcError>) -> Self {
-        Self::JsonRpc(error)
+impl RpcError {
+    /// Errors for which the same request can succeed if sent again
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RpcError::Http(_) | RpcError::Timeout => true,
+            RpcError::JsonRpc(error) => error.code == RPC_IN_WARMUP,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::JsonRpc(e) => write!(f, "RPC error {}: {}", e.code, e.message),
+            RpcError::Deserialization(e) => write!(f, "Deserialization error: {}", e),
+            RpcError::Serialization(e) => write!(f, "Serialization error: {}", e),
+            RpcError::Http(e) => write!(f, "HTTP error: {}", e),
+            RpcError::Auth(e) => write!(f, "Authentication error: {}", e),
+            RpcError::Timeout => write!(f, "Timed out"),
+            RpcError::MissingResult => write!(f, "Result not found"),
+            RpcError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_cookie() {
+        let (username, password) = parse_cookie("__cookie__:a1b2:c3\n").unwrap();
+        assert_eq!(username, "__cookie__");
+        assert_eq!(password, "a1b2:c3");
+        assert!(matches!(parse_cookie("nocolon"), Err(RpcError::Auth(_))));
+    }
+
+    #[test]
+    fn test_json_rpc_result() {
+        let response: JsonRpcResult<String> =
+            serde_json::from_str(r#"{"result":null,"error":null,"id":1}"#).unwrap();
+        assert!(matches!(response.into_result(), Ok(None)));
+
+        let response: JsonRpcResult<String> = serde_json::from_str(
+            r#"{"result":null,"error":{"code":-28,"message":"Loading block index..."},"id":1}"#,
+        )
+        .unwrap();
+        let error = response.into_result().unwrap_err();
+        assert!(error.is_transient());
+        assert!(!RpcError::MissingResult.is_transient());
     }
 }
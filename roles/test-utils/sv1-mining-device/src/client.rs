@@ -6,7 +6,7 @@ use async_std::{io::BufReader, prelude::*, task};
 use num_bigint::BigUint;
 use num_traits::FromPrimitive;
 use roles_logic_sv2::utils::Mutex;
-use std::{sync::Arc, time};
+use std::{net::SocketAddr, sync::Arc, time};
 
 use stratum_common::bitcoin::util::uint::Uint256;
 use v1::{
@@ -19,12 +19,10 @@ use v1::{
 
 use crate::{job::Job, miner::Miner};
 
-const ADDR: &str = "127.0.0.1:34255";
-
 /// Represents the Mining Device client which is connected to a Upstream node (either a SV1 Pool
 /// server or a SV1 <-> SV2 Translator Proxy server).
 #[derive(Debug, Clone)]
-pub(crate) struct Client {
+pub struct Client {
     client_id: u32,
     extranonce1: Option<Extranonce<'static>>,
     extranonce2_size: Option<usize>,
@@ -48,13 +46,13 @@ impl Client {
     /// messages to the Upstream, and the third is responsible for pass valid job submissions to
     /// the first set of channels:
     /// 1. `(sender_incoming, receiver_incoming)`:
-    ///     `sender_incoming` listens on the socket where messages are being sent from the Upstream
-    ///     node. From the socket, it reads the incoming bytes from the Upstream into a
-    ///     `BufReader`. The incoming bytes represent a message from the Upstream, and each new
-    ///     line is a new message. When it gets this line (a message) from the Upstream, it sends
-    ///     them to the `receiver_incoming` which is listening in a loop. The message line received
-    ///     by the `receiver_incoming` are then parsed by the `Client` in the `parse_message`
-    ///     method to be handled.
+    ///    `sender_incoming` listens on the socket where messages are being sent from the Upstream
+    ///    node. From the socket, it reads the incoming bytes from the Upstream into a
+    ///    `BufReader`. The incoming bytes represent a message from the Upstream, and each new
+    ///    line is a new message. When it gets this line (a message) from the Upstream, it sends
+    ///    them to the `receiver_incoming` which is listening in a loop. The message line received
+    ///    by the `receiver_incoming` are then parsed by the `Client` in the `parse_message`
+    ///    method to be handled.
     /// 2. `(sender_outgoing, receiver_outgoing)`:
     ///    When the `parse_message` method on the `Client` is called, it handles the message and
     ///    formats the a new message to be sent to the Upstream in response. It sends the response
@@ -71,8 +69,18 @@ impl Client {
     ///    task. In this task, once `receiver_share` gets the information from `sender_share`, it is
     ///    formatted as a `v1::client_to_server::Submit` and then serialized into a json message
     ///    that is sent to the Upstream via `sender_outgoing`.
-    pub(crate) async fn connect(client_id: u32) {
-        let stream = std::sync::Arc::new(TcpStream::connect(ADDR).await.unwrap());
+    ///
+    /// Until the Upstream at `upstream_addr` accepts the connection, it tries again every second.
+    pub async fn connect(client_id: u32, upstream_addr: SocketAddr) {
+        let stream = loop {
+            match TcpStream::connect(upstream_addr).await {
+                Ok(stream) => break std::sync::Arc::new(stream),
+                Err(e) => {
+                    println!("Failed to connect to {}: {}, retrying", upstream_addr, e);
+                    task::sleep(time::Duration::from_secs(1)).await;
+                }
+            }
+        };
         let (reader, writer) = (stream.clone(), stream);
 
         // `sender_incoming` listens on socket for incoming messages from the Upstream and sends
@@ -100,23 +108,30 @@ impl Client {
         miner.safe_lock(|m| m.new_target(default_target)).unwrap();
 
         let miner_cloned = miner.clone();
+        // Kept to close the share and outgoing channels once the Upstream closes the connection,
+        // so that the mining thread and the tasks below exit
+        let share_channel = sender_share.clone();
+        let outgoing_channel: Sender<String> = sender_outgoing.clone();
 
         // Reads messages sent by the Upstream from the socket to be passed to the
         // `receiver_incoming`
         task::spawn(async move {
             let mut messages = BufReader::new(&*reader).lines();
-            while let Some(message) = messages.next().await {
-                let message = message.unwrap();
-                sender_incoming.send(message).await.unwrap();
+            while let Some(Ok(message)) = messages.next().await {
+                if sender_incoming.send(message).await.is_err() {
+                    break;
+                }
             }
         });
 
         // Waits to receive a message from `sender_outgoing` and writes it to the socket for the
         // Upstream to receive
         task::spawn(async move {
-            loop {
-                let message: String = receiver_outgoing.recv().await.unwrap();
-                (&*writer).write_all(message.as_bytes()).await.unwrap();
+            while let Ok(message) = receiver_outgoing.recv().await {
+                if (&*writer).write_all(message.as_bytes()).await.is_err() {
+                    receiver_outgoing.close();
+                    break;
+                }
             }
         });
 
@@ -151,6 +166,9 @@ impl Client {
         // Is a separate thread as it can be CPU intensive and we do not want to block the reading
         // and writing of messages to the socket.
         std::thread::spawn(move || loop {
+            if sender_share.is_closed() {
+                break;
+            }
             if miner_cloned.safe_lock(|m| m.next_share()).unwrap().is_ok() {
                 let nonce = miner_cloned.safe_lock(|m| m.header.unwrap().nonce).unwrap();
                 let time = miner_cloned.safe_lock(|m| m.header.unwrap().time).unwrap();
@@ -158,10 +176,14 @@ impl Client {
                 let version = miner_cloned.safe_lock(|m| m.version).unwrap();
                 // Sends relevant candidate block header values needed to construct a
                 // `mining.submit` message to the `receiver_share` in the task that is responsible for
-                // sending messages to the Upstream node.
-                sender_share
-                    .try_send((nonce, job_id.unwrap(), version.unwrap(), time))
-                    .unwrap();
+                // sending messages to the Upstream node. Waits when the channel is full, as it
+                // happens with a very easy target.
+                if sender_share
+                    .send_blocking((nonce, job_id.unwrap(), version.unwrap(), time))
+                    .is_err()
+                {
+                    break;
+                }
             }
             miner_cloned
                 .safe_lock(|m| m.header.as_mut().map(|h| h.nonce += 1))
@@ -174,8 +196,7 @@ impl Client {
         let cloned = client.clone();
         task::spawn(async move {
             let recv = receiver_share.clone();
-            loop {
-                let (nonce, job_id, _version, ntime) = recv.recv().await.unwrap();
+            while let Ok((nonce, job_id, _version, ntime)) = recv.recv().await {
                 if cloned.clone().safe_lock(|c| c.status).unwrap() != ClientStatus::Subscribed {
                     continue;
                 }
@@ -194,7 +215,9 @@ impl Client {
                 };
                 let message: json_rpc::Message = submit.into();
                 let message = format!("{}\n", serde_json::to_string(&message).unwrap());
-                sender_outgoing_clone.send(message).await.unwrap();
+                if sender_outgoing_clone.send(message).await.is_err() {
+                    break;
+                }
             }
         });
         let recv_incoming = client.safe_lock(|c| c.receiver_incoming.clone()).unwrap();
@@ -203,7 +226,12 @@ impl Client {
             match client.clone().safe_lock(|c| c.status).unwrap() {
                 ClientStatus::Init => panic!("impossible state"),
                 ClientStatus::Configured => {
-                    let incoming = recv_incoming.clone().recv().await.unwrap();
+                    let Ok(incoming) = recv_incoming.recv().await else {
+                        println!("Upstream {} closed the connection", upstream_addr);
+                        share_channel.close();
+                        outgoing_channel.close();
+                        return;
+                    };
                     Self::parse_message(client.clone(), Ok(incoming)).await;
                 }
                 ClientStatus::Subscribed => {
@@ -213,11 +241,13 @@ impl Client {
             }
         }
         // Waits for the `sender_incoming` to get message line from socket to be parsed by the
-        // `Client`
-        loop {
-            let incoming = recv_incoming.recv().await.unwrap();
+        // `Client`, until the upstream closes the connection
+        while let Ok(incoming) = recv_incoming.recv().await {
             Self::parse_message(client.clone(), Ok(incoming)).await;
         }
+        println!("Upstream {} closed the connection", upstream_addr);
+        share_channel.close();
+        outgoing_channel.close();
     }
 
     /// Parse SV1 messages received from the Upstream node.
@@ -394,7 +424,7 @@ impl IsClient<'static> for Client {
         id: u64,
         name: String,
         password: String,
-    ) -> Result<json_rpc::Message, Error> {
+    ) -> Result<json_rpc::Message, Error<'_>> {
         match self.status() {
             ClientStatus::Init => Err(Error::IncorrectClientStatus("mining.authorize".to_string())),
             _ => {
@@ -404,7 +434,7 @@ impl IsClient<'static> for Client {
         }
     }
 
-    fn last_notify(&self) -> Option<server_to_client::Notify> {
+    fn last_notify(&self) -> Option<server_to_client::Notify<'_>> {
         None
     }
 
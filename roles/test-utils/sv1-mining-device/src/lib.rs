@@ -0,0 +1,4 @@
+pub mod client;
+pub(crate) mod job;
+pub(crate) mod miner;
+pub use client::Client;
@@ -1,9 +1,8 @@
-pub(crate) mod client;
-pub(crate) mod job;
-pub(crate) mod miner;
-pub(crate) use client::Client;
+use sv1_mining_device::Client;
+
+const ADDR: &str = "127.0.0.1:34255";
 
 #[async_std::main]
 async fn main() {
-    Client::connect(80).await
+    Client::connect(80, ADDR.parse().unwrap()).await
 }
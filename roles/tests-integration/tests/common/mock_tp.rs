@@ -0,0 +1,505 @@
+use super::sniffer::{MessagesAggregator, MsgType, Sniffer};
+use async_channel::Sender;
+use binary_sv2::{Seq064K, B016M, U256};
+use codec_sv2::{StandardEitherFrame, StandardSv2Frame};
+use quickcheck::{Arbitrary, Gen};
+use roles_logic_sv2::{
+    common_messages_sv2::{
+        Protocol, SetupConnection, SetupConnectionError, SetupConnectionSuccess,
+    },
+    errors::Error,
+    handlers::template_distribution::{ParseClientTemplateDistributionMessages, SendTo},
+    job_creator::template_gen::template_from_gen,
+    parsers::{AnyMessage, CommonMessages, TemplateDistribution},
+    template_distribution_sv2::{
+        CoinbaseOutputDataSize, NewTemplate, RequestTransactionData, RequestTransactionDataError,
+        RequestTransactionDataSuccess, SetNewPrevHash, SubmitSolution,
+    },
+    utils::{merkle_root_from_path, u256_to_block_hash, Mutex},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use stratum_common::bitcoin::{
+    blockdata::block::BlockHeader, hash_types::TxMerkleNode, hashes::Hash,
+};
+use tokio::net::{TcpListener, TcpStream};
+
+type MessageFrame = StandardEitherFrame<AnyMessage<'static>>;
+type StdFrame = StandardSv2Frame<AnyMessage<'static>>;
+
+/// Compact target of the regtest blocks, about half of the block hashes are below it
+pub const REGTEST_N_BITS: u32 = 0x207fffff;
+
+/// Target of [`REGTEST_N_BITS`] as little endian bytes
+fn regtest_target() -> U256<'static> {
+    let mut target = [0_u8; 32];
+    target[29..].copy_from_slice(&[0xff, 0xff, 0x7f]);
+    target.into()
+}
+
+/// A message that the [`MockTemplateProvider`] sends to its clients.
+#[derive(Debug, Clone)]
+pub enum TemplateEvent {
+    NewTemplate(NewTemplate<'static>),
+    SetNewPrevHash(SetNewPrevHash<'static>),
+}
+
+impl From<TemplateEvent> for TemplateDistribution<'static> {
+    fn from(event: TemplateEvent) -> Self {
+        match event {
+            TemplateEvent::NewTemplate(m) => TemplateDistribution::NewTemplate(m),
+            TemplateEvent::SetNewPrevHash(m) => TemplateDistribution::SetNewPrevHash(m),
+        }
+    }
+}
+
+/// Generates random templates, with increasing template ids, on top of random previous blocks.
+///
+/// The templates are built with `template_from_gen`, the same generator used by the property
+/// tests of the job creator.
+pub struct TemplateGenerator {
+    gen: Gen,
+    next_template_id: u64,
+}
+
+impl TemplateGenerator {
+    pub fn new() -> Self {
+        Self {
+            gen: Gen::new(255),
+            next_template_id: 1,
+        }
+    }
+
+    /// Returns a template to be mined on the current previous block
+    pub fn template(&mut self) -> NewTemplate<'static> {
+        self.new_template(false)
+    }
+
+    /// Returns a future template and the `SetNewPrevHash` that activates it
+    pub fn block(&mut self) -> (NewTemplate<'static>, SetNewPrevHash<'static>) {
+        let template = self.new_template(true);
+        let mut prev_hash = [0_u8; 32];
+        for byte in prev_hash.iter_mut() {
+            *byte = u8::arbitrary(&mut self.gen);
+        }
+        let header_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time before the unix epoch")
+            .as_secs() as u32;
+        let set_new_prev_hash = SetNewPrevHash {
+            template_id: template.template_id,
+            prev_hash: prev_hash.into(),
+            header_timestamp,
+            n_bits: REGTEST_N_BITS,
+            target: regtest_target(),
+        };
+        (template, set_new_prev_hash)
+    }
+
+    /// Returns `blocks` new blocks, each one followed by `templates_per_block` templates
+    pub fn sequence(&mut self, blocks: usize, templates_per_block: usize) -> Vec<TemplateEvent> {
+        let mut events = Vec::with_capacity(blocks * (templates_per_block + 2));
+        for _ in 0..blocks {
+            let (template, set_new_prev_hash) = self.block();
+            events.push(TemplateEvent::NewTemplate(template));
+            events.push(TemplateEvent::SetNewPrevHash(set_new_prev_hash));
+            for _ in 0..templates_per_block {
+                events.push(TemplateEvent::NewTemplate(self.template()));
+            }
+        }
+        events
+    }
+
+    fn new_template(&mut self, future_template: bool) -> NewTemplate<'static> {
+        let mut template = template_from_gen(&mut self.gen);
+        // The job creator expects the bip34 height at the start of the coinbase prefix
+        while template.coinbase_prefix.to_vec().len() < 3 {
+            template = template_from_gen(&mut self.gen);
+        }
+        template.template_id = self.next_template_id;
+        template.future_template = future_template;
+        self.next_template_id += 1;
+        template
+    }
+}
+
+impl Default for TemplateGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the [`MockTemplateProvider`] knows about the chain, it is updated when a [`TemplateEvent`]
+/// is sent and it is used to answer the clients.
+struct MockTpState {
+    script: Vec<TemplateEvent>,
+    generator: TemplateGenerator,
+    templates: HashMap<u64, NewTemplate<'static>>,
+    stale_templates: HashSet<u64>,
+    transactions: HashMap<u64, Vec<Vec<u8>>>,
+    prev_hash: Option<SetNewPrevHash<'static>>,
+    solutions: Vec<(SubmitSolution<'static>, bool)>,
+}
+
+impl MockTpState {
+    fn apply(&mut self, event: &TemplateEvent) {
+        match event {
+            TemplateEvent::NewTemplate(template) => {
+                self.generator.next_template_id = self
+                    .generator
+                    .next_template_id
+                    .max(template.template_id + 1);
+                self.templates
+                    .insert(template.template_id, template.clone());
+            }
+            TemplateEvent::SetNewPrevHash(prev_hash) => {
+                let stale: Vec<u64> = self
+                    .templates
+                    .keys()
+                    .copied()
+                    .filter(|id| *id != prev_hash.template_id)
+                    .collect();
+                for id in stale {
+                    self.templates.remove(&id);
+                    self.stale_templates.insert(id);
+                }
+                self.prev_hash = Some(prev_hash.clone());
+            }
+        }
+    }
+
+    /// Checks that the header of the solution hashes below the target of the current previous
+    /// block
+    fn is_valid(&self, solution: &SubmitSolution) -> bool {
+        let (template, prev_hash) =
+            match (self.templates.get(&solution.template_id), &self.prev_hash) {
+                (Some(template), Some(prev_hash)) => (template, prev_hash),
+                _ => return false,
+            };
+        let merkle_root = match merkle_root_from_path(
+            &solution.coinbase_tx.to_vec(),
+            &[],
+            &[],
+            &template.merkle_path.to_vec(),
+        ) {
+            Some(merkle_root) => merkle_root,
+            None => return false,
+        };
+        let header = BlockHeader {
+            version: solution.version as i32,
+            prev_blockhash: u256_to_block_hash(prev_hash.prev_hash.clone()),
+            merkle_root: TxMerkleNode::from_inner(merkle_root.try_into().unwrap()),
+            time: solution.header_timestamp,
+            bits: prev_hash.n_bits,
+            nonce: solution.header_nonce,
+        };
+        header.validate_pow(&header.target()).is_ok()
+    }
+}
+
+impl ParseClientTemplateDistributionMessages for MockTpState {
+    fn handle_coinbase_out_data_size(
+        &mut self,
+        _m: CoinbaseOutputDataSize,
+    ) -> Result<SendTo, Error> {
+        Ok(SendTo::Multiple(
+            self.script
+                .iter()
+                .map(|event| SendTo::Respond(event.clone().into()))
+                .collect(),
+        ))
+    }
+
+    fn handle_request_tx_data(&mut self, m: RequestTransactionData) -> Result<SendTo, Error> {
+        let template_id = m.template_id;
+        if self.templates.contains_key(&template_id) {
+            let transactions = self
+                .transactions
+                .get(&template_id)
+                .cloned()
+                .unwrap_or_default();
+            let transaction_list: Vec<B016M> = transactions
+                .into_iter()
+                .map(|transaction| transaction.try_into())
+                .collect::<Result<_, _>>()?;
+            Ok(SendTo::Respond(
+                TemplateDistribution::RequestTransactionDataSuccess(
+                    RequestTransactionDataSuccess {
+                        template_id,
+                        excess_data: Vec::new().try_into()?,
+                        transaction_list: Seq064K::new(transaction_list)?,
+                    },
+                ),
+            ))
+        } else {
+            let error_code = if self.stale_templates.contains(&template_id) {
+                "stale-template-id"
+            } else {
+                "template-id-not-found"
+            };
+            Ok(SendTo::Respond(
+                TemplateDistribution::RequestTransactionDataError(RequestTransactionDataError {
+                    template_id,
+                    error_code: error_code.to_string().try_into()?,
+                }),
+            ))
+        }
+    }
+
+    fn handle_request_submit_solution(&mut self, m: SubmitSolution) -> Result<SendTo, Error> {
+        let valid = self.is_valid(&m);
+        self.solutions.push((m.into_static(), valid));
+        Ok(SendTo::None(None))
+    }
+}
+
+/// A Template Provider that runs in the test process, so that the tests do not need a
+/// `bitcoind` with the sv2 patches.
+///
+/// Every client that sends `CoinbaseOutputDataSize` receives the script, by default a random
+/// future template and the `SetNewPrevHash` that activates it. The tests can then send more
+/// templates and blocks to all the connected clients with [`MockTemplateProvider::new_template`],
+/// [`MockTemplateProvider::new_block`] and [`MockTemplateProvider::send_event`].
+///
+/// `RequestTransactionData` is answered with the transactions set with
+/// [`MockTemplateProvider::set_transactions`], or with none. `SubmitSolution` is checked against
+/// the target of the last `SetNewPrevHash` and recorded, see [`MockTemplateProvider::solutions`].
+///
+/// As for the [`Sniffer`], the exchanged messages can be accessed as FIFO queues.
+#[derive(Clone)]
+pub struct MockTemplateProvider {
+    listening_address: SocketAddr,
+    state: Arc<Mutex<MockTpState>>,
+    clients: Arc<Mutex<Vec<Sender<MessageFrame>>>>,
+    downstream_messages: MessagesAggregator,
+    upstream_messages: MessagesAggregator,
+}
+
+impl MockTemplateProvider {
+    /// Creates a Template Provider that sends a random block to its clients
+    pub fn new(listening_address: SocketAddr) -> Self {
+        let script = TemplateGenerator::new().sequence(1, 0);
+        Self::with_script(listening_address, script)
+    }
+
+    /// Creates a Template Provider that sends `script` to its clients, the templates generated
+    /// later have bigger ids than the ones in `script`
+    pub fn with_script(listening_address: SocketAddr, script: Vec<TemplateEvent>) -> Self {
+        let mut state = MockTpState {
+            script: script.clone(),
+            generator: TemplateGenerator::new(),
+            templates: HashMap::new(),
+            stale_templates: HashSet::new(),
+            transactions: HashMap::new(),
+            prev_hash: None,
+            solutions: Vec::new(),
+        };
+        for event in &script {
+            state.apply(event);
+        }
+        Self {
+            listening_address,
+            state: Arc::new(Mutex::new(state)),
+            clients: Arc::new(Mutex::new(Vec::new())),
+            downstream_messages: MessagesAggregator::new(),
+            upstream_messages: MessagesAggregator::new(),
+        }
+    }
+
+    /// Starts listening, the clients are served in background tasks.
+    pub async fn start(&self) {
+        let listener = TcpListener::bind(self.listening_address)
+            .await
+            .expect("Impossible to listen on given address");
+        let self_ = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let client = self_.clone();
+                tokio::spawn(async move { client.serve(stream).await });
+            }
+        });
+    }
+
+    /// Sends a new random future template and the `SetNewPrevHash` that activates it
+    pub async fn new_block(&self) -> (NewTemplate<'static>, SetNewPrevHash<'static>) {
+        let (template, prev_hash) = self
+            .state
+            .safe_lock(|state| state.generator.block())
+            .unwrap();
+        self.send_event(TemplateEvent::NewTemplate(template.clone()))
+            .await;
+        self.send_event(TemplateEvent::SetNewPrevHash(prev_hash.clone()))
+            .await;
+        (template, prev_hash)
+    }
+
+    /// Sends a new random template built on the current previous block
+    pub async fn new_template(&self) -> NewTemplate<'static> {
+        let template = self
+            .state
+            .safe_lock(|state| state.generator.template())
+            .unwrap();
+        self.send_event(TemplateEvent::NewTemplate(template.clone()))
+            .await;
+        template
+    }
+
+    /// Sends `event` to all the clients that are already set up
+    pub async fn send_event(&self, event: TemplateEvent) {
+        self.state.safe_lock(|state| state.apply(&event)).unwrap();
+        let clients = self.clients.safe_lock(|clients| clients.clone()).unwrap();
+        for client in clients {
+            let _ = self
+                .send(
+                    &client,
+                    AnyMessage::TemplateDistribution(event.clone().into()),
+                )
+                .await;
+        }
+    }
+
+    /// Sets the transactions sent in the `RequestTransactionDataSuccess` for `template_id`
+    pub fn set_transactions(&self, template_id: u64, transactions: Vec<Vec<u8>>) {
+        self.state
+            .safe_lock(|state| state.transactions.insert(template_id, transactions))
+            .unwrap();
+    }
+
+    /// Returns the received solutions, each one with whether it meets the target
+    pub fn solutions(&self) -> Vec<(SubmitSolution<'static>, bool)> {
+        self.state
+            .safe_lock(|state| state.solutions.clone())
+            .unwrap()
+    }
+
+    /// Waits up to `timeout` for a solution that meets the target and returns it, `None` when
+    /// none arrives in time
+    pub async fn wait_for_block(&self, timeout: Duration) -> Option<SubmitSolution<'static>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let block = self
+                .solutions()
+                .into_iter()
+                .find(|(_, valid)| *valid)
+                .map(|(solution, _)| solution);
+            if block.is_some() || Instant::now() >= deadline {
+                return block;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Returns the oldest message sent by a client.
+    ///
+    /// The queue is FIFO and once a message is returned it is removed from the queue.
+    pub fn next_downstream_message(&self) -> Option<(MsgType, AnyMessage<'static>)> {
+        self.downstream_messages.next_message()
+    }
+
+    /// Returns the oldest message sent to a client.
+    ///
+    /// The queue is FIFO and once a message is returned it is removed from the queue.
+    pub fn next_upstream_message(&self) -> Option<(MsgType, AnyMessage<'static>)> {
+        self.upstream_messages.next_message()
+    }
+
+    async fn serve(self, stream: TcpStream) {
+        let (receiver, sender) = match Sniffer::create_downstream(stream).await {
+            Some(connection) => connection,
+            None => return,
+        };
+        while let Ok(mut frame) = receiver.recv().await {
+            let (msg_type, message) = Sniffer::message_from_frame(&mut frame);
+            self.downstream_messages
+                .add_message(msg_type, message.clone());
+            let responses = match message {
+                AnyMessage::Common(CommonMessages::SetupConnection(m)) => {
+                    vec![Self::on_setup_connection(m)]
+                }
+                AnyMessage::TemplateDistribution(m) => {
+                    if let TemplateDistribution::CoinbaseOutputDataSize(_) = m {
+                        self.clients
+                            .safe_lock(|clients| clients.push(sender.clone()))
+                            .unwrap();
+                    }
+                    match MockTpState::handle_message_template_distribution_desrialized(
+                        self.state.clone(),
+                        Ok(m),
+                    ) {
+                        Ok(send_to) => Self::into_messages(send_to)
+                            .into_iter()
+                            .map(AnyMessage::TemplateDistribution)
+                            .collect(),
+                        Err(e) => {
+                            println!("Mock Template Provider failed to handle message: {e:?}");
+                            vec![]
+                        }
+                    }
+                }
+                m => {
+                    println!("Mock Template Provider received unexpected message: {m:?}");
+                    vec![]
+                }
+            };
+            for response in responses {
+                if self.send(&sender, response).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn on_setup_connection(m: SetupConnection) -> AnyMessage<'static> {
+        if m.protocol != Protocol::TemplateDistributionProtocol {
+            SetupConnectionError {
+                flags: 0,
+                error_code: "unsupported-protocol".to_string().try_into().unwrap(),
+            }
+            .into()
+        } else if m.min_version > 2 || m.max_version < 2 {
+            SetupConnectionError {
+                flags: 0,
+                error_code: "protocol-version-mismatch".to_string().try_into().unwrap(),
+            }
+            .into()
+        } else {
+            SetupConnectionSuccess {
+                used_version: 2,
+                flags: 0,
+            }
+            .into()
+        }
+    }
+
+    fn into_messages(send_to: SendTo) -> Vec<TemplateDistribution<'static>> {
+        match send_to {
+            SendTo::Respond(m) => vec![m],
+            SendTo::Multiple(send_to) => {
+                send_to.into_iter().flat_map(Self::into_messages).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    async fn send(
+        &self,
+        sender: &Sender<MessageFrame>,
+        message: AnyMessage<'static>,
+    ) -> Result<(), ()> {
+        let frame: StdFrame = message
+            .clone()
+            .try_into()
+            .expect("Failed to encode message");
+        let msg_type = frame
+            .get_header()
+            .expect("Encoded frame without header")
+            .msg_type();
+        self.upstream_messages.add_message(msg_type, message);
+        sender.send(frame.into()).await.map_err(|_| ())
+    }
+}
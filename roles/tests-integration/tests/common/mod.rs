@@ -1,173 +1,37 @@
-mod sniffer;
+// Every test crate includes this module and uses only some of the helpers
+#![allow(dead_code)]
+
+pub mod mock_tp;
+pub mod sniffer;
 
-use bitcoind::{bitcoincore_rpc::RpcApi, BitcoinD, Conf};
-use flate2::read::GzDecoder;
 use key_utils::{Secp256k1PublicKey, Secp256k1SecretKey};
+pub use mock_tp::{MockTemplateProvider, TemplateEvent};
 use once_cell::sync::Lazy;
 use pool_sv2::PoolSv2;
-use sniffer::Sniffer;
+pub use sniffer::Sniffer;
 use std::{
     collections::HashSet,
     convert::TryFrom,
-    env,
-    fs::{create_dir_all, File},
-    io::{BufReader, Read},
     net::{SocketAddr, TcpListener},
-    path::{Path, PathBuf},
     str::FromStr,
     sync::Mutex,
+    time::Duration,
 };
-use tar::Archive;
+
+// Key pair of every role started by the tests, the Sniffer uses it too
+const AUTHORITY_PUBLIC_KEY: &str = "9auqWEzQDVyd2oe1JVGFLMLHZtCo2FFqZwtKA5gd9xbuEu7PH72";
+const AUTHORITY_SECRET_KEY: &str = "mkDLTBBRxdBv998612qipDYoTK3YUrqLe8uWw7gu3iXbSrn2n";
+// Pool and JDS coinbase output
+const COINBASE_OUTPUT_SCRIPT_TYPE: &str = "P2WPKH";
+const COINBASE_OUTPUT_SCRIPT_VALUE: &str =
+    "036adc3bdf21e6f9a0f0fb0066bf517e5b7909ed1563d6958a10993849a7554075";
+// Hashrate announced by the translator and by its SV1 miners, low enough that the CPU miners find
+// shares every few seconds
+const MINER_HASHRATE: f32 = 100_000.0;
 
 // prevents get_available_port from ever returning the same port twice
 static UNIQUE_PORTS: Lazy<Mutex<HashSet<u16>>> = Lazy::new(|| Mutex::new(HashSet::new()));
 
-const VERSION_TP: &str = "0.1.9";
-
-fn download_bitcoind_tarball(download_url: &str) -> Vec<u8> {
-    let response = minreq::get(download_url)
-        .send()
-        .unwrap_or_else(|_| panic!("Cannot reach URL: {}", download_url));
-    assert_eq!(
-        response.status_code, 200,
-        "URL {} didn't return 200",
-        download_url
-    );
-    response.as_bytes().to_vec()
-}
-
-fn read_tarball_from_file(path: &str) -> Vec<u8> {
-    let file = File::open(path).unwrap_or_else(|_| {
-        panic!(
-            "Cannot find {:?} specified with env var BITCOIND_TARBALL_FILE",
-            path
-        )
-    });
-    let mut reader = BufReader::new(file);
-    let mut buffer = Vec::new();
-    reader.read_to_end(&mut buffer).unwrap();
-    buffer
-}
-
-fn unpack_tarball(tarball_bytes: &[u8], destination: &Path) {
-    let decoder = GzDecoder::new(tarball_bytes);
-    let mut archive = Archive::new(decoder);
-    for mut entry in archive.entries().unwrap().flatten() {
-        if let Ok(file) = entry.path() {
-            if file.ends_with("bitcoind") {
-                entry.unpack_in(destination).unwrap();
-            }
-        }
-    }
-}
-
-fn get_bitcoind_filename(os: &str, arch: &str) -> String {
-    match (os, arch) {
-        ("macos", "aarch64") => format!("bitcoin-sv2-tp-{}-arm64-apple-darwin.tar.gz", VERSION_TP),
-        ("macos", "x86_64") => format!("bitcoin-sv2-tp-{}-x86_64-apple-darwin.tar.gz", VERSION_TP),
-        ("linux", "x86_64") => format!("bitcoin-sv2-tp-{}-x86_64-linux-gnu.tar.gz", VERSION_TP),
-        ("linux", "aarch64") => format!("bitcoin-sv2-tp-{}-aarch64-linux-gnu.tar.gz", VERSION_TP),
-        _ => format!(
-            "bitcoin-sv2-tp-{}-x86_64-apple-darwin-unsigned.zip",
-            VERSION_TP
-        ),
-    }
-}
-
-pub struct TemplateProvider {
-    bitcoind: BitcoinD,
-}
-
-impl TemplateProvider {
-    pub fn start(port: u16) -> Self {
-        let temp_dir = PathBuf::from("/tmp/.template-provider");
-        let mut conf = Conf::default();
-        let staticdir = format!(".bitcoin-{}", port);
-        conf.staticdir = Some(temp_dir.join(staticdir));
-        let port = format!("-sv2port={}", port);
-        conf.args.extend(vec![
-            "-txindex=1",
-            "-sv2",
-            &port,
-            "-debug=sv2",
-            "-sv2interval=20",
-            "-sv2feedelta=1000",
-            "-loglevel=sv2:trace",
-        ]);
-
-        let os = env::consts::OS;
-        let arch = env::consts::ARCH;
-        let download_filename = get_bitcoind_filename(os, arch);
-        let bitcoin_exe_home = temp_dir
-            .join(format!("bitcoin-sv2-tp-{}", VERSION_TP))
-            .join("bin");
-
-        if !bitcoin_exe_home.exists() {
-            let tarball_bytes = match env::var("BITCOIND_TARBALL_FILE") {
-                Ok(path) => read_tarball_from_file(&path),
-                Err(_) => {
-                    let download_endpoint =
-                        env::var("BITCOIND_DOWNLOAD_ENDPOINT").unwrap_or_else(|_| {
-                            "https://github.com/Sjors/bitcoin/releases/download".to_owned()
-                        });
-                    let url = format!(
-                        "{}/sv2-tp-{}/{}",
-                        download_endpoint, VERSION_TP, download_filename
-                    );
-                    download_bitcoind_tarball(&url)
-                }
-            };
-
-            if let Some(parent) = bitcoin_exe_home.parent() {
-                create_dir_all(parent).unwrap();
-            }
-
-            unpack_tarball(&tarball_bytes, &temp_dir);
-
-            if os == "macos" {
-                let bitcoind_binary = bitcoin_exe_home.join("bitcoind");
-                std::process::Command::new("codesign")
-                    .arg("--sign")
-                    .arg("-")
-                    .arg(&bitcoind_binary)
-                    .output()
-                    .expect("Failed to sign bitcoind binary");
-            }
-        }
-
-        env::set_var("BITCOIND_EXE", bitcoin_exe_home.join("bitcoind"));
-        let exe_path = bitcoind::exe_path().unwrap();
-
-        let bitcoind = BitcoinD::with_conf(exe_path, &conf).unwrap();
-
-        TemplateProvider { bitcoind }
-    }
-
-    fn stop(&self) {
-        let _ = self.bitcoind.client.stop().unwrap();
-    }
-
-    fn generate_blocks(&self, n: u64) {
-        let mining_address = self
-            .bitcoind
-            .client
-            .get_new_address(None, None)
-            .unwrap()
-            .require_network(bitcoind::bitcoincore_rpc::bitcoin::Network::Regtest)
-            .unwrap();
-        self.bitcoind
-            .client
-            .generate_to_address(n, &mining_address)
-            .unwrap();
-    }
-}
-
-impl Drop for TemplateProvider {
-    fn drop(&mut self) {
-        self.stop();
-    }
-}
-
 fn is_port_open(address: SocketAddr) -> bool {
     TcpListener::bind(address).is_err()
 }
@@ -202,6 +66,14 @@ pub async fn start_sniffer(listening_address: SocketAddr, upstream: SocketAddr)
     sniffer
 }
 
+fn authority_keys() -> (Secp256k1PublicKey, Secp256k1SecretKey) {
+    let public_key =
+        Secp256k1PublicKey::try_from(AUTHORITY_PUBLIC_KEY.to_string()).expect("failed");
+    let secret_key =
+        Secp256k1SecretKey::try_from(AUTHORITY_SECRET_KEY.to_string()).expect("failed");
+    (public_key, secret_key)
+}
+
 #[derive(Debug)]
 struct TestPoolSv2 {
     pool: PoolSv2,
@@ -222,18 +94,11 @@ impl TestPoolSv2 {
             .unwrap_or(SocketAddr::from_str(&format!("127.0.0.1:{}", pool_port)).unwrap());
         let is_pool_port_open = is_port_open(listening_address);
         assert!(!is_pool_port_open);
-        let authority_public_key = Secp256k1PublicKey::try_from(
-            "9auqWEzQDVyd2oe1JVGFLMLHZtCo2FFqZwtKA5gd9xbuEu7PH72".to_string(),
-        )
-        .expect("failed");
-        let authority_secret_key = Secp256k1SecretKey::try_from(
-            "mkDLTBBRxdBv998612qipDYoTK3YUrqLe8uWw7gu3iXbSrn2n".to_string(),
-        )
-        .expect("failed");
+        let (authority_public_key, authority_secret_key) = authority_keys();
         let cert_validity_sec = 3600;
         let coinbase_outputs = vec![CoinbaseOutput::new(
-            "P2WPKH".to_string(),
-            "036adc3bdf21e6f9a0f0fb0066bf517e5b7909ed1563d6958a10993849a7554075".to_string(),
+            COINBASE_OUTPUT_SCRIPT_TYPE.to_string(),
+            COINBASE_OUTPUT_SCRIPT_VALUE.to_string(),
         )];
         let pool_signature = "Stratum v2 SRI Pool".to_string();
         let tp_address = if let Some(tp_add) = template_provider_address {
@@ -279,8 +144,155 @@ pub async fn start_pool(
     pool
 }
 
-pub async fn start_template_provider(tp_port: u16) -> TemplateProvider {
-    let template_provider = TemplateProvider::start(tp_port);
-    template_provider.generate_blocks(16);
+pub async fn start_template_provider(tp_port: u16) -> MockTemplateProvider {
+    let address = SocketAddr::from(([127, 0, 0, 1], tp_port));
+    let template_provider = MockTemplateProvider::new(address);
+    template_provider.start().await;
+    template_provider
+}
+
+pub async fn start_template_provider_with_script(
+    tp_port: u16,
+    script: Vec<TemplateEvent>,
+) -> MockTemplateProvider {
+    let address = SocketAddr::from(([127, 0, 0, 1], tp_port));
+    let template_provider = MockTemplateProvider::with_script(address, script);
+    template_provider.start().await;
     template_provider
 }
+
+/// Starts a JDS without a node, so it has no mempool and never submits blocks. Returns the address
+/// where it listens for the JDCs.
+pub async fn start_jds() -> SocketAddr {
+    use jd_server::{CoinbaseOutput, Configuration, CoreRpc, JobDeclaratorServer};
+    let listening_address = get_available_address();
+    let (authority_public_key, authority_secret_key) = authority_keys();
+    let coinbase_outputs = vec![CoinbaseOutput::new(
+        COINBASE_OUTPUT_SCRIPT_TYPE.to_string(),
+        COINBASE_OUTPUT_SCRIPT_VALUE.to_string(),
+    )];
+    // An empty url means no node
+    let core_rpc = CoreRpc::new(String::new(), 0, String::new(), String::new());
+    let config = Configuration::new(
+        listening_address.to_string(),
+        authority_public_key,
+        authority_secret_key,
+        3600,
+        coinbase_outputs,
+        core_rpc,
+        Duration::from_secs(1),
+    );
+    tokio::task::spawn(async move {
+        JobDeclaratorServer::new(config).start().await;
+    });
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    listening_address
+}
+
+/// Starts a JDC that gets the templates from `template_provider`, declares the jobs to `jds` and
+/// mines with `pool`. Returns the address where it listens for the downstream.
+pub async fn start_jdc(
+    pool: SocketAddr,
+    template_provider: SocketAddr,
+    jds: SocketAddr,
+) -> SocketAddr {
+    use jd_client::{
+        proxy_config::{CoinbaseOutput, ProxyConfig, ShutdownConfig, Upstream},
+        JobDeclaratorClient,
+    };
+    let listening_address = get_available_address();
+    let (authority_public_key, authority_secret_key) = authority_keys();
+    let upstream = Upstream {
+        authority_pubkey: authority_public_key,
+        pool_address: pool.to_string(),
+        jd_address: jds.to_string(),
+        pool_signature: "Stratum v2 SRI Pool".to_string(),
+    };
+    let config = ProxyConfig {
+        downstream_address: listening_address.ip().to_string(),
+        downstream_port: listening_address.port(),
+        max_supported_version: 2,
+        min_supported_version: 2,
+        min_extranonce2_size: 8,
+        withhold: false,
+        authority_public_key,
+        authority_secret_key,
+        cert_validity_sec: 3600,
+        tp_address: template_provider.to_string(),
+        tp_authority_public_key: Some(authority_public_key),
+        retry: 10,
+        upstreams: vec![upstream],
+        timeout: Duration::from_secs(10),
+        coinbase_outputs: vec![CoinbaseOutput::new(
+            COINBASE_OUTPUT_SCRIPT_TYPE.to_string(),
+            COINBASE_OUTPUT_SCRIPT_VALUE.to_string(),
+        )],
+        test_only_do_not_send_solution_to_tp: None,
+        metrics_address: None,
+        shutdown: ShutdownConfig::default(),
+    };
+    tokio::task::spawn(async move {
+        JobDeclaratorClient::new(config).start().await;
+    });
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    listening_address
+}
+
+/// Starts a translator that opens an extended channel with `upstream`, a pool or a JDC. Returns
+/// the address where it listens for the SV1 miners.
+///
+/// The translator listens only after the channel is open, the miners started with
+/// [`start_sv1_mining_device`] wait for it.
+pub async fn start_translator(upstream: SocketAddr) -> SocketAddr {
+    use translator_sv2::{
+        proxy_config::{
+            DownstreamConfig, DownstreamDifficultyConfig, ProxyConfig, UpstreamConfig,
+            UpstreamDifficultyConfig,
+        },
+        TranslatorSv2,
+    };
+    let listening_address = get_available_address();
+    let (authority_public_key, _) = authority_keys();
+    let upstream_config = UpstreamConfig::new(
+        upstream.ip().to_string(),
+        upstream.port(),
+        authority_public_key,
+        UpstreamDifficultyConfig::new(60, MINER_HASHRATE, 0, false),
+    );
+    let downstream_config = DownstreamConfig::new(
+        listening_address.ip().to_string(),
+        listening_address.port(),
+        DownstreamDifficultyConfig::new(MINER_HASHRATE, 60.0, 0, 0),
+    );
+    let config = ProxyConfig::new(upstream_config, downstream_config, 2, 2, 8);
+    tokio::task::spawn(async move {
+        TranslatorSv2::new(config).start().await;
+    });
+    listening_address
+}
+
+/// Starts a CPU SV1 miner connected to `upstream`, usually a translator. It mines at the
+/// difficulty set by the upstream and keeps trying to connect until the upstream listens.
+pub fn start_sv1_mining_device(upstream: SocketAddr) {
+    // The miner runs on async-std
+    std::thread::spawn(move || {
+        async_std::task::block_on(sv1_mining_device::Client::connect(0, upstream));
+    });
+}
+
+/// Starts a CPU SV2 mining device that opens a standard channel with `upstream`, a pool or a
+/// proxy. It announces a hundredth of its measured hashrate, so that its shares are frequent.
+pub fn start_mining_device(upstream: SocketAddr) {
+    let (authority_public_key, _) = authority_keys();
+    tokio::task::spawn(async move {
+        mining_device::connect(
+            upstream.to_string(),
+            Some(authority_public_key),
+            None,
+            None,
+            0,
+            Some(0.01),
+        )
+        .await;
+    });
+}
@@ -1,6 +1,7 @@
 use async_channel::{Receiver, Sender};
 use codec_sv2::{
     framing_sv2::framing::Frame, HandshakeRole, Initiator, Responder, StandardEitherFrame,
+    StandardSv2Frame,
 };
 use key_utils::{Secp256k1PublicKey, Secp256k1SecretKey};
 use network_helpers_sv2::noise_connection_tokio::Connection;
@@ -18,13 +19,83 @@ use roles_logic_sv2::{
     },
     utils::Mutex,
 };
-use std::{collections::VecDeque, convert::TryInto, net::SocketAddr, sync::Arc};
+use std::{
+    collections::VecDeque,
+    convert::TryInto,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{
     net::{TcpListener, TcpStream},
     select,
+    task::AbortHandle,
 };
 type MessageFrame = StandardEitherFrame<AnyMessage<'static>>;
-type MsgType = u8;
+type StdFrame = StandardSv2Frame<AnyMessage<'static>>;
+pub(crate) type MsgType = u8;
+// Channels of a noise connection and the handles of its reading and writing tasks
+type ConnectionTasks = (
+    Receiver<MessageFrame>,
+    Sender<MessageFrame>,
+    AbortHandle,
+    AbortHandle,
+);
+
+/// One of the two roles connected through the [`Sniffer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Downstream,
+    Upstream,
+}
+
+/// What the [`Sniffer`] does with an intercepted message instead of forwarding it as it is
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// The message is not forwarded
+    Drop,
+    /// The message is forwarded after the delay, the messages that follow it wait too
+    Delay(Duration),
+    /// The message is forwarded twice
+    Duplicate,
+    /// The message is held and forwarded right after the next message in the same direction
+    Reorder,
+    /// The given message is forwarded in place of the intercepted one
+    Replace(AnyMessage<'static>),
+    /// The payload bytes at the given offsets are flipped, the offsets past the end are ignored
+    Corrupt(Vec<usize>),
+    /// The message is not forwarded and the connection with the given side is closed
+    Close(Side),
+}
+
+/// Applies a [`Fault`] to the messages of type `message_type` sent by the `from` side.
+///
+/// The message type is one of the `MESSAGE_TYPE_*` constants of `const_sv2`.
+#[derive(Debug, Clone)]
+pub struct Intercept {
+    from: Side,
+    message_type: MsgType,
+    fault: Fault,
+    remaining: Option<usize>,
+}
+
+impl Intercept {
+    /// Intercepts every matching message
+    pub fn new(from: Side, message_type: MsgType, fault: Fault) -> Self {
+        Self {
+            from,
+            message_type,
+            fault,
+            remaining: None,
+        }
+    }
+
+    /// Intercepts only the first `times` matching messages
+    pub fn times(mut self, times: usize) -> Self {
+        self.remaining = Some(times);
+        self
+    }
+}
 
 #[derive(Debug, PartialEq)]
 enum SnifferError {
@@ -44,12 +115,19 @@ enum SnifferError {
 ///
 /// It is useful for testing purposes, as it allows to assert that the roles have sent specific
 /// messages in a specific order and to inspect the messages details.
+///
+/// The [`Sniffer`] can also misbehave on purpose: each [`Intercept`] applies a [`Fault`] to some
+/// messages and [`Sniffer::close`] disconnects one of the roles. The aggregators always save the
+/// messages as the roles sent them, before any fault is applied.
 #[derive(Debug, Clone)]
 pub struct Sniffer {
     listening_address: SocketAddr,
     upstream_address: SocketAddr,
     downstream_messages: MessagesAggregator,
     upstream_messages: MessagesAggregator,
+    intercepts: Arc<Mutex<Vec<Intercept>>>,
+    connections: Arc<Mutex<Vec<(Side, AbortHandle)>>>,
+    check_on_drop: bool,
 }
 
 impl Sniffer {
@@ -61,6 +139,9 @@ impl Sniffer {
             upstream_address,
             downstream_messages: MessagesAggregator::new(),
             upstream_messages: MessagesAggregator::new(),
+            intercepts: Arc::new(Mutex::new(Vec::new())),
+            connections: Arc::new(Mutex::new(Vec::new())),
+            check_on_drop: true,
         }
     }
 
@@ -68,26 +149,81 @@ impl Sniffer {
     ///
     /// The sniffer should be started after the upstream role have been initialized and is ready to
     /// accept messages and before the downstream role starts sending messages.
-    pub async fn start(self) {
-        let (downstream_receiver, downstream_sender) =
-            Self::create_downstream(Self::wait_for_client(self.listening_address).await)
-                .await
-                .expect("Failed to create downstream");
-        let (upstream_receiver, upstream_sender) = Self::create_upstream(
-            TcpStream::connect(self.upstream_address)
-                .await
-                .expect("Failed to connect to upstream"),
-        )
-        .await
-        .expect("Failed to create upstream");
-        let downstream_messages = self.downstream_messages.clone();
-        let upstream_messages = self.upstream_messages.clone();
+    pub async fn start(mut self) {
+        // The messages are checked by the copy owned by the test
+        self.check_on_drop = false;
+        let (downstream_receiver, downstream_sender) = self
+            .connect(
+                Side::Downstream,
+                Self::wait_for_client(self.listening_address).await,
+            )
+            .await
+            .expect("Failed to create downstream");
+        let (upstream_receiver, upstream_sender) = self
+            .connect(
+                Side::Upstream,
+                TcpStream::connect(self.upstream_address)
+                    .await
+                    .expect("Failed to connect to upstream"),
+            )
+            .await
+            .expect("Failed to create upstream");
         let _ = select! {
-            r = Self::recv_from_down_send_to_up(downstream_receiver, upstream_sender, downstream_messages) => r,
-            r = Self::recv_from_up_send_to_down(upstream_receiver, downstream_sender, upstream_messages) => r,
+            r = self.forward(Side::Downstream, downstream_receiver, upstream_sender) => r,
+            r = self.forward(Side::Upstream, upstream_receiver, downstream_sender) => r,
         };
     }
 
+    /// Adds an [`Intercept`], when a message matches more than one the oldest is applied
+    pub fn intercept(&self, intercept: Intercept) {
+        self.intercepts
+            .safe_lock(|intercepts| intercepts.push(intercept))
+            .unwrap();
+    }
+
+    /// Removes all the [`Intercept`]s, the messages are forwarded as they are again
+    pub fn clear_intercepts(&self) {
+        self.intercepts
+            .safe_lock(|intercepts| intercepts.clear())
+            .unwrap();
+    }
+
+    /// Abruptly closes the connection with `side`, without sending anything. Does nothing if
+    /// `side` is not connected yet.
+    pub fn close(&self, side: Side) {
+        self.connections
+            .safe_lock(|connections| {
+                for (_, task) in connections.iter().filter(|(s, _)| *s == side) {
+                    task.abort();
+                }
+            })
+            .unwrap();
+    }
+
+    /// Waits up to `timeout` until `from` has sent a message of type `message_type` and returns
+    /// whether it did. The message stays in the queue, so that it can still be asserted.
+    pub async fn wait_for_message(
+        &self,
+        from: Side,
+        message_type: MsgType,
+        timeout: Duration,
+    ) -> bool {
+        let messages = match from {
+            Side::Downstream => &self.downstream_messages,
+            Side::Upstream => &self.upstream_messages,
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            if messages.has_message_type(message_type) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
     /// Returns the oldest message sent by downstream.
     ///
     /// The queue is FIFO and once a message is returned it is removed from the queue.
@@ -110,83 +246,148 @@ impl Sniffer {
         self.upstream_messages.next_message()
     }
 
-    async fn create_downstream(
+    // Sets up the connection with `side` and keeps its tasks, so that it can be closed
+    async fn connect(
+        &self,
+        side: Side,
         stream: TcpStream,
     ) -> Option<(Receiver<MessageFrame>, Sender<MessageFrame>)> {
-        let pub_key = "9auqWEzQDVyd2oe1JVGFLMLHZtCo2FFqZwtKA5gd9xbuEu7PH72"
+        let (receiver, sender, recv_task, send_task) = match side {
+            Side::Downstream => Self::create_downstream_with_tasks(stream).await?,
+            Side::Upstream => Self::create_upstream(stream).await?,
+        };
+        self.connections
+            .safe_lock(|connections| {
+                connections.push((side, recv_task));
+                connections.push((side, send_task));
+            })
+            .unwrap();
+        Some((receiver, sender))
+    }
+
+    pub(crate) async fn create_downstream(
+        stream: TcpStream,
+    ) -> Option<(Receiver<MessageFrame>, Sender<MessageFrame>)> {
+        Self::create_downstream_with_tasks(stream)
+            .await
+            .map(|(receiver, sender, _, _)| (receiver, sender))
+    }
+
+    async fn create_downstream_with_tasks(stream: TcpStream) -> Option<ConnectionTasks> {
+        let pub_key = super::AUTHORITY_PUBLIC_KEY
             .to_string()
             .parse::<Secp256k1PublicKey>()
             .unwrap()
             .into_bytes();
-        let prv_key = "mkDLTBBRxdBv998612qipDYoTK3YUrqLe8uWw7gu3iXbSrn2n"
+        let prv_key = super::AUTHORITY_SECRET_KEY
             .to_string()
             .parse::<Secp256k1SecretKey>()
             .unwrap()
             .into_bytes();
         let responder =
-            Responder::from_authority_kp(&pub_key, &prv_key, std::time::Duration::from_secs(10000))
-                .unwrap();
-        if let Ok((receiver_from_client, send_to_client, _, _)) =
-            Connection::new::<'static, AnyMessage<'static>>(
-                stream,
-                HandshakeRole::Responder(responder),
-            )
+            Responder::from_authority_kp(&pub_key, &prv_key, Duration::from_secs(10000)).unwrap();
+        Connection::new::<'static, AnyMessage<'static>>(stream, HandshakeRole::Responder(responder))
             .await
-        {
-            Some((receiver_from_client, send_to_client))
-        } else {
-            None
-        }
+            .ok()
     }
 
-    async fn create_upstream(
-        stream: TcpStream,
-    ) -> Option<(Receiver<MessageFrame>, Sender<MessageFrame>)> {
+    async fn create_upstream(stream: TcpStream) -> Option<ConnectionTasks> {
         let initiator = Initiator::without_pk().expect("This fn call can not fail");
-        if let Ok((receiver_from_client, send_to_client, _, _)) =
-            Connection::new::<'static, AnyMessage<'static>>(
-                stream,
-                HandshakeRole::Initiator(initiator),
-            )
+        Connection::new::<'static, AnyMessage<'static>>(stream, HandshakeRole::Initiator(initiator))
             .await
-        {
-            Some((receiver_from_client, send_to_client))
-        } else {
-            None
-        }
+            .ok()
     }
 
-    async fn recv_from_down_send_to_up(
+    // Saves the messages sent by `from` and forwards them to the other side, applying the
+    // intercepts
+    async fn forward(
+        &self,
+        from: Side,
         recv: Receiver<MessageFrame>,
         send: Sender<MessageFrame>,
-        downstream_messages: MessagesAggregator,
     ) -> Result<(), SnifferError> {
+        let (messages, sender_closed, receiver_closed) = match from {
+            Side::Downstream => (
+                &self.downstream_messages,
+                SnifferError::DownstreamClosed,
+                SnifferError::UpstreamClosed,
+            ),
+            Side::Upstream => (
+                &self.upstream_messages,
+                SnifferError::UpstreamClosed,
+                SnifferError::DownstreamClosed,
+            ),
+        };
+        let mut held = None;
         while let Ok(mut frame) = recv.recv().await {
             let (msg_type, msg) = Self::message_from_frame(&mut frame);
-            downstream_messages.add_message(msg_type, msg);
-            if send.send(frame).await.is_err() {
-                return Err(SnifferError::UpstreamClosed);
-            };
+            messages.add_message(msg_type, msg.clone());
+            let mut frames = Vec::with_capacity(2);
+            let mut reordered = false;
+            match self.fault(from, msg_type) {
+                None => frames.push(frame),
+                Some(Fault::Drop) => (),
+                Some(Fault::Delay(delay)) => {
+                    tokio::time::sleep(delay).await;
+                    frames.push(frame);
+                }
+                Some(Fault::Duplicate) => {
+                    frames.push(frame);
+                    frames.push(Self::frame_from_message(msg));
+                }
+                Some(Fault::Reorder) => {
+                    reordered = true;
+                    frames.extend(held.replace(frame));
+                }
+                Some(Fault::Replace(message)) => frames.push(Self::frame_from_message(message)),
+                Some(Fault::Corrupt(offsets)) => {
+                    if let Frame::Sv2(sv2_frame) = &mut frame {
+                        let payload = sv2_frame.payload();
+                        for offset in offsets {
+                            if let Some(byte) = payload.get_mut(offset) {
+                                *byte ^= 0xff;
+                            }
+                        }
+                    }
+                    frames.push(frame);
+                }
+                Some(Fault::Close(side)) => self.close(side),
+            }
+            if !reordered {
+                frames.extend(held.take());
+            }
+            for frame in frames {
+                if send.send(frame).await.is_err() {
+                    return Err(receiver_closed);
+                };
+            }
         }
-        Err(SnifferError::DownstreamClosed)
+        Err(sender_closed)
     }
 
-    async fn recv_from_up_send_to_down(
-        recv: Receiver<MessageFrame>,
-        send: Sender<MessageFrame>,
-        upstream_messages: MessagesAggregator,
-    ) -> Result<(), SnifferError> {
-        while let Ok(mut frame) = recv.recv().await {
-            let (msg_type, msg) = Self::message_from_frame(&mut frame);
-            upstream_messages.add_message(msg_type, msg);
-            if send.send(frame).await.is_err() {
-                return Err(SnifferError::DownstreamClosed);
-            };
-        }
-        Err(SnifferError::UpstreamClosed)
+    // Returns the fault of the oldest intercept that matches, if any
+    fn fault(&self, from: Side, message_type: MsgType) -> Option<Fault> {
+        self.intercepts
+            .safe_lock(|intercepts| {
+                let intercept = intercepts.iter_mut().find(|i| {
+                    i.from == from && i.message_type == message_type && i.remaining != Some(0)
+                })?;
+                if let Some(remaining) = intercept.remaining.as_mut() {
+                    *remaining -= 1;
+                }
+                Some(intercept.fault.clone())
+            })
+            .unwrap()
+    }
+
+    fn frame_from_message(message: AnyMessage<'static>) -> MessageFrame {
+        let frame: StdFrame = message
+            .try_into()
+            .expect("Failed to create the frame of the message");
+        frame.into()
     }
 
-    fn message_from_frame(frame: &mut MessageFrame) -> (MsgType, AnyMessage<'static>) {
+    pub(crate) fn message_from_frame(frame: &mut MessageFrame) -> (MsgType, AnyMessage<'static>) {
         match frame {
             Frame::Sv2(frame) => {
                 if let Some(header) = frame.get_header() {
@@ -429,6 +630,9 @@ macro_rules! assert_jd_message {
 // This is useful to ensure that the test has checked all exchanged messages between the roles.
 impl Drop for Sniffer {
     fn drop(&mut self) {
+        if !self.check_on_drop {
+            return;
+        }
         // Don't print backtrace on panic
         std::panic::set_hook(Box::new(|_| {
             println!();
@@ -451,24 +655,30 @@ impl Drop for Sniffer {
 }
 
 #[derive(Debug, Clone)]
-struct MessagesAggregator {
+pub(crate) struct MessagesAggregator {
     messages: Arc<Mutex<VecDeque<(MsgType, AnyMessage<'static>)>>>,
 }
 
 impl MessagesAggregator {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             messages: Arc::new(Mutex::new(VecDeque::new())),
         }
     }
 
     // Adds a message to the end of the queue.
-    fn add_message(&self, msg_type: MsgType, message: AnyMessage<'static>) {
+    pub(crate) fn add_message(&self, msg_type: MsgType, message: AnyMessage<'static>) {
         self.messages
             .safe_lock(|messages| messages.push_back((msg_type, message)))
             .unwrap();
     }
 
+    fn has_message_type(&self, message_type: MsgType) -> bool {
+        self.messages
+            .safe_lock(|messages| messages.iter().any(|(t, _)| *t == message_type))
+            .unwrap()
+    }
+
     fn is_empty(&self) -> bool {
         self.messages
             .safe_lock(|messages| messages.is_empty())
@@ -479,7 +689,7 @@ impl MessagesAggregator {
     // the queue.
     //
     // The returned message is removed from the queue.
-    fn next_message(&self) -> Option<(MsgType, AnyMessage<'static>)> {
+    pub(crate) fn next_message(&self) -> Option<(MsgType, AnyMessage<'static>)> {
         let is_state = self
             .messages
             .safe_lock(|messages| {
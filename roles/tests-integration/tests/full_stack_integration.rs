@@ -0,0 +1,63 @@
+mod common;
+
+use roles_logic_sv2::{
+    common_messages_sv2::{Protocol, SetupConnection},
+    parsers::{CommonMessages, JobDeclaration, PoolMessages},
+};
+use std::time::Duration;
+
+// Max time to wait for the CPU miners to find a block at the regtest difficulty
+const BLOCK_TIMEOUT: Duration = Duration::from_secs(60);
+
+// This test connects a SV2 mining device to the Pool and checks that the Template Provider
+// receives a block found on one of its templates.
+#[tokio::test]
+async fn mining_device_finds_block_with_pool() {
+    let tp_addr = common::get_available_address();
+    let pool_addr = common::get_available_address();
+    let tp = common::start_template_provider(tp_addr.port()).await;
+    let _ = common::start_pool(Some(pool_addr), Some(tp_addr)).await;
+    common::start_mining_device(pool_addr);
+    assert!(tp.wait_for_block(BLOCK_TIMEOUT).await.is_some());
+}
+
+// This test connects a SV1 miner to the Pool through the Translator and checks that the Template
+// Provider receives a block found on one of its templates.
+#[tokio::test]
+async fn sv1_miner_finds_block_through_translator() {
+    let tp_addr = common::get_available_address();
+    let pool_addr = common::get_available_address();
+    let tp = common::start_template_provider(tp_addr.port()).await;
+    let _ = common::start_pool(Some(pool_addr), Some(tp_addr)).await;
+    let translator_addr = common::start_translator(pool_addr).await;
+    common::start_sv1_mining_device(translator_addr);
+    assert!(tp.wait_for_block(BLOCK_TIMEOUT).await.is_some());
+}
+
+// This test starts a JDC connected to the Pool and, through the Sniffer, to the JDS. It checks
+// that the JDC sets up the Job Declaration connection and allocates its first tokens.
+#[tokio::test]
+async fn jdc_allocates_tokens_from_jds() {
+    let tp_addr = common::get_available_address();
+    let pool_addr = common::get_available_address();
+    let sniffer_addr = common::get_available_address();
+    let _tp = common::start_template_provider(tp_addr.port()).await;
+    let _ = common::start_pool(Some(pool_addr), Some(tp_addr)).await;
+    let jds_addr = common::start_jds().await;
+    let sniffer = common::start_sniffer(sniffer_addr, jds_addr).await;
+    let _ = common::start_jdc(pool_addr, tp_addr, sniffer_addr).await;
+    assert_common_message!(
+        &sniffer.next_downstream_message(),
+        SetupConnection,
+        protocol,
+        Protocol::JobDeclarationProtocol
+    );
+    assert_common_message!(&sniffer.next_upstream_message(), SetupConnectionSuccess);
+    for _ in 0..2 {
+        assert_jd_message!(&sniffer.next_downstream_message(), AllocateMiningJobToken);
+        assert_jd_message!(
+            &sniffer.next_upstream_message(),
+            AllocateMiningJobTokenSuccess
+        );
+    }
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use async_channel::Sender;
+use codec_sv2::{HandshakeRole, Initiator, StandardEitherFrame, StandardSv2Frame};
+use common::{mock_tp::TemplateGenerator, TemplateEvent};
+use network_helpers_sv2::noise_connection_tokio::Connection;
+use roles_logic_sv2::{
+    common_messages_sv2::{Protocol, SetupConnection, SetupConnectionSuccess},
+    parsers::{AnyMessage, CommonMessages, PoolMessages, TemplateDistribution},
+    template_distribution_sv2::{
+        CoinbaseOutputDataSize, NewTemplate, RequestTransactionData, RequestTransactionDataError,
+        RequestTransactionDataSuccess, SetNewPrevHash, SubmitSolution,
+    },
+    utils::{merkle_root_from_path, u256_to_block_hash},
+};
+use std::convert::TryInto;
+use stratum_common::bitcoin::{
+    blockdata::block::BlockHeader, consensus::serialize, hash_types::TxMerkleNode, hashes::Hash,
+    OutPoint, PackedLockTime, Script, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use tokio::net::TcpStream;
+
+type EitherFrame = StandardEitherFrame<AnyMessage<'static>>;
+type StdFrame = StandardSv2Frame<AnyMessage<'static>>;
+
+async fn send(sender: &Sender<EitherFrame>, message: AnyMessage<'static>) {
+    let frame: StdFrame = message.try_into().unwrap();
+    sender.send(frame.into()).await.unwrap();
+}
+
+fn coinbase() -> Vec<u8> {
+    let coinbase = Transaction {
+        version: 2,
+        lock_time: PackedLockTime(0),
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: vec![3, 16, 0, 0].into(),
+            sequence: Sequence(u32::MAX),
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 5_000_000_000,
+            script_pubkey: Script::from(vec![0x51]),
+        }],
+    };
+    serialize(&coinbase)
+}
+
+// Returns the first nonce, starting from `nonce`, for which the header does or does not meet the
+// target
+fn find_nonce(
+    template: &NewTemplate<'static>,
+    prev_hash: &SetNewPrevHash<'static>,
+    coinbase: &[u8],
+    mut nonce: u32,
+    valid: bool,
+) -> u32 {
+    let merkle_root =
+        merkle_root_from_path(coinbase, &[], &[], &template.merkle_path.to_vec()).unwrap();
+    let mut header = BlockHeader {
+        version: template.version as i32,
+        prev_blockhash: u256_to_block_hash(prev_hash.prev_hash.clone()),
+        merkle_root: TxMerkleNode::from_inner(merkle_root.try_into().unwrap()),
+        time: prev_hash.header_timestamp,
+        bits: prev_hash.n_bits,
+        nonce,
+    };
+    while header.validate_pow(&header.target()).is_ok() != valid {
+        nonce += 1;
+        header.nonce = nonce;
+    }
+    nonce
+}
+
+// This test starts the mock Template Provider with two blocks and connects to it as a pool would.
+// It checks that the scripted templates are sent, that the transaction data is served only for
+// the templates of the last block and that the solutions are checked against the regtest target.
+#[tokio::test]
+async fn mock_template_provider_serves_templates_and_checks_solutions() {
+    let tp_addr = common::get_available_address();
+    let script = TemplateGenerator::new().sequence(2, 1);
+    let (template, prev_hash) = match (&script[5], &script[4]) {
+        (TemplateEvent::NewTemplate(template), TemplateEvent::SetNewPrevHash(prev_hash)) => {
+            (template.clone(), prev_hash.clone())
+        }
+        _ => panic!("Unexpected script: {:?}", script),
+    };
+    let tp = common::start_template_provider_with_script(tp_addr.port(), script).await;
+    tp.set_transactions(template.template_id, vec![vec![1, 2, 3]]);
+
+    let stream = TcpStream::connect(tp_addr).await.unwrap();
+    let initiator = Initiator::without_pk().unwrap();
+    let (receiver, sender, _, _) =
+        Connection::new::<AnyMessage<'static>>(stream, HandshakeRole::Initiator(initiator))
+            .await
+            .unwrap();
+    tokio::spawn(async move { while receiver.recv().await.is_ok() {} });
+
+    let setup_connection = SetupConnection {
+        protocol: Protocol::TemplateDistributionProtocol,
+        min_version: 2,
+        max_version: 2,
+        flags: 0,
+        endpoint_host: tp_addr.ip().to_string().into_bytes().try_into().unwrap(),
+        endpoint_port: tp_addr.port(),
+        vendor: String::new().try_into().unwrap(),
+        hardware_version: String::new().try_into().unwrap(),
+        firmware: String::new().try_into().unwrap(),
+        device_id: String::new().try_into().unwrap(),
+    };
+    send(&sender, setup_connection.into()).await;
+    let coinbase_output_data_size = CoinbaseOutputDataSize {
+        coinbase_output_max_additional_size: 0,
+    };
+    send(
+        &sender,
+        PoolMessages::TemplateDistribution(TemplateDistribution::CoinbaseOutputDataSize(
+            coinbase_output_data_size,
+        )),
+    )
+    .await;
+    for template_id in [template.template_id, 1, 100] {
+        send(
+            &sender,
+            PoolMessages::TemplateDistribution(TemplateDistribution::RequestTransactionData(
+                RequestTransactionData { template_id },
+            )),
+        )
+        .await;
+    }
+    let coinbase = coinbase();
+    let valid_nonce = find_nonce(&template, &prev_hash, &coinbase, 0, true);
+    let invalid_nonce = find_nonce(&template, &prev_hash, &coinbase, 0, false);
+    for nonce in [valid_nonce, invalid_nonce] {
+        let solution = SubmitSolution {
+            template_id: template.template_id,
+            version: template.version,
+            header_timestamp: prev_hash.header_timestamp,
+            header_nonce: nonce,
+            coinbase_tx: coinbase.clone().try_into().unwrap(),
+        };
+        send(
+            &sender,
+            PoolMessages::TemplateDistribution(TemplateDistribution::SubmitSolution(solution)),
+        )
+        .await;
+    }
+
+    let mut solutions = tp.solutions();
+    while solutions.len() < 2 {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        solutions = tp.solutions();
+    }
+    assert_eq!(solutions[0].0.header_nonce, valid_nonce);
+    assert!(solutions[0].1);
+    assert_eq!(solutions[1].0.header_nonce, invalid_nonce);
+    assert!(!solutions[1].1);
+
+    assert_common_message!(&tp.next_downstream_message(), SetupConnection);
+    assert_tp_message!(&tp.next_downstream_message(), CoinbaseOutputDataSize);
+    for _ in 0..3 {
+        assert_tp_message!(&tp.next_downstream_message(), RequestTransactionData);
+    }
+    for _ in 0..2 {
+        assert_tp_message!(&tp.next_downstream_message(), SubmitSolution);
+    }
+    assert!(tp.next_downstream_message().is_none());
+    assert_common_message!(
+        &tp.next_upstream_message(),
+        SetupConnectionSuccess,
+        used_version,
+        2
+    );
+    for _ in 0..2 {
+        assert_tp_message!(&tp.next_upstream_message(), NewTemplate);
+        assert_tp_message!(&tp.next_upstream_message(), SetNewPrevHash);
+        assert_tp_message!(&tp.next_upstream_message(), NewTemplate);
+    }
+    assert_tp_message!(
+        &tp.next_upstream_message(),
+        RequestTransactionDataSuccess,
+        template_id,
+        template.template_id
+    );
+    assert_tp_message!(
+        &tp.next_upstream_message(),
+        RequestTransactionDataError,
+        template_id,
+        1
+    );
+    assert_tp_message!(
+        &tp.next_upstream_message(),
+        RequestTransactionDataError,
+        template_id,
+        100
+    );
+    assert!(tp.next_upstream_message().is_none());
+}
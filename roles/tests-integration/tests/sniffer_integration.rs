@@ -0,0 +1,255 @@
+mod common;
+
+use async_channel::{Receiver, Sender};
+use codec_sv2::{
+    framing_sv2::framing::Frame, HandshakeRole, Initiator, StandardEitherFrame, StandardSv2Frame,
+};
+use common::{
+    mock_tp::TemplateGenerator,
+    sniffer::{Fault, Intercept, Side},
+    Sniffer, TemplateEvent,
+};
+use const_sv2::{
+    MESSAGE_TYPE_COINBASE_OUTPUT_DATA_SIZE, MESSAGE_TYPE_NEW_TEMPLATE,
+    MESSAGE_TYPE_REQUEST_TRANSACTION_DATA, MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_ERROR,
+    MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_SUCCESS, MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS,
+    MESSAGE_TYPE_SET_NEW_PREV_HASH, MESSAGE_TYPE_SUBMIT_SOLUTION,
+};
+use network_helpers_sv2::noise_connection_tokio::Connection;
+use roles_logic_sv2::{
+    common_messages_sv2::{Protocol, SetupConnection},
+    parsers::{AnyMessage, CommonMessages, PoolMessages, TemplateDistribution},
+    template_distribution_sv2::{CoinbaseOutputDataSize, RequestTransactionData},
+};
+use std::{convert::TryInto, net::SocketAddr, time::Duration};
+use tokio::net::TcpStream;
+
+type EitherFrame = StandardEitherFrame<AnyMessage<'static>>;
+type StdFrame = StandardSv2Frame<AnyMessage<'static>>;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn send(sender: &Sender<EitherFrame>, message: AnyMessage<'static>) {
+    let frame: StdFrame = message.try_into().unwrap();
+    sender.send(frame.into()).await.unwrap();
+}
+
+// Returns the type of the next message received, `None` when the connection is closed
+async fn next_message_type(receiver: &Receiver<EitherFrame>) -> Option<u8> {
+    match tokio::time::timeout(TIMEOUT, receiver.recv())
+        .await
+        .expect("No message received")
+    {
+        Ok(Frame::Sv2(frame)) => Some(frame.get_header().unwrap().msg_type()),
+        Ok(Frame::HandShake(_)) => panic!("Unexpected handshake frame"),
+        Err(_) => None,
+    }
+}
+
+// Connects to the Sniffer as a pool would connect to the Template Provider and asks for the
+// templates
+async fn connect(
+    sniffer_addr: SocketAddr,
+    tp_addr: SocketAddr,
+) -> (Receiver<EitherFrame>, Sender<EitherFrame>) {
+    let stream = loop {
+        if let Ok(stream) = TcpStream::connect(sniffer_addr).await {
+            break stream;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    let initiator = Initiator::without_pk().unwrap();
+    let (receiver, sender, _, _) =
+        Connection::new::<AnyMessage<'static>>(stream, HandshakeRole::Initiator(initiator))
+            .await
+            .unwrap();
+    let setup_connection = SetupConnection {
+        protocol: Protocol::TemplateDistributionProtocol,
+        min_version: 2,
+        max_version: 2,
+        flags: 0,
+        endpoint_host: tp_addr.ip().to_string().into_bytes().try_into().unwrap(),
+        endpoint_port: tp_addr.port(),
+        vendor: String::new().try_into().unwrap(),
+        hardware_version: String::new().try_into().unwrap(),
+        firmware: String::new().try_into().unwrap(),
+        device_id: String::new().try_into().unwrap(),
+    };
+    send(&sender, setup_connection.into()).await;
+    let coinbase_output_data_size = CoinbaseOutputDataSize {
+        coinbase_output_max_additional_size: 0,
+    };
+    send(
+        &sender,
+        PoolMessages::TemplateDistribution(TemplateDistribution::CoinbaseOutputDataSize(
+            coinbase_output_data_size,
+        )),
+    )
+    .await;
+    (receiver, sender)
+}
+
+async fn start(
+    script: Vec<TemplateEvent>,
+) -> (
+    common::MockTemplateProvider,
+    Sniffer,
+    SocketAddr,
+    SocketAddr,
+) {
+    let tp_addr = common::get_available_address();
+    let sniffer_addr = common::get_available_address();
+    let tp = common::start_template_provider_with_script(tp_addr.port(), script).await;
+    let sniffer = common::start_sniffer(sniffer_addr, tp_addr).await;
+    (tp, sniffer, tp_addr, sniffer_addr)
+}
+
+// This test puts the Sniffer between a client and the mock Template Provider. It checks that the
+// intercepted messages are duplicated, replaced, corrupted or dropped, while the Sniffer still
+// saves them as they were sent.
+#[tokio::test]
+async fn sniffer_applies_faults_to_intercepted_messages() {
+    let script = TemplateGenerator::new().sequence(1, 1);
+    let template_id = match &script[2] {
+        TemplateEvent::NewTemplate(template) => template.template_id,
+        _ => panic!("Unexpected script: {:?}", script),
+    };
+    let (tp, sniffer, tp_addr, sniffer_addr) = start(script).await;
+    let replacement = PoolMessages::TemplateDistribution(
+        TemplateDistribution::RequestTransactionData(RequestTransactionData { template_id: 100 }),
+    );
+    for fault in [
+        Fault::Duplicate,
+        Fault::Replace(replacement),
+        // The first byte of the little endian template id
+        Fault::Corrupt(vec![0]),
+    ] {
+        sniffer.intercept(
+            Intercept::new(
+                Side::Downstream,
+                MESSAGE_TYPE_REQUEST_TRANSACTION_DATA,
+                fault,
+            )
+            .times(1),
+        );
+    }
+    sniffer.intercept(Intercept::new(
+        Side::Upstream,
+        MESSAGE_TYPE_SET_NEW_PREV_HASH,
+        Fault::Drop,
+    ));
+
+    let (receiver, sender) = connect(sniffer_addr, tp_addr).await;
+    for _ in 0..3 {
+        send(
+            &sender,
+            PoolMessages::TemplateDistribution(TemplateDistribution::RequestTransactionData(
+                RequestTransactionData { template_id },
+            )),
+        )
+        .await;
+    }
+    for expected in [
+        MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS,
+        MESSAGE_TYPE_NEW_TEMPLATE,
+        MESSAGE_TYPE_NEW_TEMPLATE,
+        MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_SUCCESS,
+        MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_SUCCESS,
+        MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_ERROR,
+        MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_ERROR,
+    ] {
+        assert_eq!(next_message_type(&receiver).await, Some(expected));
+    }
+
+    assert_common_message!(&tp.next_downstream_message(), SetupConnection);
+    assert_tp_message!(&tp.next_downstream_message(), CoinbaseOutputDataSize);
+    for expected_template_id in [template_id, template_id, 100, template_id ^ 0xff] {
+        assert_tp_message!(
+            &tp.next_downstream_message(),
+            RequestTransactionData,
+            template_id,
+            expected_template_id
+        );
+    }
+    assert!(tp.next_downstream_message().is_none());
+
+    assert_common_message!(&sniffer.next_downstream_message(), SetupConnection);
+    assert_tp_message!(&sniffer.next_downstream_message(), CoinbaseOutputDataSize);
+    // the macro binds the `template_id` field, the expected value needs another name
+    let expected_template_id = template_id;
+    for _ in 0..3 {
+        assert_tp_message!(
+            &sniffer.next_downstream_message(),
+            RequestTransactionData,
+            template_id,
+            expected_template_id
+        );
+    }
+    assert!(sniffer.next_downstream_message().is_none());
+    assert_common_message!(&sniffer.next_upstream_message(), SetupConnectionSuccess);
+    assert_tp_message!(&sniffer.next_upstream_message(), NewTemplate);
+    assert_tp_message!(&sniffer.next_upstream_message(), SetNewPrevHash);
+    assert_tp_message!(&sniffer.next_upstream_message(), NewTemplate);
+    for _ in 0..2 {
+        assert_tp_message!(
+            &sniffer.next_upstream_message(),
+            RequestTransactionDataSuccess
+        );
+    }
+    for _ in 0..2 {
+        assert_tp_message!(
+            &sniffer.next_upstream_message(),
+            RequestTransactionDataError
+        );
+    }
+    assert!(sniffer.next_upstream_message().is_none());
+}
+
+// This test checks that the Sniffer forwards a held message after the next one, that it waits
+// for the messages with a timeout and that it closes the connection with the client.
+#[tokio::test]
+async fn sniffer_reorders_messages_and_closes_connection() {
+    let (_tp, sniffer, tp_addr, sniffer_addr) =
+        start(TemplateGenerator::new().sequence(1, 1)).await;
+    sniffer.intercept(
+        Intercept::new(Side::Upstream, MESSAGE_TYPE_NEW_TEMPLATE, Fault::Reorder).times(1),
+    );
+
+    let (receiver, _sender) = connect(sniffer_addr, tp_addr).await;
+    for expected in [
+        MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS,
+        MESSAGE_TYPE_SET_NEW_PREV_HASH,
+        MESSAGE_TYPE_NEW_TEMPLATE,
+        MESSAGE_TYPE_NEW_TEMPLATE,
+    ] {
+        assert_eq!(next_message_type(&receiver).await, Some(expected));
+    }
+    assert!(
+        sniffer
+            .wait_for_message(
+                Side::Downstream,
+                MESSAGE_TYPE_COINBASE_OUTPUT_DATA_SIZE,
+                TIMEOUT
+            )
+            .await
+    );
+    assert!(
+        !sniffer
+            .wait_for_message(
+                Side::Downstream,
+                MESSAGE_TYPE_SUBMIT_SOLUTION,
+                Duration::from_millis(100)
+            )
+            .await
+    );
+
+    sniffer.close(Side::Downstream);
+    assert_eq!(next_message_type(&receiver).await, None);
+
+    assert_common_message!(&sniffer.next_downstream_message(), SetupConnection);
+    assert_tp_message!(&sniffer.next_downstream_message(), CoinbaseOutputDataSize);
+    assert_common_message!(&sniffer.next_upstream_message(), SetupConnectionSuccess);
+    assert_tp_message!(&sniffer.next_upstream_message(), NewTemplate);
+    assert_tp_message!(&sniffer.next_upstream_message(), SetNewPrevHash);
+    assert_tp_message!(&sniffer.next_upstream_message(), NewTemplate);
+}
@@ -0,0 +1,141 @@
+use crate::{
+    error::{Error, ProxyResult},
+    proxy_config::AuthConfig,
+};
+use std::collections::{HashMap, HashSet};
+use stratum_common::bitcoin::hashes::{sha256, Hash};
+
+/// Answer of the [`Authenticator`] to a `mining.authorize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Authorization {
+    Accepted,
+    Rejected,
+    /// The worker is authorized only if the upstream opens a channel for it within `timeout_secs`
+    AskUpstream {
+        timeout_secs: u64,
+    },
+}
+
+/// Authorizes the SV1 workers according with the `auth` section of the config, the allowlist and
+/// password files are read once when the translator starts.
+#[derive(Debug)]
+pub enum Authenticator {
+    AllowAll,
+    Allowlist(HashSet<String>),
+    /// Hex encoded SHA256 of `name:password` for every worker name
+    Password(HashMap<String, String>),
+    PoolValidates {
+        timeout_secs: u64,
+    },
+}
+
+impl Authenticator {
+    #[allow(clippy::result_large_err)]
+    pub fn from_config(config: &AuthConfig) -> ProxyResult<'static, Self> {
+        match config {
+            AuthConfig::None => Ok(Self::AllowAll),
+            AuthConfig::Allowlist { path } => Ok(Self::Allowlist(Self::parse_allowlist(
+                &std::fs::read_to_string(path)?,
+            ))),
+            AuthConfig::Password { path } => Ok(Self::Password(Self::parse_passwords(
+                &std::fs::read_to_string(path)?,
+            )?)),
+            AuthConfig::PoolValidates { timeout_secs } => Ok(Self::PoolValidates {
+                timeout_secs: *timeout_secs,
+            }),
+        }
+    }
+
+    pub fn authorize(&self, name: &str, password: &str) -> Authorization {
+        let accepted = match self {
+            Self::AllowAll => true,
+            Self::Allowlist(names) => names.contains(name),
+            Self::Password(hashes) => hashes
+                .get(name)
+                .map(|hash| *hash == Self::hash(name, password))
+                .unwrap_or(false),
+            Self::PoolValidates { timeout_secs } => {
+                return Authorization::AskUpstream {
+                    timeout_secs: *timeout_secs,
+                }
+            }
+        };
+        match accepted {
+            true => Authorization::Accepted,
+            false => Authorization::Rejected,
+        }
+    }
+
+    fn hash(name: &str, password: &str) -> String {
+        let hash = sha256::Hash::hash(format!("{}:{}", name, password).as_bytes());
+        format!("{:x}", hash)
+    }
+
+    /// Lines of the file that are empty or start with `#` are skipped
+    fn lines(file: &str) -> impl Iterator<Item = &str> {
+        file.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    }
+
+    fn parse_allowlist(file: &str) -> HashSet<String> {
+        Self::lines(file).map(String::from).collect()
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn parse_passwords(file: &str) -> ProxyResult<'static, HashMap<String, String>> {
+        Self::lines(file)
+            .map(|line| match line.rsplit_once(':') {
+                Some((name, hash))
+                    if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) =>
+                {
+                    Ok((name.to_string(), hash.to_ascii_lowercase()))
+                }
+                _ => Err(Error::InvalidAuthFile(format!(
+                    "expected `name:sha256` found `{}`",
+                    line
+                ))),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn allowlist_authorizes_listed_names() {
+        let names = Authenticator::parse_allowlist("# miners\nalice.1\n\n  bob.2  \n");
+        let auth = Authenticator::Allowlist(names);
+        assert_eq!(auth.authorize("alice.1", "x"), Authorization::Accepted);
+        assert_eq!(auth.authorize("bob.2", ""), Authorization::Accepted);
+        assert_eq!(auth.authorize("carol", "x"), Authorization::Rejected);
+    }
+
+    #[test]
+    fn password_is_checked_against_the_hash() {
+        // printf 'alice.1:secret' | sha256sum
+        let file = "alice.1:1C460D396FDF3C644051EB917576B61B6A3ACE93E84C8D2BDDA66B200CEE8004\n";
+        let auth = Authenticator::Password(Authenticator::parse_passwords(file).unwrap());
+        assert_eq!(auth.authorize("alice.1", "secret"), Authorization::Accepted);
+        assert_eq!(auth.authorize("alice.1", "secreT"), Authorization::Rejected);
+        assert_eq!(auth.authorize("bob", "secret"), Authorization::Rejected);
+    }
+
+    #[test]
+    fn bad_password_line_is_an_error() {
+        assert!(Authenticator::parse_passwords("alice.1:secret\n").is_err());
+        assert!(Authenticator::parse_passwords("alice.1\n").is_err());
+    }
+
+    #[test]
+    fn pool_validates_asks_upstream() {
+        let auth =
+            Authenticator::from_config(&AuthConfig::PoolValidates { timeout_secs: 1 }).unwrap();
+        assert_eq!(
+            auth.authorize("alice.1", "x"),
+            Authorization::AskUpstream { timeout_secs: 1 }
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use super::{Downstream, DownstreamMessages, SetDownstreamTarget};
 
-use super::super::error::{Error, ProxyResult};
+use super::super::{
+    error::{Error, ProxyResult},
+    proxy::Bridge,
+};
 use roles_logic_sv2::utils::Mutex;
 use std::{ops::Div, sync::Arc};
 use v1::json_rpc;
@@ -15,6 +18,7 @@ impl Downstream {
         self_: Arc<Mutex<Self>>,
         init_target: &[u8],
     ) -> ProxyResult<'static, ()> {
+        let init_target = binary_sv2::U256::try_from(init_target.to_vec())?;
         let (connection_id, upstream_difficulty_config, miner_hashrate) = self_
             .safe_lock(|d| {
                 let timestamp_secs = std::time::SystemTime::now()
@@ -23,6 +27,7 @@ impl Downstream {
                     .as_secs();
                 d.difficulty_mgmt.timestamp_of_last_update = timestamp_secs;
                 d.difficulty_mgmt.submits_since_last_update = 0;
+                d.target = Some(init_target.clone().into());
                 (
                     d.connection_id,
                     d.upstream_difficulty_config.clone(),
@@ -37,7 +42,6 @@ impl Downstream {
             })
             .map_err(|_e| Error::PoisonLock)?;
         // update downstream target with bridge
-        let init_target = binary_sv2::U256::try_from(init_target.to_vec())?;
         Self::send_message_upstream(
             self_,
             DownstreamMessages::SetDownstreamTarget(SetDownstreamTarget {
@@ -70,6 +74,80 @@ impl Downstream {
         Ok(())
     }
 
+    /// Sends to the miner, with `mining.set_difficulty`, the target that the upstream set for the
+    /// channel of this connection if it has changed. Used instead of the difficulty management
+    /// when the connection has its own channel with the upstream.
+    pub async fn update_connection_target(
+        self_: Arc<Mutex<Self>>,
+        bridge: Arc<Mutex<Bridge>>,
+    ) -> ProxyResult<'static, ()> {
+        let connection_id = self_
+            .safe_lock(|d| d.connection_id)
+            .map_err(|_e| Error::PoisonLock)?;
+        let new_target = bridge
+            .safe_lock(|b| b.take_connection_target(connection_id))
+            .map_err(|_e| Error::PoisonLock)?;
+        if let Some(new_target) = new_target {
+            let target = binary_sv2::U256::try_from(new_target.clone())?;
+            self_
+                .safe_lock(|d| d.target = Some(target.into()))
+                .map_err(|_e| Error::PoisonLock)?;
+            let message = Self::get_set_difficulty(new_target)?;
+            Self::send_message_downstream(self_, message).await?;
+        }
+        Ok(())
+    }
+
+    /// Starts the difficulty management of the connection from the hashrate of the difficulty
+    /// suggested by the miner with `mining.suggest_difficulty` or `mining.suggest_target`. The
+    /// suggestion is ignored once the first `mining.set_difficulty` has been sent, or when the
+    /// target of the connection is set by the upstream.
+    pub(super) fn suggest_hashrate(&mut self, hashrate: Option<f64>) {
+        if self.per_connection_channel || self.target.is_some() {
+            tracing::debug!(
+                "Ignoring the difficulty suggested by connection {}",
+                self.connection_id
+            );
+            return;
+        }
+        match hashrate {
+            Some(hashrate) if hashrate.is_finite() && hashrate > 0.0 => {
+                tracing::info!(
+                    "Connection {} starts from the suggested hashrate {}",
+                    self.connection_id,
+                    hashrate
+                );
+                self.difficulty_mgmt.min_individual_miner_hashrate = hashrate as f32;
+            }
+            _ => tracing::warn!(
+                "Invalid difficulty suggested by connection {}",
+                self.connection_id
+            ),
+        }
+    }
+
+    /// Hashrate at which a miner submits `shares_per_minute` shares of `difficulty`, a share of
+    /// difficulty 1 takes 2^32 hashes on average
+    pub(super) fn hashrate_from_difficulty(difficulty: f64, shares_per_minute: f32) -> f64 {
+        difficulty * 2_f64.powi(32) * shares_per_minute as f64 / 60.0
+    }
+
+    /// Hashrate at which a miner submits `shares_per_minute` shares of `target`, that is big
+    /// endian as sent by `mining.suggest_target`
+    pub(super) fn hashrate_from_suggested_target(
+        target: Vec<u8>,
+        shares_per_minute: f32,
+    ) -> Option<f64> {
+        if target.len() > 32 || Downstream::is_zero(&target) {
+            return None;
+        }
+        let mut le_target = vec![0; 32 - target.len()];
+        le_target.extend(target);
+        le_target.reverse();
+        let target = binary_sv2::U256::try_from(le_target).ok()?;
+        roles_logic_sv2::utils::hash_rate_from_target(target, shares_per_minute.into()).ok()
+    }
+
     /// if enough shares have been submitted according to the config, this function updates the difficulty for the connection and sends the new
     /// difficulty to the miner
     pub async fn try_update_difficulty_settings(
@@ -97,6 +175,9 @@ impl Downstream {
         if let Some(new_hash_rate) =
             Self::update_miner_hashrate(self_.clone(), prev_target.clone())?
         {
+            self_
+                .safe_lock(|d| d.metrics.set_channel_hashrate(channel_id, new_hash_rate))
+                .map_err(|_e| Error::PoisonLock)?;
             let new_target = match roles_logic_sv2::utils::hash_rate_to_target(
                 new_hash_rate.into(),
                 diff_mgmt.shares_per_minute.into(),
@@ -105,6 +186,9 @@ impl Downstream {
                 Err(v) => return Err(Error::TargetError(v)),
             };
             tracing::debug!("New target from hashrate: {:?}", new_target.inner_as_ref());
+            self_
+                .safe_lock(|d| d.target = Some(new_target.clone().into()))
+                .map_err(|_e| Error::PoisonLock)?;
             let message = Self::get_set_difficulty(new_target.to_vec())?;
             // send mining.set_difficulty to miner
             Downstream::send_message_downstream(self_.clone(), message).await?;
@@ -438,7 +522,7 @@ mod test {
             0,
             downstream_conf.clone(),
             Arc::new(Mutex::new(upstream_config)),
-            "0".to_string(),
+            None,
         );
         downstream.difficulty_mgmt.min_individual_miner_hashrate = start_hashrate as f32;
 
@@ -1,7 +1,8 @@
 use crate::{
     downstream_sv1,
     error::ProxyResult,
-    proxy_config::{DownstreamDifficultyConfig, UpstreamDifficultyConfig},
+    proxy::Bridge,
+    proxy_config::{ChannelMode, DownstreamDifficultyConfig, UpstreamDifficultyConfig},
     status,
 };
 use async_channel::{bounded, Receiver, Sender};
@@ -13,23 +14,32 @@ use async_std::{
 };
 use error_handling::handle_result;
 use futures::FutureExt;
+use metrics_sv2::{ChannelKind, Metrics};
 use tokio::{sync::broadcast, task::AbortHandle};
 
-use super::{kill, DownstreamMessages, SubmitShareWithChannelId, SUBSCRIBE_TIMEOUT_SECS};
+use super::{
+    kill, Authenticator, Authorization, AuthorizeWorker, DownstreamMessages, SetDownstreamTarget,
+    SubmitShareWithChannelId, SUBSCRIBE_TIMEOUT_SECS,
+};
 
 use roles_logic_sv2::{
     common_properties::{IsDownstream, IsMiningDownstream},
-    utils::Mutex,
+    mining_sv2::Target,
+    utils::{merkle_root_from_path, u256_to_block_hash, Mutex},
 };
 
 use crate::error::Error;
 use futures::select;
 use tokio_util::codec::{FramedRead, LinesCodec};
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use stratum_common::bitcoin::{
+    blockdata::block::BlockHeader,
+    hashes::{sha256d::Hash, Hash as _},
+};
 use tracing::{debug, info, warn};
 use v1::{
-    client_to_server::{self, Submit},
+    client_to_server::{self, Submit, SubmitError},
     json_rpc, server_to_client,
     utils::{Extranonce, HexU32Be},
     IsServer,
@@ -62,11 +72,27 @@ pub struct Downstream {
     extranonce2_len: usize,
     pub(super) difficulty_mgmt: DownstreamDifficultyConfig,
     pub(super) upstream_difficulty_config: Arc<Mutex<UpstreamDifficultyConfig>>,
-    last_job_id: String, // we usually receive a String on SV1 messages, no need to cast to u32
+    /// Last `mining.notify` sent to the Downstream, the only job for which shares are accepted.
+    last_notify: Option<server_to_client::Notify<'static>>,
+    /// Target of the last `mining.set_difficulty` sent to the Downstream, shares that do not meet
+    /// it are rejected without being sent to the `Bridge`.
+    pub(super) target: Option<Target>,
+    /// Epoch of the `Bridge` in which the channel of this Downstream has been opened
+    upstream_epoch: u32,
+    /// True if the Downstream sent `mining.extranonce.subscribe`, i.e. it supports
+    /// `mining.set_extranonce`.
+    extranonce_subscribed: bool,
+    /// Decides which workers of the Downstream are authorized by `mining.authorize`
+    authenticator: Arc<Authenticator>,
+    /// True if the Downstream has its own channel with the upstream, whose target is set by the
+    /// upstream instead of the difficulty management of the translator.
+    pub(super) per_connection_channel: bool,
+    pub(super) metrics: Arc<Metrics>,
 }
 
 impl Downstream {
     #[cfg(test)]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         connection_id: u32,
         authorized_names: Vec<String>,
@@ -79,7 +105,7 @@ impl Downstream {
         extranonce2_len: usize,
         difficulty_mgmt: DownstreamDifficultyConfig,
         upstream_difficulty_config: Arc<Mutex<UpstreamDifficultyConfig>>,
-        last_job_id: String,
+        last_notify: Option<server_to_client::Notify<'static>>,
     ) -> Self {
         Downstream {
             connection_id,
@@ -93,7 +119,13 @@ impl Downstream {
             extranonce2_len,
             difficulty_mgmt,
             upstream_difficulty_config,
-            last_job_id,
+            last_notify,
+            target: None,
+            upstream_epoch: 0,
+            extranonce_subscribed: false,
+            authenticator: Arc::new(Authenticator::AllowAll),
+            per_connection_channel: false,
+            metrics: Arc::new(Metrics::new()),
         }
     }
     /// Instantiate a new `Downstream`.
@@ -103,14 +135,19 @@ impl Downstream {
         connection_id: u32,
         tx_sv1_bridge: Sender<DownstreamMessages>,
         mut rx_sv1_notify: broadcast::Receiver<server_to_client::Notify<'static>>,
+        mut rx_sv1_reconnect: broadcast::Receiver<server_to_client::Reconnect>,
         tx_status: status::Sender,
         extranonce1: Vec<u8>,
-        last_notify: Option<server_to_client::Notify<'static>>,
+        mut last_notify: Option<server_to_client::Notify<'static>>,
         extranonce2_len: usize,
+        upstream_epoch: u32,
+        bridge: Arc<Mutex<Bridge>>,
         host: String,
         difficulty_config: DownstreamDifficultyConfig,
         upstream_difficulty_config: Arc<Mutex<UpstreamDifficultyConfig>>,
         task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>,
+        authenticator: Arc<Authenticator>,
+        metrics: Arc<Metrics>,
     ) {
         let stream = std::sync::Arc::new(stream);
 
@@ -122,6 +159,10 @@ impl Downstream {
         // Used to send SV1 `mining.notify` messages to the Downstreams
         let _socket_writer_notify = socket_writer;
 
+        let per_connection_channel = bridge
+            .safe_lock(|b| b.channel_mode() == ChannelMode::PerConnection)
+            .unwrap_or(false);
+
         let downstream = Arc::new(Mutex::new(Downstream {
             connection_id,
             authorized_names: vec![],
@@ -135,7 +176,13 @@ impl Downstream {
             extranonce2_len,
             difficulty_mgmt: difficulty_config,
             upstream_difficulty_config,
-            last_job_id: "".to_string(),
+            last_notify: None,
+            target: None,
+            upstream_epoch,
+            extranonce_subscribed: false,
+            authenticator,
+            per_connection_channel,
+            metrics,
         }));
         let self_ = downstream.clone();
 
@@ -183,7 +230,13 @@ impl Downstream {
                                     }
                                 }
 
-                                let res = Self::handle_incoming_sv1(self_.clone(), incoming).await;
+                                // the answer to `mining.authorize` may be up to the upstream
+                                let res = match Self::upstream_authorization(&self_, &incoming) {
+                                    Some((authorize, timeout_secs)) => {
+                                        Self::authorize_with_upstream(self_.clone(), authorize, timeout_secs).await
+                                    }
+                                    None => Self::handle_incoming_sv1(self_.clone(), incoming).await,
+                                };
                                 handle_result!(tx_status_reader, res);
                             }
                             Some(Err(_)) => {
@@ -274,26 +327,44 @@ impl Downstream {
                     }
                 };
                 if is_a && !first_sent && last_notify.is_some() {
-                    let target = handle_result!(
+                    let up_to_date = handle_result!(
                         tx_status_notify,
-                        Self::hash_rate_to_target(downstream.clone())
-                    );
-                    // make sure the mining start time is initialized and reset number of shares submitted
-                    handle_result!(
-                        tx_status_notify,
-                        Self::init_difficulty_management(downstream.clone(), &target).await
-                    );
-                    let message =
-                        handle_result!(tx_status_notify, Self::get_set_difficulty(target));
-                    handle_result!(
-                        tx_status_notify,
-                        Downstream::send_message_downstream(downstream.clone(), message).await
+                        Self::check_upstream_switch(downstream.clone(), bridge.clone()).await
                     );
+                    if !up_to_date {
+                        // wait for the next job
+                        last_notify = None;
+                        continue;
+                    }
+                    if per_connection_channel {
+                        // the target of the channel is set by the upstream
+                        handle_result!(
+                            tx_status_notify,
+                            Self::update_connection_target(downstream.clone(), bridge.clone())
+                                .await
+                        );
+                    } else {
+                        let target = handle_result!(
+                            tx_status_notify,
+                            Self::hash_rate_to_target(downstream.clone())
+                        );
+                        // make sure the mining start time is initialized and reset number of shares submitted
+                        handle_result!(
+                            tx_status_notify,
+                            Self::init_difficulty_management(downstream.clone(), &target).await
+                        );
+                        let message =
+                            handle_result!(tx_status_notify, Self::get_set_difficulty(target));
+                        handle_result!(
+                            tx_status_notify,
+                            Downstream::send_message_downstream(downstream.clone(), message).await
+                        );
+                    }
 
                     let sv1_mining_notify_msg = last_notify.clone().unwrap();
 
                     self_
-                        .safe_lock(|s| s.last_job_id = sv1_mining_notify_msg.clone().job_id)
+                        .safe_lock(|s| s.last_notify = Some(sv1_mining_notify_msg.clone()))
                         .unwrap();
 
                     let message: json_rpc::Message = sv1_mining_notify_msg.into();
@@ -312,16 +383,38 @@ impl Downstream {
                     // if hashrate has changed, update difficulty management, and send new mining.set_difficulty
                     select! {
                         res = rx_sv1_notify.recv().fuse() => {
-                            // if hashrate has changed, update difficulty management, and send new mining.set_difficulty
-                            handle_result!(tx_status_notify, Self::try_update_difficulty_settings(downstream.clone()).await);
-
                             let sv1_mining_notify_msg = handle_result!(tx_status_notify, res);
+                            if !first_sent {
+                                // no job was available when the miner connected, the first one
+                                // is sent along with the first mining.set_difficulty
+                                last_notify = Some(sv1_mining_notify_msg);
+                                continue;
+                            }
+                            // the jobs of a new upstream need the extranonce1 of a new channel
+                            let up_to_date = handle_result!(tx_status_notify, Self::check_upstream_switch(downstream.clone(), bridge.clone()).await);
+                            if !up_to_date {
+                                continue;
+                            }
+
+                            if per_connection_channel {
+                                // a new target of the upstream applies from the next job
+                                handle_result!(tx_status_notify, Self::update_connection_target(downstream.clone(), bridge.clone()).await);
+                            } else {
+                                // if hashrate has changed, update difficulty management, and send new mining.set_difficulty
+                                handle_result!(tx_status_notify, Self::try_update_difficulty_settings(downstream.clone()).await);
+                            }
+
                             let message: json_rpc::Message = sv1_mining_notify_msg.clone().into();
 
-                            self_.safe_lock(|s| s.last_job_id = sv1_mining_notify_msg.job_id).unwrap();
+                            self_.safe_lock(|s| s.last_notify = Some(sv1_mining_notify_msg)).unwrap();
 
                             handle_result!(tx_status_notify, Downstream::send_message_downstream(downstream.clone(), message).await);
                         },
+                        res = rx_sv1_reconnect.recv().fuse() => {
+                            let reconnect = handle_result!(tx_status_notify, res);
+                            info!("Asking downstream {} to reconnect to {:?}:{:?}", &host, reconnect.host, reconnect.port);
+                            handle_result!(tx_status_notify, Downstream::send_message_downstream(downstream.clone(), reconnect.into()).await);
+                        },
                         _ = rx_shutdown.recv().fuse() => {
                                 break;
                             }
@@ -338,7 +431,20 @@ impl Downstream {
                     task::sleep(std::time::Duration::from_secs(1)).await;
                 }
             }
-            let _ = Self::remove_miner_hashrate_from_channel(self_);
+            let connection_id = self_
+                .safe_lock(|d| {
+                    d.metrics.channel_closed(d.connection_id);
+                    d.metrics.downstream_disconnected();
+                    d.connection_id
+                })
+                .map_err(|_e| Error::PoisonLock);
+            if per_connection_channel {
+                if let Ok(connection_id) = connection_id {
+                    let _ = bridge.safe_lock(|b| b.on_sv1_connection_closed(connection_id));
+                }
+            } else {
+                let _ = Self::remove_miner_hashrate_from_channel(self_);
+            }
             kill(&tx_shutdown).await;
             warn!(
                 "Downstream: Shutting down sv1 downstream job notifier for {}",
@@ -353,15 +459,19 @@ impl Downstream {
     /// Accept connections from one or more SV1 Downstream roles (SV1 Mining Devices) and create a
     /// new `Downstream` for each connection.
     #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::result_large_err)]
     pub fn accept_connections(
         downstream_addr: SocketAddr,
         tx_sv1_submit: Sender<DownstreamMessages>,
         tx_mining_notify: broadcast::Sender<server_to_client::Notify<'static>>,
+        tx_reconnect: broadcast::Sender<server_to_client::Reconnect>,
         tx_status: status::Sender,
         bridge: Arc<Mutex<crate::proxy::Bridge>>,
         downstream_difficulty_config: DownstreamDifficultyConfig,
         upstream_difficulty_config: Arc<Mutex<UpstreamDifficultyConfig>>,
         task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>,
+        authenticator: Arc<Authenticator>,
+        metrics: Arc<Metrics>,
     ) {
         let task_collector_downstream = task_collector.clone();
 
@@ -372,27 +482,41 @@ impl Downstream {
             while let Some(stream) = downstream_incoming.next().await {
                 let stream = stream.expect("Err on SV1 Downstream connection stream");
                 let expected_hash_rate = downstream_difficulty_config.min_individual_miner_hashrate;
-                let open_sv1_downstream = bridge
-                    .safe_lock(|s| s.on_new_sv1_connection(expected_hash_rate))
-                    .unwrap();
+                let channel_mode = bridge.safe_lock(|s| s.channel_mode()).unwrap();
+                let open_sv1_downstream = match channel_mode {
+                    ChannelMode::Aggregated => bridge
+                        .safe_lock(|s| s.on_new_sv1_connection(expected_hash_rate))
+                        .unwrap()
+                        .map(|opened| (opened, tx_mining_notify.subscribe())),
+                    // the connection gets the jobs of its own channel
+                    ChannelMode::PerConnection => {
+                        Bridge::open_connection_channel(bridge.clone(), expected_hash_rate).await
+                    }
+                };
 
                 let host = stream.peer_addr().unwrap().to_string();
                 match open_sv1_downstream {
-                    Ok(opened) => {
+                    Ok((opened, rx_sv1_notify)) => {
                         info!("PROXY SERVER - ACCEPTING FROM DOWNSTREAM: {}", host);
+                        metrics.downstream_connected();
                         Downstream::new_downstream(
                             stream,
                             opened.channel_id,
                             tx_sv1_submit.clone(),
-                            tx_mining_notify.subscribe(),
+                            rx_sv1_notify,
+                            tx_reconnect.subscribe(),
                             tx_status.listener_to_connection(),
                             opened.extranonce,
                             opened.last_notify,
                             opened.extranonce2_len as usize,
+                            opened.upstream_epoch,
+                            bridge.clone(),
                             host,
                             downstream_difficulty_config.clone(),
                             upstream_difficulty_config.clone(),
                             task_collector_downstream.clone(),
+                            authenticator.clone(),
+                            metrics.clone(),
                         )
                         .await;
                     }
@@ -413,6 +537,7 @@ impl Downstream {
     /// As SV1 messages come in, determines if the message response needs to be translated to SV2
     /// and sent to the `Upstream`, or if a direct response can be sent back by the `Translator`
     /// (SV1 and SV2 protocol messages are NOT 1-to-1).
+    #[allow(clippy::result_large_err)]
     async fn handle_incoming_sv1(
         self_: Arc<Mutex<Self>>,
         message_sv1: json_rpc::Message,
@@ -442,6 +567,66 @@ impl Downstream {
         }
     }
 
+    /// Returns the `mining.authorize` in `message` if the decision on its worker is up to the
+    /// upstream, along with the max number of seconds to wait for it.
+    fn upstream_authorization(
+        self_: &Arc<Mutex<Self>>,
+        message: &json_rpc::Message,
+    ) -> Option<(client_to_server::Authorize, u64)> {
+        let request = match message {
+            json_rpc::Message::StandardRequest(request) if request.method == "mining.authorize" => {
+                request.clone()
+            }
+            _ => return None,
+        };
+        let authorize = client_to_server::Authorize::try_from(request).ok()?;
+        let authorization = self_
+            .safe_lock(|d| {
+                d.authenticator
+                    .authorize(&authorize.name, &authorize.password)
+            })
+            .ok()?;
+        match authorization {
+            Authorization::AskUpstream { timeout_secs } => Some((authorize, timeout_secs)),
+            Authorization::Accepted | Authorization::Rejected => None,
+        }
+    }
+
+    /// Asks the upstream, through the `Bridge`, whether the worker of `request` is authorized and
+    /// answers the `mining.authorize` with its decision. The worker is not authorized if the
+    /// upstream does not answer within `timeout_secs`.
+    async fn authorize_with_upstream(
+        self_: Arc<Mutex<Self>>,
+        request: client_to_server::Authorize,
+        timeout_secs: u64,
+    ) -> ProxyResult<'static, ()> {
+        let (tx_authorized, rx_authorized) = bounded(1);
+        Self::send_message_upstream(
+            self_.clone(),
+            DownstreamMessages::AuthorizeWorker(AuthorizeWorker {
+                name: request.name.clone(),
+                tx_authorized,
+            }),
+        )
+        .await?;
+        let authorized = matches!(
+            tokio::time::timeout(Duration::from_secs(timeout_secs), rx_authorized.recv()).await,
+            Ok(Ok(true))
+        );
+        if authorized {
+            self_
+                .safe_lock(|d| d.authorize(&request.name))
+                .map_err(|_e| Error::PoisonLock)?;
+        } else {
+            warn!(
+                "Down: worker {} not authorized by the upstream",
+                request.name
+            );
+        }
+        Self::send_message_downstream(self_, request.respond(authorized).into()).await?;
+        Ok(())
+    }
+
     /// Send SV1 response message that is generated by `Downstream` (as opposed to being received
     /// by `Bridge`) to be written to the SV1 Downstream role.
     pub(super) async fn send_message_downstream(
@@ -464,6 +649,128 @@ impl Downstream {
         let _ = sender.send(msg).await;
         Ok(())
     }
+
+    /// Opens a new channel with the `Bridge` if it has been moved to another upstream, or the
+    /// upstream changed the extranonce prefix, after the channel of this Downstream was opened,
+    /// and sends to the miner the new extranonce1 with `mining.set_extranonce`. A miner that did
+    /// not send `mining.extranonce.subscribe` is asked to reconnect instead. Called before sending
+    /// to the miner a new job, that must not be sent if `false` is returned.
+    #[allow(clippy::result_large_err)]
+    async fn check_upstream_switch(
+        self_: Arc<Mutex<Self>>,
+        bridge: Arc<Mutex<Bridge>>,
+    ) -> ProxyResult<'static, bool> {
+        let (upstream_epoch, hashrate, extranonce_subscribed, per_connection_channel) = self_
+            .safe_lock(|d| {
+                (
+                    d.upstream_epoch,
+                    d.difficulty_mgmt.min_individual_miner_hashrate,
+                    d.extranonce_subscribed,
+                    d.per_connection_channel,
+                )
+            })
+            .map_err(|_e| Error::PoisonLock)?;
+        // the `Bridge` closes the connections with their own channel when it changes upstream
+        if per_connection_channel {
+            return Ok(true);
+        }
+        let current_epoch = bridge
+            .safe_lock(|b| b.upstream_epoch())
+            .map_err(|_e| Error::PoisonLock)?;
+        if upstream_epoch == current_epoch {
+            return Ok(true);
+        }
+        if !extranonce_subscribed {
+            // the miner gets a channel with the new extranonce1 when it connects again, in the
+            // meantime it is not sent jobs that it can not mine
+            info!("Asking downstream to reconnect to get a new extranonce1");
+            let reconnect = server_to_client::Reconnect {
+                host: None,
+                port: None,
+                wait_time: None,
+            };
+            Self::send_message_downstream(self_, reconnect.into()).await?;
+            return Ok(false);
+        }
+        let opened = bridge
+            .safe_lock(|b| b.on_new_sv1_connection(hashrate))
+            .map_err(|_e| Error::PoisonLock)??;
+        let (message, target) = self_
+            .safe_lock(|d| {
+                d.metrics.channel_closed(d.connection_id);
+                d.connection_id = opened.channel_id;
+                d.extranonce1 = opened.extranonce;
+                d.extranonce2_len = opened.extranonce2_len as usize;
+                d.upstream_epoch = opened.upstream_epoch;
+                // the jobs of the previous upstream can not be mined anymore
+                d.last_notify = None;
+                for name in d.authorized_names.iter() {
+                    d.metrics.channel_opened(
+                        d.connection_id,
+                        ChannelKind::Extended,
+                        name,
+                        hashrate,
+                    );
+                }
+                let extranonce1 = d.extranonce1.clone().try_into()?;
+                let message = d.update_extranonce(extranonce1, d.extranonce2_len)?;
+                Ok::<_, Error<'static>>((message, d.target.clone()))
+            })
+            .map_err(|_e| Error::PoisonLock)??;
+        info!(
+            "Downstream moved to channel {} of the new upstream",
+            opened.channel_id
+        );
+        // the Bridge checks the shares of the new channel against the target of the miner
+        if let Some(new_target) = target {
+            Self::send_message_upstream(
+                self_.clone(),
+                DownstreamMessages::SetDownstreamTarget(SetDownstreamTarget {
+                    channel_id: opened.channel_id,
+                    new_target,
+                }),
+            )
+            .await?;
+        }
+        Self::send_message_downstream(self_, message).await?;
+        Ok(true)
+    }
+
+    /// Computes the hash of the header mined by the Downstream for `share`, as a `Target` so that
+    /// it can be compared with the Downstream and Upstream targets. Returns `None` if the coinbase
+    /// can not be rebuilt.
+    fn share_hash(
+        notify: &server_to_client::Notify<'static>,
+        extranonce1: &[u8],
+        share: &Submit<'static>,
+        version_rolling_mask: Option<&HexU32Be>,
+    ) -> Option<Target> {
+        let extranonce = [extranonce1, share.extra_nonce2.as_ref()].concat();
+        let coinbase_prefix: &Vec<u8> = notify.coin_base1.as_ref();
+        let coinbase_suffix: &Vec<u8> = notify.coin_base2.as_ref();
+        let merkle_root: [u8; 32] = merkle_root_from_path(
+            coinbase_prefix,
+            coinbase_suffix,
+            &extranonce,
+            &notify.merkle_branch,
+        )?
+        .try_into()
+        .ok()?;
+        // regarding version masking see https://github.com/slushpool/stratumprotocol/blob/master/stratum-extensions.mediawiki#changes-in-request-miningsubmit
+        let version = match (&share.version_bits, version_rolling_mask) {
+            (Some(vb), Some(mask)) => (notify.version.0 & !mask.0) | (vb.0 & mask.0),
+            _ => notify.version.0,
+        };
+        let header = BlockHeader {
+            version: version as i32,
+            prev_blockhash: u256_to_block_hash(notify.prev_hash.0.clone()),
+            merkle_root: Hash::from_inner(merkle_root).into(),
+            time: share.time.0,
+            bits: notify.bits.0,
+            nonce: share.nonce.0,
+        };
+        Some(header.block_hash().as_hash().into_inner().into())
+    }
 }
 
 /// Implements `IsServer` for `Downstream` to handle the SV1 messages.
@@ -523,41 +830,105 @@ impl IsServer<'static> for Downstream {
     /// Any numbers of workers may be authorized at any time during the session. In this way, a
     /// large number of independent Mining Devices can be handled with a single SV1 connection.
     /// https://bitcoin.stackexchange.com/questions/29416/how-do-pool-servers-handle-multiple-workers-sharing-one-connection-with-stratum
+    /// Whether a worker is authorized depends on the `auth` section of the config, when it is up
+    /// to the upstream the request is handled by `authorize_with_upstream` instead.
     fn handle_authorize(&self, request: &client_to_server::Authorize) -> bool {
         info!("Down: Authorizing");
         debug!("Down: Handling mining.authorize: {:?}", &request);
-        true
+        match self
+            .authenticator
+            .authorize(&request.name, &request.password)
+        {
+            Authorization::Accepted => true,
+            Authorization::Rejected | Authorization::AskUpstream { .. } => {
+                warn!("Down: worker {} not authorized", request.name);
+                false
+            }
+        }
     }
 
     /// When miner find the job which meets requested difficulty, it can submit share to the server.
     /// Only [Submit](client_to_server::Submit) requests for authorized user names can be submitted.
+    /// The share has already been validated by `check_submit`, so it is sent to the `Bridge`, that
+    /// forwards upstream only the ones that meet the upstream target.
     fn handle_submit(&self, request: &client_to_server::Submit<'static>) -> bool {
         info!("Down: Submitting Share {:?}", request);
         debug!("Down: Handling mining.submit: {:?}", &request);
 
-        // TODO: Check if receiving valid shares by adding diff field to Downstream
+        let to_send = SubmitShareWithChannelId {
+            channel_id: self.connection_id,
+            share: request.clone(),
+            extranonce: self.extranonce1.clone(),
+            extranonce2_len: self.extranonce2_len,
+            version_rolling_mask: self.version_rolling_mask.clone(),
+        };
 
-        if request.job_id == self.last_job_id {
-            let to_send = SubmitShareWithChannelId {
-                channel_id: self.connection_id,
-                share: request.clone(),
-                extranonce: self.extranonce1.clone(),
-                extranonce2_len: self.extranonce2_len,
-                version_rolling_mask: self.version_rolling_mask.clone(),
-            };
+        self.tx_sv1_bridge
+            .try_send(DownstreamMessages::SubmitShares(to_send))
+            .unwrap();
 
-            self.tx_sv1_bridge
-                .try_send(DownstreamMessages::SubmitShares(to_send))
-                .unwrap();
+        true
+    }
 
-            true
+    /// Rebuilds the header of the share from the last job sent to the Downstream and rejects it if
+    /// it is for another job or if its hash does not meet the Downstream target.
+    fn check_submit(&self, request: &client_to_server::Submit<'static>) -> Result<(), SubmitError> {
+        let notify = match &self.last_notify {
+            Some(notify) if notify.job_id == request.job_id => notify,
+            _ => {
+                warn!("Down: share for unknown job {}", request.job_id);
+                self.metrics.share_rejected("job-not-found");
+                return Err(SubmitError::JobNotFound);
+            }
+        };
+        let target = match &self.target {
+            Some(target) => target.clone(),
+            // no mining.set_difficulty has been sent yet
+            None => return Err(SubmitError::NotSubscribed),
+        };
+        let hash = Self::share_hash(
+            notify,
+            &self.extranonce1,
+            request,
+            self.version_rolling_mask.as_ref(),
+        )
+        .ok_or(SubmitError::Other)?;
+        if hash <= target {
+            Ok(())
         } else {
-            false
+            debug!(
+                "Down: share {} does not meet the downstream target",
+                request.id
+            );
+            self.metrics.share_rejected("difficulty-too-low");
+            Err(SubmitError::LowDifficulty)
         }
     }
 
     /// Indicates to the server that the client supports the mining.set_extranonce method.
-    fn handle_extranonce_subscribe(&self) {}
+    fn handle_extranonce_subscribe(&mut self) {
+        self.extranonce_subscribed = true;
+    }
+
+    /// The suggested difficulty is the starting one of the connection, if it comes before the
+    /// first job.
+    fn handle_suggest_difficulty(&mut self, request: &client_to_server::SuggestDifficulty) {
+        debug!("Down: Handling mining.suggest_difficulty: {:?}", &request);
+        let hashrate =
+            Self::hashrate_from_difficulty(request.value, self.difficulty_mgmt.shares_per_minute);
+        self.suggest_hashrate(Some(hashrate));
+    }
+
+    /// The suggested target is the starting one of the connection, if it comes before the first
+    /// job.
+    fn handle_suggest_target(&mut self, request: &client_to_server::SuggestTarget) {
+        debug!("Down: Handling mining.suggest_target: {:?}", &request);
+        let hashrate = Self::hashrate_from_suggested_target(
+            request.target.clone().into(),
+            self.difficulty_mgmt.shares_per_minute,
+        );
+        self.suggest_hashrate(hashrate);
+    }
 
     /// Checks if a Downstream role is authorized.
     fn is_authorized(&self, name: &str) -> bool {
@@ -566,6 +937,13 @@ impl IsServer<'static> for Downstream {
 
     /// Authorizes a Downstream role.
     fn authorize(&mut self, name: &str) {
+        // every SV1 connection has its own extended channel in the proxy
+        self.metrics.channel_opened(
+            self.connection_id,
+            ChannelKind::Extended,
+            name,
+            self.difficulty_mgmt.min_individual_miner_hashrate,
+        );
         self.authorized_names.push(name.to_string());
     }
 
@@ -609,7 +987,7 @@ impl IsServer<'static> for Downstream {
         self.version_rolling_min_bit = mask
     }
 
-    fn notify(&mut self) -> Result<json_rpc::Message, v1::error::Error> {
+    fn notify(&mut self) -> Result<json_rpc::Message, v1::error::Error<'_>> {
         unreachable!()
     }
 }
@@ -638,4 +1016,162 @@ mod tests {
         let expect = 512.0;
         assert_eq!(actual, expect);
     }
+
+    fn test_notify() -> server_to_client::Notify<'static> {
+        // coinbase with a single input whose script is extranonce1 + extranonce2 and an output
+        // with an empty script
+        let mut coin_base1 = vec![1, 0, 0, 0, 1];
+        coin_base1.extend_from_slice(&[0; 32]);
+        coin_base1.extend_from_slice(&[255, 255, 255, 255, 4]);
+        let mut coin_base2 = vec![255, 255, 255, 255, 1];
+        coin_base2.extend_from_slice(&[0; 9]);
+        coin_base2.extend_from_slice(&[0; 4]);
+        server_to_client::Notify {
+            job_id: "1".to_string(),
+            prev_hash: v1::utils::PrevHash(vec![0; 32].try_into().unwrap()),
+            coin_base1: coin_base1.into(),
+            coin_base2: coin_base2.into(),
+            merkle_branch: vec![],
+            version: HexU32Be(0x20000000),
+            bits: HexU32Be(0x1d00ffff),
+            time: HexU32Be(0),
+            clean_jobs: true,
+        }
+    }
+
+    fn test_downstream(authorized_names: Vec<String>) -> Downstream {
+        let (tx_sv1_submit, _rx_sv1_submit) = async_channel::unbounded();
+        let (tx_outgoing, _rx_outgoing) = async_channel::unbounded();
+        Downstream::new(
+            1,
+            authorized_names,
+            vec![0, 1],
+            None,
+            None,
+            tx_sv1_submit,
+            tx_outgoing,
+            true,
+            2,
+            DownstreamDifficultyConfig {
+                min_individual_miner_hashrate: 1.0,
+                shares_per_minute: 1.0,
+                submits_since_last_update: 0,
+                timestamp_of_last_update: 0,
+            },
+            Arc::new(Mutex::new(UpstreamDifficultyConfig {
+                channel_diff_update_interval: 60,
+                channel_nominal_hashrate: 0.0,
+                timestamp_of_last_update: 0,
+                should_aggregate: false,
+            })),
+            Some(test_notify()),
+        )
+    }
+
+    fn test_share() -> Submit<'static> {
+        Submit {
+            user_name: "user".to_string(),
+            job_id: "1".to_string(),
+            extra_nonce2: vec![2, 3].try_into().unwrap(),
+            time: HexU32Be(0),
+            nonce: HexU32Be(0),
+            version_bits: None,
+            id: 1,
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_shares_locally() {
+        let mut downstream = test_downstream(vec!["user".to_string()]);
+        let share = test_share();
+
+        downstream.target = Some([255; 32].into());
+        assert_eq!(downstream.check_submit(&share), Ok(()));
+
+        let mut stale = share.clone();
+        stale.job_id = "0".to_string();
+        assert_eq!(
+            downstream.check_submit(&stale),
+            Err(SubmitError::JobNotFound)
+        );
+
+        downstream.target = Some([0; 32].into());
+        assert_eq!(
+            downstream.check_submit(&share),
+            Err(SubmitError::LowDifficulty)
+        );
+    }
+
+    #[test]
+    fn rejects_unauthorized_workers() {
+        let mut downstream = test_downstream(vec![]);
+        downstream.authenticator = Arc::new(Authenticator::Allowlist(
+            ["user".to_string()].into_iter().collect(),
+        ));
+
+        let authorize = |name: &str| {
+            v1::methods::Client2Server::Authorize(client_to_server::Authorize {
+                id: 0,
+                name: name.to_string(),
+                password: "x".to_string(),
+            })
+        };
+        let response = downstream.handle_request(authorize("other")).unwrap();
+        assert_eq!(response.unwrap().result, serde_json::Value::Bool(false));
+        assert!(!downstream.is_authorized("other"));
+
+        // the share of a worker that is not authorized gets an error and is not forwarded
+        let mut share = test_share();
+        share.user_name = "other".to_string();
+        let response = downstream
+            .handle_request(v1::methods::Client2Server::Submit(share))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            response.error.unwrap().code,
+            SubmitError::UnauthorizedWorker.code()
+        );
+
+        let response = downstream.handle_request(authorize("user")).unwrap();
+        assert_eq!(response.unwrap().result, serde_json::Value::Bool(true));
+        assert!(downstream.is_authorized("user"));
+    }
+
+    #[test]
+    fn suggested_difficulty_is_the_starting_one() {
+        let mut downstream = test_downstream(vec![]);
+        let suggest = |value: f64| {
+            v1::methods::Client2Server::SuggestDifficulty(client_to_server::SuggestDifficulty {
+                id: 0,
+                value,
+            })
+        };
+
+        // no response is sent, and with one share per minute the miner does 2^32 hashes a minute
+        assert!(downstream.handle_request(suggest(60.0)).unwrap().is_none());
+        assert_eq!(
+            downstream.difficulty_mgmt.min_individual_miner_hashrate,
+            2_f32.powi(32)
+        );
+
+        // the same with the target of difficulty 1
+        let mut target = vec![0, 0, 0, 0, 255, 255];
+        target.resize(32, 0);
+        let suggest_target =
+            v1::methods::Client2Server::SuggestTarget(client_to_server::SuggestTarget {
+                id: 0,
+                target: target.into(),
+            });
+        downstream.handle_request(suggest_target).unwrap();
+        let hashrate = downstream.difficulty_mgmt.min_individual_miner_hashrate;
+        assert!((hashrate / (2_f32.powi(32) / 60.0) - 1.0).abs() < 0.01);
+
+        // once the miner got a difficulty it is up to the difficulty management
+        downstream.target = Some([255; 32].into());
+        downstream.handle_request(suggest(1.0)).unwrap();
+        assert_eq!(
+            downstream.difficulty_mgmt.min_individual_miner_hashrate,
+            hashrate
+        );
+    }
 }
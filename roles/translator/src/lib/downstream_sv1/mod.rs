@@ -1,7 +1,10 @@
+use async_channel::Sender;
 use roles_logic_sv2::mining_sv2::Target;
 use v1::{client_to_server::Submit, utils::HexU32Be};
+pub mod auth;
 pub mod diff_management;
 pub mod downstream;
+pub use auth::{Authenticator, Authorization};
 pub use downstream::Downstream;
 
 /// This constant is used as a check to ensure clients
@@ -16,6 +19,7 @@ const SUBSCRIBE_TIMEOUT_SECS: u64 = 10;
 pub enum DownstreamMessages {
     SubmitShares(SubmitShareWithChannelId),
     SetDownstreamTarget(SetDownstreamTarget),
+    AuthorizeWorker(AuthorizeWorker),
 }
 
 /// wrapper around a `mining.submit` with extra channel informationfor the Bridge to
@@ -37,6 +41,14 @@ pub struct SetDownstreamTarget {
     pub new_target: Target,
 }
 
+/// message for asking the upstream, through the Bridge, whether a worker is authorized, the
+/// answer is sent on `tx_authorized`
+#[derive(Debug, Clone)]
+pub struct AuthorizeWorker {
+    pub name: String,
+    pub tx_authorized: Sender<bool>,
+}
+
 /// This is just a wrapper function to send a message on the Downstream task shutdown channel
 /// it does not matter what message is sent because the receiving ends should shutdown on any message
 pub async fn kill(sender: &async_channel::Sender<bool>) {
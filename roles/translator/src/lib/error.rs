@@ -13,7 +13,10 @@ pub type ProxyResult<'a, T> = core::result::Result<T, Error<'a>>;
 #[derive(Debug)]
 pub enum ChannelSendError<'a> {
     SubmitSharesExtended(
-        async_channel::SendError<roles_logic_sv2::mining_sv2::SubmitSharesExtended<'a>>,
+        async_channel::SendError<(
+            roles_logic_sv2::mining_sv2::SubmitSharesExtended<'a>,
+            String,
+        )>,
     ),
     SetNewPrevHash(async_channel::SendError<roles_logic_sv2::mining_sv2::SetNewPrevHash<'a>>),
     NewExtendedMiningJob(async_channel::SendError<NewExtendedMiningJob<'a>>),
@@ -51,6 +54,8 @@ pub enum Error<'a> {
     Io(std::io::Error),
     /// Errors due to invalid extranonce from upstream
     InvalidExtranonce(String),
+    /// Errors on a bad line in the allowlist or password file of the workers
+    InvalidAuthFile(String),
     /// Errors on bad `String` to `int` conversion.
     ParseInt(std::num::ParseIntError),
     /// Errors from `roles_logic_sv2` crate.
@@ -89,6 +94,7 @@ impl<'a> fmt::Display for Error<'a> {
             CodecNoise(ref e) => write!(f, "Noise error: `{:?}", e),
             FramingSv2(ref e) => write!(f, "Framing SV2 error: `{:?}`", e),
             InvalidExtranonce(ref e) => write!(f, "Invalid Extranonce error: `{:?}", e),
+            InvalidAuthFile(ref e) => write!(f, "Invalid auth file: `{:?}`", e),
             Io(ref e) => write!(f, "I/O error: `{:?}", e),
             ParseInt(ref e) => write!(f, "Bad convert from `String` to `int`: `{:?}`", e),
             RolesSv2Logic(ref e) => write!(f, "Roles SV2 Logic Error: `{:?}`", e),
@@ -192,11 +198,19 @@ impl<'a, T> From<PoisonError<T>> for Error<'a> {
 }
 
 // *** CHANNEL SENDER ERRORS ***
-impl<'a> From<async_channel::SendError<roles_logic_sv2::mining_sv2::SubmitSharesExtended<'a>>>
-    for Error<'a>
+impl<'a>
+    From<
+        async_channel::SendError<(
+            roles_logic_sv2::mining_sv2::SubmitSharesExtended<'a>,
+            String,
+        )>,
+    > for Error<'a>
 {
     fn from(
-        e: async_channel::SendError<roles_logic_sv2::mining_sv2::SubmitSharesExtended<'a>>,
+        e: async_channel::SendError<(
+            roles_logic_sv2::mining_sv2::SubmitSharesExtended<'a>,
+            String,
+        )>,
     ) -> Self {
         Error::ChannelErrorSender(ChannelSendError::SubmitSharesExtended(e))
     }
@@ -1,5 +1,6 @@
 use async_channel::{bounded, unbounded};
-use futures::FutureExt;
+use futures::{Future, FutureExt};
+use metrics_sv2::Metrics;
 use rand::Rng;
 pub use roles_logic_sv2::utils::Mutex;
 use status::Status;
@@ -7,6 +8,7 @@ use std::{
     net::{IpAddr, SocketAddr},
     str::FromStr,
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use tokio::{
@@ -16,9 +18,16 @@ use tokio::{
 use tracing::{debug, error, info, warn};
 pub use v1::server_to_client;
 
-use proxy_config::ProxyConfig;
+use error::ProxyResult;
+use proxy_config::{ProxyConfig, UpstreamDifficultyConfig, UpstreamEndpoint};
+use roles_logic_sv2::{
+    mining_sv2::{ExtendedExtranonce, NewExtendedMiningJob, SetNewPrevHash, SubmitSharesExtended},
+    parsers::Mining,
+};
 
 use crate::status::State;
+use downstream_sv1::Authenticator;
+use upstream_sv2::UpstreamRequest;
 
 pub mod downstream_sv1;
 pub mod error;
@@ -32,6 +41,7 @@ pub mod utils;
 pub struct TranslatorSv2 {
     config: ProxyConfig,
     reconnect_wait_time: u64,
+    metrics: Arc<Metrics>,
 }
 
 impl TranslatorSv2 {
@@ -41,13 +51,21 @@ impl TranslatorSv2 {
         Self {
             config,
             reconnect_wait_time: wait_time,
+            metrics: Arc::new(Metrics::new()),
         }
     }
 
     pub async fn start(self) {
         let (tx_status, rx_status) = unbounded();
 
-        let target = Arc::new(Mutex::new(vec![0; 32]));
+        if let Some(metrics_address) = self.config.metrics_address.clone() {
+            let metrics = self.metrics.clone();
+            task::spawn(async move {
+                if let Err(e) = metrics_sv2::serve(metrics, metrics_address).await {
+                    error!("Metrics endpoint stopped: {}", e);
+                }
+            });
+        }
 
         // Sender/Receiver to send SV1 `mining.notify` message from the `Bridge` to the `Downstream`
         let (tx_sv1_notify, _rx_sv1_notify): (
@@ -55,27 +73,129 @@ impl TranslatorSv2 {
             broadcast::Receiver<server_to_client::Notify>,
         ) = broadcast::channel(10);
 
+        // Sender to send SV1 `client.reconnect` to every `Downstream` when shutting down, each
+        // `Downstream` holds a receiver until it disconnects
+        let (tx_sv1_reconnect, _) = broadcast::channel(1);
+
+        // Collects the tasks of the `Downstream`s, they outlive the connection with any upstream
         let task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>> =
             Arc::new(Mutex::new(Vec::new()));
 
-        self.internal_start(
+        // `tx_sv1_bridge` sender is used by `Downstream` to send a `DownstreamMessages` message to
+        // `Bridge` via the `rx_sv1_downstream` receiver
+        // (Sender<downstream_sv1::DownstreamMessages>, Receiver<downstream_sv1::DownstreamMessages>)
+        let (tx_sv1_bridge, rx_sv1_downstream) = unbounded();
+
+        let diff_config = Arc::new(Mutex::new(self.config.upstream_difficulty_config.clone()));
+
+        let authenticator = match Authenticator::from_config(&self.config.auth) {
+            Ok(authenticator) => Arc::new(authenticator),
+            Err(e) => {
+                error!("Failed to load the auth config: {}", e);
+                return;
+            }
+        };
+
+        let mut shutdown = Box::pin(status::shutdown_signal());
+        let upstreams = self.config.upstreams();
+
+        let mut link = match self
+            .fail_over(&upstreams, u32::MAX, diff_config.clone(), &mut shutdown)
+            .await
+        {
+            Some(link) => link,
+            None => return,
+        };
+
+        // reset channel hashrate so downstreams can manage from now on out
+        let _ = diff_config.safe_lock(|d| d.channel_nominal_hashrate = 0.0);
+
+        // Instantiate a new `Bridge` and begins handling incoming messages
+        let bridge = proxy::Bridge::new(
+            rx_sv1_downstream,
+            link.tx_sv2_submit_shares_ext.clone(),
+            link.tx_upstream_requests.clone(),
+            link.rx_sv2_set_new_prev_hash.clone(),
+            link.rx_sv2_new_ext_mining_job.clone(),
+            link.rx_sv2_extranonce.clone(),
+            link.rx_connection_channel_updates.clone(),
             tx_sv1_notify.clone(),
-            target.clone(),
-            tx_status.clone(),
+            status::Sender::Bridge(link.tx_status.clone()),
+            link.extended_extranonce
+                .take()
+                .expect("Extranonce of a new link"),
+            link.target.clone(),
+            link.up_id,
+            self.config.channel_mode,
+            link.task_collector.clone(),
+            self.metrics.clone(),
+        );
+        proxy::Bridge::start(bridge.clone());
+
+        // Format `Downstream` connection address
+        let downstream_addr = SocketAddr::new(
+            IpAddr::from_str(&self.config.downstream_address).unwrap(),
+            self.config.downstream_port,
+        );
+
+        // Accept connections from one or more SV1 Downstream roles (SV1 Mining Devices)
+        downstream_sv1::Downstream::accept_connections(
+            downstream_addr,
+            tx_sv1_bridge,
+            tx_sv1_notify,
+            tx_sv1_reconnect.clone(),
+            status::Sender::DownstreamListener(tx_status.clone()),
+            bridge.clone(),
+            self.config.downstream_difficulty_config.clone(),
+            diff_config.clone(),
             task_collector.clone(),
-        )
-        .await;
+            authenticator,
+            self.metrics.clone(),
+        );
 
-        debug!("Starting up signal listener");
-        let task_collector_ = task_collector.clone();
+        let mut health_check = tokio::time::interval(Duration::from_secs(
+            self.config.failover.health_check_interval_secs.max(1),
+        ));
+        // the first tick completes immediately
+        health_check.tick().await;
+        // the probes run in their own task, meanwhile the status and the interrupt are handled
+        let (tx_probe, rx_probe) = bounded(1);
+        let mut probing = false;
 
         debug!("Starting up status listener");
         let wait_time = self.reconnect_wait_time;
-        // Check all tasks if is_finished() is true, if so exit
         loop {
-            let task_status = tokio::select! {
-                task_status = rx_status.recv().fuse() => task_status,
-                interrupt_signal = tokio::signal::ctrl_c().fuse() => {
+            let (task_status, from_upstream) = tokio::select! {
+                task_status = rx_status.recv().fuse() => (task_status, false),
+                task_status = link.rx_status.recv().fuse() => (task_status, true),
+                _ = health_check.tick().fuse() => {
+                    if link.priority > 0 && !probing {
+                        probing = true;
+                        self.probe_upstreams(
+                            upstreams.clone(),
+                            link.priority,
+                            diff_config.clone(),
+                            tx_probe.clone(),
+                        );
+                    }
+                    continue;
+                }
+                new_link = rx_probe.recv().fuse() => {
+                    probing = false;
+                    match new_link {
+                        // `link` can have been replaced by a fail over during the probe
+                        Ok(Some(new_link)) if new_link.priority < link.priority => {
+                            info!("Switching back from {} to {}", link.address, new_link.address);
+                            link.close();
+                            link = new_link;
+                            Self::switch_bridge(&bridge, &mut link);
+                        }
+                        Ok(Some(new_link)) => new_link.close(),
+                        _ => {}
+                    }
+                    continue;
+                }
+                interrupt_signal = (&mut shutdown).fuse() => {
                     match interrupt_signal {
                         Ok(()) => {
                             info!("Interrupt received");
@@ -88,6 +208,7 @@ impl TranslatorSv2 {
                     break;
                 }
             };
+            // `link` holds both ends of its status channel
             let task_status: Status = task_status.unwrap();
 
             match task_status.state {
@@ -96,57 +217,243 @@ impl TranslatorSv2 {
                     error!("SHUTDOWN from: {}", err);
                     break;
                 }
-                State::BridgeShutdown(err) => {
-                    error!("SHUTDOWN from: {}", err);
-                    break;
-                }
-                State::UpstreamShutdown(err) => {
-                    error!("SHUTDOWN from: {}", err);
-                    break;
-                }
-                State::UpstreamTryReconnect(err) => {
-                    error!("SHUTDOWN from: {}", err);
+                State::BridgeShutdown(err)
+                | State::UpstreamShutdown(err)
+                | State::UpstreamTryReconnect(err)
+                    if from_upstream =>
+                {
+                    error!("Lost upstream {}: {}", link.address, err);
+                    link.close();
 
                     // wait a random amount of time between 0 and 3000ms
-                    // if all the downstreams try to reconnect at the same time, the upstream may fail
-                    tokio::time::sleep(std::time::Duration::from_millis(wait_time)).await;
-
-                    // kill al the tasks
-                    let task_collector_aborting = task_collector_.clone();
-                    kill_tasks(task_collector_aborting.clone());
+                    // if all the translators try to reconnect at the same time, the upstream may fail
+                    tokio::time::sleep(Duration::from_millis(wait_time)).await;
 
                     warn!("Trying reconnecting to upstream");
-                    self.internal_start(
-                        tx_sv1_notify.clone(),
-                        target.clone(),
-                        tx_status.clone(),
-                        task_collector_.clone(),
-                    )
-                    .await;
+                    link = match self
+                        .fail_over(&upstreams, u32::MAX, diff_config.clone(), &mut shutdown)
+                        .await
+                    {
+                        Some(link) => link,
+                        None => break,
+                    };
+                    Self::switch_bridge(&bridge, &mut link);
+                    self.metrics.upstream_reconnected();
+                }
+                State::BridgeShutdown(err)
+                | State::UpstreamShutdown(err)
+                | State::UpstreamTryReconnect(err) => {
+                    error!("SHUTDOWN from: {}", err);
+                    break;
                 }
                 State::Healthy(msg) => {
                     info!("HEALTHY message: {}", msg);
                 }
             }
         }
+        // a probe still running closes the link it opens
+        drop(rx_probe);
+        self.shutdown(tx_sv1_reconnect, task_collector).await;
+        link.close();
     }
 
-    async fn internal_start(
+    /// Connects, in a new task, to an upstream with a priority below `below_priority` and sends
+    /// the link to `tx_probe`, `None` if none of them is reachable. The current upstream is only
+    /// left once the preferred one has opened a channel.
+    fn probe_upstreams(
         &self,
-        tx_sv1_notify: broadcast::Sender<server_to_client::Notify<'static>>,
-        target: Arc<Mutex<Vec<u8>>>,
-        tx_status: async_channel::Sender<Status<'static>>,
+        upstreams: Vec<UpstreamEndpoint>,
+        below_priority: u32,
+        diff_config: Arc<Mutex<UpstreamDifficultyConfig>>,
+        tx_probe: async_channel::Sender<Option<UpstreamLink>>,
+    ) {
+        let translator = self.clone();
+        task::spawn(async move {
+            let link = translator
+                .connect_to_any(&upstreams, below_priority, diff_config)
+                .await;
+            if let Err(e) = tx_probe.send(link).await {
+                if let Some(link) = e.into_inner() {
+                    link.close();
+                }
+            }
+        });
+    }
+
+    /// Moves the `Bridge` to the channel opened in `link` and starts its tasks again, the
+    /// `Downstream`s get the new extranonce prefix and jobs without reconnecting
+    fn switch_bridge(bridge: &Arc<Mutex<proxy::Bridge>>, link: &mut UpstreamLink) {
+        let extended_extranonce = link
+            .extended_extranonce
+            .take()
+            .expect("Extranonce of a new link");
+        let _ = bridge.safe_lock(|b| {
+            b.switch_upstream(
+                link.tx_sv2_submit_shares_ext.clone(),
+                link.tx_upstream_requests.clone(),
+                link.rx_sv2_set_new_prev_hash.clone(),
+                link.rx_sv2_new_ext_mining_job.clone(),
+                link.rx_sv2_extranonce.clone(),
+                link.rx_connection_channel_updates.clone(),
+                extended_extranonce,
+                link.target.clone(),
+                status::Sender::Bridge(link.tx_status.clone()),
+                link.up_id,
+                link.task_collector.clone(),
+            )
+        });
+        proxy::Bridge::start(bridge.clone());
+    }
+
+    /// Stops accepting downstreams, sends `client.reconnect` to the fallback endpoint (if any)
+    /// and waits, until `timeout_secs`, for the downstreams to leave before killing every task
+    async fn shutdown(
+        &self,
+        tx_sv1_reconnect: broadcast::Sender<server_to_client::Reconnect>,
         task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>,
     ) {
-        let proxy_config = self.config.clone();
-        // Sender/Receiver to send a SV2 `SubmitSharesExtended` from the `Bridge` to the `Upstream`
-        // (Sender<SubmitSharesExtended<'static>>, Receiver<SubmitSharesExtended<'static>>)
+        info!("Shutting down");
+        let _ = task_collector.safe_lock(|t| {
+            for (handle, name) in t.iter() {
+                if name == "accept_connections" {
+                    handle.abort();
+                }
+            }
+        });
+        let config = &self.config.shutdown;
+        if let Some(fallback_host) = &config.fallback_host {
+            let reconnect = server_to_client::Reconnect {
+                host: Some(fallback_host.clone()),
+                port: Some(config.fallback_port),
+                wait_time: None,
+            };
+            if let Ok(downstreams) = tx_sv1_reconnect.send(reconnect) {
+                info!(
+                    "Asked {} downstreams to reconnect to {}:{}",
+                    downstreams, fallback_host, config.fallback_port
+                );
+                let deadline = Instant::now() + Duration::from_secs(config.timeout_secs);
+                while Instant::now() < deadline && tx_sv1_reconnect.receiver_count() > 0 {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+        kill_tasks(task_collector);
+    }
+
+    /// Tries every upstream, in rounds separated by an increasing backoff, until one of them
+    /// opens a channel. Returns `None` if the translator is stopped in the meantime.
+    async fn fail_over<S>(
+        &self,
+        upstreams: &[UpstreamEndpoint],
+        below_priority: u32,
+        diff_config: Arc<Mutex<UpstreamDifficultyConfig>>,
+        shutdown: &mut S,
+    ) -> Option<UpstreamLink>
+    where
+        S: Future<Output = std::io::Result<()>> + Unpin,
+    {
+        let max_backoff = Duration::from_secs(self.config.failover.max_backoff_secs.max(1));
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            tokio::select! {
+                link = self.connect_to_any(upstreams, below_priority, diff_config.clone()).fuse() => {
+                    if link.is_some() {
+                        return link;
+                    }
+                }
+                _ = (&mut *shutdown).fuse() => return None,
+            }
+            error!("No upstream reachable, retrying in {:?}", backoff);
+            tokio::select! {
+                _ = tokio::time::sleep(backoff).fuse() => {},
+                _ = (&mut *shutdown).fuse() => return None,
+            }
+            backoff = (backoff * 2).min(max_backoff);
+        }
+    }
+
+    /// Tries once the upstreams with a priority lower than `below_priority`, the preferred ones
+    /// first, and returns the first link that gets a channel opened
+    async fn connect_to_any(
+        &self,
+        upstreams: &[UpstreamEndpoint],
+        below_priority: u32,
+        diff_config: Arc<Mutex<UpstreamDifficultyConfig>>,
+    ) -> Option<UpstreamLink> {
+        let order = connection_order(upstreams, &mut rand::thread_rng());
+        for index in order {
+            let endpoint = &upstreams[index];
+            if endpoint.priority >= below_priority {
+                break;
+            }
+            if let Some(link) = self.connect_upstream(endpoint, diff_config.clone()).await {
+                return Some(link);
+            }
+        }
+        None
+    }
+
+    /// Connects to `endpoint` and opens the extended channel, giving up after
+    /// `connect_timeout_secs`
+    async fn connect_upstream(
+        &self,
+        endpoint: &UpstreamEndpoint,
+        diff_config: Arc<Mutex<UpstreamDifficultyConfig>>,
+    ) -> Option<UpstreamLink> {
+        // Format `Upstream` connection address
+        let address = match IpAddr::from_str(&endpoint.address) {
+            Ok(ip) => SocketAddr::new(ip, endpoint.port),
+            Err(e) => {
+                error!("Invalid upstream address {}: {}", endpoint.address, e);
+                return None;
+            }
+        };
+        let task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>> =
+            Arc::new(Mutex::new(Vec::new()));
+        let timeout = Duration::from_secs(self.config.failover.connect_timeout_secs);
+        let open = self.open_channel(address, endpoint, diff_config, task_collector.clone());
+        match tokio::time::timeout(timeout, open).await {
+            Ok(Ok(link)) => {
+                info!("Connected to Upstream {}!", address);
+                Some(link)
+            }
+            Ok(Err(e)) => {
+                error!("Failed to connect to Upstream {}: {}", address, e);
+                kill_tasks(task_collector);
+                None
+            }
+            Err(_) => {
+                error!("Timed out connecting to Upstream {}", address);
+                kill_tasks(task_collector);
+                None
+            }
+        }
+    }
+
+    async fn open_channel(
+        &self,
+        address: SocketAddr,
+        endpoint: &UpstreamEndpoint,
+        diff_config: Arc<Mutex<UpstreamDifficultyConfig>>,
+        task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>,
+    ) -> ProxyResult<'static, UpstreamLink> {
+        let proxy_config = &self.config;
+        // Status of the tasks of this upstream only, so that the ones of a link that has been
+        // closed are not mistaken for the ones of the current link
+        let (tx_status, rx_status) = unbounded();
+        let target = Arc::new(Mutex::new(vec![0; 32]));
+
+        // Sender/Receiver to send a SV2 `SubmitSharesExtended` and the name of the SV1 worker that
+        // found it from the `Bridge` to the `Upstream`
+        // (Sender<(SubmitSharesExtended<'static>, String)>, Receiver<(SubmitSharesExtended<'static>, String)>)
         let (tx_sv2_submit_shares_ext, rx_sv2_submit_shares_ext) = bounded(10);
 
-        // `tx_sv1_bridge` sender is used by `Downstream` to send a `DownstreamMessages` message to
-        // `Bridge` via the `rx_sv1_downstream` receiver
-        // (Sender<downstream_sv1::DownstreamMessages>, Receiver<downstream_sv1::DownstreamMessages>)
-        let (tx_sv1_bridge, rx_sv1_downstream) = unbounded();
+        // Sender/Receiver to send the channels that the `Upstream` has to open or close, to
+        // authorize a SV1 worker or for a single SV1 connection, from the `Bridge` to the
+        // `Upstream`
+        // (Sender<UpstreamRequest>, Receiver<UpstreamRequest>)
+        let (tx_upstream_requests, rx_upstream_requests) = bounded(10);
 
         // Sender/Receiver to send a SV2 `NewExtendedMiningJob` message from the `Upstream` to the
         // `Bridge`
@@ -154,128 +461,145 @@ impl TranslatorSv2 {
         let (tx_sv2_new_ext_mining_job, rx_sv2_new_ext_mining_job) = bounded(10);
 
         // Sender/Receiver to send a new extranonce from the `Upstream` to this `main` function to be
-        // passed to the `Downstream` upon a Downstream role connection
+        // passed to the `Downstream` upon a Downstream role connection, then to the `Bridge` when
+        // the upstream sends a new extranonce prefix
         // (Sender<ExtendedExtranonce>, Receiver<ExtendedExtranonce>)
-        let (tx_sv2_extranonce, rx_sv2_extranonce) = bounded(1);
+        let (tx_sv2_extranonce, rx_sv2_extranonce) = unbounded();
+
+        // Sender/Receiver to send the `SetTarget`, `SetExtranoncePrefix` and `CloseChannel`
+        // messages of the channels opened for single SV1 connections from the `Upstream` to the
+        // `Bridge`
+        // (Sender<Mining<'static>>, Receiver<Mining<'static>>)
+        let (tx_connection_channel_updates, rx_connection_channel_updates) = bounded(10);
 
         // Sender/Receiver to send a SV2 `SetNewPrevHash` message from the `Upstream` to the `Bridge`
         // (Sender<SetNewPrevHash<'static>>, Receiver<SetNewPrevHash<'static>>)
         let (tx_sv2_set_new_prev_hash, rx_sv2_set_new_prev_hash) = bounded(10);
 
-        // Format `Upstream` connection address
-        let upstream_addr = SocketAddr::new(
-            IpAddr::from_str(&proxy_config.upstream_address)
-                .expect("Failed to parse upstream address!"),
-            proxy_config.upstream_port,
-        );
-
-        let diff_config = Arc::new(Mutex::new(proxy_config.upstream_difficulty_config.clone()));
-        let task_collector_upstream = task_collector.clone();
         // Instantiate a new `Upstream` (SV2 Pool)
-        let upstream = match upstream_sv2::Upstream::new(
-            upstream_addr,
-            proxy_config.upstream_authority_pubkey,
+        let upstream = upstream_sv2::Upstream::new(
+            address,
+            endpoint.authority_pubkey,
+            proxy_config.user_identity.clone(),
+            proxy_config.worker_tagging,
+            proxy_config.channel_mode,
             rx_sv2_submit_shares_ext,
+            rx_upstream_requests,
             tx_sv2_set_new_prev_hash,
             tx_sv2_new_ext_mining_job,
             proxy_config.min_extranonce2_size,
             tx_sv2_extranonce,
+            tx_connection_channel_updates,
             status::Sender::Upstream(tx_status.clone()),
             target.clone(),
-            diff_config.clone(),
-            task_collector_upstream,
+            diff_config,
+            task_collector.clone(),
         )
-        .await
-        {
-            Ok(upstream) => upstream,
-            Err(e) => {
-                error!("Failed to create upstream: {}", e);
-                return;
-            }
-        };
-        let task_collector_init_task = task_collector.clone();
-        // Spawn a task to do all of this init work so that the main thread
-        // can listen for signals and failures on the status channel. This
-        // allows for the tproxy to fail gracefully if any of these init tasks
-        //fail
-        let task = task::spawn(async move {
-            // Connect to the SV2 Upstream role
-            match upstream_sv2::Upstream::connect(
-                upstream.clone(),
-                proxy_config.min_supported_version,
-                proxy_config.max_supported_version,
-            )
-            .await
-            {
-                Ok(_) => info!("Connected to Upstream!"),
-                Err(e) => {
-                    error!("Failed to connect to Upstream EXITING! : {}", e);
-                    return;
-                }
-            }
+        .await?;
 
-            // Start receiving messages from the SV2 Upstream role
-            if let Err(e) = upstream_sv2::Upstream::parse_incoming(upstream.clone()) {
-                error!("failed to create sv2 parser: {}", e);
-                return;
-            }
+        // Connect to the SV2 Upstream role
+        upstream_sv2::Upstream::connect(
+            upstream.clone(),
+            proxy_config.min_supported_version,
+            proxy_config.max_supported_version,
+        )
+        .await?;
 
-            debug!("Finished starting upstream listener");
-            // Start task handler to receive submits from the SV1 Downstream role once it connects
-            if let Err(e) = upstream_sv2::Upstream::handle_submit(upstream.clone()) {
-                error!("Failed to create submit handler: {}", e);
-                return;
-            }
+        // Start receiving messages from the SV2 Upstream role
+        upstream_sv2::Upstream::parse_incoming(upstream.clone())?;
 
-            // Receive the extranonce information from the Upstream role to send to the Downstream role
-            // once it connects also used to initialize the bridge
-            let (extended_extranonce, up_id) = rx_sv2_extranonce.recv().await.unwrap();
-            loop {
-                let target: [u8; 32] = target.safe_lock(|t| t.clone()).unwrap().try_into().unwrap();
-                if target != [0; 32] {
-                    break;
-                };
-                async_std::task::sleep(std::time::Duration::from_millis(100)).await;
-            }
+        debug!("Finished starting upstream listener");
+        // Start task handler to receive submits from the SV1 Downstream role once it connects
+        upstream_sv2::Upstream::handle_submit(upstream.clone())?;
+        // Start task handler to open the channels requested by the `Bridge`
+        upstream_sv2::Upstream::handle_upstream_requests(upstream.clone())?;
 
-            let task_collector_bridge = task_collector_init_task.clone();
-            // Instantiate a new `Bridge` and begins handling incoming messages
-            let b = proxy::Bridge::new(
-                rx_sv1_downstream,
-                tx_sv2_submit_shares_ext,
-                rx_sv2_set_new_prev_hash,
-                rx_sv2_new_ext_mining_job,
-                tx_sv1_notify.clone(),
-                status::Sender::Bridge(tx_status.clone()),
-                extended_extranonce,
-                target,
-                up_id,
-                task_collector_bridge,
-            );
-            proxy::Bridge::start(b.clone());
-
-            // Format `Downstream` connection address
-            let downstream_addr = SocketAddr::new(
-                IpAddr::from_str(&proxy_config.downstream_address).unwrap(),
-                proxy_config.downstream_port,
-            );
-
-            let task_collector_downstream = task_collector_init_task.clone();
-            // Accept connections from one or more SV1 Downstream roles (SV1 Mining Devices)
-            downstream_sv1::Downstream::accept_connections(
-                downstream_addr,
-                tx_sv1_bridge,
-                tx_sv1_notify,
-                status::Sender::DownstreamListener(tx_status.clone()),
-                b,
-                proxy_config.downstream_difficulty_config,
-                diff_config,
-                task_collector_downstream,
-            );
-        }); // End of init task
-        let _ =
-            task_collector.safe_lock(|t| t.push((task.abort_handle(), "init task".to_string())));
+        // Receive the extranonce information from the Upstream role to send to the Downstream role
+        // once it connects also used to initialize the bridge
+        let (extended_extranonce, up_id) = rx_sv2_extranonce.recv().await?;
+        loop {
+            let target: [u8; 32] = target.safe_lock(|t| t.clone())?.try_into()?;
+            if target != [0; 32] {
+                break;
+            };
+            async_std::task::sleep(Duration::from_millis(100)).await;
+        }
+
+        Ok(UpstreamLink {
+            priority: endpoint.priority,
+            address,
+            upstream,
+            tx_sv2_submit_shares_ext,
+            tx_upstream_requests,
+            rx_sv2_set_new_prev_hash,
+            rx_sv2_new_ext_mining_job,
+            rx_sv2_extranonce,
+            rx_connection_channel_updates,
+            extended_extranonce: Some(extended_extranonce),
+            up_id,
+            target,
+            tx_status,
+            rx_status,
+            task_collector,
+        })
+    }
+}
+
+/// Everything that belongs to the connection with one upstream, the `Bridge` is moved from a link
+/// to another when the translator changes upstream
+struct UpstreamLink {
+    priority: u32,
+    address: SocketAddr,
+    upstream: Arc<Mutex<upstream_sv2::Upstream>>,
+    tx_sv2_submit_shares_ext: async_channel::Sender<(SubmitSharesExtended<'static>, String)>,
+    tx_upstream_requests: async_channel::Sender<UpstreamRequest>,
+    rx_sv2_set_new_prev_hash: async_channel::Receiver<SetNewPrevHash<'static>>,
+    rx_sv2_new_ext_mining_job: async_channel::Receiver<NewExtendedMiningJob<'static>>,
+    rx_sv2_extranonce: async_channel::Receiver<(ExtendedExtranonce, u32)>,
+    rx_connection_channel_updates: async_channel::Receiver<Mining<'static>>,
+    /// Taken by the `Bridge` when it starts using the link
+    extended_extranonce: Option<ExtendedExtranonce>,
+    up_id: u32,
+    target: Arc<Mutex<Vec<u8>>>,
+    tx_status: async_channel::Sender<Status<'static>>,
+    rx_status: async_channel::Receiver<Status<'static>>,
+    /// Tasks of the `Upstream` and of the `Bridge` while it uses the link
+    task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>,
+}
+
+impl UpstreamLink {
+    fn close(&self) {
+        kill_tasks(self.task_collector.clone());
+        let _ = self.upstream.safe_lock(|u| u.disconnect());
+    }
+}
+
+/// Order in which the upstreams are tried: by ascending priority, the ones with the same priority
+/// are shuffled so that an upstream with a higher weight tends to be tried first
+fn connection_order<R: Rng>(upstreams: &[UpstreamEndpoint], rng: &mut R) -> Vec<usize> {
+    let mut remaining: Vec<usize> = (0..upstreams.len()).collect();
+    remaining.sort_by_key(|i| upstreams[*i].priority);
+    let weight = |i: &usize| upstreams[*i].weight.max(1) as u64;
+    let mut order = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let priority = upstreams[remaining[0]].priority;
+        let same_priority = remaining
+            .iter()
+            .take_while(|i| upstreams[**i].priority == priority)
+            .count();
+        let total: u64 = remaining[..same_priority].iter().map(weight).sum();
+        let mut pick = rng.gen_range(0..total);
+        let mut chosen = 0;
+        for (position, i) in remaining[..same_priority].iter().enumerate() {
+            if pick < weight(i) {
+                chosen = position;
+                break;
+            }
+            pick -= weight(i);
+        }
+        order.push(remaining.remove(chosen));
     }
+    order
 }
 
 fn kill_tasks(task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>) {
@@ -286,3 +610,43 @@ fn kill_tasks(task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>) {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn endpoint(priority: u32, weight: u32) -> UpstreamEndpoint {
+        UpstreamEndpoint {
+            address: "127.0.0.1".to_string(),
+            port: 34254,
+            authority_pubkey: "9auqWEzQDVyd2oe1JVGFLMLHZtCo2FFqZwtKA5gd9xbuEu7PH72"
+                .parse()
+                .unwrap(),
+            priority,
+            weight,
+        }
+    }
+
+    #[test]
+    fn connection_order_follows_priority_then_weight() {
+        let upstreams = vec![
+            endpoint(2, 1),
+            endpoint(0, 1),
+            endpoint(1, 1),
+            endpoint(1, 1000),
+        ];
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut heavy_first = 0;
+        for _ in 0..100 {
+            let order = connection_order(&upstreams, &mut rng);
+            assert_eq!(order.len(), 4);
+            assert_eq!(order[0], 1);
+            assert_eq!(order[3], 0);
+            if order[1] == 3 {
+                heavy_first += 1;
+            }
+        }
+        assert!(heavy_first > 90);
+    }
+}
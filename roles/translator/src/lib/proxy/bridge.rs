@@ -1,4 +1,5 @@
 use async_channel::{Receiver, Sender};
+use metrics_sv2::Metrics;
 use roles_logic_sv2::{
     channel_logic::channel_factory::{ExtendedChannelKind, ProxyExtendedChannelFactory, Share},
     mining_sv2::{
@@ -7,7 +8,11 @@ use roles_logic_sv2::{
     parsers::Mining,
     utils::{GroupId, Mutex},
 };
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{sync::broadcast, task::AbortHandle};
 use v1::{client_to_server::Submit, server_to_client, utils::HexU32Be};
 
@@ -17,12 +22,17 @@ use super::super::{
         Error::{self, PoisonLock},
         ProxyResult,
     },
+    proxy_config::ChannelMode,
     status,
+    upstream_sv2::UpstreamRequest,
 };
 use error_handling::handle_result;
 use roles_logic_sv2::{channel_logic::channel_factory::OnNewShare, Error as RolesLogicError};
 use tracing::{debug, error, info, warn};
 
+/// Max number of seconds that a SV1 connection waits for the upstream to open its channel
+const OPEN_CHANNEL_TIMEOUT_SECS: u64 = 10;
+
 /// Bridge between the SV2 `Upstream` and SV1 `Downstream` responsible for the following messaging
 /// translation:
 /// 1. SV1 `mining.submit` -> SV2 `SubmitSharesExtended`
@@ -32,8 +42,10 @@ pub struct Bridge {
     /// Receives a SV1 `mining.submit` message from the Downstream role.
     rx_sv1_downstream: Receiver<DownstreamMessages>,
     /// Sends SV2 `SubmitSharesExtended` messages translated from SV1 `mining.submit` messages to
-    /// the `Upstream`.
-    tx_sv2_submit_shares_ext: Sender<SubmitSharesExtended<'static>>,
+    /// the `Upstream`, along with the name of the SV1 worker that submitted them.
+    tx_sv2_submit_shares_ext: Sender<(SubmitSharesExtended<'static>, String)>,
+    /// Sends to the `Upstream` the channels to open or close with the SV2 Upstream role.
+    tx_upstream_requests: Sender<UpstreamRequest>,
     /// Receives a SV2 `SetNewPrevHash` message from the `Upstream` to be translated (along with a
     /// SV2 `NewExtendedMiningJob` message) to a SV1 `mining.submit` for the `Downstream`.
     rx_sv2_set_new_prev_hash: Receiver<SetNewPrevHash<'static>>,
@@ -41,6 +53,12 @@ pub struct Bridge {
     /// with a SV2 `SetNewPrevHash` message) to a SV1 `mining.submit` to be sent to the
     /// `Downstream`.
     rx_sv2_new_ext_mining_job: Receiver<NewExtendedMiningJob<'static>>,
+    /// Receives the extended extranonce built from a SV2 `SetExtranoncePrefix` message by the
+    /// `Upstream`, the new prefix applies to the jobs received after it.
+    rx_sv2_extranonce: Receiver<(ExtendedExtranonce, u32)>,
+    /// Receives the `SetTarget`, `SetExtranoncePrefix` and `CloseChannel` messages of the channels
+    /// opened for single SV1 connections.
+    rx_connection_channel_updates: Receiver<Mining<'static>>,
     /// Sends SV1 `mining.notify` message (translated from the SV2 `SetNewPrevHash` and
     /// `NewExtendedMiningJob` messages stored in the `NextMiningNotify`) to the `Downstream`.
     tx_sv1_notify: broadcast::Sender<server_to_client::Notify<'static>>,
@@ -63,7 +81,52 @@ pub struct Bridge {
     last_p_hash: Option<SetNewPrevHash<'static>>,
     target: Arc<Mutex<Vec<u8>>>,
     last_job_id: u32,
+    /// Shared by the channel factories of every upstream, so that the channel ids of the
+    /// `Downstream`s are never reused
+    ids: Arc<Mutex<GroupId>>,
+    /// Incremented every time the `Bridge` is moved to another upstream or the upstream changes
+    /// the extranonce prefix, a `Downstream` whose channel has been opened in a previous epoch
+    /// has to open a new one.
+    upstream_epoch: u32,
+    channel_mode: ChannelMode,
+    /// Channel opened by the `Upstream` with the SV2 Upstream role, its jobs are the ones of the
+    /// `Downstream`s that share it
+    upstream_channel_id: u32,
+    /// Channels opened for single SV1 connections when `channel_mode` is `PerConnection`
+    connection_channels: HashMap<u32, ConnectionChannel>,
     task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>,
+    metrics: Arc<Metrics>,
+}
+
+/// Jobs and target of a channel opened with the SV2 Upstream role for a single SV1 connection.
+/// Created by whichever comes first between the channel and its first job.
+#[derive(Debug)]
+struct ConnectionChannel {
+    tx_sv1_notify: broadcast::Sender<server_to_client::Notify<'static>>,
+    last_notify: Option<server_to_client::Notify<'static>>,
+    future_jobs: Vec<NewExtendedMiningJob<'static>>,
+    /// Target set by the upstream that has not been sent to the miner yet
+    new_target: Option<Vec<u8>>,
+    sequence_number: u32,
+}
+
+impl ConnectionChannel {
+    fn new() -> Self {
+        let (tx_sv1_notify, _) = broadcast::channel(10);
+        Self {
+            tx_sv1_notify,
+            last_notify: None,
+            future_jobs: vec![],
+            new_target: None,
+            sequence_number: 0,
+        }
+    }
+
+    fn notify(&mut self, notify: server_to_client::Notify<'static>) {
+        // the miner may not be subscribed yet, it gets `last_notify` when it is
+        let _ = self.tx_sv1_notify.send(notify.clone());
+        self.last_notify = Some(notify);
+    }
 }
 
 impl Bridge {
@@ -71,47 +134,145 @@ impl Bridge {
     /// Instantiate a new `Bridge`.
     pub fn new(
         rx_sv1_downstream: Receiver<DownstreamMessages>,
-        tx_sv2_submit_shares_ext: Sender<SubmitSharesExtended<'static>>,
+        tx_sv2_submit_shares_ext: Sender<(SubmitSharesExtended<'static>, String)>,
+        tx_upstream_requests: Sender<UpstreamRequest>,
         rx_sv2_set_new_prev_hash: Receiver<SetNewPrevHash<'static>>,
         rx_sv2_new_ext_mining_job: Receiver<NewExtendedMiningJob<'static>>,
+        rx_sv2_extranonce: Receiver<(ExtendedExtranonce, u32)>,
+        rx_connection_channel_updates: Receiver<Mining<'static>>,
         tx_sv1_notify: broadcast::Sender<server_to_client::Notify<'static>>,
         tx_status: status::Sender,
         extranonces: ExtendedExtranonce,
         target: Arc<Mutex<Vec<u8>>>,
         up_id: u32,
+        channel_mode: ChannelMode,
         task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>,
+        metrics: Arc<Metrics>,
     ) -> Arc<Mutex<Self>> {
         let ids = Arc::new(Mutex::new(GroupId::new()));
-        let share_per_min = 1.0;
-        let upstream_target: [u8; 32] =
-            target.safe_lock(|t| t.clone()).unwrap().try_into().unwrap();
-        let upstream_target: Target = upstream_target.into();
         Arc::new(Mutex::new(Self {
             rx_sv1_downstream,
             tx_sv2_submit_shares_ext,
+            tx_upstream_requests,
             rx_sv2_set_new_prev_hash,
             rx_sv2_new_ext_mining_job,
+            rx_sv2_extranonce,
+            rx_connection_channel_updates,
             tx_sv1_notify,
             tx_status,
             last_notify: None,
-            channel_factory: ProxyExtendedChannelFactory::new(
-                ids,
-                extranonces,
-                None,
-                share_per_min,
-                ExtendedChannelKind::Proxy { upstream_target },
-                None,
-                String::from(""),
-                up_id,
-            ),
+            channel_factory: Self::new_channel_factory(ids.clone(), extranonces, &target, up_id),
             future_jobs: vec![],
             last_p_hash: None,
             target,
             last_job_id: 0,
+            ids,
+            upstream_epoch: 0,
+            channel_mode,
+            upstream_channel_id: up_id,
+            connection_channels: HashMap::new(),
             task_collector,
+            metrics,
         }))
     }
 
+    fn new_channel_factory(
+        ids: Arc<Mutex<GroupId>>,
+        extranonces: ExtendedExtranonce,
+        target: &Arc<Mutex<Vec<u8>>>,
+        up_id: u32,
+    ) -> ProxyExtendedChannelFactory {
+        let share_per_min = 1.0;
+        let upstream_target: [u8; 32] =
+            target.safe_lock(|t| t.clone()).unwrap().try_into().unwrap();
+        let upstream_target: Target = upstream_target.into();
+        ProxyExtendedChannelFactory::new(
+            ids,
+            extranonces,
+            None,
+            share_per_min,
+            ExtendedChannelKind::Proxy { upstream_target },
+            None,
+            String::from(""),
+            up_id,
+        )
+    }
+
+    /// Moves the `Bridge` to the channel opened with another upstream. The jobs of the previous
+    /// upstream are dropped and every `Downstream` opens a new channel, with the new extranonce
+    /// prefix, when it gets the first job of the new upstream. The tasks of the `Bridge` have to
+    /// be started again with [`Bridge::start`], they are collected in `task_collector`. The SV1
+    /// connections that had their own channel are closed, the miners get a channel with the new
+    /// upstream when they connect again.
+    #[allow(clippy::too_many_arguments)]
+    pub fn switch_upstream(
+        &mut self,
+        tx_sv2_submit_shares_ext: Sender<(SubmitSharesExtended<'static>, String)>,
+        tx_upstream_requests: Sender<UpstreamRequest>,
+        rx_sv2_set_new_prev_hash: Receiver<SetNewPrevHash<'static>>,
+        rx_sv2_new_ext_mining_job: Receiver<NewExtendedMiningJob<'static>>,
+        rx_sv2_extranonce: Receiver<(ExtendedExtranonce, u32)>,
+        rx_connection_channel_updates: Receiver<Mining<'static>>,
+        extranonces: ExtendedExtranonce,
+        target: Arc<Mutex<Vec<u8>>>,
+        tx_status: status::Sender,
+        up_id: u32,
+        task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>,
+    ) {
+        self.tx_sv2_submit_shares_ext = tx_sv2_submit_shares_ext;
+        self.tx_upstream_requests = tx_upstream_requests;
+        self.tx_status = tx_status;
+        self.rx_sv2_set_new_prev_hash = rx_sv2_set_new_prev_hash;
+        self.rx_sv2_new_ext_mining_job = rx_sv2_new_ext_mining_job;
+        self.rx_sv2_extranonce = rx_sv2_extranonce;
+        self.rx_connection_channel_updates = rx_connection_channel_updates;
+        self.channel_factory =
+            Self::new_channel_factory(self.ids.clone(), extranonces, &target, up_id);
+        self.target = target;
+        self.task_collector = task_collector;
+        self.last_notify = None;
+        self.future_jobs = vec![];
+        self.last_p_hash = None;
+        self.last_job_id = 0;
+        self.upstream_epoch += 1;
+        self.upstream_channel_id = up_id;
+        // dropping the notifiers closes the SV1 connections
+        self.connection_channels.clear();
+        // a job of the previous upstream may have been dropped before being handled
+        crate::upstream_sv2::upstream::IS_NEW_JOB_HANDLED
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Number of times the extranonce space of the `Bridge` has changed
+    pub fn upstream_epoch(&self) -> u32 {
+        self.upstream_epoch
+    }
+
+    pub fn channel_mode(&self) -> ChannelMode {
+        self.channel_mode
+    }
+
+    /// True if the jobs of `channel_id` are the ones of a single SV1 connection
+    fn is_connection_channel(&self, channel_id: u32) -> bool {
+        self.channel_mode == ChannelMode::PerConnection && channel_id != self.upstream_channel_id
+    }
+
+    /// Takes the extranonce prefixes sent by the `Upstream` with `SetExtranoncePrefix`. As when
+    /// the upstream changes, every `Downstream` opens a new channel, with the new prefix, before
+    /// sending the next job to the miner.
+    #[allow(clippy::result_large_err)]
+    fn update_extranonce_prefix(self_: Arc<Mutex<Self>>) -> ProxyResult<'static, ()> {
+        self_
+            .safe_lock(|b| {
+                while let Ok((extranonces, channel_id)) = b.rx_sv2_extranonce.try_recv() {
+                    info!("New extranonce prefix for channel {}", channel_id);
+                    b.channel_factory.update_extranonces(extranonces);
+                    b.upstream_epoch += 1;
+                }
+            })
+            .map_err(|_| PoisonLock)
+    }
+
     #[allow(clippy::result_large_err)]
     pub fn on_new_sv1_connection(
         &mut self,
@@ -133,6 +294,7 @@ impl Bridge {
                                 extranonce,
                                 target: self.target.clone(),
                                 extranonce2_len,
+                                upstream_epoch: self.upstream_epoch,
                             });
                         }
                         Mining::OpenMiningChannelError(_) => todo!(),
@@ -153,14 +315,199 @@ impl Bridge {
         ))
     }
 
+    /// Asks the upstream to open a channel for a new SV1 connection, used instead of
+    /// `on_new_sv1_connection` when `channel_mode` is `PerConnection`. The connection gets the
+    /// jobs of its channel from the returned receiver.
+    pub async fn open_connection_channel(
+        self_: Arc<Mutex<Self>>,
+        hash_rate: f32,
+    ) -> ProxyResult<
+        'static,
+        (
+            OpenSv1Downstream,
+            broadcast::Receiver<server_to_client::Notify<'static>>,
+        ),
+    > {
+        let tx_upstream_requests = self_
+            .safe_lock(|b| b.tx_upstream_requests.clone())
+            .map_err(|_| PoisonLock)?;
+        let (tx_opened, rx_opened) = async_channel::bounded(1);
+        tx_upstream_requests
+            .send(UpstreamRequest::OpenChannel {
+                nominal_hash_rate: hash_rate,
+                tx_opened,
+            })
+            .await
+            .map_err(|e| {
+                Error::ChannelErrorSender(super::super::error::ChannelSendError::General(
+                    e.to_string(),
+                ))
+            })?;
+        // the upstream closes the channel if it is opened after the timeout
+        let timeout = Duration::from_secs(OPEN_CHANNEL_TIMEOUT_SECS);
+        let success = match tokio::time::timeout(timeout, rx_opened.recv()).await {
+            Ok(Ok(success)) => success,
+            Ok(Err(_)) => {
+                return Err(Error::SubprotocolMining(
+                    "Bridge: upstream refused to open a channel".to_string(),
+                ))
+            }
+            Err(_) => {
+                return Err(Error::SubprotocolMining(
+                    "Bridge: timed out opening a channel with the upstream".to_string(),
+                ))
+            }
+        };
+        self_
+            .safe_lock(|b| {
+                let channel = b
+                    .connection_channels
+                    .entry(success.channel_id)
+                    .or_insert_with(ConnectionChannel::new);
+                // sent to the miner before the first job, unless the upstream already updated it
+                if channel.new_target.is_none() {
+                    channel.new_target = Some(success.target.to_vec());
+                }
+                let opened = OpenSv1Downstream {
+                    channel_id: success.channel_id,
+                    last_notify: channel.last_notify.clone(),
+                    extranonce: success.extranonce_prefix.to_vec(),
+                    target: Arc::new(Mutex::new(success.target.to_vec())),
+                    extranonce2_len: success.extranonce_size,
+                    upstream_epoch: b.upstream_epoch,
+                };
+                (opened, channel.tx_sv1_notify.subscribe())
+            })
+            .map_err(|_| PoisonLock)
+    }
+
+    /// Takes the target that the upstream set for the channel of a single SV1 connection, if it
+    /// has not been sent to the miner yet
+    pub fn take_connection_target(&mut self, channel_id: u32) -> Option<Vec<u8>> {
+        self.connection_channels
+            .get_mut(&channel_id)
+            .and_then(|channel| channel.new_target.take())
+    }
+
+    /// Closes the channel of a SV1 connection that had its own one with the upstream
+    pub fn on_sv1_connection_closed(&mut self, channel_id: u32) {
+        if self.connection_channels.remove(&channel_id).is_some() {
+            // the upstream may be gone already
+            let _ = self
+                .tx_upstream_requests
+                .try_send(UpstreamRequest::CloseChannel(channel_id));
+        }
+    }
+
+    /// Applies a message of the upstream to the channel of a single SV1 connection
+    fn on_connection_channel_update(&mut self, update: Mining<'static>) {
+        match update {
+            Mining::SetTarget(m) => {
+                // the miner gets it along with the next job
+                self.connection_channels
+                    .entry(m.channel_id)
+                    .or_insert_with(ConnectionChannel::new)
+                    .new_target = Some(m.maximum_target.to_vec());
+            }
+            // the miner gets a channel with the new prefix when it connects again
+            Mining::SetExtranoncePrefix(m) => {
+                info!("New extranonce prefix for channel {}", m.channel_id);
+                self.on_sv1_connection_closed(m.channel_id);
+            }
+            Mining::CloseChannel(m) => {
+                warn!("Channel {} closed by the upstream", m.channel_id);
+                self.connection_channels.remove(&m.channel_id);
+            }
+            _ => (),
+        }
+    }
+
+    /// Sends a job to the SV1 connection of its channel, or keeps it until its prev hash
+    #[allow(clippy::result_large_err)]
+    fn on_connection_channel_job(
+        &mut self,
+        job: NewExtendedMiningJob<'static>,
+    ) -> ProxyResult<'static, ()> {
+        let last_p_hash = self.last_p_hash.clone();
+        let channel = self
+            .connection_channels
+            .entry(job.channel_id)
+            .or_insert_with(ConnectionChannel::new);
+        if job.is_future() {
+            channel.future_jobs.push(job);
+            return Ok(());
+        }
+        let last_p_hash = last_p_hash.ok_or(Error::RolesSv2Logic(
+            RolesLogicError::JobIsNotFutureButPrevHashNotPresent,
+        ))?;
+        channel.notify(crate::proxy::next_mining_notify::create_notify(
+            last_p_hash,
+            job,
+            false,
+        ));
+        Ok(())
+    }
+
+    /// Sends to the SV1 connections with their own channel the future job activated by `p_hash`.
+    /// Returns true if `p_hash` is for one of these channels only.
+    fn on_connection_channels_prev_hash(&mut self, p_hash: &SetNewPrevHash<'static>) -> bool {
+        let own_channel = self.connection_channels.contains_key(&p_hash.channel_id);
+        for (channel_id, channel) in self.connection_channels.iter_mut() {
+            if own_channel && *channel_id != p_hash.channel_id {
+                continue;
+            }
+            if let Some(i) = channel
+                .future_jobs
+                .iter()
+                .position(|job| job.job_id == p_hash.job_id)
+            {
+                let job = channel.future_jobs.swap_remove(i);
+                channel.future_jobs.clear();
+                channel.notify(crate::proxy::next_mining_notify::create_notify(
+                    p_hash.clone(),
+                    job,
+                    true,
+                ));
+            }
+        }
+        own_channel
+    }
+
     /// Starts the tasks that receive SV1 and SV2 messages to be translated and sent to their
     /// respective roles.
     pub fn start(self_: Arc<Mutex<Self>>) {
         Self::handle_new_prev_hash(self_.clone());
         Self::handle_new_extended_mining_job(self_.clone());
+        Self::handle_connection_channel_updates(self_.clone());
         Self::handle_downstream_messages(self_);
     }
 
+    /// Receives the messages of the upstream for the channels of single SV1 connections.
+    fn handle_connection_channel_updates(self_: Arc<Mutex<Self>>) {
+        let task_collector_connection_channel_updates =
+            self_.safe_lock(|b| b.task_collector.clone()).unwrap();
+        let (rx_connection_channel_updates, tx_status) = self_
+            .safe_lock(|s| (s.rx_connection_channel_updates.clone(), s.tx_status.clone()))
+            .unwrap();
+        let handle_connection_channel_updates = tokio::task::spawn(async move {
+            loop {
+                let update = handle_result!(tx_status, rx_connection_channel_updates.recv().await);
+                handle_result!(
+                    tx_status,
+                    self_
+                        .safe_lock(|b| b.on_connection_channel_update(update))
+                        .map_err(|_| PoisonLock)
+                );
+            }
+        });
+        let _ = task_collector_connection_channel_updates.safe_lock(|a| {
+            a.push((
+                handle_connection_channel_updates.abort_handle(),
+                "handle_connection_channel_updates".to_string(),
+            ))
+        });
+    }
+
     /// Receives a `DownstreamMessages` message from the `Downstream`, handles based on the
     /// variant received.
     fn handle_downstream_messages(self_: Arc<Mutex<Self>>) {
@@ -186,6 +533,19 @@ impl Bridge {
                             Self::handle_update_downstream_target(self_.clone(), new_target)
                         );
                     }
+                    DownstreamMessages::AuthorizeWorker(worker) => {
+                        let tx_upstream_requests = handle_result!(
+                            tx_status,
+                            self_
+                                .safe_lock(|b| b.tx_upstream_requests.clone())
+                                .map_err(|_| PoisonLock)
+                        );
+                        // if the upstream is gone the `Downstream` stops waiting for the answer
+                        // when `tx_authorized` is dropped
+                        let _ = tx_upstream_requests
+                            .send(UpstreamRequest::AuthorizeWorker(worker))
+                            .await;
+                    }
                 };
             }
         });
@@ -212,19 +572,42 @@ impl Bridge {
     }
     /// receives a `SubmitShareWithChannelId` and validates the shares and sends to `Upstream` if
     /// the share meets the upstream target
+    #[allow(clippy::result_large_err)]
     async fn handle_submit_shares(
         self_: Arc<Mutex<Self>>,
         share: SubmitShareWithChannelId,
     ) -> ProxyResult<'static, ()> {
-        let (tx_sv2_submit_shares_ext, target_mutex, tx_status) = self_
+        let (tx_sv2_submit_shares_ext, target_mutex, tx_status, metrics) = self_
             .safe_lock(|s| {
                 (
                     s.tx_sv2_submit_shares_ext.clone(),
                     s.target.clone(),
                     s.tx_status.clone(),
+                    s.metrics.clone(),
                 )
             })
             .map_err(|_| PoisonLock)?;
+        // the `Downstream` of a SV1 connection with its own channel checked the share against the
+        // target of the channel, it is sent as it is
+        let connection_share = self_
+            .safe_lock(|s| s.translate_connection_submit(&share))
+            .map_err(|_| PoisonLock)?;
+        match connection_share {
+            Some(Ok(sv2_submit)) => {
+                metrics.share_accepted();
+                tx_sv2_submit_shares_ext
+                    .send((sv2_submit, share.share.user_name))
+                    .await?;
+                return Ok(());
+            }
+            Some(Err(e)) => {
+                warn!("Dropping share of channel {}: {:?}", share.channel_id, e);
+                metrics.share_rejected("stale");
+                return Ok(());
+            }
+            None => (),
+        }
+
         let upstream_target: [u8; 32] = target_mutex
             .safe_lock(|t| t.clone())
             .map_err(|_| PoisonLock)?
@@ -234,27 +617,39 @@ impl Bridge {
             .safe_lock(|s| s.channel_factory.set_target(&mut upstream_target))
             .map_err(|_| PoisonLock)?;
 
-        let sv2_submit = self_
+        let worker = share.share.user_name.clone();
+        let sv2_submit = match self_
             .safe_lock(|s| {
                 s.translate_submit(share.channel_id, share.share, share.version_rolling_mask)
             })
-            .map_err(|_| PoisonLock)??;
+            .map_err(|_| PoisonLock)?
+        {
+            Ok(sv2_submit) => sv2_submit,
+            // Shares queued before a switch to another upstream refer to jobs that the new
+            // channel factory does not know, they are dropped
+            Err(e) => {
+                warn!("Dropping share of channel {}: {:?}", share.channel_id, e);
+                metrics.share_rejected("stale");
+                return Ok(());
+            }
+        };
         let res = self_
             .safe_lock(|s| s.channel_factory.on_submit_shares_extended(sv2_submit))
             .map_err(|_| PoisonLock);
 
         match res {
             Ok(Ok(OnNewShare::SendErrorDownstream(e))) => {
-                warn!(
-                    "Submit share error {:?}",
-                    std::str::from_utf8(&e.error_code.to_vec()[..])
-                );
+                let error_code = e.error_code.to_vec();
+                let error_code = std::str::from_utf8(&error_code[..]).unwrap_or("unknown");
+                warn!("Submit share error {:?}", error_code);
+                metrics.share_rejected(error_code);
             }
             Ok(Ok(OnNewShare::SendSubmitShareUpstream((share, _)))) => {
                 info!("SHARE MEETS UPSTREAM TARGET");
+                metrics.share_accepted();
                 match share {
                     Share::Extended(share) => {
-                        tx_sv2_submit_shares_ext.send(share).await?;
+                        tx_sv2_submit_shares_ext.send((share, worker)).await?;
                     }
                     // We are in an extended channel shares are extended
                     Share::Standard(_) => unreachable!(),
@@ -264,6 +659,7 @@ impl Bridge {
             Ok(Ok(OnNewShare::RelaySubmitShareUpstream)) => unreachable!(),
             Ok(Ok(OnNewShare::ShareMeetDownstreamTarget)) => {
                 debug!("SHARE MEETS DOWNSTREAM TARGET");
+                metrics.share_accepted();
             }
             // Proxy do not have JD capabilities
             Ok(Ok(OnNewShare::ShareMeetBitcoinTarget(..))) => unreachable!(),
@@ -291,6 +687,46 @@ impl Bridge {
             .channel_factory
             .last_valid_job_version()
             .ok_or(Error::RolesSv2Logic(RolesLogicError::NoValidJob))?;
+        // I put 0 below cause sequence_number is not what should be TODO
+        Self::sv2_submit(
+            channel_id,
+            0,
+            last_version,
+            sv1_submit,
+            version_rolling_mask,
+        )
+    }
+
+    /// Translates a share of a SV1 connection with its own channel, `None` if the channel of the
+    /// share is shared with other connections.
+    #[allow(clippy::result_large_err)]
+    fn translate_connection_submit(
+        &mut self,
+        share: &SubmitShareWithChannelId,
+    ) -> Option<ProxyResult<'static, SubmitSharesExtended<'static>>> {
+        let channel = self.connection_channels.get_mut(&share.channel_id)?;
+        let last_version = match &channel.last_notify {
+            Some(notify) => notify.version.0,
+            None => return Some(Err(Error::RolesSv2Logic(RolesLogicError::NoValidJob))),
+        };
+        channel.sequence_number += 1;
+        Some(Self::sv2_submit(
+            share.channel_id,
+            channel.sequence_number,
+            last_version,
+            share.share.clone(),
+            share.version_rolling_mask.clone(),
+        ))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn sv2_submit(
+        channel_id: u32,
+        sequence_number: u32,
+        last_version: u32,
+        sv1_submit: Submit,
+        version_rolling_mask: Option<HexU32Be>,
+    ) -> ProxyResult<'static, SubmitSharesExtended<'static>> {
         let version = match (sv1_submit.version_bits, version_rolling_mask) {
             // regarding version masking see https://github.com/slushpool/stratumprotocol/blob/master/stratum-extensions.mediawiki#changes-in-request-miningsubmit
             (Some(vb), Some(mask)) => (last_version & !mask.0) | (vb.0 & mask.0),
@@ -301,8 +737,7 @@ impl Bridge {
         let extranonce2 = mining_device_extranonce;
         Ok(SubmitSharesExtended {
             channel_id,
-            // I put 0 below cause sequence_number is not what should be TODO
-            sequence_number: 0,
+            sequence_number,
             job_id: sv1_submit.job_id.parse::<u32>()?,
             nonce: sv1_submit.nonce.0,
             ntime: sv1_submit.time.0,
@@ -321,9 +756,15 @@ impl Bridge {
         {
             tokio::task::yield_now().await;
         }
-        self_
-            .safe_lock(|s| s.last_p_hash = Some(sv2_set_new_prev_hash.clone()))
+        let own_channel = self_
+            .safe_lock(|s| {
+                s.last_p_hash = Some(sv2_set_new_prev_hash.clone());
+                s.on_connection_channels_prev_hash(&sv2_set_new_prev_hash)
+            })
             .map_err(|_| PoisonLock)?;
+        if own_channel {
+            return Ok(());
+        }
 
         let on_new_prev_hash_res = self_
             .safe_lock(|s| {
@@ -379,12 +820,13 @@ impl Bridge {
     fn handle_new_prev_hash(self_: Arc<Mutex<Self>>) {
         let task_collector_handle_new_prev_hash =
             self_.safe_lock(|b| b.task_collector.clone()).unwrap();
-        let (tx_sv1_notify, rx_sv2_set_new_prev_hash, tx_status) = self_
+        let (tx_sv1_notify, rx_sv2_set_new_prev_hash, tx_status, metrics) = self_
             .safe_lock(|s| {
                 (
                     s.tx_sv1_notify.clone(),
                     s.rx_sv2_set_new_prev_hash.clone(),
                     s.tx_status.clone(),
+                    s.metrics.clone(),
                 )
             })
             .unwrap();
@@ -394,6 +836,7 @@ impl Bridge {
                 // Receive `SetNewPrevHash` from `Upstream`
                 let sv2_set_new_prev_hash: SetNewPrevHash =
                     handle_result!(tx_status, rx_sv2_set_new_prev_hash.clone().recv().await);
+                let received_at = Instant::now();
                 debug!(
                     "handle_new_prev_hash job_id: {:?}",
                     &sv2_set_new_prev_hash.job_id
@@ -406,7 +849,8 @@ impl Bridge {
                         tx_sv1_notify.clone(),
                     )
                     .await
-                )
+                );
+                metrics.observe_prev_hash_latency(received_at.elapsed());
             }
         });
         let _ = task_collector_handle_new_prev_hash.safe_lock(|a| {
@@ -417,11 +861,21 @@ impl Bridge {
         });
     }
 
+    #[allow(clippy::result_large_err)]
     async fn handle_new_extended_mining_job_(
         self_: Arc<Mutex<Self>>,
         sv2_new_extended_mining_job: NewExtendedMiningJob<'static>,
         tx_sv1_notify: broadcast::Sender<server_to_client::Notify<'static>>,
     ) -> Result<(), Error<'static>> {
+        let channel_id = sv2_new_extended_mining_job.channel_id;
+        if self_
+            .safe_lock(|s| s.is_connection_channel(channel_id))
+            .map_err(|_| PoisonLock)?
+        {
+            return self_
+                .safe_lock(|s| s.on_connection_channel_job(sv2_new_extended_mining_job))
+                .map_err(|_| PoisonLock)?;
+        }
         // convert to non segwit jobs so we dont have to depend if miner's support segwit or not
         self_
             .safe_lock(|s| {
@@ -480,12 +934,13 @@ impl Bridge {
     fn handle_new_extended_mining_job(self_: Arc<Mutex<Self>>) {
         let task_collector_new_extended_mining_job =
             self_.safe_lock(|b| b.task_collector.clone()).unwrap();
-        let (tx_sv1_notify, rx_sv2_new_ext_mining_job, tx_status) = self_
+        let (tx_sv1_notify, rx_sv2_new_ext_mining_job, tx_status, metrics) = self_
             .safe_lock(|s| {
                 (
                     s.tx_sv1_notify.clone(),
                     s.rx_sv2_new_ext_mining_job.clone(),
                     s.tx_status.clone(),
+                    s.metrics.clone(),
                 )
             })
             .unwrap();
@@ -497,6 +952,11 @@ impl Bridge {
                     tx_status.clone(),
                     rx_sv2_new_ext_mining_job.clone().recv().await
                 );
+                let received_at = Instant::now();
+                // a new extranonce prefix applies to the jobs received after it
+                handle_result!(tx_status, Self::update_extranonce_prefix(self_.clone()));
+                // future jobs are only sent downstream with the next prev hash
+                let is_future = sv2_new_extended_mining_job.is_future();
                 debug!(
                     "handle_new_extended_mining_job job_id: {:?}",
                     &sv2_new_extended_mining_job.job_id
@@ -510,6 +970,9 @@ impl Bridge {
                     )
                     .await
                 );
+                if !is_future {
+                    metrics.observe_template_latency(received_at.elapsed());
+                }
                 crate::upstream_sv2::upstream::IS_NEW_JOB_HANDLED
                     .store(true, std::sync::atomic::Ordering::SeqCst);
             }
@@ -528,6 +991,7 @@ pub struct OpenSv1Downstream {
     pub extranonce: Vec<u8>,
     pub target: Arc<Mutex<Vec<u8>>>,
     pub extranonce2_len: u16,
+    pub upstream_epoch: u32,
 }
 
 #[cfg(test)]
@@ -543,19 +1007,32 @@ mod test {
         #[allow(dead_code)]
         pub struct BridgeInterface {
             pub tx_sv1_submit: Sender<DownstreamMessages>,
-            pub rx_sv2_submit_shares_ext: Receiver<SubmitSharesExtended<'static>>,
+            pub rx_sv2_submit_shares_ext: Receiver<(SubmitSharesExtended<'static>, String)>,
+            pub rx_upstream_requests: Receiver<UpstreamRequest>,
             pub tx_sv2_set_new_prev_hash: Sender<SetNewPrevHash<'static>>,
             pub tx_sv2_new_ext_mining_job: Sender<NewExtendedMiningJob<'static>>,
+            pub tx_sv2_extranonce: Sender<(ExtendedExtranonce, u32)>,
+            pub tx_connection_channel_updates: Sender<Mining<'static>>,
             pub rx_sv1_notify: broadcast::Receiver<server_to_client::Notify<'static>>,
         }
 
         pub fn create_bridge(
             extranonces: ExtendedExtranonce,
+        ) -> (Arc<Mutex<Bridge>>, BridgeInterface) {
+            create_bridge_with_mode(extranonces, ChannelMode::Aggregated)
+        }
+
+        pub fn create_bridge_with_mode(
+            extranonces: ExtendedExtranonce,
+            channel_mode: ChannelMode,
         ) -> (Arc<Mutex<Bridge>>, BridgeInterface) {
             let (tx_sv1_submit, rx_sv1_submit) = bounded(1);
             let (tx_sv2_submit_shares_ext, rx_sv2_submit_shares_ext) = bounded(1);
+            let (tx_upstream_requests, rx_upstream_requests) = bounded(1);
             let (tx_sv2_set_new_prev_hash, rx_sv2_set_new_prev_hash) = bounded(1);
             let (tx_sv2_new_ext_mining_job, rx_sv2_new_ext_mining_job) = bounded(1);
+            let (tx_sv2_extranonce, rx_sv2_extranonce) = bounded(1);
+            let (tx_connection_channel_updates, rx_connection_channel_updates) = bounded(1);
             let (tx_sv1_notify, rx_sv1_notify) = broadcast::channel(1);
             let (tx_status, _rx_status) = bounded(1);
             let upstream_target = vec![
@@ -565,8 +1042,11 @@ mod test {
             let interface = BridgeInterface {
                 tx_sv1_submit,
                 rx_sv2_submit_shares_ext,
+                rx_upstream_requests,
                 tx_sv2_set_new_prev_hash,
                 tx_sv2_new_ext_mining_job,
+                tx_sv2_extranonce,
+                tx_connection_channel_updates,
                 rx_sv1_notify,
             };
 
@@ -574,14 +1054,19 @@ mod test {
             let b = Bridge::new(
                 rx_sv1_submit,
                 tx_sv2_submit_shares_ext,
+                tx_upstream_requests,
                 rx_sv2_set_new_prev_hash,
                 rx_sv2_new_ext_mining_job,
+                rx_sv2_extranonce,
+                rx_connection_channel_updates,
                 tx_sv1_notify,
                 status::Sender::Bridge(tx_status),
                 extranonces,
                 Arc::new(Mutex::new(upstream_target)),
                 1,
+                channel_mode,
                 task_collector,
+                Arc::new(Metrics::new()),
             );
             (b, interface)
         }
@@ -597,6 +1082,49 @@ mod test {
                 id: 0,
             }
         }
+
+        /// Job with a coinbase that can be translated to a `mining.notify`, the script sig is
+        /// only made of the 32 bytes of extranonce expected by `create_notify`
+        pub fn create_job(
+            channel_id: u32,
+            job_id: u32,
+            future: bool,
+        ) -> NewExtendedMiningJob<'static> {
+            use stratum_common::{
+                bitcoin,
+                bitcoin::{blockdata::witness::Witness, hashes::Hash},
+            };
+            let out_id = bitcoin::hashes::sha256d::Hash::from_slice(&[0_u8; 32]).unwrap();
+            let in_ = bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint {
+                    txid: bitcoin::Txid::from_hash(out_id),
+                    vout: 0xffff_ffff,
+                },
+                script_sig: vec![89_u8; 32].into(),
+                sequence: bitcoin::Sequence(0),
+                witness: Witness::from_vec(vec![]),
+            };
+            let tx = bitcoin::Transaction {
+                version: 1,
+                lock_time: bitcoin::PackedLockTime(0),
+                input: vec![in_],
+                output: vec![],
+            }
+            .serialize();
+            NewExtendedMiningJob {
+                channel_id,
+                job_id,
+                min_ntime: binary_sv2::Sv2Option::new(match future {
+                    true => None,
+                    false => Some(1),
+                }),
+                version: 2,
+                version_rolling_allowed: false,
+                merkle_path: vec![].into(),
+                coinbase_tx_prefix: tx[0..42].to_vec().try_into().unwrap(),
+                coinbase_tx_suffix: tx[74..].to_vec().try_into().unwrap(),
+            }
+        }
     }
 
     #[test]
@@ -681,4 +1209,98 @@ mod test {
             })
             .unwrap();
     }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn new_extranonce_prefix_starts_a_new_epoch() {
+        let extranonces = ExtendedExtranonce::new(0..6, 6..8, 8..16);
+        let (bridge, interface) = test_utils::create_bridge(extranonces);
+        let before = bridge
+            .safe_lock(|b| b.on_new_sv1_connection(10_000_000_000.0))
+            .unwrap()
+            .unwrap();
+
+        let prefix: roles_logic_sv2::mining_sv2::Extranonce = vec![7_u8; 6].try_into().unwrap();
+        let new_extranonces =
+            ExtendedExtranonce::from_upstream_extranonce(prefix, 0..6, 6..8, 8..16).unwrap();
+        interface
+            .tx_sv2_extranonce
+            .try_send((new_extranonces, 1))
+            .unwrap();
+        Bridge::update_extranonce_prefix(bridge.clone()).unwrap();
+
+        let after = bridge
+            .safe_lock(|b| b.on_new_sv1_connection(10_000_000_000.0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(after.upstream_epoch, before.upstream_epoch + 1);
+        assert_eq!(before.extranonce[..6], [0; 6]);
+        assert_eq!(after.extranonce[..6], [7; 6]);
+    }
+
+    #[tokio::test]
+    async fn connection_channel_gets_its_own_jobs_and_target() {
+        use roles_logic_sv2::mining_sv2::{OpenExtendedMiningChannelSuccess, SetTarget};
+
+        let extranonces = ExtendedExtranonce::new(0..6, 6..8, 8..16);
+        let (bridge, interface) =
+            test_utils::create_bridge_with_mode(extranonces, ChannelMode::PerConnection);
+        let rx_upstream_requests = interface.rx_upstream_requests.clone();
+        tokio::spawn(async move {
+            if let Ok(UpstreamRequest::OpenChannel { tx_opened, .. }) =
+                rx_upstream_requests.recv().await
+            {
+                let success = OpenExtendedMiningChannelSuccess {
+                    request_id: 1,
+                    channel_id: 7,
+                    target: [255_u8; 32].into(),
+                    extranonce_size: 8,
+                    extranonce_prefix: vec![1_u8; 8].try_into().unwrap(),
+                };
+                tx_opened.send(success).await.unwrap();
+            }
+        });
+        let (opened, mut rx_sv1_notify) = Bridge::open_connection_channel(bridge.clone(), 1e9)
+            .await
+            .unwrap();
+        assert_eq!(opened.channel_id, 7);
+        assert_eq!(opened.extranonce, vec![1; 8]);
+        assert_eq!(opened.extranonce2_len, 8);
+
+        bridge
+            .safe_lock(|b| {
+                // the target of the channel is sent once, then every time the upstream changes it
+                assert_eq!(b.take_connection_target(7), Some(vec![255; 32]));
+                assert_eq!(b.take_connection_target(7), None);
+                b.on_connection_channel_update(Mining::SetTarget(SetTarget {
+                    channel_id: 7,
+                    maximum_target: [1_u8; 32].into(),
+                }));
+                assert_eq!(b.take_connection_target(7), Some(vec![1; 32]));
+
+                b.on_connection_channel_job(test_utils::create_job(7, 3, true))
+                    .unwrap();
+                let prev_hash = SetNewPrevHash {
+                    channel_id: 7,
+                    job_id: 3,
+                    prev_hash: [3_u8; 32].into(),
+                    min_ntime: 1,
+                    nbits: 9,
+                };
+                assert!(b.on_connection_channels_prev_hash(&prev_hash));
+            })
+            .unwrap();
+        let notify = rx_sv1_notify.try_recv().unwrap();
+        assert_eq!(notify.job_id, "3");
+        assert!(notify.clean_jobs);
+        // the jobs of the channel are not sent to the other SV1 connections
+        assert!(interface.rx_sv1_notify.is_empty());
+
+        bridge.safe_lock(|b| b.on_sv1_connection_closed(7)).unwrap();
+        assert!(matches!(
+            interface.rx_upstream_requests.try_recv(),
+            Ok(UpstreamRequest::CloseChannel(7))
+        ));
+        assert!(rx_sv1_notify.recv().await.is_err());
+    }
 }
@@ -6,6 +6,23 @@ pub struct ProxyConfig {
     pub upstream_address: String,
     pub upstream_port: u16,
     pub upstream_authority_pubkey: Secp256k1PublicKey,
+    /// Upstreams used when the one of `upstream_address` is not reachable
+    #[serde(default)]
+    pub backup_upstreams: Vec<UpstreamEndpoint>,
+    #[serde(default)]
+    pub failover: FailoverConfig,
+    /// `user_identity` of the extended channel opened with the upstream, e.g. the pool account
+    #[serde(default = "default_user_identity")]
+    pub user_identity: String,
+    /// When true every share sent upstream carries the name of the SV1 worker that found it, so
+    /// that the pool can credit each worker. Only enable it with pools that support the
+    /// Worker-Specific Hashrate Tracking extension.
+    #[serde(default)]
+    pub worker_tagging: bool,
+    /// Whether the SV1 connections share the extended channel of the translator or each of them
+    /// gets its own channel with the upstream
+    #[serde(default)]
+    pub channel_mode: ChannelMode,
     pub downstream_address: String,
     pub downstream_port: u16,
     pub max_supported_version: u16,
@@ -13,6 +30,128 @@ pub struct ProxyConfig {
     pub min_extranonce2_size: u16,
     pub downstream_difficulty_config: DownstreamDifficultyConfig,
     pub upstream_difficulty_config: UpstreamDifficultyConfig,
+    /// Address where the Prometheus metrics are served, eg `0.0.0.0:9184`
+    #[serde(default)]
+    pub metrics_address: Option<String>,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    /// How the SV1 workers are authorized, every worker is when not set
+    #[serde(default)]
+    pub auth: AuthConfig,
+}
+
+fn default_user_identity() -> String {
+    "translator".to_string()
+}
+
+/// A SV2 Upstream role the translator can connect to
+#[derive(Debug, Deserialize, Clone)]
+pub struct UpstreamEndpoint {
+    pub address: String,
+    pub port: u16,
+    pub authority_pubkey: Secp256k1PublicKey,
+    /// Upstreams with a lower value are preferred, the one of `upstream_address` has priority 0
+    #[serde(default = "default_backup_priority")]
+    pub priority: u32,
+    /// Among the upstreams with the same priority, the chance of being picked first is
+    /// proportional to the weight
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_backup_priority() -> u32 {
+    1
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// How the translator moves between its upstreams
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct FailoverConfig {
+    /// Max number of seconds to connect to an upstream and get its channel opened
+    pub connect_timeout_secs: u64,
+    /// Max number of seconds between two rounds of connection attempts when no upstream is
+    /// reachable, the wait doubles after every round
+    pub max_backoff_secs: u64,
+    /// Every how many seconds the upstreams preferred to the current one are tried, so that the
+    /// translator goes back to them once they recover
+    pub health_check_interval_secs: u64,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 10,
+            max_backoff_secs: 60,
+            health_check_interval_secs: 30,
+        }
+    }
+}
+
+/// How the SV1 connections are mapped to channels with the upstream
+///
+/// Standard channels are not offered: their jobs only carry the merkle root, while a SV1 miner
+/// needs the coinbase to roll `extranonce2`, so every channel opened by the translator is extended.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelMode {
+    /// Every SV1 connection gets a slice of the extranonce space of the extended channel of the
+    /// translator, its difficulty is managed by the translator
+    #[default]
+    Aggregated,
+    /// Every SV1 connection opens its own extended channel with the upstream, that sets its
+    /// difficulty with `SetTarget`. The channel is closed when the miner disconnects.
+    PerConnection,
+}
+
+/// What the translator does with its downstreams when it is stopped
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// Host that the downstreams are asked to reconnect to with `client.reconnect`, when not set
+    /// they are only disconnected
+    pub fallback_host: Option<String>,
+    pub fallback_port: u16,
+    /// Max number of seconds that the downstreams have to leave after the `client.reconnect`
+    pub timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            fallback_host: None,
+            fallback_port: 34255,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Decides which `mining.authorize` requests the translator accepts
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum AuthConfig {
+    /// Every worker is authorized
+    #[default]
+    None,
+    /// Only the worker names listed in `path`, one per line, are authorized
+    Allowlist { path: String },
+    /// `path` has a `name:hash` line for every worker, where `hash` is the hex encoded SHA256 of
+    /// `name:password`
+    Password { path: String },
+    /// The upstream authorizes the worker if it opens a channel with the worker name as
+    /// `user_identity`, an `OpenMiningChannelError` rejects it
+    PoolValidates {
+        /// Max number of seconds to wait for the answer of the upstream
+        #[serde(default = "default_pool_validation_timeout")]
+        timeout_secs: u64,
+    },
+}
+
+fn default_pool_validation_timeout() -> u64 {
+    10
 }
 
 pub struct UpstreamConfig {
@@ -66,6 +205,11 @@ impl ProxyConfig {
             upstream_address: upstream.address,
             upstream_port: upstream.port,
             upstream_authority_pubkey: upstream.authority_pubkey,
+            backup_upstreams: Vec::new(),
+            failover: FailoverConfig::default(),
+            user_identity: default_user_identity(),
+            worker_tagging: false,
+            channel_mode: ChannelMode::default(),
             downstream_address: downstream.address,
             downstream_port: downstream.port,
             max_supported_version,
@@ -73,8 +217,24 @@ impl ProxyConfig {
             min_extranonce2_size,
             downstream_difficulty_config: downstream.difficulty_config,
             upstream_difficulty_config: upstream.difficulty_config,
+            metrics_address: None,
+            shutdown: ShutdownConfig::default(),
+            auth: AuthConfig::default(),
         }
     }
+
+    /// Every upstream, the one of `upstream_address` first
+    pub fn upstreams(&self) -> Vec<UpstreamEndpoint> {
+        let mut upstreams = vec![UpstreamEndpoint {
+            address: self.upstream_address.clone(),
+            port: self.upstream_port,
+            authority_pubkey: self.upstream_authority_pubkey,
+            priority: 0,
+            weight: default_weight(),
+        }];
+        upstreams.extend(self.backup_upstreams.iter().cloned());
+        upstreams
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -1,5 +1,7 @@
+use crate::downstream_sv1::AuthorizeWorker;
+use async_channel::Sender;
 use codec_sv2::{StandardEitherFrame, StandardSv2Frame};
-use roles_logic_sv2::parsers::PoolMessages;
+use roles_logic_sv2::{mining_sv2::OpenExtendedMiningChannelSuccess, parsers::PoolMessages};
 
 pub mod diff_management;
 pub mod upstream;
@@ -11,6 +13,22 @@ pub type Message = PoolMessages<'static>;
 pub type StdFrame = StandardSv2Frame<Message>;
 pub type EitherFrame = StandardEitherFrame<Message>;
 
+/// Channels that the `Bridge` asks the `Upstream` to open or close with the SV2 Upstream role
+#[derive(Debug)]
+pub enum UpstreamRequest {
+    /// Opens a channel with the name of the worker as `user_identity` and closes it right away,
+    /// the worker is authorized if the channel is opened
+    AuthorizeWorker(AuthorizeWorker),
+    /// Opens the channel of a single SV1 connection, `tx_opened` gets its
+    /// `OpenExtendedMiningChannelSuccess` and is dropped if the upstream refuses it
+    OpenChannel {
+        nominal_hash_rate: f32,
+        tx_opened: Sender<OpenExtendedMiningChannelSuccess<'static>>,
+    },
+    /// Closes the channel of a SV1 connection that has been closed
+    CloseChannel(u32),
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Sv2MiningConnection {
     _version: u16,
@@ -4,15 +4,16 @@ use crate::{
         Error::{CodecNoise, InvalidExtranonce, PoisonLock, UpstreamIncoming},
         ProxyResult,
     },
-    proxy_config::UpstreamDifficultyConfig,
+    proxy_config::{ChannelMode, UpstreamDifficultyConfig},
     status,
-    upstream_sv2::{EitherFrame, Message, StdFrame, UpstreamConnection},
+    upstream_sv2::{EitherFrame, Message, StdFrame, UpstreamConnection, UpstreamRequest},
 };
 use async_channel::{Receiver, Sender};
 use async_std::net::TcpStream;
-use binary_sv2::u256_from_int;
+use binary_sv2::{u256_from_int, B032};
 use codec_sv2::{HandshakeRole, Initiator};
 use error_handling::handle_result;
+use framing_sv2::header::Header;
 use key_utils::Secp256k1PublicKey;
 use network_helpers_sv2::Connection;
 use roles_logic_sv2::{
@@ -23,8 +24,9 @@ use roles_logic_sv2::{
         mining::{ParseUpstreamMiningMessages, SendTo},
     },
     mining_sv2::{
-        ExtendedExtranonce, Extranonce, NewExtendedMiningJob, OpenExtendedMiningChannel,
-        SetNewPrevHash, SubmitSharesExtended,
+        user_identity_tlv, CloseChannel, ExtendedExtranonce, Extranonce, NewExtendedMiningJob,
+        OpenExtendedMiningChannel, OpenExtendedMiningChannelSuccess, SetNewPrevHash,
+        SubmitSharesExtended,
     },
     parsers::Mining,
     routing_logic::{CommonRoutingLogic, MiningRoutingLogic, NoRouting},
@@ -34,6 +36,7 @@ use roles_logic_sv2::{
     Error::NoUpstreamsConnected,
 };
 use std::{
+    collections::{HashMap, HashSet},
     net::SocketAddr,
     sync::{atomic::AtomicBool, Arc},
 };
@@ -68,11 +71,40 @@ pub struct Upstream {
     last_job_id: Option<u32>,
     /// Bytes used as implicit first part of `extranonce`.
     extranonce_prefix: Option<Vec<u8>>,
+    /// Size of the part of the `extranonce` that follows `extranonce_prefix`, set by the
+    /// `OpenExtendedMiningChannelSuccess` message.
+    extranonce_size: u16,
     /// Represents a connection to a SV2 Upstream role.
     pub(super) connection: UpstreamConnection,
-    /// Receives SV2 `SubmitSharesExtended` messages translated from SV1 `mining.submit` messages.
-    /// Translated by and sent from the `Bridge`.
-    rx_sv2_submit_shares_ext: Receiver<SubmitSharesExtended<'static>>,
+    /// Receives SV2 `SubmitSharesExtended` messages translated from SV1 `mining.submit` messages,
+    /// along with the name of the SV1 worker that submitted them. Translated by and sent from the
+    /// `Bridge`.
+    rx_sv2_submit_shares_ext: Receiver<(SubmitSharesExtended<'static>, String)>,
+    /// Receives from the `Bridge` the channels to open or close with the SV2 Upstream role, on
+    /// top of the one opened by the `Upstream` itself.
+    rx_upstream_requests: Receiver<UpstreamRequest>,
+    /// Where to send the answer for the workers whose channel has been requested, by request id.
+    pending_authorizations: HashMap<u32, Sender<bool>>,
+    /// Where to send the channels requested for single SV1 connections, by request id.
+    pending_channels: HashMap<u32, Sender<OpenExtendedMiningChannelSuccess<'static>>>,
+    /// Request id of the last channel requested by the `Bridge`, the channel opened by the
+    /// `Upstream` itself has request id 0.
+    last_request_id: u32,
+    /// Channels opened for single SV1 connections, their shares are sent as they are and their
+    /// `SetTarget`, `SetExtranoncePrefix` and `CloseChannel` go to the `Bridge`.
+    connection_channels: HashSet<u32>,
+    /// Channels closed by the translator, the messages that the upstream sent for them before
+    /// getting the `CloseChannel` are dropped.
+    closed_channels: HashSet<u32>,
+    /// Sends to the `Bridge` the `SetTarget`, `SetExtranoncePrefix` and `CloseChannel` messages of
+    /// the channels opened for single SV1 connections.
+    tx_connection_channel_updates: Sender<Mining<'static>>,
+    channel_mode: ChannelMode,
+    /// `user_identity` of the extended channel opened with the SV2 Upstream role.
+    user_identity: String,
+    /// When true the name of the SV1 worker is appended to every share as a Worker-Specific
+    /// Hashrate Tracking TLV.
+    worker_tagging: bool,
     /// Sends SV2 `SetNewPrevHash` messages to be translated (along with SV2 `NewExtendedMiningJob`
     /// messages) into SV1 `mining.notify` messages. Received and translated by the `Bridge`.
     tx_sv2_set_new_prev_hash: Sender<SetNewPrevHash<'static>>,
@@ -119,30 +151,23 @@ impl Upstream {
     pub async fn new(
         address: SocketAddr,
         authority_public_key: Secp256k1PublicKey,
-        rx_sv2_submit_shares_ext: Receiver<SubmitSharesExtended<'static>>,
+        user_identity: String,
+        worker_tagging: bool,
+        channel_mode: ChannelMode,
+        rx_sv2_submit_shares_ext: Receiver<(SubmitSharesExtended<'static>, String)>,
+        rx_upstream_requests: Receiver<UpstreamRequest>,
         tx_sv2_set_new_prev_hash: Sender<SetNewPrevHash<'static>>,
         tx_sv2_new_ext_mining_job: Sender<NewExtendedMiningJob<'static>>,
         min_extranonce_size: u16,
         tx_sv2_extranonce: Sender<(ExtendedExtranonce, u32)>,
+        tx_connection_channel_updates: Sender<Mining<'static>>,
         tx_status: status::Sender,
         target: Arc<Mutex<Vec<u8>>>,
         difficulty_config: Arc<Mutex<UpstreamDifficultyConfig>>,
         task_collector: Arc<Mutex<Vec<(AbortHandle, String)>>>,
     ) -> ProxyResult<'static, Arc<Mutex<Self>>> {
-        // Connect to the SV2 Upstream role retry connection every 5 seconds.
-        let socket = loop {
-            match TcpStream::connect(address).await {
-                Ok(socket) => break socket,
-                Err(e) => {
-                    error!(
-                        "Failed to connect to Upstream role at {}, retrying in 5s: {}",
-                        address, e
-                    );
-
-                    sleep(Duration::from_secs(5)).await;
-                }
-            }
-        };
+        // Connect to the SV2 Upstream role, retries and failover are up to the caller
+        let socket = TcpStream::connect(address).await?;
 
         let pub_key: Secp256k1PublicKey = authority_public_key;
         let initiator = Initiator::from_raw_k(pub_key.into_bytes())?;
@@ -155,7 +180,12 @@ impl Upstream {
         // Channel to send and receive messages to the SV2 Upstream role
         let (receiver, sender) = Connection::new(socket, HandshakeRole::Initiator(initiator), 10)
             .await
-            .unwrap();
+            .map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::ConnectionAborted,
+                    format!("Noise handshake with {} failed: {:?}", address, e),
+                )
+            })?;
         // Initialize `UpstreamConnection` with channel for SV2 Upstream role communication and
         // channel for downstream Translator Proxy communication
         let connection = UpstreamConnection { receiver, sender };
@@ -163,7 +193,18 @@ impl Upstream {
         Ok(Arc::new(Mutex::new(Self {
             connection,
             rx_sv2_submit_shares_ext,
+            rx_upstream_requests,
+            pending_authorizations: HashMap::new(),
+            pending_channels: HashMap::new(),
+            last_request_id: 0,
+            connection_channels: HashSet::new(),
+            closed_channels: HashSet::new(),
+            tx_connection_channel_updates,
+            channel_mode,
+            user_identity,
+            worker_tagging,
             extranonce_prefix: None,
+            extranonce_size: 0,
             tx_sv2_set_new_prev_hash,
             tx_sv2_new_ext_mining_job,
             channel_id: None,
@@ -180,6 +221,7 @@ impl Upstream {
     }
 
     /// Setups the connection with the SV2 Upstream role (most typically a SV2 Pool).
+    #[allow(clippy::result_large_err)]
     pub async fn connect(
         self_: Arc<Mutex<Self>>,
         min_version: u16,
@@ -228,37 +270,36 @@ impl Upstream {
         )?;
 
         // Send open channel request before returning
-        let nominal_hash_rate = self_
+        let (nominal_hash_rate, user_identity) = self_
             .safe_lock(|u| {
                 u.difficulty_config
-                    .safe_lock(|c| c.channel_nominal_hashrate)
+                    .safe_lock(|c| (c.channel_nominal_hashrate, u.user_identity.clone()))
                     .map_err(|_e| PoisonLock)
             })
             .map_err(|_e| PoisonLock)??;
-        let user_identity = "ABC".to_string().try_into()?;
+        let user_identity = user_identity.try_into()?;
         let open_channel = Mining::OpenExtendedMiningChannel(OpenExtendedMiningChannel {
-            request_id: 0, // TODO
-            user_identity, // TODO
+            // this is the only channel opened on the connection
+            request_id: 0,
+            user_identity,
             nominal_hash_rate,
             max_target: u256_from_int(u64::MAX), // TODO
             min_extranonce_size: 8, // 8 is the max extranonce2 size the braiins pool supports
         });
 
-        // reset channel hashrate so downstreams can manage from now on out
-        self_
-            .safe_lock(|u| {
-                u.difficulty_config
-                    .safe_lock(|d| d.channel_nominal_hashrate = 0.0)
-                    .map_err(|_e| PoisonLock)
-            })
-            .map_err(|_e| PoisonLock)??;
-
         let sv2_frame: StdFrame = Message::Mining(open_channel).try_into()?;
         connection.send(sv2_frame).await?;
 
         Ok(())
     }
 
+    /// Closes the connection with the SV2 Upstream role, the tasks using it are stopped by the
+    /// caller.
+    pub fn disconnect(&mut self) {
+        self.connection.receiver.close();
+        self.connection.sender.close();
+    }
+
     /// Parses the incoming SV2 message from the Upstream role and routes the message to the
     /// appropriate handler.
     #[allow(clippy::result_large_err)]
@@ -272,8 +313,10 @@ impl Upstream {
             tx_sv2_extranonce,
             tx_sv2_new_ext_mining_job,
             tx_sv2_set_new_prev_hash,
+            tx_connection_channel_updates,
             recv,
             tx_status,
+            channel_mode,
         ) = clone
             .safe_lock(|s| {
                 (
@@ -281,12 +324,16 @@ impl Upstream {
                     s.tx_sv2_extranonce.clone(),
                     s.tx_sv2_new_ext_mining_job.clone(),
                     s.tx_sv2_set_new_prev_hash.clone(),
+                    s.tx_connection_channel_updates.clone(),
                     s.connection.receiver.clone(),
                     s.tx_status.clone(),
+                    s.channel_mode,
                 )
             })
             .map_err(|_| PoisonLock)?;
-        {
+        // every channel opened for a single SV1 connection keeps the hashrate it has been opened
+        // with, the one of the translator is not used for mining
+        if channel_mode == ChannelMode::Aggregated {
             let self_ = self_.clone();
             let tx_status = tx_status.clone();
             let start_diff_management = tokio::task::spawn(async move {
@@ -359,37 +406,67 @@ impl Upstream {
                     }
                     // Does not send the messages anywhere, but instead handle them internally
                     Ok(SendTo::None(Some(m))) => {
+                        if let Some(channel_id) = Self::connection_channel_update(&m) {
+                            let is_connection_channel = self_
+                                .safe_lock(|u| u.connection_channels.contains(&channel_id))
+                                .map_err(|_e| PoisonLock);
+                            if handle_result!(tx_status, is_connection_channel) {
+                                handle_result!(
+                                    tx_status,
+                                    tx_connection_channel_updates.send(m).await.map_err(|e| {
+                                        super::super::error::Error::ChannelErrorSender(
+                                            super::super::error::ChannelSendError::General(
+                                                e.to_string(),
+                                            ),
+                                        )
+                                    })
+                                );
+                                continue;
+                            }
+                        }
                         match m {
                             Mining::OpenExtendedMiningChannelSuccess(m) => {
-                                let prefix_len = m.extranonce_prefix.len();
                                 // update upstream_extranonce1_size for tracking
                                 let miner_extranonce2_size = self_
                                     .safe_lock(|u| {
-                                        u.upstream_extranonce1_size = prefix_len;
+                                        u.upstream_extranonce1_size = m.extranonce_prefix.len();
                                         u.min_extranonce_size as usize
                                     })
                                     .map_err(|_e| PoisonLock);
                                 let miner_extranonce2_size =
                                     handle_result!(tx_status, miner_extranonce2_size);
-                                let extranonce_prefix: Extranonce = m.extranonce_prefix.into();
-                                // Create the extended extranonce that will be saved in bridge and
-                                // it will be used to open downstream (sv1) channels
-                                // range 0 is the extranonce1 from upstream
-                                // range 1 is the extranonce1 added by the tproxy
-                                // range 2 is the extranonce2 used by the miner for rolling
-                                // range 0 + range 1 is the extranonce1 sent to the miner
-                                let tproxy_e1_len = super::super::utils::proxy_extranonce1_len(
-                                    m.extranonce_size as usize,
-                                    miner_extranonce2_size,
+                                let extended = handle_result!(
+                                    tx_status,
+                                    Self::extended_extranonce(
+                                        m.extranonce_prefix,
+                                        m.extranonce_size as usize,
+                                        miner_extranonce2_size,
+                                    )
                                 );
-                                let range_0 = 0..prefix_len; // upstream extranonce1
-                                let range_1 = prefix_len..prefix_len + tproxy_e1_len; // downstream extranonce1
-                                let range_2 = prefix_len + tproxy_e1_len
-                                    ..prefix_len + m.extranonce_size as usize; // extranonce2
-                                let extended = handle_result!(tx_status, ExtendedExtranonce::from_upstream_extranonce(
-                                    extranonce_prefix.clone(), range_0.clone(), range_1.clone(), range_2.clone(),
-                                ).ok_or_else(|| InvalidExtranonce(format!("Impossible to create a valid extended extranonce from {:?} {:?} {:?} {:?}",
-                                    extranonce_prefix,range_0,range_1,range_2))));
+                                handle_result!(
+                                    tx_status,
+                                    tx_sv2_extranonce.send((extended, m.channel_id)).await
+                                );
+                            }
+                            Mining::SetExtranoncePrefix(m) => {
+                                let sizes = self_
+                                    .safe_lock(|u| {
+                                        u.upstream_extranonce1_size = m.extranonce_prefix.len();
+                                        (u.extranonce_size as usize, u.min_extranonce_size as usize)
+                                    })
+                                    .map_err(|_e| PoisonLock);
+                                let (extranonce_size, miner_extranonce2_size) =
+                                    handle_result!(tx_status, sizes);
+                                let extended = handle_result!(
+                                    tx_status,
+                                    Self::extended_extranonce(
+                                        m.extranonce_prefix,
+                                        extranonce_size,
+                                        miner_extranonce2_size,
+                                    )
+                                );
+                                info!("Up: Extranonce prefix of channel {} updated", m.channel_id);
+                                // the `Bridge` takes the new prefix before translating the next job
                                 handle_result!(
                                     tx_status,
                                     tx_sv2_extranonce.send((extended, m.channel_id)).await
@@ -452,6 +529,50 @@ impl Upstream {
 
         Ok(())
     }
+    /// Channel of the messages that have to go to the `Bridge` when they refer to a channel
+    /// opened for a single SV1 connection.
+    fn connection_channel_update(m: &Mining<'static>) -> Option<u32> {
+        match m {
+            Mining::SetTarget(m) => Some(m.channel_id),
+            Mining::SetExtranoncePrefix(m) => Some(m.channel_id),
+            Mining::CloseChannel(m) => Some(m.channel_id),
+            _ => None,
+        }
+    }
+
+    /// Creates the extended extranonce that will be saved in bridge and it will be used to open
+    /// downstream (sv1) channels
+    /// range 0 is the extranonce1 from upstream
+    /// range 1 is the extranonce1 added by the tproxy
+    /// range 2 is the extranonce2 used by the miner for rolling
+    /// range 0 + range 1 is the extranonce1 sent to the miner
+    #[allow(clippy::result_large_err)]
+    fn extended_extranonce(
+        extranonce_prefix: B032<'static>,
+        extranonce_size: usize,
+        miner_extranonce2_size: usize,
+    ) -> ProxyResult<'static, ExtendedExtranonce> {
+        let prefix_len = extranonce_prefix.len();
+        let extranonce_prefix: Extranonce = extranonce_prefix.into();
+        let tproxy_e1_len =
+            super::super::utils::proxy_extranonce1_len(extranonce_size, miner_extranonce2_size);
+        let range_0 = 0..prefix_len; // upstream extranonce1
+        let range_1 = prefix_len..prefix_len + tproxy_e1_len; // downstream extranonce1
+        let range_2 = prefix_len + tproxy_e1_len..prefix_len + extranonce_size; // extranonce2
+        ExtendedExtranonce::from_upstream_extranonce(
+            extranonce_prefix.clone(),
+            range_0.clone(),
+            range_1.clone(),
+            range_2.clone(),
+        )
+        .ok_or_else(|| {
+            InvalidExtranonce(format!(
+                "Impossible to create a valid extended extranonce from {:?} {:?} {:?} {:?}",
+                extranonce_prefix, range_0, range_1, range_2
+            ))
+        })
+    }
+
     #[allow(clippy::result_large_err)]
     fn get_job_id(
         self_: &Arc<Mutex<Self>>,
@@ -477,39 +598,52 @@ impl Upstream {
     pub fn handle_submit(self_: Arc<Mutex<Self>>) -> ProxyResult<'static, ()> {
         let task_collector = self_.safe_lock(|s| s.task_collector.clone()).unwrap();
         let clone = self_.clone();
-        let (tx_frame, receiver, tx_status) = clone
+        let (tx_frame, receiver, tx_status, worker_tagging) = clone
             .safe_lock(|s| {
                 (
                     s.connection.sender.clone(),
                     s.rx_sv2_submit_shares_ext.clone(),
                     s.tx_status.clone(),
+                    s.worker_tagging,
                 )
             })
             .map_err(|_| PoisonLock)?;
 
         let handle_submit = tokio::task::spawn(async move {
             loop {
-                let mut sv2_submit: SubmitSharesExtended =
+                let (mut sv2_submit, worker): (SubmitSharesExtended, String) =
                     handle_result!(tx_status, receiver.recv().await);
 
-                let channel_id = self_
-                    .safe_lock(|s| {
-                        s.channel_id
-                            .ok_or(super::super::error::Error::RolesSv2Logic(
-                                RolesLogicError::NotFoundChannelId,
-                            ))
-                    })
+                // the shares of a channel opened for a single SV1 connection already have the
+                // channel and job ids of the upstream
+                let is_connection_channel = self_
+                    .safe_lock(|s| s.connection_channels.contains(&sv2_submit.channel_id))
                     .map_err(|_e| PoisonLock);
-                sv2_submit.channel_id =
-                    handle_result!(tx_status, handle_result!(tx_status, channel_id));
-                let job_id = Self::get_job_id(&self_);
-                sv2_submit.job_id = handle_result!(tx_status, handle_result!(tx_status, job_id));
+                if !handle_result!(tx_status, is_connection_channel) {
+                    let channel_id = self_
+                        .safe_lock(|s| {
+                            s.channel_id
+                                .ok_or(super::super::error::Error::RolesSv2Logic(
+                                    RolesLogicError::NotFoundChannelId,
+                                ))
+                        })
+                        .map_err(|_e| PoisonLock);
+                    sv2_submit.channel_id =
+                        handle_result!(tx_status, handle_result!(tx_status, channel_id));
+                    let job_id = Self::get_job_id(&self_);
+                    sv2_submit.job_id =
+                        handle_result!(tx_status, handle_result!(tx_status, job_id));
+                }
 
                 let message = Message::Mining(
                     roles_logic_sv2::parsers::Mining::SubmitSharesExtended(sv2_submit),
                 );
 
                 let frame: StdFrame = handle_result!(tx_status, message.try_into());
+                let frame = match worker_tagging {
+                    true => handle_result!(tx_status, Self::tag_share(frame, &worker)),
+                    false => frame,
+                };
                 // Doesnt actually send because of Braiins Pool issue that needs to be fixed
 
                 let frame: EitherFrame = frame.into();
@@ -529,8 +663,134 @@ impl Upstream {
         Ok(())
     }
 
-    fn _is_contained_in_upstream_target(&self, _share: SubmitSharesExtended) -> bool {
-        todo!()
+    /// Opens and closes the channels requested by the `Bridge`: the ones used to authorize a SV1
+    /// worker, that are closed right away, and the ones of single SV1 connections.
+    #[allow(clippy::result_large_err)]
+    pub fn handle_upstream_requests(self_: Arc<Mutex<Self>>) -> ProxyResult<'static, ()> {
+        let task_collector = self_.safe_lock(|s| s.task_collector.clone()).unwrap();
+        let (tx_frame, receiver, tx_status) = self_
+            .safe_lock(|s| {
+                (
+                    s.connection.sender.clone(),
+                    s.rx_upstream_requests.clone(),
+                    s.tx_status.clone(),
+                )
+            })
+            .map_err(|_| PoisonLock)?;
+
+        let handle_upstream_requests = tokio::task::spawn(async move {
+            loop {
+                let request: UpstreamRequest = handle_result!(tx_status, receiver.recv().await);
+                let message = Self::upstream_request_message(&self_, request);
+                let message = match handle_result!(tx_status, message) {
+                    Some(message) => message,
+                    None => continue,
+                };
+                let frame: StdFrame =
+                    handle_result!(tx_status, Message::Mining(message).try_into());
+                let frame: EitherFrame = frame.into();
+                handle_result!(
+                    tx_status,
+                    tx_frame.send(frame).await.map_err(|e| {
+                        super::super::error::Error::ChannelErrorSender(
+                            super::super::error::ChannelSendError::General(e.to_string()),
+                        )
+                    })
+                );
+            }
+        });
+        let _ = task_collector.safe_lock(|a| {
+            a.push((
+                handle_upstream_requests.abort_handle(),
+                "handle_upstream_requests".to_string(),
+            ))
+        });
+
+        Ok(())
+    }
+
+    /// Message to send to the SV2 Upstream role for a request of the `Bridge`, `None` if the
+    /// request has already been answered.
+    #[allow(clippy::result_large_err)]
+    fn upstream_request_message(
+        self_: &Arc<Mutex<Self>>,
+        request: UpstreamRequest,
+    ) -> ProxyResult<'static, Option<Mining<'static>>> {
+        self_
+            .safe_lock(|s| -> ProxyResult<'static, Option<Mining<'static>>> {
+                match request {
+                    UpstreamRequest::AuthorizeWorker(worker) => {
+                        let user_identity = match worker.name.clone().try_into() {
+                            Ok(user_identity) => user_identity,
+                            // can not be sent upstream, the worker is not authorized
+                            Err(_) => {
+                                let _ = worker.tx_authorized.try_send(false);
+                                return Ok(None);
+                            }
+                        };
+                        let nominal_hash_rate = s
+                            .difficulty_config
+                            .safe_lock(|c| c.channel_nominal_hashrate)
+                            .map_err(|_e| PoisonLock)?;
+                        s.last_request_id += 1;
+                        s.pending_authorizations
+                            .insert(s.last_request_id, worker.tx_authorized);
+                        Ok(Some(Mining::OpenExtendedMiningChannel(
+                            OpenExtendedMiningChannel {
+                                request_id: s.last_request_id,
+                                user_identity,
+                                // the channel is closed right away, the hashrate only has to be valid
+                                nominal_hash_rate: nominal_hash_rate.max(1.0),
+                                max_target: u256_from_int(u64::MAX),
+                                min_extranonce_size: 8,
+                            },
+                        )))
+                    }
+                    UpstreamRequest::OpenChannel {
+                        nominal_hash_rate,
+                        tx_opened,
+                    } => {
+                        let user_identity = s.user_identity.clone().try_into()?;
+                        s.last_request_id += 1;
+                        s.pending_channels.insert(s.last_request_id, tx_opened);
+                        Ok(Some(Mining::OpenExtendedMiningChannel(
+                            OpenExtendedMiningChannel {
+                                request_id: s.last_request_id,
+                                user_identity,
+                                nominal_hash_rate,
+                                max_target: u256_from_int(u64::MAX),
+                                // the miner rolls the whole extranonce of the channel
+                                min_extranonce_size: s.min_extranonce_size,
+                            },
+                        )))
+                    }
+                    UpstreamRequest::CloseChannel(channel_id) => {
+                        if !s.connection_channels.remove(&channel_id) {
+                            return Ok(None);
+                        }
+                        s.closed_channels.insert(channel_id);
+                        Ok(Some(Mining::CloseChannel(CloseChannel {
+                            channel_id,
+                            reason_code: "sv1-connection-closed".to_string().try_into()?,
+                        })))
+                    }
+                }
+            })
+            .map_err(|_e| PoisonLock)?
+    }
+
+    /// Appends to the serialized `SubmitSharesExtended` in `frame` the TLV that carries the name
+    /// of the SV1 worker that found the share, and updates the length in the frame header.
+    #[allow(clippy::result_large_err)]
+    fn tag_share(frame: StdFrame, worker: &str) -> ProxyResult<'static, StdFrame> {
+        let tlv = user_identity_tlv(worker);
+        let mut bytes = vec![0; frame.encoded_length()];
+        frame.serialize(&mut bytes)?;
+        let len = (bytes.len() - Header::SIZE + tlv.len()) as u32;
+        // the header ends with the payload length as a U24
+        bytes[Header::SIZE - 3..Header::SIZE].copy_from_slice(&len.to_le_bytes()[..3]);
+        bytes.extend_from_slice(&tlv);
+        Ok(StdFrame::from_bytes_unchecked(bytes.into()))
     }
 
     /// Creates the `SetupConnection` message to setup the connection with the SV2 Upstream role.
@@ -669,6 +929,31 @@ impl ParseUpstreamMiningMessages<Downstream, NullDownstreamMiningSelector, NoRou
         &mut self,
         m: roles_logic_sv2::mining_sv2::OpenExtendedMiningChannelSuccess,
     ) -> Result<SendTo<Downstream>, RolesLogicError> {
+        // the channel has only been opened to authorize a worker
+        if let Some(tx_authorized) = self.pending_authorizations.remove(&m.request_id) {
+            info!("Up: Worker of request {} authorized", m.request_id);
+            let _ = tx_authorized.try_send(true);
+            self.closed_channels.insert(m.channel_id);
+            return Ok(SendTo::Respond(Mining::CloseChannel(CloseChannel {
+                channel_id: m.channel_id,
+                reason_code: "worker-authorized".to_string().try_into()?,
+            })));
+        }
+        // the channel of a single SV1 connection
+        if let Some(tx_opened) = self.pending_channels.remove(&m.request_id) {
+            let channel_id = m.channel_id;
+            if tx_opened.try_send(m.into_static()).is_ok() {
+                info!("Up: Opened channel {} for a SV1 connection", channel_id);
+                self.connection_channels.insert(channel_id);
+                return Ok(SendTo::None(None));
+            }
+            // the SV1 connection stopped waiting for it
+            self.closed_channels.insert(channel_id);
+            return Ok(SendTo::Respond(Mining::CloseChannel(CloseChannel {
+                channel_id,
+                reason_code: "sv1-connection-closed".to_string().try_into()?,
+            })));
+        }
         let tproxy_e1_len = super::super::utils::proxy_extranonce1_len(
             m.extranonce_size as usize,
             self.min_extranonce_size.into(),
@@ -686,15 +971,35 @@ impl ParseUpstreamMiningMessages<Downstream, NullDownstreamMiningSelector, NoRou
         info!("Up: Successfully Opened Extended Mining Channel");
         self.channel_id = Some(m.channel_id);
         self.extranonce_prefix = Some(m.extranonce_prefix.to_vec());
+        self.extranonce_size = m.extranonce_size;
         let m = Mining::OpenExtendedMiningChannelSuccess(m.into_static());
         Ok(SendTo::None(Some(m)))
     }
 
-    /// Handles the SV2 `OpenExtendedMiningChannelError` message (TODO).
+    /// Handles the SV2 `OpenExtendedMiningChannelError` message. The error rejects the worker
+    /// when the channel has been requested to authorize it, otherwise the `Upstream` is closed.
     fn handle_open_mining_channel_error(
         &mut self,
         m: roles_logic_sv2::mining_sv2::OpenMiningChannelError,
     ) -> Result<roles_logic_sv2::handlers::mining::SendTo<Downstream>, RolesLogicError> {
+        if let Some(tx_authorized) = self.pending_authorizations.remove(&m.request_id) {
+            warn!(
+                "Up: Worker of request {} not authorized: {}",
+                m.request_id,
+                std::str::from_utf8(m.error_code.as_ref()).unwrap_or("unknown error code")
+            );
+            let _ = tx_authorized.try_send(false);
+            return Ok(SendTo::None(None));
+        }
+        // dropping the sender closes the SV1 connection that requested the channel
+        if self.pending_channels.remove(&m.request_id).is_some() {
+            warn!(
+                "Up: Channel of request {} refused: {}",
+                m.request_id,
+                std::str::from_utf8(m.error_code.as_ref()).unwrap_or("unknown error code")
+            );
+            return Ok(SendTo::None(None));
+        }
         Ok(SendTo::None(Some(Mining::OpenMiningChannelError(
             m.as_static(),
         ))))
@@ -718,12 +1023,25 @@ impl ParseUpstreamMiningMessages<Downstream, NullDownstreamMiningSelector, NoRou
         Ok(SendTo::None(Some(Mining::CloseChannel(m.as_static()))))
     }
 
-    /// Handles the SV2 `SetExtranoncePrefix` message (TODO).
+    /// Handles the SV2 `SetExtranoncePrefix` message. The new prefix is sent to the `Bridge`,
+    /// that moves the `Downstream`s to it when they get the next job. The SV1 connection of a
+    /// channel opened only for it is closed instead.
     fn handle_set_extranonce_prefix(
         &mut self,
-        _: roles_logic_sv2::mining_sv2::SetExtranoncePrefix,
+        m: roles_logic_sv2::mining_sv2::SetExtranoncePrefix,
     ) -> Result<roles_logic_sv2::handlers::mining::SendTo<Downstream>, RolesLogicError> {
-        todo!()
+        if self.connection_channels.contains(&m.channel_id) {
+            return Ok(SendTo::None(Some(Mining::SetExtranoncePrefix(
+                m.into_static(),
+            ))));
+        }
+        if self.channel_id != Some(m.channel_id) {
+            return Err(RolesLogicError::NotFoundChannelId);
+        }
+        self.extranonce_prefix = Some(m.extranonce_prefix.to_vec());
+        Ok(SendTo::None(Some(Mining::SetExtranoncePrefix(
+            m.into_static(),
+        ))))
     }
 
     /// Handles the SV2 `SubmitSharesSuccess` message.
@@ -759,7 +1077,7 @@ impl ParseUpstreamMiningMessages<Downstream, NullDownstreamMiningSelector, NoRou
         &mut self,
         m: roles_logic_sv2::mining_sv2::NewExtendedMiningJob,
     ) -> Result<roles_logic_sv2::handlers::mining::SendTo<Downstream>, RolesLogicError> {
-        if self.is_work_selection_enabled() {
+        if self.is_work_selection_enabled() || self.closed_channels.contains(&m.channel_id) {
             Ok(SendTo::None(None))
         } else {
             IS_NEW_JOB_HANDLED.store(false, std::sync::atomic::Ordering::SeqCst);
@@ -781,7 +1099,7 @@ impl ParseUpstreamMiningMessages<Downstream, NullDownstreamMiningSelector, NoRou
         &mut self,
         m: roles_logic_sv2::mining_sv2::SetNewPrevHash,
     ) -> Result<roles_logic_sv2::handlers::mining::SendTo<Downstream>, RolesLogicError> {
-        if self.is_work_selection_enabled() {
+        if self.is_work_selection_enabled() || self.closed_channels.contains(&m.channel_id) {
             Ok(SendTo::None(None))
         } else {
             let message = Mining::SetNewPrevHash(m.into_static());
@@ -807,13 +1125,17 @@ impl ParseUpstreamMiningMessages<Downstream, NullDownstreamMiningSelector, NoRou
     }
 
     /// Handles the SV2 `SetTarget` message which updates the Downstream role(s) target
-    /// difficulty via the SV1 `mining.set_difficulty` message.
+    /// difficulty via the SV1 `mining.set_difficulty` message. The target of a channel opened for
+    /// a single SV1 connection is sent to the `Bridge`.
     fn handle_set_target(
         &mut self,
         m: roles_logic_sv2::mining_sv2::SetTarget,
     ) -> Result<roles_logic_sv2::handlers::mining::SendTo<Downstream>, RolesLogicError> {
         info!("SetTarget: {:?}", m);
         let m = m.into_static();
+        if self.connection_channels.contains(&m.channel_id) {
+            return Ok(SendTo::None(Some(Mining::SetTarget(m))));
+        }
 
         self.target
             .safe_lock(|t| *t = m.maximum_target.to_vec())
@@ -11,6 +11,8 @@ cd "$dir"
   cbindgen --crate binary_codec_sv2 >> ../scripts/sv2.h
   cbindgen --crate common_messages_sv2 >> ../scripts/sv2.h
   cbindgen --crate template_distribution_sv2 >> ../scripts/sv2.h
+  cbindgen --crate mining_sv2 >> ../scripts/sv2.h
+  cbindgen --crate job_declaration_sv2 >> ../scripts/sv2.h
   cbindgen --crate codec_sv2 >> ../scripts/sv2.h
   cbindgen --crate sv2_ffi >> ../scripts/sv2.h
 cd ..